@@ -0,0 +1,250 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::output::Finding;
+
+/// The unknown operations found in each file by an earlier run.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Baseline {
+    files: BTreeMap<PathBuf, Vec<Entry>>,
+}
+
+/// Identical unknown operations in the same content stream, which are counted rather than
+/// identified by their position so that unrelated changes to a stream don't move them.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Entry {
+    pub location: String,
+    pub operator: String,
+    /// The operands in PDF syntax.
+    pub operands: Vec<String>,
+    pub count: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    New,
+    Fixed,
+    /// The file is in the baseline but wasn't checked, because it no longer exists or couldn't be
+    /// loaded.
+    Removed,
+}
+
+/// A difference between the baseline and the current run.
+#[derive(Serialize)]
+pub struct Change {
+    pub status: Status,
+    pub file: PathBuf,
+    #[serde(flatten)]
+    pub entry: Entry,
+}
+
+impl Baseline {
+    pub fn load(path: &Path) -> Result<Option<Baseline>, Box<dyn Error>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    pub fn record(&mut self, file: &Path, findings: &[Finding]) {
+        let mut counts = BTreeMap::new();
+        for finding in findings {
+            let key = (&finding.location, &finding.operator, &finding.operand_keys);
+            *counts.entry(key).or_insert(0) += 1;
+        }
+
+        let entries = counts
+            .into_iter()
+            .map(|((location, operator, operands), count)| Entry {
+                location: location.clone(),
+                operator: operator.clone(),
+                operands: operands.clone(),
+                count,
+            })
+            .collect();
+        self.files.insert(file.to_path_buf(), entries);
+    }
+
+    /// Replaces the entries of the files in `current`, keeping those of files that weren't
+    /// checked this time.
+    pub fn update(&mut self, current: Baseline) {
+        self.files.extend(current.files);
+    }
+
+    /// Lists what's new and what's been fixed in `current`, and the entries of files that are
+    /// missing from `current`.
+    pub fn compare(&self, current: &Baseline) -> Vec<Change> {
+        let mut changes = Vec::new();
+        let empty = Vec::new();
+
+        for (file, entries) in &current.files {
+            let baseline = self.files.get(file).unwrap_or(&empty);
+
+            diff(file, Status::New, entries, baseline, &mut changes);
+            diff(file, Status::Fixed, baseline, entries, &mut changes);
+        }
+
+        for (file, entries) in &self.files {
+            if !current.files.contains_key(file) {
+                diff(file, Status::Removed, entries, &empty, &mut changes);
+            }
+        }
+
+        changes
+    }
+}
+
+/// Adds a change for each entry in `entries` that occurs more often than in `other`.
+fn diff(
+    file: &Path,
+    status: Status,
+    entries: &[Entry],
+    other: &[Entry],
+    changes: &mut Vec<Change>,
+) {
+    for entry in entries {
+        let other_count = other
+            .iter()
+            .find(|other| {
+                other.location == entry.location
+                    && other.operator == entry.operator
+                    && other.operands == entry.operands
+            })
+            .map_or(0, |other| other.count);
+
+        if entry.count > other_count {
+            changes.push(Change {
+                status,
+                file: file.to_path_buf(),
+                entry: Entry {
+                    count: entry.count - other_count,
+                    ..entry.clone()
+                },
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use pdf::primitive::{PdfString, Primitive};
+
+    use super::*;
+
+    fn findings(file: &str, operations: &[(&str, Primitive)]) -> Vec<Finding> {
+        operations
+            .iter()
+            .enumerate()
+            .map(|(index, (operator, operand))| {
+                Finding::new(
+                    Path::new(file),
+                    1,
+                    "page 1".into(),
+                    index,
+                    None,
+                    operator,
+                    std::slice::from_ref(operand),
+                )
+            })
+            .collect()
+    }
+
+    fn summary(changes: &[Change]) -> Vec<(Status, &str, &str, &str, usize)> {
+        changes
+            .iter()
+            .map(|change| {
+                (
+                    change.status,
+                    change.file.to_str().unwrap(),
+                    change.entry.operator.as_str(),
+                    change.entry.operands[0].as_str(),
+                    change.entry.count,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn records_compares_and_updates() {
+        let string = Primitive::String(PdfString::new(b"a\xff".to_vec()));
+
+        let mut recorded = Baseline::default();
+        recorded.record(
+            Path::new("a.pdf"),
+            &findings(
+                "a.pdf",
+                &[
+                    ("foo", Primitive::Integer(1)),
+                    ("foo", Primitive::Integer(1)),
+                ],
+            ),
+        );
+        recorded.record(
+            Path::new("b.pdf"),
+            &findings("b.pdf", &[("bar", string.clone())]),
+        );
+        recorded.record(
+            Path::new("c.pdf"),
+            &findings("c.pdf", &[("baz", Primitive::Null)]),
+        );
+
+        let path = env::temp_dir().join(format!("check-baseline-{}.json", process::id()));
+        recorded.save(&path).unwrap();
+        let mut baseline = Baseline::load(&path).unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut current = Baseline::default();
+        current.record(
+            Path::new("a.pdf"),
+            &findings(
+                "a.pdf",
+                &[
+                    ("foo", Primitive::Integer(1)),
+                    ("qux", Primitive::Integer(2)),
+                ],
+            ),
+        );
+        current.record(Path::new("b.pdf"), &findings("b.pdf", &[("bar", string)]));
+
+        assert_eq!(
+            summary(&baseline.compare(&current)),
+            [
+                (Status::New, "a.pdf", "qux", "2", 1),
+                (Status::Fixed, "a.pdf", "foo", "1", 1),
+                (Status::Removed, "c.pdf", "baz", "null", 1),
+            ]
+        );
+        assert_eq!(baseline.files[Path::new("b.pdf")][0].operands, ["(a\\377)"]);
+
+        baseline.update(current);
+        let mut again = Baseline::default();
+        again.record(
+            Path::new("a.pdf"),
+            &findings("a.pdf", &[("qux", Primitive::Integer(2))]),
+        );
+        assert_eq!(
+            summary(&baseline.compare(&again)),
+            [
+                (Status::Fixed, "a.pdf", "foo", "1", 1),
+                (Status::Removed, "b.pdf", "bar", "(a\\377)", 1),
+                (Status::Removed, "c.pdf", "baz", "null", 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Expands the paths given on the command line into the PDF files to check. Directories are
+/// searched recursively and arguments containing `*`, `?` or `[` are treated as glob patterns.
+/// Paths that can't be read are kept, so that checking them reports them as failed.
+pub fn expand(arguments: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    let mut visited = HashSet::new();
+
+    for argument in arguments {
+        let is_pattern = argument
+            .to_str()
+            .is_some_and(|argument| argument.contains(&['*', '?', '['][..]));
+
+        if is_pattern {
+            for path in glob::glob(&argument.to_string_lossy())? {
+                let path = match path {
+                    Ok(path) => path,
+                    Err(error) => {
+                        files.push(error.path().to_path_buf());
+                        continue;
+                    }
+                };
+                if path.is_dir() {
+                    collect_pdfs(&path, &mut visited, &mut files);
+                } else {
+                    files.push(path);
+                }
+            }
+        } else if argument.is_dir() {
+            collect_pdfs(argument, &mut visited, &mut files);
+        } else {
+            files.push(argument.clone());
+        }
+    }
+
+    // A file can be matched by more than one argument, only check it once.
+    let mut seen = HashSet::new();
+    files.retain(|path| seen.insert(path.clone()));
+
+    Ok(files)
+}
+
+/// Collects the PDF files in a directory and its subdirectories. Directories are searched once
+/// however they're reached, so that symbolic links can't make it loop.
+fn collect_pdfs(directory: &Path, visited: &mut HashSet<PathBuf>, files: &mut Vec<PathBuf>) {
+    let entries = fs::canonicalize(directory).and_then(|canonical| {
+        if !visited.insert(canonical) {
+            return Ok(Vec::new());
+        }
+        fs::read_dir(directory)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+    });
+    let mut entries = match entries {
+        Ok(entries) => entries,
+        Err(_) => {
+            files.push(directory.to_path_buf());
+            return;
+        }
+    };
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_pdfs(&path, visited, files);
+        } else if is_pdf(&path) {
+            files.push(path);
+        }
+    }
+}
+
+fn is_pdf(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pdf"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[test]
+    fn expands_directories_and_patterns() {
+        let root = env::temp_dir().join(format!("check-files-{}", process::id()));
+        for file in &[
+            "a.pdf",
+            "b.txt",
+            "nested/c.pdf",
+            "nested/deeper/d.pdf",
+            "nested/e.PDF",
+            "nested/f.txt",
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+
+        let expanded = expand(&[
+            root.join("*.pdf"),
+            root.join("nested"),
+            root.join("a.pdf"),
+            root.join("missing.pdf"),
+        ]);
+        fs::remove_dir_all(&root).unwrap();
+
+        let relative = expanded
+            .unwrap()
+            .into_iter()
+            .map(|path| path.strip_prefix(&root).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            relative,
+            [
+                Path::new("a.pdf"),
+                Path::new("nested/c.pdf"),
+                Path::new("nested/deeper/d.pdf"),
+                Path::new("nested/e.PDF"),
+                Path::new("missing.pdf"),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symbolic_links_to_directories_once() {
+        let root = env::temp_dir().join(format!("check-links-{}", process::id()));
+        fs::create_dir_all(root.join("nested")).unwrap();
+        fs::write(root.join("nested/a.pdf"), b"").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("nested/loop")).unwrap();
+
+        let expanded = expand(&[root.clone()]);
+        fs::remove_dir_all(&root).unwrap();
+
+        let relative = expanded
+            .unwrap()
+            .into_iter()
+            .map(|path| path.strip_prefix(&root).unwrap().to_path_buf())
+            .collect::<Vec<_>>();
+        assert_eq!(relative, [Path::new("nested/a.pdf")]);
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use pdf::{file::File as PdfFile, object::Resolve};
+use structopt::StructOpt;
+
+use typed_pdf::{
+    document::{read_trailer, RawDocument, ReachableContent},
+    error::Error,
+    parser::{ParseError, Span},
+    *,
+};
+
+mod baseline;
+mod files;
+mod output;
+mod statistics;
+
+use baseline::{Baseline, Status};
+use output::{Finding, Format, Output};
+use statistics::Statistics;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "Portfolio Planner")]
+struct Opt {
+    /// Output format: text, json or ndjson
+    #[structopt(long, default_value = "text")]
+    format: Format,
+
+    /// Number of files to check at once, defaults to the number of CPUs
+    #[structopt(long)]
+    jobs: Option<usize>,
+
+    /// Exit with a nonzero status if any file couldn't be checked
+    #[structopt(long)]
+    fail_on_error: bool,
+
+    /// Also check Form XObjects, tiling patterns, annotation appearances and Type 3 glyphs
+    #[structopt(long)]
+    exhaustive: bool,
+
+    /// Compare unknown operations with those recorded in this file, reporting only new and fixed
+    /// ones and failing if there are new ones. The file is created if it doesn't exist.
+    #[structopt(long, parse(from_os_str))]
+    baseline: Option<PathBuf>,
+
+    /// Record the results of this run in the baseline file
+    #[structopt(long)]
+    update_baseline: bool,
+
+    /// Files, directories or glob patterns to process
+    #[structopt(name = "FILE", parse(from_os_str))]
+    files: Vec<PathBuf>,
+}
+
+#[derive(Default)]
+struct Report {
+    findings: Vec<Finding>,
+    statistics: Statistics,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let opt = Opt::from_args();
+    let files = files::expand(&opt.files)?;
+    let jobs = opt
+        .jobs
+        .or_else(|| thread::available_parallelism().ok().map(usize::from))
+        .unwrap_or(1)
+        .max(1);
+
+    let queue = Arc::new(Mutex::new(files.clone().into_iter().enumerate()));
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..jobs.min(files.len()) {
+        let queue = Arc::clone(&queue);
+        let sender = sender.clone();
+        let exhaustive = opt.exhaustive;
+
+        thread::spawn(move || loop {
+            let next = queue.lock().map(|mut queue| queue.next());
+            let (index, path) = match next {
+                Ok(Some(next)) => next,
+                _ => break,
+            };
+
+            let report = panic::catch_unwind(AssertUnwindSafe(|| check_file(&path, exhaustive)))
+                .unwrap_or_else(|panic| Err(panic_message(&*panic)));
+            if sender.send((index, report)).is_err() {
+                break;
+            }
+        });
+    }
+    drop(sender);
+
+    let baseline = match &opt.baseline {
+        Some(path) => Baseline::load(path)?,
+        None => None,
+    };
+    let mut current = Baseline::default();
+
+    let mut output = Output::new(opt.format);
+    let mut statistics = Statistics::default();
+    let mut failures = Vec::new();
+
+    // Reports arrive in the order files finish, print them in the order they were given.
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    for (index, report) in receiver {
+        pending.insert(index, report);
+
+        while let Some(report) = pending.remove(&next) {
+            let path = &files[next];
+            output.file(path)?;
+
+            match report {
+                Ok(report) => {
+                    if opt.baseline.is_some() {
+                        current.record(path, &report.findings);
+                    } else {
+                        for finding in &report.findings {
+                            output.finding(finding)?;
+                        }
+                    }
+                    statistics.merge(report.statistics);
+                }
+                Err(error) => failures.push((path.clone(), error)),
+            }
+            next += 1;
+        }
+    }
+
+    let mut regressed = false;
+    if let Some(baseline) = &baseline {
+        for change in baseline.compare(&current) {
+            regressed |= change.status == Status::New;
+            output.change(&change)?;
+        }
+    }
+
+    output.finish(&failures)?;
+    if output.format() == Format::Text {
+        statistics.print_summary();
+    }
+
+    if let Some(path) = &opt.baseline {
+        let updated = match baseline {
+            Some(mut baseline) if opt.update_baseline => {
+                baseline.update(current);
+                Some(baseline)
+            }
+            Some(_) => None,
+            None => Some(current),
+        };
+
+        if let Some(updated) = updated {
+            updated.save(path)?;
+            eprintln!("Recorded baseline in {}", path.display());
+        }
+    }
+
+    if regressed || (opt.fail_on_error && !failures.is_empty()) {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn check_file(path: &Path, exhaustive: bool) -> Result<Report, String> {
+    let mut report = Report::default();
+
+    let data = fs::read(path).map_err(|error| error.to_string())?;
+    let file = PdfFile::open(path).map_err(|error| error.to_string())?;
+    let document = read_trailer(&data, &file)
+        .and_then(|trailer| RawDocument::load(&file, &trailer))
+        .map_err(|error| error.to_string())?;
+
+    if exhaustive {
+        let mut location = String::new();
+        document
+            .for_each_content(&file, |content| {
+                location = content.path_string();
+                check_content(&file, path, &content, &mut report)
+            })
+            .map_err(|error| format!("{}: {}", location, error))?;
+    } else {
+        for page in &document.pages {
+            page.content(&file)
+                .and_then(|content| check_content(&file, path, &content, &mut report))
+                .map_err(|error| format!("page {}: {}", page.number, error))?;
+        }
+    }
+
+    Ok(report)
+}
+
+fn check_content<R: Resolve>(
+    resolve: &R,
+    path: &Path,
+    content: &ReachableContent,
+    report: &mut Report,
+) -> Result<(), Error> {
+    let mut parser = content.parser(resolve)?.with_recovery();
+    let mut index = 0;
+
+    while let Some(operation) = parser.next_spanned() {
+        let operation = match operation {
+            Ok(operation) => operation,
+            Err(ParseError::Syntax {
+                stream,
+                byte_range,
+                reason,
+            }) => {
+                report.statistics.record_malformed();
+                report.findings.push(Finding::malformed(
+                    path,
+                    content.page(),
+                    content.path_string(),
+                    index,
+                    &Span { stream, byte_range },
+                    &reason,
+                ));
+                index += 1;
+                continue;
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let operator = operation.value.operator.as_str();
+
+        if let Operation::Unknown { operands, .. } = normalize_operation(&operation.value) {
+            report
+                .statistics
+                .record_unknown(path, content.page(), operator, operands);
+            report.findings.push(Finding::new(
+                path,
+                content.page(),
+                content.path_string(),
+                index,
+                operation.span.as_ref(),
+                operator,
+                operands,
+            ));
+        } else {
+            report.statistics.record_typed(operator);
+        }
+        index += 1;
+    }
+
+    Ok(())
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        format!("panicked: {}", message)
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        format!("panicked: {}", message)
+    } else {
+        "panicked".to_string()
+    }
+}
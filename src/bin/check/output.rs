@@ -0,0 +1,411 @@
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use itertools::Itertools;
+use pdf::primitive::Primitive;
+use serde::Serialize;
+
+use typed_pdf::{
+    parser::{Span, OPERATORS},
+    writer::write_primitive,
+};
+
+use crate::baseline::{Change, Status};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+    Ndjson,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            _ => Err(format!(
+                "Unknown format {}, expected text, json or ndjson",
+                format
+            )),
+        }
+    }
+}
+
+/// An operation that couldn't be typed, or a malformed one, which has no operator.
+#[derive(Serialize)]
+pub struct Finding {
+    pub file: PathBuf,
+    pub page: usize,
+    pub location: String,
+    pub index: usize,
+    pub stream: Option<usize>,
+    pub byte_range: Option<(usize, usize)>,
+    pub operator: String,
+    /// The operands in their `Debug` form, for display.
+    pub operands: Vec<String>,
+    /// The operands in PDF syntax, which baselines are keyed on.
+    #[serde(skip)]
+    pub operand_keys: Vec<String>,
+    pub reason: String,
+}
+
+impl Finding {
+    pub fn new(
+        file: &Path,
+        page: usize,
+        location: String,
+        index: usize,
+        span: Option<&Span>,
+        operator: &str,
+        operands: &[Primitive],
+    ) -> Self {
+        let reason = if OPERATORS.contains(&operator) {
+            "unsupported operands"
+        } else {
+            "unsupported operator"
+        };
+
+        Finding {
+            file: file.to_path_buf(),
+            page,
+            location,
+            index,
+            stream: span.map(|span| span.stream),
+            byte_range: span.map(|span| (span.byte_range.start, span.byte_range.end)),
+            operator: operator.to_string(),
+            operands: operands
+                .iter()
+                .map(|operand| format!("{:?}", operand))
+                .collect(),
+            operand_keys: operands.iter().map(operand_string).collect(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// A malformed operation the parser skipped over.
+    pub fn malformed(
+        file: &Path,
+        page: usize,
+        location: String,
+        index: usize,
+        span: &Span,
+        reason: &str,
+    ) -> Self {
+        Finding {
+            file: file.to_path_buf(),
+            page,
+            location,
+            index,
+            stream: Some(span.stream),
+            byte_range: Some((span.byte_range.start, span.byte_range.end)),
+            operator: String::new(),
+            operands: Vec::new(),
+            operand_keys: Vec::new(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Writes an operand in PDF syntax, which unlike `Primitive`'s `Debug` output doesn't change
+/// between `pdf` releases, so baselines stay comparable. Bytes of strings that aren't printable
+/// ASCII are written as octal escapes.
+fn operand_string(operand: &Primitive) -> String {
+    let mut written = Vec::new();
+    write_primitive(&mut written, operand).expect("writing to a Vec doesn't fail");
+
+    let mut operand = String::with_capacity(written.len());
+    for byte in written {
+        match byte {
+            b' '..=b'~' => operand.push(byte as char),
+            byte => operand.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    operand.trim_end().to_string()
+}
+
+/// A record of JSON output, tagged with its `"type"` since a run with a baseline writes changes
+/// rather than findings.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Record<'a> {
+    Finding(&'a Finding),
+    Change(&'a Change),
+}
+
+pub struct Output<W> {
+    format: Format,
+    findings: usize,
+    out: W,
+}
+
+impl Output<io::Stdout> {
+    pub fn new(format: Format) -> Self {
+        Output::with_writer(format, io::stdout())
+    }
+}
+
+impl<W: Write> Output<W> {
+    pub fn with_writer(format: Format, out: W) -> Self {
+        Output {
+            format,
+            findings: 0,
+            out,
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn file(&mut self, path: &Path) -> io::Result<()> {
+        if self.format != Format::Text {
+            return Ok(());
+        }
+
+        if let Some(filename) = path.to_str() {
+            writeln!(self.out, "File: {}", filename)
+        } else {
+            writeln!(self.out, "Illegal filename")
+        }
+    }
+
+    pub fn finding(&mut self, finding: &Finding) -> io::Result<()> {
+        match self.format {
+            Format::Text => {
+                if finding.operator.is_empty() {
+                    write!(self.out, "Malformed Operation ({})", finding.reason)?;
+                } else {
+                    write!(self.out, "Unknown Operation {}", finding.operator)?;
+                    if !finding.operands.is_empty() {
+                        write!(self.out, " {}", finding.operands.iter().format(", "))?;
+                    }
+                }
+                if let (Some(stream), Some((start, end))) = (finding.stream, finding.byte_range) {
+                    write!(
+                        self.out,
+                        " at {}, stream {}, bytes {}..{}",
+                        finding.location, stream, start, end
+                    )?;
+                }
+                writeln!(self.out)?;
+            }
+            Format::Json | Format::Ndjson => self.record(&Record::Finding(finding))?,
+        }
+        self.findings += 1;
+
+        Ok(())
+    }
+
+    pub fn change(&mut self, change: &Change) -> io::Result<()> {
+        match self.format {
+            Format::Text => {
+                let status = match change.status {
+                    Status::New => "New",
+                    Status::Fixed => "Fixed",
+                    Status::Removed => "Removed",
+                };
+                if change.entry.operator.is_empty() {
+                    write!(self.out, "{} Malformed Operation", status)?;
+                } else {
+                    write!(
+                        self.out,
+                        "{} Unknown Operation {}",
+                        status, change.entry.operator
+                    )?;
+                    if !change.entry.operands.is_empty() {
+                        write!(self.out, " {}", change.entry.operands.iter().format(", "))?;
+                    }
+                }
+                writeln!(
+                    self.out,
+                    " in {} at {} ({} times)",
+                    change.file.display(),
+                    change.entry.location,
+                    change.entry.count
+                )?;
+            }
+            Format::Json | Format::Ndjson => self.record(&Record::Change(change))?,
+        }
+        self.findings += 1;
+
+        Ok(())
+    }
+
+    fn record(&mut self, record: &Record) -> io::Result<()> {
+        let record = serde_json::to_string(record)?;
+
+        match self.format {
+            Format::Json if self.findings == 0 => writeln!(self.out, "[{}", record),
+            Format::Json => writeln!(self.out, ",{}", record),
+            _ => writeln!(self.out, "{}", record),
+        }
+    }
+
+    pub fn finish(&mut self, failures: &[(PathBuf, String)]) -> io::Result<()> {
+        match self.format {
+            Format::Text => {
+                if !failures.is_empty() {
+                    writeln!(self.out)?;
+                    writeln!(self.out, "Failed to check {} files:", failures.len())?;
+                    for (path, error) in failures {
+                        writeln!(self.out, "{}: {}", path.display(), error)?;
+                    }
+                }
+            }
+            Format::Json => {
+                if self.findings == 0 {
+                    writeln!(self.out, "[]")?;
+                } else {
+                    writeln!(self.out, "]")?;
+                }
+            }
+            Format::Ndjson => {}
+        }
+
+        if self.format != Format::Text {
+            for (path, error) in failures {
+                eprintln!("Failed to check {}: {}", path.display(), error);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::baseline::Entry;
+
+    fn json(findings: usize) -> String {
+        let finding = Finding::new(Path::new("a.pdf"), 1, "page 1".into(), 0, None, "foo", &[]);
+
+        let mut output = Output::with_writer(Format::Json, Vec::new());
+        for _ in 0..findings {
+            output.finding(&finding).unwrap();
+        }
+        output.finish(&[]).unwrap();
+
+        String::from_utf8(output.out).unwrap()
+    }
+
+    #[test]
+    fn prints_where_findings_are() {
+        let span = Span {
+            stream: 1,
+            byte_range: 10..15,
+        };
+        let finding = Finding::new(
+            Path::new("a.pdf"),
+            3,
+            "page 3 > XObject /Fm1".into(),
+            4,
+            Some(&span),
+            "foo",
+            &[],
+        );
+
+        let mut output = Output::with_writer(Format::Text, Vec::new());
+        output.finding(&finding).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output.out).unwrap(),
+            "Unknown Operation foo at page 3 > XObject /Fm1, stream 1, bytes 10..15\n"
+        );
+    }
+
+    #[test]
+    fn prints_malformed_operations() {
+        let span = Span {
+            stream: 0,
+            byte_range: 2..7,
+        };
+        let finding = Finding::malformed(
+            Path::new("a.pdf"),
+            1,
+            "page 1".into(),
+            1,
+            &span,
+            "unexpected )",
+        );
+
+        let mut output = Output::with_writer(Format::Text, Vec::new());
+        output.finding(&finding).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output.out).unwrap(),
+            "Malformed Operation (unexpected )) at page 1, stream 0, bytes 2..7\n"
+        );
+    }
+
+    #[test]
+    fn prints_operands_after_the_operator() {
+        let operands = [Primitive::Integer(1), Primitive::Name("F1".into())];
+        let finding = Finding::new(
+            Path::new("a.pdf"),
+            1,
+            "page 1".into(),
+            0,
+            None,
+            "Tf",
+            &operands,
+        );
+
+        let mut output = Output::with_writer(Format::Text, Vec::new());
+        output.finding(&finding).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output.out).unwrap(),
+            format!(
+                "Unknown Operation Tf {:?}, {:?}\n",
+                operands[0], operands[1]
+            )
+        );
+    }
+
+    #[test]
+    fn tags_json_records() {
+        let finding = Finding::new(Path::new("a.pdf"), 1, "page 1".into(), 0, None, "foo", &[]);
+        let change = Change {
+            status: Status::New,
+            file: PathBuf::from("a.pdf"),
+            entry: Entry {
+                location: "page 1".into(),
+                operator: "foo".into(),
+                operands: Vec::new(),
+                count: 1,
+            },
+        };
+
+        let mut output = Output::with_writer(Format::Ndjson, Vec::new());
+        output.finding(&finding).unwrap();
+        output.change(&change).unwrap();
+
+        let types = String::from_utf8(output.out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["type"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(types, ["finding", "change"]);
+    }
+
+    #[test]
+    fn frames_json_arrays() {
+        let record = r#"{"type":"finding","file":"a.pdf","page":1,"location":"page 1","index":0,"stream":null,"byte_range":null,"operator":"foo","operands":[],"reason":"unsupported operator"}"#;
+
+        assert_eq!(json(0), "[]\n");
+        assert_eq!(json(1), format!("[{}\n]\n", record));
+        assert_eq!(json(3), format!("[{0}\n,{0}\n,{0}\n]\n", record));
+        for findings in 0..4 {
+            let parsed: serde_json::Value = serde_json::from_str(&json(findings)).unwrap();
+            assert_eq!(parsed.as_array().map(Vec::len), Some(findings));
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use pdf::primitive::Primitive;
+
+#[derive(Default)]
+pub struct Statistics {
+    operators: BTreeMap<String, OperatorStatistics>,
+    malformed: usize,
+}
+
+#[derive(Default)]
+struct OperatorStatistics {
+    typed: usize,
+    unknown: usize,
+    signatures: BTreeMap<String, Occurrences>,
+}
+
+#[derive(Default)]
+struct Occurrences {
+    count: usize,
+    pages: BTreeMap<PathBuf, BTreeSet<usize>>,
+}
+
+impl Statistics {
+    pub fn record_typed(&mut self, operator: &str) {
+        self.operator(operator).typed += 1;
+    }
+
+    pub fn record_malformed(&mut self) {
+        self.malformed += 1;
+    }
+
+    pub fn record_unknown(
+        &mut self,
+        file: &Path,
+        page: usize,
+        operator: &str,
+        operands: &[Primitive],
+    ) {
+        let statistics = self.operator(operator);
+        statistics.unknown += 1;
+
+        let signature = if operands.is_empty() {
+            "-".to_string()
+        } else {
+            operands.iter().map(primitive_type).join(" ")
+        };
+        let occurrences = statistics.signatures.entry(signature).or_default();
+        occurrences.count += 1;
+        occurrences
+            .pages
+            .entry(file.to_path_buf())
+            .or_default()
+            .insert(page);
+    }
+
+    /// Adds the statistics gathered for another file.
+    pub fn merge(&mut self, other: Statistics) {
+        self.malformed += other.malformed;
+        for (operator, other) in other.operators {
+            let statistics = self.operator(&operator);
+            statistics.typed += other.typed;
+            statistics.unknown += other.unknown;
+
+            for (signature, other) in other.signatures {
+                let occurrences = statistics.signatures.entry(signature).or_default();
+                occurrences.count += other.count;
+                for (file, pages) in other.pages {
+                    occurrences.pages.entry(file).or_default().extend(pages);
+                }
+            }
+        }
+    }
+
+    fn operator(&mut self, operator: &str) -> &mut OperatorStatistics {
+        self.operators.entry(operator.to_string()).or_default()
+    }
+
+    /// The number of typed and unknown operations seen, over all operators.
+    fn totals(&self) -> (usize, usize) {
+        self.operators
+            .values()
+            .fold((0, 0), |(typed, unknown), statistics| {
+                (typed + statistics.typed, unknown + statistics.unknown)
+            })
+    }
+
+    pub fn print_summary(&self) {
+        let (typed, unknown) = self.totals();
+
+        println!();
+        println!(
+            "{:<10} {:>10} {:>10} {:>10}",
+            "Operator", "Total", "Typed", "Unknown"
+        );
+        for (operator, statistics) in self.operators_by_count() {
+            println!(
+                "{:<10} {:>10} {:>10} {:>10}",
+                operator,
+                statistics.typed + statistics.unknown,
+                statistics.typed,
+                statistics.unknown
+            );
+        }
+        println!(
+            "{:<10} {:>10} {:>10} {:>10}",
+            "All",
+            typed + unknown,
+            typed,
+            unknown
+        );
+        if self.malformed > 0 {
+            println!("{:<10} {:>10}", "Malformed", self.malformed);
+        }
+
+        if unknown == 0 {
+            return;
+        }
+
+        println!();
+        println!(
+            "{:<10} {:<30} {:>10} Locations",
+            "Operator", "Operands", "Unknown"
+        );
+        for (operator, statistics) in self.operators_by_count() {
+            let signatures = statistics
+                .signatures
+                .iter()
+                .sorted_by_key(|(_, occurrences)| std::cmp::Reverse(occurrences.count));
+
+            for (signature, occurrences) in signatures {
+                let locations = occurrences
+                    .pages
+                    .iter()
+                    .format_with(", ", |(file, pages), f| {
+                        f(&format_args!(
+                            "{} (pages {})",
+                            file.display(),
+                            pages.iter().format(", ")
+                        ))
+                    });
+
+                println!(
+                    "{:<10} {:<30} {:>10} {}",
+                    operator, signature, occurrences.count, locations
+                );
+            }
+        }
+    }
+
+    fn operators_by_count(&self) -> impl Iterator<Item = (&String, &OperatorStatistics)> {
+        self.operators.iter().sorted_by_key(|(_, statistics)| {
+            std::cmp::Reverse(statistics.typed + statistics.unknown)
+        })
+    }
+}
+
+fn primitive_type(primitive: &Primitive) -> &'static str {
+    match primitive {
+        Primitive::Null => "Null",
+        Primitive::Integer(_) => "Integer",
+        Primitive::Number(_) => "Number",
+        Primitive::Boolean(_) => "Boolean",
+        Primitive::String(_) => "String",
+        Primitive::Stream(_) => "Stream",
+        Primitive::Dictionary(_) => "Dictionary",
+        Primitive::Array(_) => "Array",
+        Primitive::Reference(_) => "Reference",
+        Primitive::Name(_) => "Name",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_statistics_of_files() {
+        let mut first = Statistics::default();
+        first.record_typed("q");
+        first.record_typed("Tj");
+        first.record_unknown(Path::new("a.pdf"), 1, "Tj", &[Primitive::Integer(1)]);
+        first.record_unknown(Path::new("a.pdf"), 2, "Tj", &[Primitive::Integer(2)]);
+
+        let mut second = Statistics::default();
+        second.record_typed("q");
+        second.record_typed("Tj");
+        second.record_unknown(Path::new("a.pdf"), 3, "Tj", &[Primitive::Integer(3)]);
+        second.record_unknown(Path::new("b.pdf"), 1, "Tj", &[]);
+        second.record_unknown(Path::new("b.pdf"), 1, "foo", &[Primitive::Null]);
+        second.record_malformed();
+
+        let mut statistics = Statistics::default();
+        statistics.merge(first);
+        statistics.merge(second);
+
+        assert_eq!(statistics.totals(), (4, 5));
+        assert_eq!(statistics.malformed, 1);
+
+        let tj = &statistics.operators["Tj"];
+        assert_eq!((tj.typed, tj.unknown), (2, 4));
+        let integer = &tj.signatures["Integer"];
+        assert_eq!(integer.count, 3);
+        assert_eq!(
+            integer.pages[Path::new("a.pdf")].iter().collect::<Vec<_>>(),
+            [&1, &2, &3]
+        );
+        assert_eq!(tj.signatures["-"].count, 1);
+        assert_eq!(
+            statistics
+                .operators_by_count()
+                .map(|(operator, _)| operator.as_str())
+                .collect::<Vec<_>>(),
+            ["Tj", "q", "foo"]
+        );
+    }
+}
@@ -0,0 +1,995 @@
+use std::sync::Arc;
+
+use pdf::{
+    object::Resolve,
+    primitive::{Dictionary, Primitive},
+};
+
+use crate::{
+    document::{self, get, get_dictionary, get_integer, get_number, get_numbers, stream_data},
+    error::Error,
+    function::Function,
+    icc::{ColorSpaceSignature, IccProfile, D50},
+    ColorRenderingIntent, Operation, UntypedColor,
+};
+
+/// How deeply color spaces can refer to other color spaces, e.g. an Indexed space with a
+/// Separation base.
+const MAX_COLOR_SPACE_DEPTH: usize = 8;
+
+/// The D65 white point of sRGB.
+const D65: [f32; 3] = [0.9505, 1.0, 1.089];
+
+const XYZ_TO_LINEAR_SRGB: [[f32; 3]; 3] = [
+    [3.2406, -1.5372, -0.4986],
+    [-0.9689, 1.8758, 0.0415],
+    [0.0557, -0.2040, 1.0570],
+];
+
+const BRADFORD: [[f32; 3]; 3] = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+const BRADFORD_INVERSE: [[f32; 3]; 3] = [
+    [0.9869929, -0.1470543, 0.1599627],
+    [0.4323053, 0.5183603, 0.0492912],
+    [-0.0085287, 0.0400428, 0.9684867],
+];
+
+/// A color space, section 8.6 of ISO 32000-1.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColorSpace {
+    DeviceGray,
+    DeviceRGB,
+    DeviceCMYK,
+    CalGray {
+        white_point: [f32; 3],
+        black_point: [f32; 3],
+        gamma: f32,
+    },
+    CalRGB {
+        white_point: [f32; 3],
+        black_point: [f32; 3],
+        gamma: [f32; 3],
+        /// `[XA YA ZA XB YB ZB XC YC ZC]`
+        matrix: [f32; 9],
+    },
+    Lab {
+        white_point: [f32; 3],
+        black_point: [f32; 3],
+        /// `[a_min a_max b_min b_max]`
+        range: [f32; 4],
+    },
+    ICCBased {
+        components: usize,
+        /// `/Alternate`, or the device space with the same number of components if there's none.
+        alternate: Box<ColorSpace>,
+        range: Vec<f32>,
+        /// The embedded profile, `None` if it couldn't be read or doesn't match `/N`, in which
+        /// case colors are converted with the alternate space.
+        profile: Option<Arc<IccProfile>>,
+    },
+    Indexed {
+        base: Box<ColorSpace>,
+        hival: usize,
+        /// The colors of the table in the base space, `base.components()` bytes each.
+        lookup: Vec<u8>,
+    },
+    Separation {
+        name: String,
+        alternate: Box<ColorSpace>,
+        tint_transform: Function,
+    },
+    DeviceN {
+        names: Vec<String>,
+        alternate: Box<ColorSpace>,
+        tint_transform: Function,
+    },
+    Pattern {
+        /// The space uncolored patterns' colors are given in.
+        underlying: Option<Box<ColorSpace>>,
+    },
+}
+
+impl ColorSpace {
+    /// Looks up the color space named by a `CS` or `cs` operation, which is either a device
+    /// color space or a `/ColorSpace` resource.
+    pub fn from_resources<R: Resolve>(
+        resolve: &R,
+        resources: &Dictionary,
+        name: &str,
+    ) -> Result<Self, Error> {
+        if let Some(color_space) = Self::from_family(name) {
+            return Ok(color_space);
+        }
+
+        let color_spaces = get_dictionary(resolve, resources, "ColorSpace")?
+            .ok_or_else(|| Error::MissingEntry(format!("ColorSpace /{}", name)))?;
+        let color_space = color_spaces
+            .get(name)
+            .ok_or_else(|| Error::MissingEntry(format!("ColorSpace /{}", name)))?;
+
+        Self::from_primitive(resolve, color_space)
+    }
+
+    /// Parses a color space name or array.
+    pub fn from_primitive<R: Resolve>(resolve: &R, primitive: &Primitive) -> Result<Self, Error> {
+        Self::parse(resolve, primitive, 0)
+    }
+
+    /// Parses the `/CS` or `/ColorSpace` entry of an inline image, which may use the
+    /// abbreviations `/G`, `/RGB`, `/CMYK` and `/I`, or name a `/ColorSpace` resource.
+    pub fn from_inline_image<R: Resolve>(
+        resolve: &R,
+        resources: &Dictionary,
+        primitive: &Primitive,
+    ) -> Result<Self, Error> {
+        let expand = |primitive: &Primitive| match primitive {
+            Primitive::Name(name) => match name.as_str() {
+                "G" => Primitive::Name("DeviceGray".into()),
+                "RGB" => Primitive::Name("DeviceRGB".into()),
+                "CMYK" => Primitive::Name("DeviceCMYK".into()),
+                "I" => Primitive::Name("Indexed".into()),
+                _ => primitive.clone(),
+            },
+            _ => primitive.clone(),
+        };
+
+        match expand(primitive) {
+            Primitive::Name(name) => Self::from_resources(resolve, resources, &name),
+            Primitive::Array(array) => Self::from_primitive(
+                resolve,
+                &Primitive::Array(array.iter().map(expand).collect()),
+            ),
+            primitive => Self::from_primitive(resolve, &primitive),
+        }
+    }
+
+    /// The color spaces that can be given by name alone.
+    fn from_family(name: &str) -> Option<Self> {
+        match name {
+            "DeviceGray" => Some(ColorSpace::DeviceGray),
+            "DeviceRGB" => Some(ColorSpace::DeviceRGB),
+            "DeviceCMYK" => Some(ColorSpace::DeviceCMYK),
+            "Pattern" => Some(ColorSpace::Pattern { underlying: None }),
+            _ => None,
+        }
+    }
+
+    fn parse<R: Resolve>(resolve: &R, primitive: &Primitive, depth: usize) -> Result<Self, Error> {
+        let invalid = || Error::InvalidEntry("ColorSpace".into());
+        if depth > MAX_COLOR_SPACE_DEPTH {
+            return Err(invalid());
+        }
+
+        let array = match document::resolve(resolve, primitive)? {
+            Primitive::Name(name) => return Self::from_family(&name).ok_or_else(invalid),
+            Primitive::Array(array) => array,
+            _ => return Err(invalid()),
+        };
+        let (family, parameters) = match array.split_first() {
+            Some((Primitive::Name(family), parameters)) => (family.as_str(), parameters),
+            _ => return Err(invalid()),
+        };
+        let parameter = |i: usize| -> Result<Primitive, Error> {
+            document::resolve(resolve, parameters.get(i).ok_or_else(invalid)?)
+        };
+        let dictionary = |i: usize| -> Result<Dictionary, Error> {
+            match parameter(i)? {
+                Primitive::Dictionary(dictionary) => Ok(dictionary),
+                _ => Err(invalid()),
+            }
+        };
+
+        match family {
+            "CalGray" => {
+                let dictionary = dictionary(0)?;
+                let (white_point, black_point) = white_and_black_points(resolve, &dictionary)?;
+
+                Ok(ColorSpace::CalGray {
+                    white_point,
+                    black_point,
+                    gamma: get_number(resolve, &dictionary, "Gamma")?.unwrap_or(1.0),
+                })
+            }
+            "CalRGB" => {
+                let dictionary = dictionary(0)?;
+                let (white_point, black_point) = white_and_black_points(resolve, &dictionary)?;
+
+                Ok(ColorSpace::CalRGB {
+                    white_point,
+                    black_point,
+                    gamma: numbers(resolve, &dictionary, "Gamma", [1.0; 3])?,
+                    matrix: numbers(
+                        resolve,
+                        &dictionary,
+                        "Matrix",
+                        [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+                    )?,
+                })
+            }
+            "Lab" => {
+                let dictionary = dictionary(0)?;
+                let (white_point, black_point) = white_and_black_points(resolve, &dictionary)?;
+
+                Ok(ColorSpace::Lab {
+                    white_point,
+                    black_point,
+                    range: numbers(
+                        resolve,
+                        &dictionary,
+                        "Range",
+                        [-100.0, 100.0, -100.0, 100.0],
+                    )?,
+                })
+            }
+            "ICCBased" => {
+                let stream = match parameter(0)? {
+                    Primitive::Stream(stream) => stream,
+                    _ => return Err(invalid()),
+                };
+                let components = match get_integer(resolve, &stream.info, "N")? {
+                    Some(n @ 1) | Some(n @ 3) | Some(n @ 4) => n as usize,
+                    _ => return Err(Error::InvalidEntry("ICCBased /N".into())),
+                };
+                let alternate = match get(resolve, &stream.info, "Alternate")? {
+                    Some(alternate) => Self::parse(resolve, &alternate, depth + 1)?,
+                    None => match components {
+                        1 => ColorSpace::DeviceGray,
+                        3 => ColorSpace::DeviceRGB,
+                        _ => ColorSpace::DeviceCMYK,
+                    },
+                };
+                let range = get_numbers(resolve, &stream.info, "Range")?
+                    .filter(|range| range.len() == 2 * components)
+                    .unwrap_or_else(|| [0.0, 1.0].repeat(components));
+
+                let profile = IccProfile::parse(&stream_data(resolve, &stream)?)
+                    .ok()
+                    .filter(|profile| profile.components() == Some(components));
+
+                Ok(ColorSpace::ICCBased {
+                    components,
+                    alternate: Box::new(alternate),
+                    range,
+                    profile: profile.map(Arc::new),
+                })
+            }
+            "Indexed" => {
+                let base = Self::parse(resolve, &parameter(0)?, depth + 1)?;
+                let hival = match parameter(1)? {
+                    Primitive::Integer(hival @ 0..=255) => hival as usize,
+                    _ => return Err(Error::InvalidEntry("Indexed hival".into())),
+                };
+                let lookup = match parameter(2)? {
+                    Primitive::String(lookup) => lookup.as_bytes().to_vec(),
+                    Primitive::Stream(stream) => stream_data(resolve, &stream)?,
+                    _ => return Err(Error::InvalidEntry("Indexed lookup".into())),
+                };
+                if lookup.len() < (hival + 1) * base.components() {
+                    return Err(Error::InvalidEntry("Indexed lookup".into()));
+                }
+
+                Ok(ColorSpace::Indexed {
+                    base: Box::new(base),
+                    hival,
+                    lookup,
+                })
+            }
+            "Separation" => {
+                let name = match parameter(0)? {
+                    Primitive::Name(name) => name,
+                    _ => return Err(invalid()),
+                };
+
+                Ok(ColorSpace::Separation {
+                    name,
+                    alternate: Box::new(Self::parse(resolve, &parameter(1)?, depth + 1)?),
+                    tint_transform: Function::from_primitive(resolve, &parameter(2)?)?,
+                })
+            }
+            "DeviceN" => {
+                let names = match parameter(0)? {
+                    Primitive::Array(names) => names
+                        .iter()
+                        .map(|name| match name {
+                            Primitive::Name(name) => Ok(name.clone()),
+                            _ => Err(invalid()),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                    _ => return Err(invalid()),
+                };
+
+                Ok(ColorSpace::DeviceN {
+                    names,
+                    alternate: Box::new(Self::parse(resolve, &parameter(1)?, depth + 1)?),
+                    tint_transform: Function::from_primitive(resolve, &parameter(2)?)?,
+                })
+            }
+            "Pattern" => Ok(ColorSpace::Pattern {
+                underlying: match parameters.first() {
+                    Some(underlying) => {
+                        Some(Box::new(Self::parse(resolve, underlying, depth + 1)?))
+                    }
+                    None => None,
+                },
+            }),
+            family => Self::from_family(family).ok_or_else(invalid),
+        }
+    }
+
+    /// The number of components of a color in this space. Pattern spaces count the components
+    /// of their underlying space.
+    pub fn components(&self) -> usize {
+        match self {
+            ColorSpace::DeviceGray
+            | ColorSpace::CalGray { .. }
+            | ColorSpace::Indexed { .. }
+            | ColorSpace::Separation { .. } => 1,
+            ColorSpace::DeviceRGB | ColorSpace::CalRGB { .. } | ColorSpace::Lab { .. } => 3,
+            ColorSpace::DeviceCMYK => 4,
+            ColorSpace::ICCBased { components, .. } => *components,
+            ColorSpace::DeviceN { names, .. } => names.len(),
+            ColorSpace::Pattern { underlying } => underlying
+                .as_ref()
+                .map_or(0, |underlying| underlying.components()),
+        }
+    }
+
+    /// The range of each component.
+    pub fn ranges(&self) -> Vec<(f32, f32)> {
+        match self {
+            ColorSpace::Lab { range, .. } => {
+                vec![(0.0, 100.0), (range[0], range[1]), (range[2], range[3])]
+            }
+            ColorSpace::ICCBased { range, .. } => range
+                .chunks_exact(2)
+                .map(|range| (range[0], range[1]))
+                .collect(),
+            ColorSpace::Indexed { hival, .. } => vec![(0.0, *hival as f32)],
+            color_space => vec![(0.0, 1.0); color_space.components()],
+        }
+    }
+
+    /// The color `CS` and `cs` select, section 8.6.8 of ISO 32000-1.
+    pub fn initial_color(&self) -> Vec<f32> {
+        match self {
+            ColorSpace::DeviceCMYK => vec![0.0, 0.0, 0.0, 1.0],
+            ColorSpace::Separation { .. } | ColorSpace::DeviceN { .. } => {
+                vec![1.0; self.components()]
+            }
+            ColorSpace::Pattern { .. } => Vec::new(),
+            color_space => color_space
+                .ranges()
+                .iter()
+                .map(|(min, max)| 0f32.max(*min).min(*max))
+                .collect(),
+        }
+    }
+
+    /// Converts a color to sRGB, `None` for colors that don't paint anything, such as a
+    /// Separation named `None`, or that can't be converted, such as patterns.
+    pub fn to_rgb(&self, color: &[f32], intent: ColorRenderingIntent) -> Option<[f32; 3]> {
+        let component = |i: usize| clamp(color.get(i).copied().unwrap_or(0.0));
+
+        match self {
+            ColorSpace::DeviceGray => {
+                let gray = component(0);
+                Some([gray, gray, gray])
+            }
+            ColorSpace::DeviceRGB => Some([component(0), component(1), component(2)]),
+            ColorSpace::DeviceCMYK => {
+                let k = component(3);
+                Some([
+                    1.0 - (component(0) + k).min(1.0),
+                    1.0 - (component(1) + k).min(1.0),
+                    1.0 - (component(2) + k).min(1.0),
+                ])
+            }
+            ColorSpace::CalGray {
+                white_point,
+                black_point,
+                gamma,
+            } => {
+                let a = component(0).powf(*gamma);
+                let xyz = [white_point[0] * a, white_point[1] * a, white_point[2] * a];
+                Some(xyz_to_rgb(xyz, white_point, black_point, intent))
+            }
+            ColorSpace::CalRGB {
+                white_point,
+                black_point,
+                gamma,
+                matrix,
+            } => {
+                let abc = [
+                    component(0).powf(gamma[0]),
+                    component(1).powf(gamma[1]),
+                    component(2).powf(gamma[2]),
+                ];
+                let xyz = [
+                    matrix[0] * abc[0] + matrix[3] * abc[1] + matrix[6] * abc[2],
+                    matrix[1] * abc[0] + matrix[4] * abc[1] + matrix[7] * abc[2],
+                    matrix[2] * abc[0] + matrix[5] * abc[1] + matrix[8] * abc[2],
+                ];
+                Some(xyz_to_rgb(xyz, white_point, black_point, intent))
+            }
+            ColorSpace::Lab {
+                white_point,
+                black_point,
+                range,
+            } => {
+                let l = color.first().copied().unwrap_or(0.0).clamp(0.0, 100.0);
+                let a = color
+                    .get(1)
+                    .copied()
+                    .unwrap_or(0.0)
+                    .max(range[0])
+                    .min(range[1]);
+                let b = color
+                    .get(2)
+                    .copied()
+                    .unwrap_or(0.0)
+                    .max(range[2])
+                    .min(range[3]);
+
+                let m = (l + 16.0) / 116.0;
+                let l = m + a / 500.0;
+                let n = m - b / 200.0;
+                let g = |x: f32| {
+                    if x >= 6.0 / 29.0 {
+                        x * x * x
+                    } else {
+                        108.0 / 841.0 * (x - 4.0 / 29.0)
+                    }
+                };
+                let xyz = [
+                    white_point[0] * g(l),
+                    white_point[1] * g(m),
+                    white_point[2] * g(n),
+                ];
+                Some(xyz_to_rgb(xyz, white_point, black_point, intent))
+            }
+            // Profiles without a transform for any intent are converted with the alternate space.
+            ColorSpace::ICCBased {
+                profile: Some(profile),
+                range,
+                alternate,
+                ..
+            } => match profile.to_xyz(&normalize(color, range), intent) {
+                Some(xyz) => Some(xyz_to_rgb(xyz, &D50, &[0.0; 3], intent)),
+                None => alternate.to_rgb(color, intent),
+            },
+            ColorSpace::ICCBased { alternate, .. } => alternate.to_rgb(color, intent),
+            ColorSpace::Indexed { .. } => {
+                let (base, color) = self.lookup(color)?;
+                base.to_rgb(&color, intent)
+            }
+            ColorSpace::Separation { name, .. } if name == "None" => None,
+            // `All` is every colorant, including black, so no tint is white and full tint is
+            // black.
+            ColorSpace::Separation { name, .. } if name == "All" => {
+                let gray = 1.0 - component(0);
+                Some([gray, gray, gray])
+            }
+            ColorSpace::Separation {
+                alternate,
+                tint_transform,
+                ..
+            }
+            | ColorSpace::DeviceN {
+                alternate,
+                tint_transform,
+                ..
+            } => alternate.to_rgb(&tint_transform.eval(color), intent),
+            ColorSpace::Pattern { .. } => None,
+        }
+    }
+
+    /// Converts a color to DeviceCMYK. Device colors use the conversions from section 10.3 of
+    /// ISO 32000-1, with full black generation and undercolor removal, and CIE based colors go
+    /// through sRGB.
+    pub fn to_cmyk(&self, color: &[f32], intent: ColorRenderingIntent) -> Option<[f32; 4]> {
+        let component = |i: usize| clamp(color.get(i).copied().unwrap_or(0.0));
+
+        match self {
+            ColorSpace::DeviceCMYK => {
+                Some([component(0), component(1), component(2), component(3)])
+            }
+            ColorSpace::DeviceGray => Some([0.0, 0.0, 0.0, 1.0 - component(0)]),
+            // CMYK profiles describe device CMYK values, so they're used as they are.
+            ColorSpace::ICCBased {
+                profile: Some(profile),
+                ..
+            } if profile.color_space == ColorSpaceSignature::Cmyk => {
+                Some([component(0), component(1), component(2), component(3)])
+            }
+            ColorSpace::ICCBased {
+                profile: None,
+                alternate,
+                ..
+            } => alternate.to_cmyk(color, intent),
+            ColorSpace::Indexed { .. } => {
+                let (base, color) = self.lookup(color)?;
+                base.to_cmyk(&color, intent)
+            }
+            ColorSpace::Separation { name, .. } if name == "None" => None,
+            ColorSpace::Separation { name, .. } if name == "All" => {
+                let tint = component(0);
+                Some([tint, tint, tint, tint])
+            }
+            ColorSpace::Separation {
+                alternate,
+                tint_transform,
+                ..
+            }
+            | ColorSpace::DeviceN {
+                alternate,
+                tint_transform,
+                ..
+            } => alternate.to_cmyk(&tint_transform.eval(color), intent),
+            ColorSpace::Pattern { .. } => None,
+            color_space => {
+                let [r, g, b] = color_space.to_rgb(color, intent)?;
+                let k = (1.0 - r).min(1.0 - g).min(1.0 - b);
+                Some([1.0 - r - k, 1.0 - g - k, 1.0 - b - k, k])
+            }
+        }
+    }
+
+    /// Looks up an Indexed color in the table, giving the base space and the color in it.
+    pub(crate) fn lookup(&self, color: &[f32]) -> Option<(&ColorSpace, Vec<f32>)> {
+        match self {
+            ColorSpace::Indexed {
+                base,
+                hival,
+                lookup,
+            } => {
+                let index = color.first()?.round().max(0.0).min(*hival as f32) as usize;
+                let components = base.components();
+                let entry = lookup.get(index * components..(index + 1) * components)?;
+
+                let color = entry
+                    .iter()
+                    .zip(base.ranges())
+                    .map(|(byte, (min, max))| min + f32::from(*byte) / 255.0 * (max - min))
+                    .collect();
+                Some((base, color))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A color value and the space it's given in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Color {
+    pub color_space: ColorSpace,
+    pub components: Vec<f32>,
+    /// The pattern selected by `SCN` or `scn` in a Pattern color space.
+    pub pattern: Option<String>,
+}
+
+impl Default for Color {
+    /// The initial color of a page, black in DeviceGray.
+    fn default() -> Self {
+        Color::new(ColorSpace::DeviceGray, vec![0.0])
+    }
+}
+
+impl Color {
+    pub fn new(color_space: ColorSpace, components: Vec<f32>) -> Self {
+        Color {
+            color_space,
+            components,
+            pattern: None,
+        }
+    }
+
+    /// The color set by a color operation, given the current color space for the kind of
+    /// operation, stroking or nonstroking. `None` for operations that don't set a color.
+    pub fn from_operation(operation: &Operation, current: &ColorSpace) -> Option<Self> {
+        match operation {
+            Operation::SetGrayLevelForStrokingOperations(gray)
+            | Operation::SetGrayLevelForNonStrokingOperations(gray) => {
+                Some(Color::new(ColorSpace::DeviceGray, vec![*gray]))
+            }
+            Operation::SetRGBColorForStrokingOperations(r, g, b)
+            | Operation::SetRGBColorForNonStrokingOperations(r, g, b) => {
+                Some(Color::new(ColorSpace::DeviceRGB, vec![*r, *g, *b]))
+            }
+            Operation::SetCMYKColorForStrokingOperations(c, m, y, k)
+            | Operation::SetCMYKColorForNonStrokingOperations(c, m, y, k) => {
+                Some(Color::new(ColorSpace::DeviceCMYK, vec![*c, *m, *y, *k]))
+            }
+            Operation::SetColorForStrokingOperations(color)
+            | Operation::SetColorForNonStrokingOperations(color) => {
+                let components = match color {
+                    UntypedColor::DeviceGrayCalGrayOrIndexed(a) => vec![*a],
+                    UntypedColor::DeviceRGBCalRGBOrLab(a, b, c) => vec![*a, *b, *c],
+                    UntypedColor::DeviceCMYK(a, b, c, d) => vec![*a, *b, *c, *d],
+                };
+                Some(Color::new(current.clone(), components))
+            }
+            Operation::SetColorForStrokingOperationsICCBasedAndSpecialColorSpaces { cs, name }
+            | Operation::SetColorForNonStrokingOperationsICCBasedAndSpecialColorSpaces {
+                cs,
+                name,
+            } => Some(Color {
+                color_space: current.clone(),
+                components: cs.clone(),
+                pattern: name.as_ref().map(|name| name.0.to_string()),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn to_rgb(&self, intent: ColorRenderingIntent) -> Option<[f32; 3]> {
+        self.color_space.to_rgb(&self.components, intent)
+    }
+
+    pub fn to_cmyk(&self, intent: ColorRenderingIntent) -> Option<[f32; 4]> {
+        self.color_space.to_cmyk(&self.components, intent)
+    }
+}
+
+/// Maps each component from its range to `[0, 1]`.
+fn normalize(color: &[f32], range: &[f32]) -> Vec<f32> {
+    color
+        .iter()
+        .zip(range.chunks_exact(2))
+        .map(|(value, range)| {
+            if range[1] > range[0] {
+                (value - range[0]) / (range[1] - range[0])
+            } else {
+                *value
+            }
+        })
+        .collect()
+}
+
+fn clamp(value: f32) -> f32 {
+    value.clamp(0.0, 1.0)
+}
+
+fn numbers<R: Resolve, const N: usize>(
+    resolve: &R,
+    dictionary: &Dictionary,
+    key: &str,
+    default: [f32; N],
+) -> Result<[f32; N], Error> {
+    match get_numbers(resolve, dictionary, key)? {
+        Some(values) if values.len() == N => {
+            let mut array = [0.0; N];
+            array.copy_from_slice(&values);
+            Ok(array)
+        }
+        Some(_) => Err(Error::InvalidEntry(key.into())),
+        None => Ok(default),
+    }
+}
+
+fn white_and_black_points<R: Resolve>(
+    resolve: &R,
+    dictionary: &Dictionary,
+) -> Result<([f32; 3], [f32; 3]), Error> {
+    if dictionary.get("WhitePoint").is_none() {
+        return Err(Error::MissingEntry("WhitePoint".into()));
+    }
+
+    Ok((
+        numbers(resolve, dictionary, "WhitePoint", [0.0; 3])?,
+        numbers(resolve, dictionary, "BlackPoint", [0.0; 3])?,
+    ))
+}
+
+fn multiply(matrix: &[[f32; 3]; 3], vector: [f32; 3]) -> [f32; 3] {
+    let row =
+        |i: usize| matrix[i][0] * vector[0] + matrix[i][1] * vector[1] + matrix[i][2] * vector[2];
+    [row(0), row(1), row(2)]
+}
+
+/// Converts CIE XYZ relative to `white_point` to sRGB. The colorimetric intents keep the
+/// source's black point, and only relative colorimetric adapts the white point to sRGB's:
+/// absolute colorimetric reproduces the color as measured. Perceptual and saturation adapt the
+/// white point and also map the source's black point to sRGB's.
+fn xyz_to_rgb(
+    xyz: [f32; 3],
+    white_point: &[f32; 3],
+    black_point: &[f32; 3],
+    intent: ColorRenderingIntent,
+) -> [f32; 3] {
+    let xyz = match intent {
+        ColorRenderingIntent::AbsoluteColorimetric => xyz,
+        ColorRenderingIntent::RelativeColorimetric => adapt(xyz, white_point),
+        ColorRenderingIntent::Perceptual | ColorRenderingIntent::Saturation => {
+            let mut compensated = [0.0; 3];
+            for i in 0..3 {
+                let scale = white_point[i] - black_point[i];
+                compensated[i] = if scale > 0.0 {
+                    (xyz[i] - black_point[i]) * white_point[i] / scale
+                } else {
+                    xyz[i]
+                };
+            }
+            adapt(compensated, white_point)
+        }
+    };
+
+    let linear = multiply(&XYZ_TO_LINEAR_SRGB, xyz);
+    let encode = |c: f32| {
+        let c = clamp(c);
+        if c <= 0.003_130_8 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    };
+    [encode(linear[0]), encode(linear[1]), encode(linear[2])]
+}
+
+/// Adapts XYZ from `white_point` to D65 with the Bradford transform.
+fn adapt(xyz: [f32; 3], white_point: &[f32; 3]) -> [f32; 3] {
+    let source = multiply(&BRADFORD, *white_point);
+    let destination = multiply(&BRADFORD, D65);
+    let cone = multiply(&BRADFORD, xyz);
+
+    let scaled = [
+        cone[0] * destination[0] / source[0],
+        cone[1] * destination[1] / source[1],
+        cone[2] * destination[2] / source[2],
+    ];
+    multiply(&BRADFORD_INVERSE, scaled)
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::{object::NoResolve, primitive::PdfString};
+
+    use super::*;
+    use crate::{
+        fixtures::{dictionary, name, stream},
+        icc::DeviceClass,
+    };
+
+    const INTENT: ColorRenderingIntent = ColorRenderingIntent::RelativeColorimetric;
+
+    fn close(a: &[f32], b: &[f32]) -> bool {
+        a.iter().zip(b).all(|(a, b)| (a - b).abs() < 0.01)
+    }
+
+    #[test]
+    fn converts_colors() {
+        assert_eq!(
+            ColorSpace::DeviceCMYK.to_rgb(&[0.0, 1.0, 0.5, 0.25], INTENT),
+            Some([0.75, 0.0, 0.25])
+        );
+        assert_eq!(
+            ColorSpace::DeviceRGB.to_cmyk(&[1.0, 0.5, 0.0], INTENT),
+            Some([0.0, 0.5, 1.0, 0.0])
+        );
+
+        // The white point of Lab is white in sRGB, whatever the illuminant.
+        let lab = ColorSpace::Lab {
+            white_point: [0.9642, 1.0, 0.8249],
+            black_point: [0.0; 3],
+            range: [-100.0, 100.0, -100.0, 100.0],
+        };
+        assert!(close(
+            &lab.to_rgb(&[100.0, 0.0, 0.0], INTENT).unwrap(),
+            &[1.0; 3]
+        ));
+        let absolute = lab
+            .to_rgb(
+                &[100.0, 0.0, 0.0],
+                ColorRenderingIntent::AbsoluteColorimetric,
+            )
+            .unwrap();
+        assert!(absolute[2] < 0.95);
+
+        let indexed = ColorSpace::from_primitive(
+            &NoResolve,
+            &Primitive::Array(vec![
+                Primitive::Name("Indexed".into()),
+                Primitive::Name("DeviceRGB".into()),
+                Primitive::Integer(1),
+                Primitive::String(PdfString::new(vec![255, 0, 0, 0, 0, 255])),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(indexed.to_rgb(&[1.0], INTENT), Some([0.0, 0.0, 1.0]));
+        assert_eq!(indexed.to_cmyk(&[0.0], INTENT), Some([0.0, 1.0, 1.0, 0.0]));
+    }
+
+    fn numbers(numbers: &[f32]) -> Primitive {
+        Primitive::Array(numbers.iter().map(|n| Primitive::Number(*n)).collect())
+    }
+
+    fn parse(array: Vec<Primitive>) -> ColorSpace {
+        ColorSpace::from_primitive(&NoResolve, &Primitive::Array(array)).unwrap()
+    }
+
+    #[test]
+    fn expands_abbreviations_only_in_inline_images() {
+        let mut color_spaces = Dictionary::new();
+        color_spaces.insert("RGB".to_string(), name("DeviceCMYK"));
+        let mut resources = Dictionary::new();
+        resources.insert(
+            "ColorSpace".to_string(),
+            Primitive::Dictionary(color_spaces),
+        );
+
+        assert_eq!(
+            ColorSpace::from_resources(&NoResolve, &resources, "RGB").unwrap(),
+            ColorSpace::DeviceCMYK
+        );
+        assert!(ColorSpace::from_resources(&NoResolve, &resources, "G").is_err());
+
+        assert_eq!(
+            ColorSpace::from_inline_image(&NoResolve, &resources, &name("G")).unwrap(),
+            ColorSpace::DeviceGray
+        );
+        assert_eq!(
+            ColorSpace::from_inline_image(&NoResolve, &resources, &name("DeviceRGB")).unwrap(),
+            ColorSpace::DeviceRGB
+        );
+        let indexed = Primitive::Array(vec![
+            name("I"),
+            name("RGB"),
+            Primitive::Integer(0),
+            Primitive::String(PdfString::new(vec![255, 0, 0])),
+        ]);
+        assert_eq!(
+            ColorSpace::from_inline_image(&NoResolve, &resources, &indexed).unwrap(),
+            ColorSpace::Indexed {
+                base: Box::new(ColorSpace::DeviceRGB),
+                hival: 0,
+                lookup: vec![255, 0, 0],
+            }
+        );
+        assert!(ColorSpace::from_primitive(&NoResolve, &indexed).is_err());
+    }
+
+    #[test]
+    fn converts_cie_based_and_special_colors() {
+        let d65 = numbers(&[0.9505, 1.0, 1.089]);
+
+        let cal_gray = parse(vec![
+            name("CalGray"),
+            dictionary(vec![
+                ("WhitePoint", d65.clone()),
+                ("Gamma", Primitive::Number(2.2)),
+            ]),
+        ]);
+        assert!(close(&cal_gray.to_rgb(&[1.0], INTENT).unwrap(), &[1.0; 3]));
+        assert!(close(&cal_gray.to_rgb(&[0.0], INTENT).unwrap(), &[0.0; 3]));
+        let [r, g, b] = cal_gray.to_rgb(&[0.5], INTENT).unwrap();
+        assert!(close(&[r, g], &[b, b]) && b > 0.1 && b < 0.9);
+
+        // CalRGB with sRGB's primaries and white point.
+        let cal_rgb_array = vec![
+            name("CalRGB"),
+            dictionary(vec![
+                ("WhitePoint", d65),
+                ("Gamma", numbers(&[2.2, 2.2, 2.2])),
+                (
+                    "Matrix",
+                    numbers(&[
+                        0.4124, 0.2126, 0.0193, 0.3576, 0.7152, 0.1192, 0.1805, 0.0722, 0.9505,
+                    ]),
+                ),
+            ]),
+        ];
+        let cal_rgb = parse(cal_rgb_array.clone());
+        assert!(close(
+            &cal_rgb.to_rgb(&[1.0, 0.0, 0.0], INTENT).unwrap(),
+            &[1.0, 0.0, 0.0]
+        ));
+        assert!(close(
+            &cal_rgb.to_rgb(&[0.0, 0.0, 1.0], INTENT).unwrap(),
+            &[0.0, 0.0, 1.0]
+        ));
+        assert!(close(
+            &cal_rgb.to_rgb(&[1.0; 3], INTENT).unwrap(),
+            &[1.0; 3]
+        ));
+
+        // A Type 2 tint transform from a spot color to orange.
+        let separation = parse(vec![
+            name("Separation"),
+            name("Spot"),
+            name("DeviceCMYK"),
+            dictionary(vec![
+                ("FunctionType", Primitive::Integer(2)),
+                ("Domain", numbers(&[0.0, 1.0])),
+                ("C0", numbers(&[0.0; 4])),
+                ("C1", numbers(&[0.0, 0.5, 1.0, 0.0])),
+                ("N", Primitive::Integer(1)),
+            ]),
+        ]);
+        assert_eq!(
+            separation.to_cmyk(&[0.5], INTENT),
+            Some([0.0, 0.25, 0.5, 0.0])
+        );
+        assert_eq!(separation.to_rgb(&[0.5], INTENT), Some([1.0, 0.75, 0.5]));
+
+        // A Type 4 tint transform mapping cyan as it is and the spot color to half yellow.
+        let device_n = parse(vec![
+            name("DeviceN"),
+            Primitive::Array(vec![name("Cyan"), name("Spot")]),
+            name("DeviceCMYK"),
+            stream(
+                vec![
+                    ("FunctionType", Primitive::Integer(4)),
+                    ("Domain", numbers(&[0.0, 1.0, 0.0, 1.0])),
+                    ("Range", numbers(&[0.0, 1.0].repeat(4))),
+                ],
+                b"{ 0.5 mul 0 exch 0 }",
+            ),
+        ]);
+        assert_eq!(device_n.components(), 2);
+        assert_eq!(
+            device_n.to_cmyk(&[1.0, 0.5], INTENT),
+            Some([1.0, 0.0, 0.25, 0.0])
+        );
+        assert_eq!(device_n.to_rgb(&[1.0, 0.5], INTENT), Some([0.0, 1.0, 0.75]));
+
+        // Profiles that can't be read fall back to `/Alternate`, or to the device space with
+        // the same number of components.
+        let icc_based = |entries| parse(vec![name("ICCBased"), stream(entries, b"not a profile")]);
+        let alternate = icc_based(vec![
+            ("N", Primitive::Integer(3)),
+            ("Alternate", Primitive::Array(cal_rgb_array)),
+        ]);
+        assert!(matches!(
+            &alternate,
+            ColorSpace::ICCBased { profile: None, alternate, .. } if **alternate == cal_rgb
+        ));
+        assert!(close(
+            &alternate.to_rgb(&[1.0, 0.0, 0.0], INTENT).unwrap(),
+            &[1.0, 0.0, 0.0]
+        ));
+
+        let cmyk = icc_based(vec![("N", Primitive::Integer(4))]);
+        assert!(matches!(
+            &cmyk,
+            ColorSpace::ICCBased { profile: None, alternate, .. }
+                if **alternate == ColorSpace::DeviceCMYK
+        ));
+        assert_eq!(
+            cmyk.to_rgb(&[0.0, 1.0, 0.5, 0.25], INTENT),
+            Some([0.75, 0.0, 0.25])
+        );
+        assert_eq!(
+            cmyk.to_cmyk(&[0.0, 1.0, 0.5, 0.25], INTENT),
+            Some([0.0, 1.0, 0.5, 0.25])
+        );
+
+        // So do profiles that were read but have no transform to the connection space.
+        let untransformed = ColorSpace::ICCBased {
+            components: 3,
+            alternate: Box::new(ColorSpace::DeviceRGB),
+            range: vec![0.0, 1.0, 0.0, 1.0, 0.0, 1.0],
+            profile: Some(Arc::new(IccProfile {
+                version: (4, 3, 0),
+                device_class: DeviceClass::Display,
+                color_space: ColorSpaceSignature::Rgb,
+                pcs: ColorSpaceSignature::Xyz,
+                rendering_intent: INTENT,
+                cmm: String::new(),
+                manufacturer: String::new(),
+                creator: String::new(),
+                profile_id: [0; 16],
+                description: None,
+                copyright: None,
+                media_white_point: None,
+                transforms: [None, None, None],
+            })),
+        };
+        assert_eq!(
+            untransformed.to_rgb(&[1.0, 0.0, 0.5], INTENT),
+            Some([1.0, 0.0, 0.5])
+        );
+    }
+}
@@ -0,0 +1,746 @@
+use std::{collections::HashMap, mem, ops::Range};
+
+use pdf::object::Resolve;
+
+use crate::{
+    color::Color,
+    document::{RawDocument, RawPage},
+    error::Error,
+    font::Font,
+    geometry::{Matrix, Rect},
+    graphics_state::{GraphicsState, GraphicsStateStack},
+    normalize_operation,
+    raster::Path,
+    text::{breaks_line, WORD_GAP},
+    text_state::TextState,
+    writer::{operator, write_operation},
+    Name, Operation, TextOrGlyphPositioning,
+};
+
+/// More edits than this between two operation streams, after their common start and end, aren't
+/// looked for: what's between is reported as changed as a whole.
+const MAX_EDITS: usize = 1000;
+
+/// How far apart positions can be, in default user space units, and still be the same.
+const TOLERANCE: f32 = 0.01;
+
+/// A difference between two operation streams, by index into each.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Inserted {
+        new: usize,
+    },
+    Deleted {
+        old: usize,
+    },
+    /// An operation replaced by one with the same operator and different operands.
+    Changed {
+        old: usize,
+        new: usize,
+    },
+}
+
+/// A difference between the operations of two pages, with the operations as they'd be written.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperationChange {
+    pub change: Change,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// The text shown on one line in one font, size and color.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextRun {
+    pub text: String,
+    /// Where the run starts on its baseline, in default user space.
+    pub origin: (f32, f32),
+    /// The name of the `/Font` resource.
+    pub font: Option<String>,
+    pub font_size: f32,
+    pub color: Color,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TextChange {
+    Inserted(TextRun),
+    Deleted(TextRun),
+    /// A run with different text in place of another.
+    Edited {
+        old: TextRun,
+        new: TextRun,
+    },
+    /// The same text shown somewhere else.
+    Moved {
+        old: TextRun,
+        new: TextRun,
+    },
+    /// The same text shown in a different font, size or color.
+    Restyled {
+        old: TextRun,
+        new: TextRun,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphicKind {
+    Path,
+    /// An XObject painted by `Do`, by resource name.
+    XObject(String),
+    InlineImage,
+}
+
+/// A path, image or form painted by a page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Graphic {
+    pub kind: GraphicKind,
+    /// In default user space. The bounds of an XObject are the unit square it's painted in.
+    pub bounds: Rect,
+    pub fill: Option<Color>,
+    pub stroke: Option<Color>,
+    /// What the graphic looks like wherever it's painted: the path relative to its bounds, or
+    /// the size and data of an image, in hundredths of a unit.
+    shape: Vec<i64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphicChange {
+    Inserted(Graphic),
+    Deleted(Graphic),
+    /// The same graphic painted somewhere else.
+    Moved {
+        old: Graphic,
+        new: Graphic,
+    },
+    /// The same graphic painted in different colors.
+    Recolored {
+        old: Graphic,
+        new: Graphic,
+    },
+}
+
+/// The differences between two pages, both between their operations and between the text and
+/// graphics they paint.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PageDiff {
+    /// One based page number.
+    pub number: usize,
+    pub operations: Vec<OperationChange>,
+    pub text: Vec<TextChange>,
+    pub graphics: Vec<GraphicChange>,
+}
+
+impl PageDiff {
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty() && self.text.is_empty() && self.graphics.is_empty()
+    }
+}
+
+/// Compares two operation streams. Operations are the same when they're written the same way,
+/// so differences in formatting and in how numbers are written don't count.
+pub fn diff_operations(old: &[Operation], new: &[Operation]) -> Vec<Change> {
+    let old_written: Vec<_> = old.iter().map(write).collect();
+    let new_written: Vec<_> = new.iter().map(write).collect();
+    changes(&old_written, &new_written, |old_index, new_index| {
+        operator(&old[old_index]) == operator(&new[new_index])
+    })
+}
+
+/// Compares the content of two pages. Form XObjects are compared by name and placement, not by
+/// their own content.
+pub fn diff_pages<A: Resolve, B: Resolve>(
+    old_resolve: &A,
+    old: &RawPage,
+    new_resolve: &B,
+    new: &RawPage,
+) -> Result<PageDiff, Error> {
+    let mut diff = diff_content(
+        &Content::load(old_resolve, old)?,
+        &Content::load(new_resolve, new)?,
+    );
+    diff.number = new.number;
+    Ok(diff)
+}
+
+/// Compares the pages of two documents by page number. Pages only one of them has are compared
+/// with an empty page.
+pub fn diff_documents<A: Resolve, B: Resolve>(
+    old_resolve: &A,
+    old: &RawDocument,
+    new_resolve: &B,
+    new: &RawDocument,
+) -> Result<Vec<PageDiff>, Error> {
+    let pages = old.pages.len().max(new.pages.len());
+    let mut diffs = Vec::with_capacity(pages);
+    for number in 0..pages {
+        let old = match old.pages.get(number) {
+            Some(page) => Content::load(old_resolve, page)?,
+            None => Content::default(),
+        };
+        let new = match new.pages.get(number) {
+            Some(page) => Content::load(new_resolve, page)?,
+            None => Content::default(),
+        };
+        diffs.push(PageDiff {
+            number: number + 1,
+            ..diff_content(&old, &new)
+        });
+    }
+
+    Ok(diffs)
+}
+
+fn diff_content(old: &Content, new: &Content) -> PageDiff {
+    let same_operator =
+        |old_index: usize, new_index: usize| old.operators[old_index] == new.operators[new_index];
+    let operations = changes(&old.operations, &new.operations, same_operator)
+        .into_iter()
+        .map(|change| {
+            let (old_index, new_index) = match change {
+                Change::Inserted { new } => (None, Some(new)),
+                Change::Deleted { old } => (Some(old), None),
+                Change::Changed { old, new } => (Some(old), Some(new)),
+            };
+            OperationChange {
+                change,
+                old: old_index.map(|index| old.operations[index].clone()),
+                new: new_index.map(|index| new.operations[index].clone()),
+            }
+        })
+        .collect();
+
+    PageDiff {
+        number: 0,
+        operations,
+        text: diff_text(&old.text, &new.text),
+        graphics: diff_graphics(&old.graphics, &new.graphics),
+    }
+}
+
+fn write(operation: &Operation) -> String {
+    let mut written = Vec::new();
+    // Writing to a `Vec` can't fail.
+    write_operation(&mut written, operation).unwrap();
+    String::from_utf8_lossy(&written).into_owned()
+}
+
+/// The differences between two written operation streams, pairing deleted and inserted
+/// operations for which `same_operator` holds, given their indices, as changed ones.
+fn changes<F: Fn(usize, usize) -> bool>(
+    old: &[String],
+    new: &[String],
+    same_operator: F,
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for step in align(old.len(), new.len(), &common(old, new)) {
+        let (deleted, inserted) = match step {
+            Step::Gap(deleted, inserted) => (deleted, inserted),
+            Step::Same(..) => continue,
+        };
+
+        let mut inserted = inserted.peekable();
+        for old_index in deleted {
+            match inserted.peek() {
+                Some(&new_index) if same_operator(old_index, new_index) => {
+                    changes.push(Change::Changed {
+                        old: old_index,
+                        new: new_index,
+                    });
+                    inserted.next();
+                }
+                _ => changes.push(Change::Deleted { old: old_index }),
+            }
+        }
+        changes.extend(inserted.map(|new| Change::Inserted { new }));
+    }
+
+    changes
+}
+
+fn diff_text(old: &[TextRun], new: &[TextRun]) -> Vec<TextChange> {
+    let old_text: Vec<_> = old.iter().map(|run| &run.text).collect();
+    let new_text: Vec<_> = new.iter().map(|run| &run.text).collect();
+
+    let mut changes = Vec::new();
+    for step in align(old.len(), new.len(), &common(&old_text, &new_text)) {
+        match step {
+            Step::Same(old_index, new_index) => {
+                let (old, new) = (&old[old_index], &new[new_index]);
+                if !same_point(old.origin, new.origin) {
+                    changes.push(TextChange::Moved {
+                        old: old.clone(),
+                        new: new.clone(),
+                    });
+                }
+                if (&old.font, old.font_size, &old.color) != (&new.font, new.font_size, &new.color)
+                {
+                    changes.push(TextChange::Restyled {
+                        old: old.clone(),
+                        new: new.clone(),
+                    });
+                }
+            }
+            Step::Gap(deleted, inserted) => {
+                let (mut deleted, mut inserted) = (old[deleted].iter(), new[inserted].iter());
+                loop {
+                    match (deleted.next(), inserted.next()) {
+                        (Some(old), Some(new)) => changes.push(TextChange::Edited {
+                            old: old.clone(),
+                            new: new.clone(),
+                        }),
+                        (Some(old), None) => changes.push(TextChange::Deleted(old.clone())),
+                        (None, Some(new)) => changes.push(TextChange::Inserted(new.clone())),
+                        (None, None) => break,
+                    }
+                }
+            }
+        }
+    }
+
+    changes
+}
+
+fn diff_graphics(old: &[Graphic], new: &[Graphic]) -> Vec<GraphicChange> {
+    let old_shapes: Vec<_> = old
+        .iter()
+        .map(|graphic| (&graphic.kind, &graphic.shape))
+        .collect();
+    let new_shapes: Vec<_> = new
+        .iter()
+        .map(|graphic| (&graphic.kind, &graphic.shape))
+        .collect();
+
+    let mut changes = Vec::new();
+    for step in align(old.len(), new.len(), &common(&old_shapes, &new_shapes)) {
+        match step {
+            Step::Same(old_index, new_index) => {
+                let (old, new) = (&old[old_index], &new[new_index]);
+                let (old_corner, new_corner) = (
+                    (old.bounds.x_min, old.bounds.y_min),
+                    (new.bounds.x_min, new.bounds.y_min),
+                );
+                if !same_point(old_corner, new_corner) {
+                    changes.push(GraphicChange::Moved {
+                        old: old.clone(),
+                        new: new.clone(),
+                    });
+                }
+                if (&old.fill, &old.stroke) != (&new.fill, &new.stroke) {
+                    changes.push(GraphicChange::Recolored {
+                        old: old.clone(),
+                        new: new.clone(),
+                    });
+                }
+            }
+            Step::Gap(deleted, inserted) => {
+                changes.extend(old[deleted].iter().cloned().map(GraphicChange::Deleted));
+                changes.extend(new[inserted].iter().cloned().map(GraphicChange::Inserted));
+            }
+        }
+    }
+
+    changes
+}
+
+fn same_point(a: (f32, f32), b: (f32, f32)) -> bool {
+    (a.0 - b.0).abs() <= TOLERANCE && (a.1 - b.1).abs() <= TOLERANCE
+}
+
+/// A step through two sequences aligned by the pairs of indices of their common items: either a
+/// common pair, or the items between two of them.
+enum Step {
+    Same(usize, usize),
+    Gap(Range<usize>, Range<usize>),
+}
+
+fn align(old: usize, new: usize, common: &[(usize, usize)]) -> Vec<Step> {
+    let mut steps = Vec::new();
+    let mut next = (0, 0);
+    for &(old_index, new_index) in common.iter().chain(&[(old, new)]) {
+        if next.0 < old_index || next.1 < new_index {
+            steps.push(Step::Gap(next.0..old_index, next.1..new_index));
+        }
+        if old_index < old {
+            steps.push(Step::Same(old_index, new_index));
+        }
+        next = (old_index + 1, new_index + 1);
+    }
+
+    steps
+}
+
+/// The pairs of indices of a longest common subsequence of two sequences, found with Myers'
+/// algorithm after taking out their common start and end.
+fn common<T: PartialEq>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut pairs: Vec<_> = (0..prefix).map(|index| (index, index)).collect();
+    pairs.extend(
+        myers(
+            &old[prefix..old.len() - suffix],
+            &new[prefix..new.len() - suffix],
+        )
+        .into_iter()
+        .map(|(old, new)| (old + prefix, new + prefix)),
+    );
+    pairs.extend((0..suffix).map(|index| (old.len() - suffix + index, new.len() - suffix + index)));
+    pairs
+}
+
+fn myers<T: PartialEq>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let limit = (old.len() + new.len()).min(MAX_EDITS) as isize;
+    let offset = limit + 1;
+    // The furthest `x` reached on each diagonal `k = x - y`, offset to be an index.
+    let mut furthest = vec![0; 2 * offset as usize + 1];
+    // `furthest` before each number of edits, for the diagonals that number can reach.
+    let mut trace = Vec::new();
+
+    for edits in 0..=limit {
+        trace.push(furthest[(offset - edits) as usize..=(offset + edits) as usize].to_vec());
+        for k in (-edits..=edits).step_by(2) {
+            let index = (offset + k) as usize;
+            let mut x = if k == -edits || (k != edits && furthest[index - 1] < furthest[index + 1])
+            {
+                furthest[index + 1]
+            } else {
+                furthest[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            furthest[index] = x;
+
+            if x >= n && y >= m {
+                return backtrack(&trace, n, m);
+            }
+        }
+    }
+
+    Vec::new()
+}
+
+/// Follows the furthest reaching paths recorded by `myers` back from the end, collecting the
+/// diagonal moves.
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<(usize, usize)> {
+    let (mut x, mut y) = (n, m);
+    let mut pairs = Vec::new();
+
+    for (edits, furthest) in trace.iter().enumerate().rev() {
+        let edits = edits as isize;
+        let k = x - y;
+        // Where the edit before the diagonal that ends at `(x, y)` started.
+        let (previous_x, previous_y) = if edits == 0 {
+            (0, 0)
+        } else {
+            let at = |k: isize| furthest[(k + edits) as usize];
+            let previous = if k == -edits || (k != edits && at(k - 1) < at(k + 1)) {
+                k + 1
+            } else {
+                k - 1
+            };
+            (at(previous), at(previous) - previous)
+        };
+
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            pairs.push((x as usize, y as usize));
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+
+    pairs.reverse();
+    pairs
+}
+
+/// What a page paints, as compared by `diff_pages`.
+#[derive(Default)]
+struct Content {
+    /// The page's operations as `write` writes them.
+    operations: Vec<String>,
+    /// The operator of each operation, empty for malformed ones.
+    operators: Vec<String>,
+    text: Vec<TextRun>,
+    graphics: Vec<Graphic>,
+}
+
+impl Content {
+    fn load<R: Resolve>(resolve: &R, page: &RawPage) -> Result<Self, Error> {
+        let operations = page
+            .content_parser(resolve)?
+            .collect::<Result<Vec<_>, _>>()?;
+        let operations: Vec<_> = operations.iter().map(normalize_operation).collect();
+        let resources = page.resources(resolve)?;
+
+        let mut content = Content {
+            operations: operations.iter().map(write).collect(),
+            operators: operations
+                .iter()
+                .map(|operation| operator(operation).unwrap_or("").to_string())
+                .collect(),
+            ..Content::default()
+        };
+        let mut stack = GraphicsStateStack::new(GraphicsState::default());
+        let (mut text, mut saved_text) = (TextState::default(), Vec::new());
+        let mut fonts = HashMap::new();
+        let mut path = Path::new();
+        // Whether the next text starts a new run.
+        let mut new_run = true;
+
+        for operation in &operations {
+            if path.apply(operation) {
+                continue;
+            }
+            text.update(operation);
+            let state = stack.current();
+            if breaks_line(operation) {
+                new_run = true;
+            }
+
+            match operation {
+                Operation::FillPathUsingNonZeroWindingNumberRule
+                | Operation::ObsoleteFillPathUsingNonZeroWindingMumberRule
+                | Operation::FillPathUsingEvenOddRule => {
+                    content.paint(mem::take(&mut path), true, false, state)
+                }
+                Operation::StrokePath => content.paint(mem::take(&mut path), false, true, state),
+                Operation::FillAndStrokePathUsingNonZeroWindingNumber
+                | Operation::FillAndStrokePathUsingEvenOddRule => {
+                    content.paint(mem::take(&mut path), true, true, state)
+                }
+                Operation::CloseAndStrokePath => {
+                    path.close();
+                    content.paint(mem::take(&mut path), false, true, state)
+                }
+                Operation::CloseFillAndStrokePathUsingNonZeroWindingNumber
+                | Operation::CloseFillAndStrokePathUsingEvenOddRule => {
+                    path.close();
+                    content.paint(mem::take(&mut path), true, true, state)
+                }
+                Operation::EndPathWithoutFillingOrStroking => path = Path::new(),
+                Operation::ShowText(string)
+                | Operation::MoveToNextLineAndShowText(string)
+                | Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText {
+                    text: string,
+                    ..
+                } => {
+                    let font = text.load_font(resolve, &resources, &mut fonts)?;
+                    content.show(&mut text, font, string, state, &mut new_run);
+                }
+                Operation::ShowTextAllowingIndividualGlyphPositioning(array) => {
+                    let font = text.load_font(resolve, &resources, &mut fonts)?;
+                    for item in array {
+                        match item {
+                            TextOrGlyphPositioning::Text(string) => {
+                                content.show(&mut text, font, string, state, &mut new_run)
+                            }
+                            TextOrGlyphPositioning::GlyphPositioning(offset) => {
+                                text.adjust(font, *offset);
+                                match content.text.last_mut() {
+                                    Some(run) if -offset >= WORD_GAP && !new_run => {
+                                        run.text.push(' ')
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                    }
+                }
+                // The text of strings that aren't UTF-8 isn't known, so the runs around them are
+                // kept apart.
+                Operation::Unknown { operator, .. }
+                    if matches!(*operator, "Tj" | "TJ" | "'" | "\"") =>
+                {
+                    new_run = true;
+                }
+                Operation::InvokeNamedXObject(Name(name)) => content.graphics.push(Graphic {
+                    kind: GraphicKind::XObject(name.to_string()),
+                    bounds: Rect::new(0.0, 0.0, 1.0, 1.0).transform(&state.ctm),
+                    fill: None,
+                    stroke: None,
+                    shape: linear(&state.ctm),
+                }),
+                Operation::EndInlineImageObject(data) => {
+                    let mut shape = linear(&state.ctm);
+                    shape.extend(data.iter().map(|byte| i64::from(*byte)));
+                    content.graphics.push(Graphic {
+                        kind: GraphicKind::InlineImage,
+                        bounds: Rect::new(0.0, 0.0, 1.0, 1.0).transform(&state.ctm),
+                        fill: None,
+                        stroke: None,
+                        shape,
+                    });
+                }
+                Operation::SaveGraphicsState => saved_text.push(text.clone()),
+                Operation::RestoreGraphicsState => {
+                    if let Some(saved) = saved_text.pop() {
+                        text = saved;
+                    }
+                }
+                _ => {}
+            }
+
+            stack.update(operation, &resources, resolve)?;
+        }
+
+        Ok(content)
+    }
+
+    fn paint(&mut self, path: Path, fill: bool, stroke: bool, state: &GraphicsState) {
+        let path = path.transform(&state.ctm);
+        let bounds = match path.bounds() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        let mut shape = Vec::new();
+        for subpath in &path.subpaths {
+            // Keeps subpaths apart, since no rounded coordinate can be this small.
+            shape.push(if subpath.closed {
+                i64::MIN
+            } else {
+                i64::MIN + 1
+            });
+            for (x, y) in &subpath.points {
+                shape.push(hundredths(x - bounds.x_min));
+                shape.push(hundredths(y - bounds.y_min));
+            }
+        }
+
+        self.graphics.push(Graphic {
+            kind: GraphicKind::Path,
+            bounds,
+            fill: if fill {
+                Some(state.fill_color.clone())
+            } else {
+                None
+            },
+            stroke: if stroke {
+                Some(state.stroke_color.clone())
+            } else {
+                None
+            },
+            shape,
+        });
+    }
+
+    /// Adds a string to the current text run, or starts a new one with it.
+    fn show(
+        &mut self,
+        text: &mut TextState,
+        font: &Font,
+        string: &str,
+        state: &GraphicsState,
+        new_run: &mut bool,
+    ) {
+        let continues = !*new_run
+            && matches!(
+                self.text.last(),
+                Some(run) if run.font == text.font
+                    && run.font_size == text.font_size
+                    && run.color == state.fill_color
+            );
+        match self.text.last_mut() {
+            Some(run) if continues => run.text.push_str(string),
+            _ => self.text.push(TextRun {
+                text: string.to_string(),
+                origin: (text.matrix * state.ctm).transform(0.0, text.rise),
+                font: text.font.clone(),
+                font_size: text.font_size,
+                color: state.fill_color.clone(),
+            }),
+        }
+        *new_run = false;
+
+        text.show(font, string.as_bytes(), &state.ctm, |_| {});
+    }
+}
+
+fn hundredths(value: f32) -> i64 {
+    (value * 100.0).round() as i64
+}
+
+/// The scale, rotation and skew of a matrix, without its translation.
+fn linear(matrix: &Matrix) -> Vec<i64> {
+    [matrix.a, matrix.b, matrix.c, matrix.d]
+        .iter()
+        .map(|value| hundredths(*value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::object::NoResolve;
+
+    use super::*;
+    use crate::{color::ColorSpace, fixtures};
+
+    fn page(content: &[u8]) -> RawPage {
+        fixtures::page(fixtures::courier_resources(), content)
+    }
+
+    #[test]
+    fn reports_changed_operations_text_and_graphics() {
+        let old = page(b"BT /F1 10 Tf 100 700 Td (Hello) Tj ET 1 0 0 rg 0 0 10 10 re f");
+        let new = page(
+            b"BT /F1 10.0 Tf 100 710 Td (Hello) Tj ET 0 0 1 rg 0 0 10 10 re f 5 w 20 0 10 10 re S",
+        );
+        let diff = diff_pages(&NoResolve, &old, &NoResolve, &new).unwrap();
+
+        assert_eq!(
+            diff.operations
+                .iter()
+                .map(|change| change.change)
+                .collect::<Vec<_>>(),
+            vec![
+                Change::Changed { old: 2, new: 2 },
+                Change::Changed { old: 5, new: 5 },
+                Change::Inserted { new: 8 },
+                Change::Inserted { new: 9 },
+                Change::Inserted { new: 10 },
+            ]
+        );
+        assert_eq!(diff.operations[1].new.as_deref(), Some("0 0 1 rg"));
+
+        match &diff.text[..] {
+            [TextChange::Moved { old, new }] => {
+                assert_eq!((old.origin, new.origin), ((100.0, 700.0), (100.0, 710.0)))
+            }
+            text => panic!("unexpected text changes {:?}", text),
+        }
+        match &diff.graphics[..] {
+            [GraphicChange::Recolored { new, .. }, GraphicChange::Inserted(inserted)] => {
+                assert_eq!(
+                    new.fill,
+                    Some(Color::new(ColorSpace::DeviceRGB, vec![0.0, 0.0, 1.0]))
+                );
+                assert_eq!(inserted.bounds, Rect::new(20.0, 0.0, 30.0, 10.0));
+            }
+            graphics => panic!("unexpected graphic changes {:?}", graphics),
+        }
+
+        // Inline image data can hold anything, including spaces, before its operator.
+        let old = page(b"q BI /W 2 /H 1 /CS /G /BPC 8 ID a b EI Q");
+        let new = page(b"q BI /W 2 /H 1 /CS /G /BPC 8 ID c d EI Q");
+        let diff = diff_pages(&NoResolve, &old, &NoResolve, &new).unwrap();
+        assert_eq!(
+            diff.operations
+                .iter()
+                .map(|change| change.change)
+                .collect::<Vec<_>>(),
+            vec![Change::Changed { old: 3, new: 3 }]
+        );
+
+        let letters = |text: &str| text.chars().map(|c| c.to_string()).collect::<Vec<_>>();
+        assert_eq!(common(&letters("abcabba"), &letters("cbabac")).len(), 4);
+    }
+}
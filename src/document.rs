@@ -0,0 +1,784 @@
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fmt,
+    io::{Cursor, Read},
+};
+
+use flate2::read::ZlibDecoder;
+use itertools::Itertools;
+
+use pdf::{
+    backend::Backend,
+    content::Operation as PdfOperation,
+    object::{Object, PlainRef, Resolve, Stream},
+    primitive::{Dictionary, PdfStream, Primitive},
+};
+
+use crate::{
+    error::Error,
+    parser::{ContentStreamParser, ParseError, DEFAULT_CHUNK_SIZE},
+    PrimitiveExt,
+};
+
+const MAX_REFERENCE_DEPTH: usize = 32;
+const PAGE_INHERITABLE_KEYS: &[&str] = &["Resources", "MediaBox", "CropBox", "Rotate"];
+
+/// Follows references until a direct object is reached.
+pub fn resolve<R: Resolve>(resolve: &R, primitive: &Primitive) -> Result<Primitive, Error> {
+    let mut primitive = primitive.clone();
+
+    for _ in 0..MAX_REFERENCE_DEPTH {
+        match primitive {
+            Primitive::Reference(reference) => primitive = resolve.resolve(reference)?,
+            primitive => return Ok(primitive),
+        }
+    }
+
+    Err(Error::InvalidEntry("reference chain".into()))
+}
+
+/// Looks up `key` in `dictionary`, following references.
+pub fn get<R: Resolve>(
+    resolve: &R,
+    dictionary: &Dictionary,
+    key: &str,
+) -> Result<Option<Primitive>, Error> {
+    dictionary
+        .get(key)
+        .map(|primitive| self::resolve(resolve, primitive))
+        .transpose()
+}
+
+/// Looks up a dictionary valued `key` in `dictionary`, following references.
+pub fn get_dictionary<R: Resolve>(
+    resolve: &R,
+    dictionary: &Dictionary,
+    key: &str,
+) -> Result<Option<Dictionary>, Error> {
+    match get(resolve, dictionary, key)? {
+        Some(Primitive::Dictionary(dictionary)) => Ok(Some(dictionary)),
+        Some(Primitive::Null) | None => Ok(None),
+        Some(_) => Err(Error::InvalidEntry(key.into())),
+    }
+}
+
+/// Looks up a number valued `key` in `dictionary`, following references.
+pub fn get_number<R: Resolve>(
+    resolve: &R,
+    dictionary: &Dictionary,
+    key: &str,
+) -> Result<Option<f32>, Error> {
+    match get(resolve, dictionary, key)? {
+        Some(primitive) => primitive
+            .try_to_f()
+            .map(Some)
+            .ok_or_else(|| Error::InvalidEntry(key.into())),
+        None => Ok(None),
+    }
+}
+
+/// Looks up an integer valued `key` in `dictionary`, following references.
+pub fn get_integer<R: Resolve>(
+    resolve: &R,
+    dictionary: &Dictionary,
+    key: &str,
+) -> Result<Option<i32>, Error> {
+    match get(resolve, dictionary, key)? {
+        Some(Primitive::Integer(integer)) => Ok(Some(integer)),
+        Some(_) => Err(Error::InvalidEntry(key.into())),
+        None => Ok(None),
+    }
+}
+
+/// Looks up a boolean valued `key` in `dictionary`, following references.
+pub fn get_bool<R: Resolve>(
+    resolve: &R,
+    dictionary: &Dictionary,
+    key: &str,
+) -> Result<Option<bool>, Error> {
+    match get(resolve, dictionary, key)? {
+        Some(Primitive::Boolean(boolean)) => Ok(Some(boolean)),
+        Some(_) => Err(Error::InvalidEntry(key.into())),
+        None => Ok(None),
+    }
+}
+
+/// Looks up a name valued `key` in `dictionary`, following references.
+pub fn get_name<R: Resolve>(
+    resolve: &R,
+    dictionary: &Dictionary,
+    key: &str,
+) -> Result<Option<String>, Error> {
+    match get(resolve, dictionary, key)? {
+        Some(Primitive::Name(name)) => Ok(Some(name)),
+        Some(_) => Err(Error::InvalidEntry(key.into())),
+        None => Ok(None),
+    }
+}
+
+/// Looks up a text string valued `key` in `dictionary`, following references.
+pub fn get_text<R: Resolve>(
+    resolve: &R,
+    dictionary: &Dictionary,
+    key: &str,
+) -> Result<Option<String>, Error> {
+    match get(resolve, dictionary, key)? {
+        Some(Primitive::String(string)) => Ok(Some(decode_text(string.as_bytes()))),
+        Some(Primitive::Null) | None => Ok(None),
+        Some(_) => Err(Error::InvalidEntry(key.into())),
+    }
+}
+
+/// Decodes a text string, section 7.9.2.2 of ISO 32000-1: UTF-16BE with a byte order mark, or
+/// PDFDocEncoding, which is read as Latin-1. UTF-8 with a byte order mark, from PDF 2.0, is
+/// also accepted.
+pub fn decode_text(bytes: &[u8]) -> String {
+    match bytes {
+        [0xfe, 0xff, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        [0xef, 0xbb, 0xbf, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        bytes => bytes.iter().map(|byte| char::from(*byte)).collect(),
+    }
+}
+
+/// Looks up an array of numbers valued `key` in `dictionary`, following references.
+pub fn get_numbers<R: Resolve>(
+    resolve: &R,
+    dictionary: &Dictionary,
+    key: &str,
+) -> Result<Option<Vec<f32>>, Error> {
+    match get(resolve, dictionary, key)? {
+        Some(Primitive::Array(array)) => array
+            .iter()
+            .map(|primitive| {
+                self::resolve(resolve, primitive)?
+                    .try_to_f()
+                    .ok_or_else(|| Error::InvalidEntry(key.into()))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some),
+        Some(_) => Err(Error::InvalidEntry(key.into())),
+        None => Ok(None),
+    }
+}
+
+/// Looks up a named resource of the given category, such as `/ExtGState`, following references.
+pub fn get_resource<R: Resolve>(
+    resolve: &R,
+    resources: &Dictionary,
+    category: &str,
+    name: &str,
+) -> Result<Primitive, Error> {
+    get_dictionary(resolve, resources, category)?
+        .and_then(|category| category.get(name).cloned())
+        .map(|resource| self::resolve(resolve, &resource))
+        .transpose()?
+        .ok_or_else(|| Error::MissingEntry(format!("{} /{}", category, name)))
+}
+
+/// The decoded data of a stream.
+pub fn stream_data<R: Resolve>(resolve: &R, stream: &PdfStream) -> Result<Vec<u8>, Error> {
+    let stream = Stream::<()>::from_primitive(Primitive::Stream(stream.clone()), resolve)?;
+
+    Ok(stream.data()?.to_vec())
+}
+
+/// Reads the decoded data of a stream.
+pub type StreamReader<'a> = Box<dyn Read + 'a>;
+
+/// A reader over the decoded data of a stream. Unfiltered and `FlateDecode` streams are decoded
+/// as they're read, so only a chunk of their decoded data is in memory at a time. Streams with
+/// other filters, or with `/DecodeParms`, are decoded up front.
+pub fn stream_reader<'a, R: Resolve>(
+    resolve: &R,
+    stream: Cow<'a, PdfStream>,
+) -> Result<StreamReader<'a>, Error> {
+    let filters = match get(resolve, &stream.info, "Filter")? {
+        Some(Primitive::Null) | None => Vec::new(),
+        Some(Primitive::Name(filter)) => vec![filter],
+        Some(Primitive::Array(filters)) => filters
+            .iter()
+            .map(|filter| match self::resolve(resolve, filter)? {
+                Primitive::Name(filter) => Ok(filter),
+                _ => Err(Error::InvalidEntry("Filter".into())),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(Error::InvalidEntry("Filter".into())),
+    };
+    let flate = match filters.as_slice() {
+        [] => false,
+        [filter] if filter == "FlateDecode" => true,
+        _ => return Ok(Box::new(Cursor::new(stream_data(resolve, &stream)?))),
+    };
+    if flate
+        && !matches!(
+            get(resolve, &stream.info, "DecodeParms")?,
+            Some(Primitive::Null) | None
+        )
+    {
+        return Ok(Box::new(Cursor::new(stream_data(resolve, &stream)?)));
+    }
+
+    let data = match stream {
+        Cow::Borrowed(stream) => Cursor::new(Cow::Borrowed(&stream.data[..])),
+        Cow::Owned(stream) => Cursor::new(Cow::Owned(stream.data)),
+    };
+    if flate {
+        Ok(Box::new(ZlibDecoder::new(data)))
+    } else {
+        Ok(Box::new(data))
+    }
+}
+
+/// Reads the trailer of the newest revision of a document from its data. `pdf` only keeps the
+/// typed catalog, not the dictionary or the reference to it.
+pub fn read_trailer<B: Backend, R: Resolve>(data: &B, resolve: &R) -> Result<Dictionary, Error> {
+    let start = data.locate_start_offset()?;
+    let (_, trailer) = data.read_xref_table_and_trailer(start, resolve)?;
+
+    Ok(trailer)
+}
+
+/// A document's catalog and page tree as untyped dictionaries, for reaching the parts of a
+/// document that `pdf` doesn't have types for.
+pub struct RawDocument {
+    pub catalog: Dictionary,
+    pub pages: Vec<RawPage>,
+}
+
+pub struct RawPage {
+    /// One based page number.
+    pub number: usize,
+    pub reference: Option<PlainRef>,
+    /// The page dictionary, with inherited entries such as `/Resources` copied in.
+    pub dictionary: Dictionary,
+}
+
+impl RawDocument {
+    /// Loads the catalog and page tree of a document, starting from the `/Root` entry of its
+    /// trailer, see [`read_trailer`].
+    pub fn load<R: Resolve>(resolve: &R, trailer: &Dictionary) -> Result<RawDocument, Error> {
+        let catalog = get_dictionary(resolve, trailer, "Root")?
+            .ok_or_else(|| Error::MissingEntry("Root".into()))?;
+
+        let mut pages = Vec::new();
+        if let Some(root) = catalog.get("Pages") {
+            let mut visited = HashSet::new();
+            collect_pages(resolve, root, &Dictionary::new(), &mut visited, &mut pages)?;
+        }
+
+        Ok(RawDocument { catalog, pages })
+    }
+}
+
+impl RawPage {
+    pub fn resources<R: Resolve>(&self, resolve: &R) -> Result<Dictionary, Error> {
+        Ok(get_dictionary(resolve, &self.dictionary, "Resources")?.unwrap_or_else(Dictionary::new))
+    }
+
+    /// The page's `/Contents` streams, still encoded.
+    pub fn contents<R: Resolve>(&self, resolve: &R) -> Result<Vec<PdfStream>, Error> {
+        let streams = match get(resolve, &self.dictionary, "Contents")? {
+            Some(Primitive::Array(streams)) => streams,
+            Some(Primitive::Null) | None => Vec::new(),
+            Some(stream) => vec![stream],
+        };
+
+        streams
+            .iter()
+            .map(|stream| match self::resolve(resolve, stream)? {
+                Primitive::Stream(stream) => Ok(stream),
+                _ => Err(Error::InvalidEntry("Contents".into())),
+            })
+            .collect()
+    }
+
+    /// The decoded data of each of the page's `/Contents` streams.
+    pub fn content_streams<R: Resolve>(&self, resolve: &R) -> Result<Vec<Vec<u8>>, Error> {
+        self.contents(resolve)?
+            .iter()
+            .map(|stream| stream_data(resolve, stream))
+            .collect()
+    }
+
+    /// The page's own content, without the content nested in it.
+    pub fn content<R: Resolve>(&self, resolve: &R) -> Result<ReachableContent, Error> {
+        Ok(ReachableContent {
+            path: vec![Location::Page(self.number)],
+            reference: self.reference,
+            resources: self.resources(resolve)?,
+            streams: self.contents(resolve)?,
+        })
+    }
+
+    /// A parser over all of the page's content streams, decoding them as they're parsed.
+    pub fn content_parser<R: Resolve>(
+        &self,
+        resolve: &R,
+    ) -> Result<ContentStreamParser<StreamReader<'static>>, Error> {
+        let streams = self
+            .contents(resolve)?
+            .into_iter()
+            .map(|stream| stream_reader(resolve, Cow::Owned(stream)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ContentStreamParser::from_streams(
+            streams,
+            DEFAULT_CHUNK_SIZE,
+        ))
+    }
+
+    /// The operations of all of the page's content streams. Malformed operations are skipped, as
+    /// [`ContentStreamParser::with_recovery`] does, so that the rest of a damaged page can still
+    /// be read.
+    pub fn operations<R: Resolve>(&self, resolve: &R) -> Result<Vec<PdfOperation>, Error> {
+        recovered_operations(self.content_parser(resolve)?)
+    }
+}
+
+/// The operations a parser reads with recovery, skipping malformed ones like
+/// [`RawPage::operations`]. Used for content that isn't a page's, such as forms and pattern cells.
+pub(crate) fn recovered_operations<S: Read>(
+    parser: ContentStreamParser<S>,
+) -> Result<Vec<PdfOperation>, Error> {
+    let mut operations = Vec::new();
+    for operation in parser.with_recovery() {
+        match operation {
+            Ok(operation) => operations.push(operation),
+            Err(ParseError::Syntax { .. }) => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    Ok(operations)
+}
+
+pub(crate) fn has_type(dictionary: &Dictionary, name: &str) -> bool {
+    matches!(dictionary.get("Type"), Some(Primitive::Name(n)) if n == name)
+}
+
+fn collect_pages<R: Resolve>(
+    resolve: &R,
+    node: &Primitive,
+    inherited: &Dictionary,
+    visited: &mut HashSet<PlainRef>,
+    pages: &mut Vec<RawPage>,
+) -> Result<(), Error> {
+    let reference = match node {
+        Primitive::Reference(reference) => {
+            if !visited.insert(*reference) {
+                return Err(Error::InvalidEntry("Kids".into()));
+            }
+            Some(*reference)
+        }
+        _ => None,
+    };
+
+    let mut dictionary = match self::resolve(resolve, node)? {
+        Primitive::Dictionary(dictionary) => dictionary,
+        _ => return Err(Error::InvalidEntry("Kids".into())),
+    };
+
+    for key in PAGE_INHERITABLE_KEYS {
+        if dictionary.get(key).is_none() {
+            if let Some(value) = inherited.get(key) {
+                dictionary.insert(key.to_string(), value.clone());
+            }
+        }
+    }
+
+    if has_type(&dictionary, "Page") {
+        pages.push(RawPage {
+            number: pages.len() + 1,
+            reference,
+            dictionary,
+        });
+        return Ok(());
+    }
+
+    let kids = match get(resolve, &dictionary, "Kids")? {
+        Some(Primitive::Array(kids)) => kids,
+        _ => return Err(Error::InvalidEntry("Kids".into())),
+    };
+
+    for kid in &kids {
+        collect_pages(resolve, kid, &dictionary, visited, pages)?;
+    }
+
+    Ok(())
+}
+
+/// A step on the way from a page to a content stream nested in it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Location {
+    Page(usize),
+    XObject(String),
+    Pattern(String),
+    /// An annotation's appearance stream, by index in `/Annots`, appearance type and state.
+    Annotation {
+        index: usize,
+        appearance: String,
+        state: Option<String>,
+    },
+    /// A glyph description in a Type 3 font.
+    Glyph {
+        font: String,
+        glyph: String,
+    },
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Location::Page(number) => write!(f, "page {}", number),
+            Location::XObject(name) => write!(f, "XObject /{}", name),
+            Location::Pattern(name) => write!(f, "Pattern /{}", name),
+            Location::Annotation {
+                index,
+                appearance,
+                state: Some(state),
+            } => write!(f, "Annotation {} /{} /{}", index, appearance, state),
+            Location::Annotation {
+                index, appearance, ..
+            } => write!(f, "Annotation {} /{}", index, appearance),
+            Location::Glyph { font, glyph } => write!(f, "Font /{} > CharProc /{}", font, glyph),
+        }
+    }
+}
+
+/// A content stream reachable from a document and the resources its operations refer to.
+pub struct ReachableContent {
+    pub path: Vec<Location>,
+    pub reference: Option<PlainRef>,
+    pub resources: Dictionary,
+    /// The streams of the content, still encoded. A page's content may be split over several
+    /// streams.
+    pub streams: Vec<PdfStream>,
+}
+
+impl ReachableContent {
+    /// The number of the page the content was reached from.
+    pub fn page(&self) -> usize {
+        match self.path.first() {
+            Some(Location::Page(number)) => *number,
+            _ => 0,
+        }
+    }
+
+    /// The path to the content, such as `page 3 > XObject /Fm1 > Pattern /P0`.
+    pub fn path_string(&self) -> String {
+        self.path.iter().map(ToString::to_string).join(" > ")
+    }
+
+    /// A parser over the content, decoding its streams as they're parsed.
+    pub fn parser<R: Resolve>(
+        &self,
+        resolve: &R,
+    ) -> Result<ContentStreamParser<StreamReader<'_>>, Error> {
+        let streams = self
+            .streams
+            .iter()
+            .map(|stream| stream_reader(resolve, Cow::Borrowed(stream)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ContentStreamParser::from_streams(
+            streams,
+            DEFAULT_CHUNK_SIZE,
+        ))
+    }
+}
+
+impl RawDocument {
+    /// Calls `f` with every content stream reachable from the document's pages: the pages
+    /// themselves, Form XObjects, tiling pattern cells, annotation appearance streams and Type 3
+    /// glyph descriptions, recursively. Streams shared between several pages or resources are
+    /// only visited once, from the first place they're reached.
+    pub fn for_each_content<R, F>(&self, resolve: &R, f: F) -> Result<(), Error>
+    where
+        R: Resolve,
+        F: FnMut(ReachableContent) -> Result<(), Error>,
+    {
+        let mut visitor = ContentVisitor {
+            resolve,
+            visited: HashSet::new(),
+            f,
+        };
+
+        for page in &self.pages {
+            if let Some(reference) = page.reference {
+                visitor.visited.insert(reference);
+            }
+
+            let content = page.content(resolve)?;
+            visitor.visit_resources(&content.path, &content.resources)?;
+            visitor.visit_annotations(page, &content.path, &content.resources)?;
+
+            (visitor.f)(content)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct ContentVisitor<'a, R, F> {
+    resolve: &'a R,
+    visited: HashSet<PlainRef>,
+    f: F,
+}
+
+impl<'a, R, F> ContentVisitor<'a, R, F>
+where
+    R: Resolve,
+    F: FnMut(ReachableContent) -> Result<(), Error>,
+{
+    fn visit_resources(&mut self, path: &[Location], resources: &Dictionary) -> Result<(), Error> {
+        let resolve = self.resolve;
+
+        if let Some(xobjects) = get_dictionary(resolve, resources, "XObject")? {
+            for (name, xobject) in xobjects.iter() {
+                let location = Location::XObject(name.clone());
+                self.visit_stream(
+                    path,
+                    location,
+                    xobject,
+                    resources,
+                    |info| matches!(info.get("Subtype"), Some(Primitive::Name(n)) if n == "Form"),
+                )?;
+            }
+        }
+
+        if let Some(patterns) = get_dictionary(resolve, resources, "Pattern")? {
+            for (name, pattern) in patterns.iter() {
+                let location = Location::Pattern(name.clone());
+                self.visit_stream(path, location, pattern, resources, |info| {
+                    matches!(info.get("PatternType"), Some(Primitive::Integer(1)))
+                })?;
+            }
+        }
+
+        if let Some(fonts) = get_dictionary(resolve, resources, "Font")? {
+            for (font_name, font) in fonts.iter() {
+                if let Primitive::Reference(reference) = font {
+                    if !self.visited.insert(*reference) {
+                        continue;
+                    }
+                }
+
+                let font = match self::resolve(resolve, font)? {
+                    Primitive::Dictionary(font) => font,
+                    _ => continue,
+                };
+                if !matches!(font.get("Subtype"), Some(Primitive::Name(n)) if n == "Type3") {
+                    continue;
+                }
+
+                let font_resources = get_dictionary(resolve, &font, "Resources")?
+                    .unwrap_or_else(|| resources.clone());
+                let char_procs =
+                    get_dictionary(resolve, &font, "CharProcs")?.unwrap_or_else(Dictionary::new);
+                for (glyph, char_proc) in char_procs.iter() {
+                    let location = Location::Glyph {
+                        font: font_name.clone(),
+                        glyph: glyph.clone(),
+                    };
+                    self.visit_stream(path, location, char_proc, &font_resources, |_| true)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn visit_annotations(
+        &mut self,
+        page: &RawPage,
+        path: &[Location],
+        resources: &Dictionary,
+    ) -> Result<(), Error> {
+        let resolve = self.resolve;
+        let annotations = match get(resolve, &page.dictionary, "Annots")? {
+            Some(Primitive::Array(annotations)) => annotations,
+            _ => return Ok(()),
+        };
+
+        for (index, annotation) in annotations.iter().enumerate() {
+            let annotation = match self::resolve(resolve, annotation)? {
+                Primitive::Dictionary(annotation) => annotation,
+                _ => continue,
+            };
+            let appearances = match get_dictionary(resolve, &annotation, "AP")? {
+                Some(appearances) => appearances,
+                None => continue,
+            };
+
+            for (appearance, streams) in appearances.iter() {
+                let location = |state: Option<&String>| Location::Annotation {
+                    index,
+                    appearance: appearance.clone(),
+                    state: state.cloned(),
+                };
+
+                match self::resolve(resolve, streams)? {
+                    // A dictionary of streams, one for each appearance state.
+                    Primitive::Dictionary(states) => {
+                        for (state, stream) in states.iter() {
+                            self.visit_stream(
+                                path,
+                                location(Some(state)),
+                                stream,
+                                resources,
+                                |_| true,
+                            )?;
+                        }
+                    }
+                    _ => self.visit_stream(path, location(None), streams, resources, |_| true)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Visits a content stream and the resources it uses, if `is_content` accepts its dictionary.
+    fn visit_stream<P>(
+        &mut self,
+        path: &[Location],
+        location: Location,
+        stream: &Primitive,
+        parent_resources: &Dictionary,
+        is_content: P,
+    ) -> Result<(), Error>
+    where
+        P: Fn(&Dictionary) -> bool,
+    {
+        let resolve = self.resolve;
+        let reference = match stream {
+            Primitive::Reference(reference) => {
+                if !self.visited.insert(*reference) {
+                    return Ok(());
+                }
+                Some(*reference)
+            }
+            _ => None,
+        };
+
+        let stream = match self::resolve(resolve, stream)? {
+            Primitive::Stream(stream) => stream,
+            _ => return Ok(()),
+        };
+        if !is_content(&stream.info) {
+            return Ok(());
+        }
+
+        // Streams without their own resources use those of the content they're used from.
+        let resources = get_dictionary(resolve, &stream.info, "Resources")?
+            .unwrap_or_else(|| parent_resources.clone());
+        let mut path = path.to_vec();
+        path.push(location);
+
+        self.visit_resources(&path, &resources)?;
+
+        (self.f)(ReachableContent {
+            reference,
+            streams: vec![stream],
+            path,
+            resources,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::fixtures::{dictionary, name, Objects};
+
+    fn reference(id: u64, gen: u16) -> PlainRef {
+        PlainRef { id, gen }
+    }
+
+    #[test]
+    fn loads_the_catalog_from_the_trailer() {
+        let pages = |kids: Vec<Primitive>| {
+            dictionary(vec![
+                ("Type", name("Pages")),
+                ("Kids", Primitive::Array(kids)),
+                ("Resources", dictionary(vec![])),
+            ])
+        };
+        let page = dictionary(vec![("Type", name("Page"))]);
+
+        // Object 1 is the catalog of an earlier revision, replaced by object 4 in generation 1.
+        let objects = Objects(
+            vec![
+                (
+                    reference(1, 0),
+                    dictionary(vec![
+                        ("Type", name("Catalog")),
+                        ("Pages", Primitive::Reference(reference(2, 0))),
+                    ]),
+                ),
+                (reference(2, 0), pages(vec![page.clone()])),
+                (
+                    reference(4, 1),
+                    dictionary(vec![
+                        ("Type", name("Catalog")),
+                        ("Pages", Primitive::Reference(reference(5, 0))),
+                    ]),
+                ),
+                (reference(5, 0), pages(vec![page.clone(), page])),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let mut trailer = Dictionary::new();
+        trailer.insert("Root".to_string(), Primitive::Reference(reference(4, 1)));
+
+        let document = RawDocument::load(&objects, &trailer).unwrap();
+        assert_eq!(document.pages.len(), 2);
+        assert!(document.pages[1].dictionary.get("Resources").is_some());
+        assert!(matches!(
+            RawDocument::load(&objects, &Dictionary::new()),
+            Err(Error::MissingEntry(key)) if key == "Root"
+        ));
+    }
+
+    #[test]
+    fn reads_flate_streams_as_they_are_decoded() {
+        use std::io::Write;
+
+        let content = b"q 1 0 0 1 72 720 cm BT /F1 12 Tf (Hello) Tj ET Q".repeat(100);
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&content).unwrap();
+        let data = encoder.finish().unwrap();
+
+        let objects = Objects(HashMap::new());
+        let filters = vec![
+            Primitive::Name("FlateDecode".to_string()),
+            Primitive::Array(vec![Primitive::Name("FlateDecode".to_string())]),
+        ];
+        for filter in filters {
+            let mut info = Dictionary::new();
+            info.insert("Filter".to_string(), filter);
+            let stream = PdfStream {
+                info,
+                data: data.clone(),
+            };
+
+            let mut decoded = Vec::new();
+            stream_reader(&objects, Cow::Borrowed(&stream))
+                .unwrap()
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, content);
+        }
+    }
+}
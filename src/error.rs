@@ -0,0 +1,48 @@
+use std::{error::Error as StdError, fmt};
+
+use pdf::error::PdfError;
+
+use crate::parser::ParseError;
+
+#[derive(Debug)]
+pub enum Error {
+    Pdf(PdfError),
+    Parse(ParseError),
+    /// A required dictionary entry or named resource doesn't exist.
+    MissingEntry(String),
+    /// A dictionary entry exists but doesn't have the expected type or value.
+    InvalidEntry(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Pdf(error) => write!(f, "{}", error),
+            Error::Parse(error) => write!(f, "{}", error),
+            Error::MissingEntry(key) => write!(f, "Missing entry {}", key),
+            Error::InvalidEntry(key) => write!(f, "Invalid entry {}", key),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Pdf(error) => Some(error),
+            Error::Parse(error) => Some(error),
+            Error::MissingEntry(_) | Error::InvalidEntry(_) => None,
+        }
+    }
+}
+
+impl From<PdfError> for Error {
+    fn from(error: PdfError) -> Self {
+        Error::Pdf(error)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::Parse(error)
+    }
+}
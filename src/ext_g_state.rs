@@ -0,0 +1,325 @@
+use pdf::{
+    object::Resolve,
+    primitive::{Dictionary, Primitive},
+};
+
+use crate::{
+    document::{self, get, get_bool, get_integer, get_name, get_number, get_numbers, get_resource},
+    error::Error,
+    ColorRenderingIntent, LineCapStyle, LineJoinStyle, PrimitiveExt,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl BlendMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            // Compatible is a deprecated synonym for Normal.
+            "Normal" | "Compatible" => Some(BlendMode::Normal),
+            "Multiply" => Some(BlendMode::Multiply),
+            "Screen" => Some(BlendMode::Screen),
+            "Overlay" => Some(BlendMode::Overlay),
+            "Darken" => Some(BlendMode::Darken),
+            "Lighten" => Some(BlendMode::Lighten),
+            "ColorDodge" => Some(BlendMode::ColorDodge),
+            "ColorBurn" => Some(BlendMode::ColorBurn),
+            "HardLight" => Some(BlendMode::HardLight),
+            "SoftLight" => Some(BlendMode::SoftLight),
+            "Difference" => Some(BlendMode::Difference),
+            "Exclusion" => Some(BlendMode::Exclusion),
+            "Hue" => Some(BlendMode::Hue),
+            "Saturation" => Some(BlendMode::Saturation),
+            "Color" => Some(BlendMode::Color),
+            "Luminosity" => Some(BlendMode::Luminosity),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SoftMaskType {
+    Alpha,
+    Luminosity,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SoftMask {
+    None,
+    Mask {
+        subtype: SoftMaskType,
+        /// The transparency group XObject the mask is derived from.
+        group: Primitive,
+        /// The backdrop color, `/BC`.
+        backdrop: Option<Vec<f32>>,
+        /// The transfer function, `/TR`.
+        transfer: Option<Primitive>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DashPattern {
+    pub array: Vec<f32>,
+    pub phase: f32,
+}
+
+/// A graphics state parameter dictionary, Table 58 in ISO 32000-1. Entries that weren't set
+/// are `None`, function valued entries are kept as primitives.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExtGState {
+    /// `/LW`
+    pub line_width: Option<f32>,
+    /// `/LC`
+    pub line_cap: Option<LineCapStyle>,
+    /// `/LJ`
+    pub line_join: Option<LineJoinStyle>,
+    /// `/ML`
+    pub miter_limit: Option<f32>,
+    /// `/D`
+    pub dash_pattern: Option<DashPattern>,
+    /// `/RI`
+    pub rendering_intent: Option<ColorRenderingIntent>,
+    /// `/OP`
+    pub stroke_overprint: Option<bool>,
+    /// `/op`, which defaults to `/OP` when only that is given.
+    pub fill_overprint: Option<bool>,
+    /// `/OPM`
+    pub overprint_mode: Option<i32>,
+    /// `/Font`, a font dictionary reference and size.
+    pub font: Option<(Primitive, f32)>,
+    /// `/BG`
+    pub black_generation: Option<Primitive>,
+    /// `/BG2`
+    pub black_generation2: Option<Primitive>,
+    /// `/UCR`
+    pub undercolor_removal: Option<Primitive>,
+    /// `/UCR2`
+    pub undercolor_removal2: Option<Primitive>,
+    /// `/TR`
+    pub transfer: Option<Primitive>,
+    /// `/TR2`
+    pub transfer2: Option<Primitive>,
+    /// `/HT`
+    pub halftone: Option<Primitive>,
+    /// `/FL`
+    pub flatness: Option<f32>,
+    /// `/SM`
+    pub smoothness: Option<f32>,
+    /// `/SA`
+    pub stroke_adjustment: Option<bool>,
+    /// `/BM`, the first supported mode if an array is given.
+    pub blend_mode: Option<BlendMode>,
+    /// `/SMask`
+    pub soft_mask: Option<SoftMask>,
+    /// `/CA`
+    pub stroke_alpha: Option<f32>,
+    /// `/ca`
+    pub fill_alpha: Option<f32>,
+    /// `/AIS`
+    pub alpha_is_shape: Option<bool>,
+    /// `/TK`
+    pub text_knockout: Option<bool>,
+}
+
+impl ExtGState {
+    /// Looks up the dictionary named by a `gs` operation in the `/ExtGState` resources.
+    pub fn from_resources<R: Resolve>(
+        resolve: &R,
+        resources: &Dictionary,
+        name: &str,
+    ) -> Result<Self, Error> {
+        match get_resource(resolve, resources, "ExtGState", name)? {
+            Primitive::Dictionary(dictionary) => Self::from_dictionary(resolve, &dictionary),
+            _ => Err(Error::InvalidEntry(format!("ExtGState /{}", name))),
+        }
+    }
+
+    pub fn from_dictionary<R: Resolve>(
+        resolve: &R,
+        dictionary: &Dictionary,
+    ) -> Result<Self, Error> {
+        let line_cap = match get_integer(resolve, dictionary, "LC")? {
+            Some(0) => Some(LineCapStyle::ButtCap),
+            Some(1) => Some(LineCapStyle::RoundCap),
+            Some(2) => Some(LineCapStyle::ProjectingSquareCap),
+            Some(_) => return Err(Error::InvalidEntry("LC".into())),
+            None => None,
+        };
+
+        let line_join = match get_integer(resolve, dictionary, "LJ")? {
+            Some(0) => Some(LineJoinStyle::MiterJoin),
+            Some(1) => Some(LineJoinStyle::RoundJoin),
+            Some(2) => Some(LineJoinStyle::BevelJoin),
+            Some(_) => return Err(Error::InvalidEntry("LJ".into())),
+            None => None,
+        };
+
+        let dash_pattern = match get(resolve, dictionary, "D")? {
+            Some(Primitive::Array(pattern)) => match pattern.as_slice() {
+                [array, phase] => {
+                    let array = match document::resolve(resolve, array)? {
+                        Primitive::Array(array) => array
+                            .iter()
+                            .map(|n| n.try_to_f())
+                            .collect::<Option<Vec<_>>>(),
+                        _ => None,
+                    };
+                    match (array, phase.try_to_f()) {
+                        (Some(array), Some(phase)) => Some(DashPattern { array, phase }),
+                        _ => return Err(Error::InvalidEntry("D".into())),
+                    }
+                }
+                _ => return Err(Error::InvalidEntry("D".into())),
+            },
+            Some(_) => return Err(Error::InvalidEntry("D".into())),
+            None => None,
+        };
+
+        let rendering_intent = get_name(resolve, dictionary, "RI")?
+            .map(|name| match name.as_str() {
+                "AbsoluteColorimetric" => Ok(ColorRenderingIntent::AbsoluteColorimetric),
+                "RelativeColorimetric" => Ok(ColorRenderingIntent::RelativeColorimetric),
+                "Saturation" => Ok(ColorRenderingIntent::Saturation),
+                "Perceptual" => Ok(ColorRenderingIntent::Perceptual),
+                _ => Err(Error::InvalidEntry("RI".into())),
+            })
+            .transpose()?;
+
+        let stroke_overprint = get_bool(resolve, dictionary, "OP")?;
+        let fill_overprint = get_bool(resolve, dictionary, "op")?.or(stroke_overprint);
+
+        let font = match get(resolve, dictionary, "Font")? {
+            Some(Primitive::Array(font)) => match font.as_slice() {
+                [font, size] => match size.try_to_f() {
+                    Some(size) => Some((font.clone(), size)),
+                    None => return Err(Error::InvalidEntry("Font".into())),
+                },
+                _ => return Err(Error::InvalidEntry("Font".into())),
+            },
+            Some(_) => return Err(Error::InvalidEntry("Font".into())),
+            None => None,
+        };
+
+        let blend_mode = match get(resolve, dictionary, "BM")? {
+            Some(Primitive::Name(name)) => BlendMode::from_name(&name),
+            Some(Primitive::Array(names)) => names.iter().find_map(|name| match name {
+                Primitive::Name(name) => BlendMode::from_name(name),
+                _ => None,
+            }),
+            Some(_) => return Err(Error::InvalidEntry("BM".into())),
+            None => None,
+        };
+
+        let soft_mask = match get(resolve, dictionary, "SMask")? {
+            Some(Primitive::Name(name)) if name == "None" => Some(SoftMask::None),
+            Some(Primitive::Dictionary(mask)) => {
+                let subtype = match get_name(resolve, &mask, "S")?.as_deref() {
+                    Some("Alpha") => SoftMaskType::Alpha,
+                    Some("Luminosity") => SoftMaskType::Luminosity,
+                    _ => return Err(Error::InvalidEntry("SMask /S".into())),
+                };
+                let group = mask
+                    .get("G")
+                    .cloned()
+                    .ok_or_else(|| Error::MissingEntry("SMask /G".into()))?;
+
+                Some(SoftMask::Mask {
+                    subtype,
+                    group,
+                    backdrop: get_numbers(resolve, &mask, "BC")?,
+                    transfer: get(resolve, &mask, "TR")?,
+                })
+            }
+            Some(_) => return Err(Error::InvalidEntry("SMask".into())),
+            None => None,
+        };
+
+        Ok(ExtGState {
+            line_width: get_number(resolve, dictionary, "LW")?,
+            line_cap,
+            line_join,
+            miter_limit: get_number(resolve, dictionary, "ML")?,
+            dash_pattern,
+            rendering_intent,
+            stroke_overprint,
+            fill_overprint,
+            overprint_mode: get_integer(resolve, dictionary, "OPM")?,
+            font,
+            black_generation: get(resolve, dictionary, "BG")?,
+            black_generation2: get(resolve, dictionary, "BG2")?,
+            undercolor_removal: get(resolve, dictionary, "UCR")?,
+            undercolor_removal2: get(resolve, dictionary, "UCR2")?,
+            transfer: get(resolve, dictionary, "TR")?,
+            transfer2: get(resolve, dictionary, "TR2")?,
+            halftone: get(resolve, dictionary, "HT")?,
+            flatness: get_number(resolve, dictionary, "FL")?,
+            smoothness: get_number(resolve, dictionary, "SM")?,
+            stroke_adjustment: get_bool(resolve, dictionary, "SA")?,
+            blend_mode,
+            soft_mask,
+            stroke_alpha: get_number(resolve, dictionary, "CA")?,
+            fill_alpha: get_number(resolve, dictionary, "ca")?,
+            alpha_is_shape: get_bool(resolve, dictionary, "AIS")?,
+            text_knockout: get_bool(resolve, dictionary, "TK")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::object::NoResolve;
+
+    use super::*;
+    use crate::graphics_state::GraphicsState;
+
+    #[test]
+    fn applies_to_graphics_state() {
+        let mut dictionary = Dictionary::new();
+        dictionary.insert("LW".to_string(), Primitive::Integer(3));
+        dictionary.insert("LC".to_string(), Primitive::Integer(1));
+        dictionary.insert("OP".to_string(), Primitive::Boolean(true));
+        dictionary.insert("ca".to_string(), Primitive::Number(0.5));
+        dictionary.insert(
+            "BM".to_string(),
+            Primitive::Array(vec![
+                Primitive::Name("Unsupported".into()),
+                Primitive::Name("Multiply".into()),
+            ]),
+        );
+        dictionary.insert("TR2".to_string(), Primitive::Name("Default".into()));
+
+        let ext_g_state = ExtGState::from_dictionary(&NoResolve, &dictionary).unwrap();
+        assert_eq!(ext_g_state.fill_overprint, Some(true));
+        assert_eq!(ext_g_state.blend_mode, Some(BlendMode::Multiply));
+
+        let mut state = GraphicsState {
+            transfer: Some(Primitive::Name("Identity".into())),
+            ..GraphicsState::default()
+        };
+        state.apply(&ext_g_state);
+
+        assert_eq!(state.line_width, 3.0);
+        assert_eq!(state.line_cap, LineCapStyle::RoundCap);
+        assert!(state.stroke_overprint && state.fill_overprint);
+        assert_eq!(state.fill_alpha, 0.5);
+        assert_eq!(state.stroke_alpha, 1.0);
+        assert_eq!(state.transfer, None);
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use pdf::{
+    object::{PlainRef, Resolve},
+    primitive::{Dictionary, PdfStream, Primitive},
+};
+
+use crate::document::RawPage;
+
+/// Indirect objects by reference, resolving missing ones as an error.
+pub struct Objects(pub HashMap<PlainRef, Primitive>);
+
+impl Resolve for Objects {
+    fn resolve(&self, reference: PlainRef) -> pdf::error::Result<Primitive> {
+        self.0
+            .get(&reference)
+            .cloned()
+            .ok_or(pdf::error::PdfError::NullRef {
+                obj_nr: reference.id,
+            })
+    }
+}
+
+pub fn dictionary(entries: Vec<(&str, Primitive)>) -> Primitive {
+    Primitive::Dictionary(entries_dictionary(entries))
+}
+
+pub fn name(name: &str) -> Primitive {
+    Primitive::Name(name.to_string())
+}
+
+pub fn stream(entries: Vec<(&str, Primitive)>, data: &[u8]) -> Primitive {
+    Primitive::Stream(PdfStream {
+        info: entries_dictionary(entries),
+        data: data.to_vec(),
+    })
+}
+
+fn entries_dictionary(entries: Vec<(&str, Primitive)>) -> Dictionary {
+    let mut dictionary = Dictionary::new();
+    for (key, value) in entries {
+        dictionary.insert(key.to_string(), value);
+    }
+    dictionary
+}
+
+/// Page resources with Courier as the font `/F1`.
+pub fn courier_resources() -> Dictionary {
+    let mut font = Dictionary::new();
+    font.insert(
+        "BaseFont".to_string(),
+        Primitive::Name("Courier".to_string()),
+    );
+    let mut fonts = Dictionary::new();
+    fonts.insert("F1".to_string(), Primitive::Dictionary(font));
+    let mut resources = Dictionary::new();
+    resources.insert("Font".to_string(), Primitive::Dictionary(fonts));
+    resources
+}
+
+/// The first page of a document, with `resources` and a single content stream of `content`.
+pub fn page(resources: Dictionary, content: &[u8]) -> RawPage {
+    let mut page = Dictionary::new();
+    page.insert("Resources".to_string(), Primitive::Dictionary(resources));
+    page.insert(
+        "Contents".to_string(),
+        Primitive::Stream(PdfStream {
+            info: Dictionary::new(),
+            data: content.to_vec(),
+        }),
+    );
+
+    RawPage {
+        number: 1,
+        reference: None,
+        dictionary: page,
+    }
+}
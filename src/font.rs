@@ -0,0 +1,369 @@
+use std::{collections::HashMap, ops::Range};
+
+use pdf::{
+    object::Resolve,
+    primitive::{Dictionary, Primitive},
+};
+
+use crate::{
+    document::{self, get, get_dictionary, get_integer, get_name, get_number, get_numbers},
+    error::Error,
+};
+
+/// The ascent and descent used for fonts that don't give theirs, in text space units per unit of
+/// font size.
+const DEFAULT_ASCENT: f32 = 0.8;
+const DEFAULT_DESCENT: f32 = -0.2;
+
+/// The width of the glyphs of a standard 14 font that doesn't have `/Widths`, in glyph space
+/// units. Courier is monospaced, the others are only approximated by an average width.
+const COURIER_WIDTH: f32 = 600.0;
+const AVERAGE_WIDTH: f32 = 500.0;
+/// Wider than any glyph of the standard 14 fonts, in glyph space units.
+const WIDEST: f32 = 1100.0;
+/// The vertical advance of glyphs in fonts without `/DW2`, in glyph space units.
+const VERTICAL_ADVANCE: f32 = 1000.0;
+
+/// The metrics of a font needed to lay out the glyphs a string shows: how it's split into
+/// character codes and how wide each of their glyphs is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Font {
+    /// `/BaseFont`
+    pub base_font: Option<String>,
+    /// Whether character codes are two bytes long, as they are in Type 0 fonts. Type 0 fonts
+    /// with a CMap that mixes code lengths are taken to use two byte codes throughout.
+    pub two_byte: bool,
+    /// Whether glyphs are written top to bottom, as they are in Type 0 fonts with a vertical
+    /// CMap. Each glyph is then taken to be centered below the text position and to move it down
+    /// by the vertical advance.
+    pub vertical: bool,
+    /// The glyph widths by character code, in glyph space units.
+    widths: HashMap<u32, f32>,
+    /// The width of the glyphs that `widths` doesn't have.
+    default_width: f32,
+    /// The vertical advance of every glyph, in glyph space units. `/W2` and `/DW2` aren't read.
+    vertical_advance: f32,
+    /// Whether where the glyphs are is only approximated: `default_width` stands in for widths
+    /// that aren't known, or the font is a Type 0 font whose CMap isn't `Identity-H`, so that
+    /// codes may have other lengths, map to other CIDs or be written vertically.
+    approximate: bool,
+    /// The scale from glyph space to text space, 1/1000 except for Type 3 fonts.
+    scale: f32,
+    /// The top of the glyphs above the baseline, in text space units per unit of font size.
+    pub ascent: f32,
+    /// The bottom of the glyphs below the baseline, which is negative.
+    pub descent: f32,
+}
+
+impl Font {
+    /// Looks up the font named by a `Tf` operation in the `/Font` resources.
+    pub fn from_resources<R: Resolve>(
+        resolve: &R,
+        resources: &Dictionary,
+        name: &str,
+    ) -> Result<Self, Error> {
+        let fonts = get_dictionary(resolve, resources, "Font")?
+            .ok_or_else(|| Error::MissingEntry(format!("Font /{}", name)))?;
+        match get(resolve, &fonts, name)? {
+            Some(Primitive::Dictionary(font)) => Font::load(resolve, &font),
+            Some(_) => Err(Error::InvalidEntry(format!("Font /{}", name))),
+            None => Err(Error::MissingEntry(format!("Font /{}", name))),
+        }
+    }
+
+    /// Looks up a font like `from_resources`, loading each font only once for a `cache`.
+    pub(crate) fn cached<'c, R: Resolve>(
+        resolve: &R,
+        resources: &Dictionary,
+        cache: &'c mut HashMap<String, Font>,
+        name: &str,
+    ) -> Result<&'c Font, Error> {
+        if !cache.contains_key(name) {
+            let font = Font::from_resources(resolve, resources, name)?;
+            cache.insert(name.to_string(), font);
+        }
+
+        Ok(&cache[name])
+    }
+
+    pub fn load<R: Resolve>(resolve: &R, font: &Dictionary) -> Result<Self, Error> {
+        let subtype = get_name(resolve, font, "Subtype")?;
+        let base_font = get_name(resolve, font, "BaseFont")?;
+
+        if subtype.as_deref() == Some("Type0") {
+            let descendant = match get(resolve, font, "DescendantFonts")? {
+                Some(Primitive::Array(fonts)) => match fonts.first() {
+                    Some(descendant) => match document::resolve(resolve, descendant)? {
+                        Primitive::Dictionary(descendant) => descendant,
+                        _ => return Err(Error::InvalidEntry("DescendantFonts".into())),
+                    },
+                    None => return Err(Error::MissingEntry("DescendantFonts".into())),
+                },
+                _ => return Err(Error::MissingEntry("DescendantFonts".into())),
+            };
+            let (ascent, descent) = vertical_metrics(resolve, &descendant)?;
+            let (vertical, identity) = match get(resolve, font, "Encoding")? {
+                Some(Primitive::Name(name)) => (name.ends_with("-V"), name == "Identity-H"),
+                Some(Primitive::Stream(cmap)) => {
+                    (get_integer(resolve, &cmap.info, "WMode")? == Some(1), false)
+                }
+                _ => (false, false),
+            };
+
+            return Ok(Font {
+                base_font,
+                two_byte: true,
+                vertical,
+                widths: cid_widths(resolve, &descendant)?,
+                default_width: get_number(resolve, &descendant, "DW")?.unwrap_or(1000.0),
+                vertical_advance: VERTICAL_ADVANCE,
+                approximate: !identity,
+                scale: 0.001,
+                ascent,
+                descent,
+            });
+        }
+
+        let mut widths = HashMap::new();
+        if let Some(values) = get_numbers(resolve, font, "Widths")? {
+            let first = get_integer(resolve, font, "FirstChar")?.unwrap_or(0).max(0) as u32;
+            for (code, width) in (first..).zip(values) {
+                widths.insert(code, width);
+            }
+        }
+
+        let missing_width = match get_dictionary(resolve, font, "FontDescriptor")? {
+            Some(descriptor) => get_number(resolve, &descriptor, "MissingWidth")?,
+            None => None,
+        };
+        let (default_width, approximate) = match missing_width {
+            Some(width) => (width, false),
+            None if !widths.is_empty() => (0.0, false),
+            None if matches!(&base_font, Some(name) if name.starts_with("Courier")) => {
+                (COURIER_WIDTH, false)
+            }
+            None => (AVERAGE_WIDTH, true),
+        };
+
+        let (mut scale, (mut ascent, mut descent)) = (0.001, vertical_metrics(resolve, font)?);
+        if subtype.as_deref() == Some("Type3") {
+            let matrix = get_numbers(resolve, font, "FontMatrix")?
+                .unwrap_or_else(|| vec![0.001, 0.0, 0.0, 0.001, 0.0, 0.0]);
+            scale = matrix.first().copied().unwrap_or(0.001);
+            if let Some([_, lly, _, ury]) = get_numbers(resolve, font, "FontBBox")?.as_deref() {
+                let y_scale = matrix.get(3).copied().unwrap_or(0.001);
+                if lly != ury {
+                    ascent = ury * y_scale;
+                    descent = lly * y_scale;
+                }
+            }
+        }
+
+        Ok(Font {
+            base_font,
+            two_byte: false,
+            vertical: false,
+            widths,
+            default_width,
+            vertical_advance: VERTICAL_ADVANCE,
+            approximate,
+            scale,
+            ascent,
+            descent,
+        })
+    }
+
+    /// Splits a string into character codes, along with the bytes of each.
+    pub fn codes<'a>(&self, bytes: &'a [u8]) -> impl Iterator<Item = (Range<usize>, u32)> + 'a {
+        let length = if self.two_byte { 2 } else { 1 };
+
+        (0..bytes.len()).step_by(length).map(move |start| {
+            let end = (start + length).min(bytes.len());
+            let code = bytes[start..end]
+                .iter()
+                .fold(0, |code, byte| code << 8 | u32::from(*byte));
+            (start..end, code)
+        })
+    }
+
+    /// The width of a character code's glyph, in text space units per unit of font size.
+    pub fn width(&self, code: u32) -> f32 {
+        self.widths
+            .get(&code)
+            .copied()
+            .unwrap_or(self.default_width)
+            * self.scale
+    }
+
+    /// How far a glyph moves the text position down in vertical fonts, in text space units per
+    /// unit of font size.
+    pub fn vertical_advance(&self) -> f32 {
+        self.vertical_advance * self.scale
+    }
+
+    /// Whether where the glyphs are is only approximated, as it is for the standard 14 fonts
+    /// other than Courier when the font doesn't give their widths, and for Type 0 fonts with a
+    /// CMap other than `Identity-H`.
+    pub fn is_approximate(&self) -> bool {
+        self.approximate
+    }
+
+    /// The font with its approximated glyphs widened to more than any of them can be, to find the
+    /// most a string can cover. Every byte is taken to be a code of its own, so that the string
+    /// has as many glyphs as it can.
+    pub fn widest(&self) -> Font {
+        let mut font = self.clone();
+        if font.approximate {
+            let widest = font
+                .widths
+                .drain()
+                .fold(WIDEST.max(font.default_width), |widest, (_, width)| {
+                    widest.max(width)
+                });
+            font.two_byte = false;
+            font.default_width = widest;
+            font.vertical_advance = widest.max(font.vertical_advance);
+        }
+        font
+    }
+
+    /// Whether a character code gets word spacing, which only single byte code 32 does.
+    pub fn is_space(&self, code: u32) -> bool {
+        !self.two_byte && code == 32
+    }
+}
+
+/// `/Ascent` and `/Descent` from a font's descriptor, scaled to text space.
+fn vertical_metrics<R: Resolve>(resolve: &R, font: &Dictionary) -> Result<(f32, f32), Error> {
+    let descriptor = match get_dictionary(resolve, font, "FontDescriptor")? {
+        Some(descriptor) => descriptor,
+        None => return Ok((DEFAULT_ASCENT, DEFAULT_DESCENT)),
+    };
+
+    let ascent = get_number(resolve, &descriptor, "Ascent")?.filter(|ascent| *ascent > 0.0);
+    let descent = get_number(resolve, &descriptor, "Descent")?.filter(|descent| *descent < 0.0);
+    Ok((
+        ascent.map_or(DEFAULT_ASCENT, |ascent| ascent / 1000.0),
+        descent.map_or(DEFAULT_DESCENT, |descent| descent / 1000.0),
+    ))
+}
+
+/// The `/W` array of a CIDFont, which lists widths either as `c [w1 w2 ...]` for consecutive
+/// codes or as `first last w` for a range of codes with the same width.
+fn cid_widths<R: Resolve>(resolve: &R, font: &Dictionary) -> Result<HashMap<u32, f32>, Error> {
+    let mut widths = HashMap::new();
+    let array = match get(resolve, font, "W")? {
+        Some(Primitive::Array(array)) => array,
+        _ => return Ok(widths),
+    };
+
+    let number = |primitive: &Primitive| match document::resolve(resolve, primitive)? {
+        Primitive::Integer(integer) => Ok(integer as f32),
+        Primitive::Number(number) => Ok(number),
+        _ => Err(Error::InvalidEntry("W".into())),
+    };
+
+    let mut i = 0;
+    while i + 1 < array.len() {
+        let first = number(&array[i])?.max(0.0) as u32;
+        match document::resolve(resolve, &array[i + 1])? {
+            Primitive::Array(values) => {
+                for (code, value) in (first..=u32::MAX).zip(&values) {
+                    widths.insert(code, number(value)?);
+                }
+                i += 2;
+            }
+            last => {
+                let last = number(&last)?.max(0.0) as u32;
+                let width = number(
+                    array
+                        .get(i + 2)
+                        .ok_or_else(|| Error::InvalidEntry("W".into()))?,
+                )?;
+                // Guards against huge ranges in broken files.
+                for code in first..=last.min(first.saturating_add(0xffff)) {
+                    widths.insert(code, width);
+                }
+                i += 3;
+            }
+        }
+    }
+
+    Ok(widths)
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::object::NoResolve;
+
+    use super::*;
+
+    #[test]
+    fn reads_simple_and_composite_widths() {
+        let mut simple = Dictionary::new();
+        simple.insert(
+            "Subtype".to_string(),
+            Primitive::Name("TrueType".to_string()),
+        );
+        simple.insert("FirstChar".to_string(), Primitive::Integer(65));
+        simple.insert(
+            "Widths".to_string(),
+            Primitive::Array(vec![Primitive::Integer(600), Primitive::Integer(700)]),
+        );
+        let simple = Font::load(&NoResolve, &simple).unwrap();
+
+        assert_eq!(
+            simple
+                .codes(b"AB ")
+                .map(|(_, code)| (simple.width(code) * 1000.0).round())
+                .collect::<Vec<_>>(),
+            vec![600.0, 700.0, 0.0]
+        );
+        assert!(simple.is_space(32));
+
+        let mut descendant = Dictionary::new();
+        descendant.insert("DW".to_string(), Primitive::Integer(500));
+        descendant.insert(
+            "W".to_string(),
+            Primitive::Array(vec![
+                Primitive::Integer(1),
+                Primitive::Array(vec![Primitive::Integer(250), Primitive::Integer(300)]),
+                Primitive::Integer(10),
+                Primitive::Integer(12),
+                Primitive::Integer(1000),
+            ]),
+        );
+        let mut composite = Dictionary::new();
+        composite.insert("Subtype".to_string(), Primitive::Name("Type0".to_string()));
+        composite.insert(
+            "DescendantFonts".to_string(),
+            Primitive::Array(vec![Primitive::Dictionary(descendant)]),
+        );
+        let composite = Font::load(&NoResolve, &composite).unwrap();
+
+        assert_eq!(
+            composite
+                .codes(b"\x00\x01\x00\x02\x00\x0b\x00\x20")
+                .map(|(bytes, code)| (bytes, (composite.width(code) * 1000.0).round()))
+                .collect::<Vec<_>>(),
+            vec![(0..2, 250.0), (2..4, 300.0), (4..6, 1000.0), (6..8, 500.0)]
+        );
+        assert!(!composite.is_space(32));
+    }
+
+    #[test]
+    fn stops_cid_widths_at_the_last_code() {
+        let mut descendant = Dictionary::new();
+        descendant.insert(
+            "W".to_string(),
+            Primitive::Array(vec![
+                Primitive::Number(5e9),
+                Primitive::Array(vec![Primitive::Integer(500), Primitive::Integer(600)]),
+            ]),
+        );
+
+        let widths = cid_widths(&NoResolve, &descendant).unwrap();
+        assert_eq!(widths.len(), 1);
+        assert_eq!(widths[&u32::MAX], 500.0);
+    }
+}
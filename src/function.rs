@@ -0,0 +1,599 @@
+use pdf::{
+    object::Resolve,
+    primitive::{Dictionary, Primitive},
+};
+
+use crate::{
+    document::{self, get, get_integer, get_number, get_numbers, stream_data},
+    error::Error,
+    postscript::{CalculatorError, Program},
+};
+
+/// The most inputs a sampled function can have. Interpolation visits `2^inputs` corners of the
+/// cell containing the input, and no real function comes close to this.
+const MAX_SAMPLED_INPUTS: usize = 16;
+
+/// How deeply stitching functions can be nested, so that a function which is one of its own
+/// `/Functions` can't recurse without bound.
+const MAX_FUNCTION_DEPTH: usize = 8;
+
+/// A PDF function, section 7.10 of ISO 32000-1.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Function {
+    Sampled(SampledFunction),
+    Exponential(ExponentialFunction),
+    Stitching(StitchingFunction),
+    PostScript(PostScriptFunction),
+}
+
+/// A Type 0 function, which interpolates between samples in a table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SampledFunction {
+    pub domain: Vec<f32>,
+    pub range: Vec<f32>,
+    /// The number of samples in each input dimension.
+    pub size: Vec<usize>,
+    pub encode: Vec<f32>,
+    /// The samples, already mapped through `/Decode`, with the first input dimension varying
+    /// fastest and all outputs of a sample stored together.
+    pub samples: Vec<f32>,
+}
+
+/// A Type 2 function, `y = C0 + x^N * (C1 - C0)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExponentialFunction {
+    pub domain: Vec<f32>,
+    pub range: Option<Vec<f32>>,
+    pub c0: Vec<f32>,
+    pub c1: Vec<f32>,
+    pub n: f32,
+}
+
+/// A Type 3 function, which splits its domain between other one input functions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StitchingFunction {
+    pub domain: Vec<f32>,
+    pub range: Option<Vec<f32>>,
+    pub functions: Vec<Function>,
+    pub bounds: Vec<f32>,
+    pub encode: Vec<f32>,
+}
+
+/// A Type 4 function, written in a subset of PostScript.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PostScriptFunction {
+    pub domain: Vec<f32>,
+    pub range: Vec<f32>,
+    pub program: Program,
+}
+
+impl Function {
+    /// Parses a function dictionary or stream.
+    pub fn from_primitive<R: Resolve>(resolve: &R, primitive: &Primitive) -> Result<Self, Error> {
+        Self::parse(resolve, primitive, 0)
+    }
+
+    /// Parses the `/Function` entry of shadings and similar dictionaries, which is either one
+    /// function or an array of one input, one output functions, one for each output.
+    pub fn from_entry<R: Resolve>(
+        resolve: &R,
+        dictionary: &Dictionary,
+        key: &str,
+    ) -> Result<Vec<Self>, Error> {
+        match get(resolve, dictionary, key)? {
+            Some(Primitive::Array(functions)) => functions
+                .iter()
+                .map(|function| Self::from_primitive(resolve, function))
+                .collect(),
+            Some(function) => Ok(vec![Self::from_primitive(resolve, &function)?]),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn parse<R: Resolve>(resolve: &R, primitive: &Primitive, depth: usize) -> Result<Self, Error> {
+        if depth > MAX_FUNCTION_DEPTH {
+            return Err(Error::InvalidEntry("Function".into()));
+        }
+
+        match document::resolve(resolve, primitive)? {
+            Primitive::Dictionary(dictionary) => {
+                Self::from_dictionary(resolve, &dictionary, None, depth)
+            }
+            Primitive::Stream(stream) => {
+                let data = stream_data(resolve, &stream)?;
+                Self::from_dictionary(resolve, &stream.info, Some(data), depth)
+            }
+            _ => Err(Error::InvalidEntry("Function".into())),
+        }
+    }
+
+    fn from_dictionary<R: Resolve>(
+        resolve: &R,
+        dictionary: &Dictionary,
+        data: Option<Vec<u8>>,
+        depth: usize,
+    ) -> Result<Self, Error> {
+        let domain = get_numbers(resolve, dictionary, "Domain")?
+            .ok_or_else(|| Error::MissingEntry("Domain".into()))?;
+        let range = get_numbers(resolve, dictionary, "Range")?;
+        let function_type = get_integer(resolve, dictionary, "FunctionType")?
+            .ok_or_else(|| Error::MissingEntry("FunctionType".into()))?;
+
+        match (function_type, data) {
+            (0, Some(data)) => {
+                let range = range.ok_or_else(|| Error::MissingEntry("Range".into()))?;
+                let size = get_numbers(resolve, dictionary, "Size")?
+                    .ok_or_else(|| Error::MissingEntry("Size".into()))?
+                    .iter()
+                    .map(|size| *size as usize)
+                    .collect::<Vec<_>>();
+                let bits_per_sample = get_integer(resolve, dictionary, "BitsPerSample")?
+                    .ok_or_else(|| Error::MissingEntry("BitsPerSample".into()))?;
+                let encode = get_numbers(resolve, dictionary, "Encode")?.unwrap_or_else(|| {
+                    size.iter()
+                        .flat_map(|size| vec![0.0, (*size as f32 - 1.0).max(0.0)])
+                        .collect()
+                });
+                let decode =
+                    get_numbers(resolve, dictionary, "Decode")?.unwrap_or_else(|| range.clone());
+
+                if size.len() * 2 != domain.len()
+                    || size.len() > MAX_SAMPLED_INPUTS
+                    || encode.len() != domain.len()
+                    || decode.len() != range.len()
+                    || size.contains(&0)
+                    || !matches!(bits_per_sample, 1 | 2 | 4 | 8 | 12 | 16 | 24 | 32)
+                {
+                    return Err(Error::InvalidEntry("FunctionType 0".into()));
+                }
+
+                let outputs = range.len() / 2;
+                let count = size
+                    .iter()
+                    .try_fold(outputs, |count, size| count.checked_mul(*size))
+                    .filter(|count| {
+                        count
+                            .checked_mul(bits_per_sample as usize)
+                            .is_some_and(|bits| bits <= data.len() * 8)
+                    })
+                    .ok_or_else(|| Error::InvalidEntry("FunctionType 0 data".into()))?;
+                let max = (2f64.powi(bits_per_sample) - 1.0) as f32;
+                let mut reader = BitReader::new(&data);
+                let samples = (0..count)
+                    .map(|i| {
+                        let sample = reader
+                            .read(bits_per_sample as u32)
+                            .ok_or_else(|| Error::InvalidEntry("FunctionType 0 data".into()))?;
+                        let j = i % outputs;
+                        Ok(interpolate(
+                            sample as f32,
+                            0.0,
+                            max,
+                            decode[2 * j],
+                            decode[2 * j + 1],
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+
+                Ok(Function::Sampled(SampledFunction {
+                    domain,
+                    range,
+                    size,
+                    encode,
+                    samples,
+                }))
+            }
+            (2, _) => {
+                let c0 = get_numbers(resolve, dictionary, "C0")?.unwrap_or_else(|| vec![0.0]);
+                let c1 = get_numbers(resolve, dictionary, "C1")?.unwrap_or_else(|| vec![1.0]);
+                let n = get_number(resolve, dictionary, "N")?
+                    .ok_or_else(|| Error::MissingEntry("N".into()))?;
+
+                if c0.len() != c1.len() {
+                    return Err(Error::InvalidEntry("C1".into()));
+                }
+
+                Ok(Function::Exponential(ExponentialFunction {
+                    domain,
+                    range,
+                    c0,
+                    c1,
+                    n,
+                }))
+            }
+            (3, _) => {
+                let functions = match get(resolve, dictionary, "Functions")? {
+                    Some(Primitive::Array(functions)) => functions
+                        .iter()
+                        .map(|function| Self::parse(resolve, function, depth + 1))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    _ => return Err(Error::MissingEntry("Functions".into())),
+                };
+                let bounds = get_numbers(resolve, dictionary, "Bounds")?
+                    .ok_or_else(|| Error::MissingEntry("Bounds".into()))?;
+                let encode = get_numbers(resolve, dictionary, "Encode")?
+                    .ok_or_else(|| Error::MissingEntry("Encode".into()))?;
+
+                if domain.len() != 2
+                    || functions.is_empty()
+                    || bounds.len() + 1 != functions.len()
+                    || encode.len() != functions.len() * 2
+                {
+                    return Err(Error::InvalidEntry("FunctionType 3".into()));
+                }
+
+                Ok(Function::Stitching(StitchingFunction {
+                    domain,
+                    range,
+                    functions,
+                    bounds,
+                    encode,
+                }))
+            }
+            (4, Some(code)) => Ok(Function::PostScript(PostScriptFunction {
+                domain,
+                range: range.ok_or_else(|| Error::MissingEntry("Range".into()))?,
+                program: Program::parse(&code)
+                    .map_err(|error| Error::InvalidEntry(format!("FunctionType 4: {}", error)))?,
+            })),
+            _ => Err(Error::InvalidEntry("FunctionType".into())),
+        }
+    }
+
+    pub fn domain(&self) -> &[f32] {
+        match self {
+            Function::Sampled(function) => &function.domain,
+            Function::Exponential(function) => &function.domain,
+            Function::Stitching(function) => &function.domain,
+            Function::PostScript(function) => &function.domain,
+        }
+    }
+
+    pub fn range(&self) -> Option<&[f32]> {
+        match self {
+            Function::Sampled(function) => Some(&function.range),
+            Function::Exponential(function) => function.range.as_deref(),
+            Function::Stitching(function) => function.range.as_deref(),
+            Function::PostScript(function) => Some(&function.range),
+        }
+    }
+
+    /// Evaluates the function. Inputs are clipped to the domain and outputs to the range.
+    pub fn eval(&self, input: &[f32]) -> Vec<f32> {
+        let input = clip(input, self.domain());
+
+        let output = match self {
+            Function::Sampled(function) => function.eval(&input),
+            Function::Exponential(function) => function.eval(&input),
+            Function::Stitching(function) => function.eval(&input),
+            Function::PostScript(function) => function.eval(&input),
+        };
+
+        match self.range() {
+            Some(range) => clip(&output, range),
+            None => output,
+        }
+    }
+}
+
+impl SampledFunction {
+    fn eval(&self, input: &[f32]) -> Vec<f32> {
+        let outputs = self.range.len() / 2;
+
+        // The position of the input in the sample table, and the index of the sample below it.
+        let mut positions = Vec::with_capacity(self.size.len());
+        let mut stride = outputs;
+        let mut base = 0;
+        for (i, size) in self.size.iter().enumerate() {
+            let x = input.get(i).copied().unwrap_or(self.domain[2 * i]);
+            let e = interpolate(
+                x,
+                self.domain[2 * i],
+                self.domain[2 * i + 1],
+                self.encode[2 * i],
+                self.encode[2 * i + 1],
+            )
+            .max(0.0)
+            .min(*size as f32 - 1.0);
+
+            let below = (e.floor() as usize).min(size - 1);
+            let fraction = e - below as f32;
+            let step = if below + 1 < *size { stride } else { 0 };
+
+            base += below * stride;
+            positions.push((fraction, step));
+            stride *= size;
+        }
+
+        // Multilinear interpolation between the samples at the corners of the cell.
+        let mut output = vec![0.0; outputs];
+        for corner in 0..1usize << positions.len() {
+            let mut weight = 1.0;
+            let mut index = base;
+            for (i, (fraction, step)) in positions.iter().enumerate() {
+                if corner & (1 << i) == 0 {
+                    weight *= 1.0 - fraction;
+                } else {
+                    weight *= fraction;
+                    index += step;
+                }
+            }
+
+            if weight != 0.0 {
+                for (j, output) in output.iter_mut().enumerate() {
+                    *output += weight * self.samples[index + j];
+                }
+            }
+        }
+
+        output
+    }
+}
+
+impl ExponentialFunction {
+    fn eval(&self, input: &[f32]) -> Vec<f32> {
+        let x = input.first().copied().unwrap_or(0.0);
+        let power = x.powf(self.n);
+
+        self.c0
+            .iter()
+            .zip(&self.c1)
+            .map(|(c0, c1)| c0 + power * (c1 - c0))
+            .collect()
+    }
+}
+
+impl StitchingFunction {
+    fn eval(&self, input: &[f32]) -> Vec<f32> {
+        let x = input.first().copied().unwrap_or(self.domain[0]);
+
+        let k = self
+            .bounds
+            .iter()
+            .position(|bound| x < *bound)
+            .unwrap_or(self.bounds.len());
+        let low = if k == 0 {
+            self.domain[0]
+        } else {
+            self.bounds[k - 1]
+        };
+        let high = if k == self.bounds.len() {
+            self.domain[1]
+        } else {
+            self.bounds[k]
+        };
+
+        let x = interpolate(x, low, high, self.encode[2 * k], self.encode[2 * k + 1]);
+        self.functions[k].eval(&[x])
+    }
+}
+
+impl PostScriptFunction {
+    /// Runs the program, returning the values it leaves on top of the stack, one for each output.
+    pub fn run(&self, input: &[f32]) -> Result<Vec<f32>, CalculatorError> {
+        let outputs = self.range.len() / 2;
+        let mut stack = self.program.run(input)?;
+
+        if stack.len() < outputs {
+            return Err(CalculatorError::StackUnderflow);
+        }
+        Ok(stack.split_off(stack.len() - outputs))
+    }
+
+    /// Programs that fail to run give the minimum of each output's range.
+    fn eval(&self, input: &[f32]) -> Vec<f32> {
+        self.run(input)
+            .unwrap_or_else(|_| self.range.chunks(2).map(|range| range[0]).collect())
+    }
+}
+
+/// Maps `x` from `[x_min, x_max]` to `[y_min, y_max]`.
+pub(crate) fn interpolate(x: f32, x_min: f32, x_max: f32, y_min: f32, y_max: f32) -> f32 {
+    if x_max == x_min {
+        y_min
+    } else {
+        y_min + (x - x_min) * (y_max - y_min) / (x_max - x_min)
+    }
+}
+
+fn clip(values: &[f32], bounds: &[f32]) -> Vec<f32> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| match bounds.get(2 * i..2 * i + 2) {
+            Some([min, max]) => value.max(*min).min(*max),
+            _ => *value,
+        })
+        .collect()
+}
+
+/// Reads big endian values of up to 32 bits from packed binary data.
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        BitReader { data, position: 0 }
+    }
+
+    pub(crate) fn read(&mut self, bits: u32) -> Option<u32> {
+        let mut value = 0u64;
+
+        for _ in 0..bits {
+            let byte = self.data.get(self.position / 8)?;
+            let bit = (byte >> (7 - self.position % 8)) & 1;
+            value = value << 1 | u64::from(bit);
+            self.position += 1;
+        }
+
+        Some(value as u32)
+    }
+
+    /// Skips to the start of the next byte.
+    pub(crate) fn align(&mut self) {
+        self.position = self.position.div_ceil(8) * 8;
+    }
+
+    pub(crate) fn has_data(&self) -> bool {
+        self.position / 8 < self.data.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::{object::NoResolve, primitive::PdfStream};
+
+    use super::*;
+
+    fn numbers(numbers: &[f32]) -> Primitive {
+        Primitive::Array(numbers.iter().map(|n| Primitive::Number(*n)).collect())
+    }
+
+    fn parse(
+        function_type: i32,
+        entries: Vec<(&str, Primitive)>,
+        data: Option<&[u8]>,
+    ) -> Result<Function, Error> {
+        let mut dictionary = Dictionary::new();
+        dictionary.insert(
+            "FunctionType".to_string(),
+            Primitive::Integer(function_type),
+        );
+        dictionary.insert("Domain".to_string(), numbers(&[0.0, 1.0]));
+        for (key, value) in entries {
+            dictionary.insert(key.to_string(), value);
+        }
+
+        let primitive = match data {
+            Some(data) => Primitive::Stream(PdfStream {
+                info: dictionary,
+                data: data.to_vec(),
+            }),
+            None => Primitive::Dictionary(dictionary),
+        };
+        Function::from_primitive(&NoResolve, &primitive)
+    }
+
+    fn function(
+        function_type: i32,
+        entries: Vec<(&str, Primitive)>,
+        data: Option<&[u8]>,
+    ) -> Function {
+        parse(function_type, entries, data).unwrap()
+    }
+
+    #[test]
+    fn evaluates_functions() {
+        let sampled = function(
+            0,
+            vec![
+                ("Range", numbers(&[0.0, 1.0])),
+                ("Size", numbers(&[3.0])),
+                ("BitsPerSample", Primitive::Integer(8)),
+            ],
+            Some(&[0, 255, 0]),
+        );
+        assert_eq!(sampled.eval(&[0.25]), vec![0.5]);
+        assert_eq!(sampled.eval(&[2.0]), vec![0.0]);
+
+        let mut linear = Dictionary::new();
+        linear.insert("FunctionType".to_string(), Primitive::Integer(2));
+        linear.insert("Domain".to_string(), numbers(&[0.0, 1.0]));
+        linear.insert("N".to_string(), Primitive::Integer(1));
+        let linear = Primitive::Dictionary(linear);
+
+        let stitching = function(
+            3,
+            vec![
+                ("Functions", Primitive::Array(vec![linear.clone(), linear])),
+                ("Bounds", numbers(&[0.5])),
+                ("Encode", numbers(&[0.0, 1.0, 1.0, 0.0])),
+            ],
+            None,
+        );
+        assert_eq!(stitching.eval(&[0.25]), vec![0.5]);
+        assert!((stitching.eval(&[0.6])[0] - 0.8).abs() < 1e-6);
+
+        let postscript = function(
+            4,
+            vec![("Range", numbers(&[0.0, 1.0, 0.0, 1.0]))],
+            Some(b"{ dup 2 mul exch 1 exch sub }"),
+        );
+        assert_eq!(postscript.eval(&[0.25]), vec![0.5, 0.75]);
+        assert_eq!(postscript.eval(&[0.75]), vec![1.0, 0.25]);
+
+        let failing = function(
+            4,
+            vec![("Range", numbers(&[0.5, 1.0]))],
+            Some(b"{ pop pop }"),
+        );
+        assert_eq!(failing.eval(&[0.0]), vec![0.5]);
+    }
+
+    #[test]
+    fn rejects_oversized_sample_tables() {
+        let sampled = |inputs: usize, size: f32, data: &[u8]| {
+            parse(
+                0,
+                vec![
+                    ("Domain", numbers(&vec![0.0; 2 * inputs])),
+                    ("Range", numbers(&[0.0, 1.0])),
+                    ("Size", numbers(&vec![size; inputs])),
+                    ("BitsPerSample", Primitive::Integer(8)),
+                ],
+                Some(data),
+            )
+        };
+
+        assert!(sampled(2, 2.0, &[0; 4]).is_ok());
+        assert!(matches!(
+            sampled(2, 2.0, &[0; 3]),
+            Err(Error::InvalidEntry(_))
+        ));
+        assert!(matches!(
+            sampled(4, 1e9, &[0; 4]),
+            Err(Error::InvalidEntry(_))
+        ));
+        assert!(matches!(
+            sampled(17, 1.0, &[0; 4]),
+            Err(Error::InvalidEntry(_))
+        ));
+        assert!(matches!(
+            sampled(1, f32::INFINITY, &[0; 4]),
+            Err(Error::InvalidEntry(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_stitching_functions() {
+        let stitching = |function: Primitive, domain: &[f32]| {
+            let mut dictionary = Dictionary::new();
+            dictionary.insert("FunctionType".to_string(), Primitive::Integer(3));
+            dictionary.insert("Domain".to_string(), numbers(domain));
+            dictionary.insert("Functions".to_string(), Primitive::Array(vec![function]));
+            dictionary.insert("Bounds".to_string(), numbers(&[]));
+            dictionary.insert("Encode".to_string(), numbers(&[0.0, 1.0]));
+            Primitive::Dictionary(dictionary)
+        };
+        let mut linear = Dictionary::new();
+        linear.insert("FunctionType".to_string(), Primitive::Integer(2));
+        linear.insert("Domain".to_string(), numbers(&[0.0, 1.0]));
+        linear.insert("N".to_string(), Primitive::Integer(1));
+
+        let mut nested = Primitive::Dictionary(linear);
+        for _ in 0..MAX_FUNCTION_DEPTH {
+            nested = stitching(nested, &[0.0, 1.0]);
+        }
+        assert!(Function::from_primitive(&NoResolve, &nested).is_ok());
+        assert!(matches!(
+            Function::from_primitive(&NoResolve, &stitching(nested.clone(), &[0.0, 1.0])),
+            Err(Error::InvalidEntry(_))
+        ));
+        assert!(matches!(
+            Function::from_primitive(&NoResolve, &stitching(nested, &[0.0])),
+            Err(Error::InvalidEntry(_))
+        ));
+    }
+}
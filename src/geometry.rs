@@ -0,0 +1,154 @@
+use std::ops::Mul;
+
+/// A transformation matrix `[a b c d e f]`, mapping `(x, y)` to
+/// `(a * x + c * y + e, b * x + d * y + f)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Matrix {
+    pub const IDENTITY: Matrix = Matrix::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+
+    pub const fn new(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        Matrix { a, b, c, d, e, f }
+    }
+
+    pub fn translate(x: f32, y: f32) -> Self {
+        Matrix::new(1.0, 0.0, 0.0, 1.0, x, y)
+    }
+
+    pub fn from_slice(values: &[f32]) -> Option<Self> {
+        match values {
+            [a, b, c, d, e, f] => Some(Matrix::new(*a, *b, *c, *d, *e, *f)),
+            _ => None,
+        }
+    }
+
+    pub fn transform(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        )
+    }
+
+    /// Transforms a distance, ignoring the translation.
+    pub fn transform_vector(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.a * x + self.c * y, self.b * x + self.d * y)
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.a * self.d - self.b * self.c
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.determinant();
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+
+        Some(Matrix::new(
+            self.d / determinant,
+            -self.b / determinant,
+            -self.c / determinant,
+            self.a / determinant,
+            (self.c * self.f - self.d * self.e) / determinant,
+            (self.b * self.e - self.a * self.f) / determinant,
+        ))
+    }
+}
+
+impl Default for Matrix {
+    fn default() -> Self {
+        Matrix::IDENTITY
+    }
+}
+
+/// `m * n` applies `m` first and then `n`, the order used by the `cm` operator: `cm` with `m`
+/// changes the CTM to `m * ctm`.
+impl Mul for Matrix {
+    type Output = Matrix;
+
+    fn mul(self, n: Matrix) -> Matrix {
+        Matrix::new(
+            self.a * n.a + self.b * n.c,
+            self.a * n.b + self.b * n.d,
+            self.c * n.a + self.d * n.c,
+            self.c * n.b + self.d * n.d,
+            self.e * n.a + self.f * n.c + n.e,
+            self.e * n.b + self.f * n.d + n.f,
+        )
+    }
+}
+
+/// An axis aligned rectangle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x_min: f32,
+    pub y_min: f32,
+    pub x_max: f32,
+    pub y_max: f32,
+}
+
+impl Rect {
+    /// A rectangle from two opposite corners, in any order.
+    pub fn new(x1: f32, y1: f32, x2: f32, y2: f32) -> Self {
+        Rect {
+            x_min: x1.min(x2),
+            y_min: y1.min(y2),
+            x_max: x1.max(x2),
+            y_max: y1.max(y2),
+        }
+    }
+
+    pub fn from_slice(values: &[f32]) -> Option<Self> {
+        match values {
+            [x1, y1, x2, y2] => Some(Rect::new(*x1, *y1, *x2, *y2)),
+            _ => None,
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.x_max - self.x_min
+    }
+
+    pub fn height(&self) -> f32 {
+        self.y_max - self.y_min
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x_min && x <= self.x_max && y >= self.y_min && y <= self.y_max
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x_min < other.x_max
+            && other.x_min < self.x_max
+            && self.y_min < other.y_max
+            && other.y_min < self.y_max
+    }
+
+    /// The bounding box of this rectangle after transforming it by `matrix`.
+    pub fn transform(&self, matrix: &Matrix) -> Rect {
+        let corners = [
+            matrix.transform(self.x_min, self.y_min),
+            matrix.transform(self.x_max, self.y_min),
+            matrix.transform(self.x_min, self.y_max),
+            matrix.transform(self.x_max, self.y_max),
+        ];
+
+        corners.iter().skip(1).fold(
+            Rect::new(corners[0].0, corners[0].1, corners[0].0, corners[0].1),
+            |rect, (x, y)| Rect {
+                x_min: rect.x_min.min(*x),
+                y_min: rect.y_min.min(*y),
+                x_max: rect.x_max.max(*x),
+                y_max: rect.y_max.max(*y),
+            },
+        )
+    }
+}
@@ -0,0 +1,287 @@
+use pdf::{
+    object::Resolve,
+    primitive::{Dictionary, Primitive},
+};
+
+use crate::{
+    color::{Color, ColorSpace},
+    error::Error,
+    ext_g_state::{BlendMode, DashPattern, ExtGState, SoftMask},
+    geometry::Matrix,
+    text_state::TextState,
+    ColorRenderingIntent, LineCapStyle, LineJoinStyle, Name, Operation,
+};
+
+/// The device independent graphics state parameters, Table 52 in ISO 32000-1, with their initial
+/// values from a fresh page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphicsState {
+    pub ctm: Matrix,
+    pub stroke_color: Color,
+    pub fill_color: Color,
+    pub line_width: f32,
+    pub line_cap: LineCapStyle,
+    pub line_join: LineJoinStyle,
+    pub miter_limit: f32,
+    pub dash_pattern: DashPattern,
+    pub rendering_intent: ColorRenderingIntent,
+    pub stroke_adjustment: bool,
+    pub blend_mode: BlendMode,
+    pub soft_mask: SoftMask,
+    pub stroke_alpha: f32,
+    pub fill_alpha: f32,
+    pub alpha_is_shape: bool,
+    pub text_knockout: bool,
+    pub stroke_overprint: bool,
+    pub fill_overprint: bool,
+    pub overprint_mode: i32,
+    /// The font and size set by a graphics state parameter dictionary's `/Font` entry.
+    pub font: Option<(Primitive, f32)>,
+    pub black_generation: Option<Primitive>,
+    pub undercolor_removal: Option<Primitive>,
+    pub transfer: Option<Primitive>,
+    pub halftone: Option<Primitive>,
+    pub flatness: f32,
+    pub smoothness: f32,
+    /// The text state parameters set by `Tc`, `Tw`, `Tz`, `TL`, `Tf`, `Tr` and `Ts`. The text
+    /// matrices belong to text objects rather than the graphics state, and are left as they are.
+    pub text: TextState,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        GraphicsState {
+            ctm: Matrix::IDENTITY,
+            stroke_color: Color::default(),
+            fill_color: Color::default(),
+            line_width: 1.0,
+            line_cap: LineCapStyle::ButtCap,
+            line_join: LineJoinStyle::MiterJoin,
+            miter_limit: 10.0,
+            dash_pattern: DashPattern {
+                array: Vec::new(),
+                phase: 0.0,
+            },
+            rendering_intent: ColorRenderingIntent::RelativeColorimetric,
+            stroke_adjustment: false,
+            blend_mode: BlendMode::Normal,
+            soft_mask: SoftMask::None,
+            stroke_alpha: 1.0,
+            fill_alpha: 1.0,
+            alpha_is_shape: false,
+            text_knockout: true,
+            stroke_overprint: false,
+            fill_overprint: false,
+            overprint_mode: 0,
+            font: None,
+            black_generation: None,
+            undercolor_removal: None,
+            transfer: None,
+            halftone: None,
+            flatness: 1.0,
+            smoothness: 0.0,
+            text: TextState::default(),
+        }
+    }
+}
+
+impl GraphicsState {
+    /// Sets the parameters given in a graphics state parameter dictionary, as the `gs` operator
+    /// does.
+    pub fn apply(&mut self, ext_g_state: &ExtGState) {
+        let ExtGState {
+            line_width,
+            line_cap,
+            line_join,
+            miter_limit,
+            dash_pattern,
+            rendering_intent,
+            stroke_overprint,
+            fill_overprint,
+            overprint_mode,
+            font,
+            black_generation,
+            black_generation2,
+            undercolor_removal,
+            undercolor_removal2,
+            transfer,
+            transfer2,
+            halftone,
+            flatness,
+            smoothness,
+            stroke_adjustment,
+            blend_mode,
+            soft_mask,
+            stroke_alpha,
+            fill_alpha,
+            alpha_is_shape,
+            text_knockout,
+        } = ext_g_state;
+
+        set(&mut self.line_width, line_width);
+        set(&mut self.line_cap, line_cap);
+        set(&mut self.line_join, line_join);
+        set(&mut self.miter_limit, miter_limit);
+        set(&mut self.dash_pattern, dash_pattern);
+        set(&mut self.rendering_intent, rendering_intent);
+        set(&mut self.stroke_overprint, stroke_overprint);
+        set(&mut self.fill_overprint, fill_overprint);
+        set(&mut self.overprint_mode, overprint_mode);
+        set(&mut self.flatness, flatness);
+        set(&mut self.smoothness, smoothness);
+        set(&mut self.stroke_adjustment, stroke_adjustment);
+        set(&mut self.blend_mode, blend_mode);
+        set(&mut self.soft_mask, soft_mask);
+        set(&mut self.stroke_alpha, stroke_alpha);
+        set(&mut self.fill_alpha, fill_alpha);
+        set(&mut self.alpha_is_shape, alpha_is_shape);
+        set(&mut self.text_knockout, text_knockout);
+
+        if font.is_some() {
+            self.font = font.clone();
+        }
+        if halftone.is_some() {
+            self.halftone = halftone.clone();
+        }
+
+        // The second versions of these functions take precedence, and can be `/Default`.
+        set_function(
+            &mut self.black_generation,
+            black_generation2,
+            black_generation,
+        );
+        set_function(
+            &mut self.undercolor_removal,
+            undercolor_removal2,
+            undercolor_removal,
+        );
+        set_function(&mut self.transfer, transfer2, transfer);
+    }
+}
+
+fn set<T: Clone>(parameter: &mut T, value: &Option<T>) {
+    if let Some(value) = value {
+        *parameter = value.clone();
+    }
+}
+
+fn set_function(
+    parameter: &mut Option<Primitive>,
+    second: &Option<Primitive>,
+    first: &Option<Primitive>,
+) {
+    match second.as_ref().or(first.as_ref()) {
+        Some(Primitive::Name(name)) if name == "Default" => *parameter = None,
+        Some(function) => *parameter = Some(function.clone()),
+        None => {}
+    }
+}
+
+/// Tracks the graphics state through a content stream, including the states saved by `q`.
+#[derive(Clone, Debug, Default)]
+pub struct GraphicsStateStack {
+    current: GraphicsState,
+    saved: Vec<GraphicsState>,
+}
+
+impl GraphicsStateStack {
+    pub fn new(initial: GraphicsState) -> Self {
+        GraphicsStateStack {
+            current: initial,
+            saved: Vec::new(),
+        }
+    }
+
+    pub fn current(&self) -> &GraphicsState {
+        &self.current
+    }
+
+    pub fn current_mut(&mut self) -> &mut GraphicsState {
+        &mut self.current
+    }
+
+    /// The number of states saved by `q` that haven't been restored yet.
+    pub fn depth(&self) -> usize {
+        self.saved.len()
+    }
+
+    /// Updates the state for an operation. `gs`, `CS` and `cs` operations look up their
+    /// parameter dictionary or color space in `resources`.
+    pub fn update<R: Resolve>(
+        &mut self,
+        operation: &Operation,
+        resources: &Dictionary,
+        resolve: &R,
+    ) -> Result<(), Error> {
+        let state = &mut self.current;
+
+        match operation {
+            Operation::SaveGraphicsState => self.saved.push(state.clone()),
+            Operation::RestoreGraphicsState => {
+                // Unbalanced Q operations are ignored, as most readers do.
+                if let Some(saved) = self.saved.pop() {
+                    self.current = saved;
+                }
+            }
+            Operation::ConcatenateMatrixToCurrentTransformationMatrix(a, b, c, d, e, f) => {
+                state.ctm = Matrix::new(*a, *b, *c, *d, *e, *f) * state.ctm;
+            }
+            Operation::SetLineWidth(width) => state.line_width = *width,
+            Operation::SetLineCapStyle(style) => state.line_cap = *style,
+            Operation::SetLineJoinStyle(style) => state.line_join = *style,
+            Operation::SetMiterLimit(limit) => state.miter_limit = *limit,
+            Operation::SetLineDashPattern { array, phase } => {
+                state.dash_pattern = DashPattern {
+                    array: array.clone(),
+                    phase: *phase,
+                }
+            }
+            Operation::SetColorRenderingIntent(intent) => state.rendering_intent = *intent,
+            Operation::SetFlatnessTolerance(flatness) => state.flatness = *flatness as f32,
+            Operation::SetParametersFromGraphicsStateParameterDictionary(Name(name)) => {
+                state.apply(&ExtGState::from_resources(resolve, resources, name)?)
+            }
+            Operation::SetColorSpaceForStrokingOperations(Name(name)) => {
+                let color_space = ColorSpace::from_resources(resolve, resources, name)?;
+                let components = color_space.initial_color();
+                state.stroke_color = Color::new(color_space, components);
+            }
+            Operation::SetColorSpaceForNonStrokingOperations(Name(name)) => {
+                let color_space = ColorSpace::from_resources(resolve, resources, name)?;
+                let components = color_space.initial_color();
+                state.fill_color = Color::new(color_space, components);
+            }
+            Operation::SetGrayLevelForStrokingOperations(_)
+            | Operation::SetRGBColorForStrokingOperations(..)
+            | Operation::SetCMYKColorForStrokingOperations(..)
+            | Operation::SetColorForStrokingOperations(_)
+            | Operation::SetColorForStrokingOperationsICCBasedAndSpecialColorSpaces { .. } => {
+                if let Some(color) =
+                    Color::from_operation(operation, &state.stroke_color.color_space)
+                {
+                    state.stroke_color = color;
+                }
+            }
+            Operation::SetGrayLevelForNonStrokingOperations(_)
+            | Operation::SetRGBColorForNonStrokingOperations(..)
+            | Operation::SetCMYKColorForNonStrokingOperations(..)
+            | Operation::SetColorForNonStrokingOperations(_)
+            | Operation::SetColorForNonStrokingOperationsICCBasedAndSpecialColorSpaces { .. } => {
+                if let Some(color) = Color::from_operation(operation, &state.fill_color.color_space)
+                {
+                    state.fill_color = color;
+                }
+            }
+            Operation::SetCharacterSpacing(_)
+            | Operation::SetWordSpacing(_)
+            | Operation::SetHorizontalTextScaling(_)
+            | Operation::SetTextLeading(_)
+            | Operation::SetTextFontAndSize { .. }
+            | Operation::SetTextRenderingMode(_)
+            | Operation::SetTextRise(_) => state.text.update(operation),
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1010 @@
+//! A reader for the ICC profiles embedded in `/ICCBased` color spaces, ICC.1:2010 (version 4)
+//! and the version 2 tag types still common in PDF files.
+
+use crate::{error::Error, ColorRenderingIntent};
+
+/// The largest color lookup table that will be read, in values.
+const MAX_CLUT_SIZE: usize = 1 << 24;
+/// The most input channels a lookup table can interpolate between.
+const MAX_CLUT_INPUTS: usize = 8;
+/// The D50 illuminant of the profile connection space.
+pub const D50: [f32; 3] = [0.9642, 1.0, 0.8249];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeviceClass {
+    Input,
+    Display,
+    Output,
+    DeviceLink,
+    ColorSpace,
+    Abstract,
+    NamedColor,
+    Other(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColorSpaceSignature {
+    Xyz,
+    Lab,
+    Gray,
+    Rgb,
+    Cmyk,
+    /// Any other color space, with its number of components if it's known.
+    Other(String, Option<usize>),
+}
+
+impl ColorSpaceSignature {
+    /// Reads the signature from its raw bytes, which needn't be ASCII.
+    fn from_signature(signature: &[u8]) -> Self {
+        match signature {
+            b"XYZ " => ColorSpaceSignature::Xyz,
+            b"Lab " => ColorSpaceSignature::Lab,
+            b"GRAY" => ColorSpaceSignature::Gray,
+            b"RGB " => ColorSpaceSignature::Rgb,
+            b"CMYK" => ColorSpaceSignature::Cmyk,
+            signature => {
+                let components = match signature {
+                    b"Luv " | b"YCbr" | b"Yxy " | b"HSV " | b"HLS " | b"CMY " => Some(3),
+                    // `2CLR` to `FCLR`, two to fifteen color components.
+                    [digit, b'C', b'L', b'R'] => (*digit as char)
+                        .to_digit(16)
+                        .map(|components| components as usize),
+                    _ => None,
+                };
+                ColorSpaceSignature::Other(
+                    String::from_utf8_lossy(signature).into_owned(),
+                    components,
+                )
+            }
+        }
+    }
+
+    pub fn components(&self) -> Option<usize> {
+        match self {
+            ColorSpaceSignature::Gray => Some(1),
+            ColorSpaceSignature::Xyz | ColorSpaceSignature::Lab | ColorSpaceSignature::Rgb => {
+                Some(3)
+            }
+            ColorSpaceSignature::Cmyk => Some(4),
+            ColorSpaceSignature::Other(_, components) => *components,
+        }
+    }
+}
+
+/// A tone reproduction curve, mapping `[0, 1]` to `[0, 1]`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Curve {
+    Identity,
+    Gamma(f32),
+    /// Evenly spaced samples, interpolated linearly.
+    Table(Vec<f32>),
+    /// A `parametricCurveType` function and its parameters `g a b c d e f`, as many as the
+    /// function uses.
+    Parametric {
+        function: u16,
+        parameters: Vec<f32>,
+    },
+}
+
+impl Curve {
+    pub fn eval(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+
+        let y = match self {
+            Curve::Identity => x,
+            Curve::Gamma(gamma) => x.powf(*gamma),
+            Curve::Table(table) => match table.len() {
+                0 => x,
+                1 => table[0],
+                len => {
+                    let position = x * (len - 1) as f32;
+                    let i = (position.floor() as usize).min(len - 2);
+                    let fraction = position - i as f32;
+                    table[i] + fraction * (table[i + 1] - table[i])
+                }
+            },
+            Curve::Parametric {
+                function,
+                parameters,
+            } => {
+                let p = |i: usize| parameters.get(i).copied().unwrap_or(0.0);
+                let (g, a, b, c, d, e, f) = (p(0), p(1), p(2), p(3), p(4), p(5), p(6));
+                let power = |x: f32| (a * x + b).max(0.0).powf(g);
+
+                match function {
+                    0 => x.powf(g),
+                    1 if a * x + b >= 0.0 => power(x),
+                    1 => 0.0,
+                    2 if a * x + b >= 0.0 => power(x) + c,
+                    2 => c,
+                    3 if x >= d => power(x),
+                    3 => c * x,
+                    4 if x >= d => power(x) + e,
+                    4 => c * x + f,
+                    _ => x,
+                }
+            }
+        };
+
+        if y.is_finite() {
+            y
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A color lookup table, with the first input varying slowest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clut {
+    pub grid_points: Vec<usize>,
+    pub outputs: usize,
+    /// The table, normalized to `[0, 1]`.
+    pub values: Vec<f32>,
+}
+
+impl Clut {
+    pub fn eval(&self, input: &[f32]) -> Vec<f32> {
+        let mut positions = Vec::with_capacity(self.grid_points.len());
+        let mut stride = self.outputs;
+        let mut strides = vec![0; self.grid_points.len()];
+        for (i, points) in self.grid_points.iter().enumerate().rev() {
+            strides[i] = stride;
+            stride *= points;
+        }
+
+        let mut base = 0;
+        for (i, points) in self.grid_points.iter().enumerate() {
+            let x = input.get(i).copied().unwrap_or(0.0).clamp(0.0, 1.0) * (points - 1) as f32;
+            let below = (x.floor() as usize).min(points - 1);
+            let step = if below + 1 < *points { strides[i] } else { 0 };
+
+            base += below * strides[i];
+            positions.push((x - below as f32, step));
+        }
+
+        let mut output = vec![0.0; self.outputs];
+        for corner in 0..1usize << positions.len() {
+            let mut weight = 1.0;
+            let mut index = base;
+            for (i, (fraction, step)) in positions.iter().enumerate() {
+                if corner & (1 << i) == 0 {
+                    weight *= 1.0 - fraction;
+                } else {
+                    weight *= fraction;
+                    index += step;
+                }
+            }
+
+            if weight != 0.0 {
+                for (j, output) in output.iter_mut().enumerate() {
+                    *output += weight * self.values[index + j];
+                }
+            }
+        }
+
+        output
+    }
+}
+
+/// How a lookup table encodes the profile connection space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PcsEncoding {
+    /// `lut8Type`
+    Lut8,
+    /// `lut16Type`, with the version 2 Lab encoding.
+    Lut16,
+    /// `lutAToBType`
+    AToB,
+}
+
+/// A device to PCS lookup table transform. `lut8Type` and `lut16Type` tables only have A
+/// curves, a CLUT and B curves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut {
+    pub encoding: PcsEncoding,
+    pub a_curves: Vec<Curve>,
+    pub clut: Option<Clut>,
+    pub m_curves: Vec<Curve>,
+    /// A 3×3 matrix and an offset, applied after the M curves.
+    pub matrix: Option<([[f32; 3]; 3], [f32; 3])>,
+    pub b_curves: Vec<Curve>,
+}
+
+impl Lut {
+    fn eval(&self, input: &[f32]) -> Vec<f32> {
+        let curves = |curves: &[Curve], values: Vec<f32>| {
+            if curves.is_empty() {
+                values
+            } else {
+                values
+                    .iter()
+                    .zip(curves)
+                    .map(|(value, curve)| curve.eval(*value))
+                    .collect()
+            }
+        };
+
+        let mut values = curves(&self.a_curves, input.to_vec());
+        if let Some(clut) = &self.clut {
+            values = clut.eval(&values);
+        }
+        values = curves(&self.m_curves, values);
+        if let Some((matrix, offset)) = &self.matrix {
+            if values.len() == 3 {
+                values = (0..3)
+                    .map(|i| {
+                        matrix[i][0] * values[0]
+                            + matrix[i][1] * values[1]
+                            + matrix[i][2] * values[2]
+                            + offset[i]
+                    })
+                    .collect();
+            }
+        }
+        curves(&self.b_curves, values)
+    }
+}
+
+/// How a profile maps device colors to the profile connection space.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Transform {
+    /// A `kTRC` curve, giving the luminance of the D50 white.
+    GrayTrc(Curve),
+    /// `rTRC`, `gTRC` and `bTRC` curves and the `rXYZ`, `gXYZ` and `bXYZ` colorants as matrix
+    /// columns.
+    MatrixTrc {
+        curves: [Curve; 3],
+        matrix: [[f32; 3]; 3],
+    },
+    Lut(Lut),
+}
+
+/// An ICC profile's header, metadata and device to PCS transforms.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IccProfile {
+    /// Major, minor and bug fix version.
+    pub version: (u8, u8, u8),
+    pub device_class: DeviceClass,
+    pub color_space: ColorSpaceSignature,
+    /// The profile connection space, XYZ or Lab.
+    pub pcs: ColorSpaceSignature,
+    pub rendering_intent: ColorRenderingIntent,
+    /// The preferred CMM, manufacturer and creator signatures.
+    pub cmm: String,
+    pub manufacturer: String,
+    pub creator: String,
+    /// The MD5 profile ID of version 4 profiles, all zeros if it wasn't computed.
+    pub profile_id: [u8; 16],
+    /// `desc`
+    pub description: Option<String>,
+    /// `cprt`
+    pub copyright: Option<String>,
+    /// `wtpt`
+    pub media_white_point: Option<[f32; 3]>,
+    /// The `A2B0`, `A2B1` and `A2B2` transforms, or the matrix/TRC transform that's used for all
+    /// intents.
+    pub transforms: [Option<Transform>; 3],
+}
+
+impl IccProfile {
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        let invalid = |what: &str| Error::InvalidEntry(format!("ICC profile {}", what));
+        let data = Data(data);
+
+        if data.bytes(36, 4) != Some(b"acsp") {
+            return Err(invalid("signature"));
+        }
+        let version = data.bytes(8, 2).ok_or_else(|| invalid("header"))?;
+        let version = (version[0], version[1] >> 4, version[1] & 0xf);
+        let device_class = match data.signature(12).as_deref() {
+            Some("scnr") => DeviceClass::Input,
+            Some("mntr") => DeviceClass::Display,
+            Some("prtr") => DeviceClass::Output,
+            Some("link") => DeviceClass::DeviceLink,
+            Some("spac") => DeviceClass::ColorSpace,
+            Some("abst") => DeviceClass::Abstract,
+            Some("nmcl") => DeviceClass::NamedColor,
+            Some(class) => DeviceClass::Other(class.to_string()),
+            None => return Err(invalid("header")),
+        };
+        let color_space = ColorSpaceSignature::from_signature(
+            data.bytes(16, 4).ok_or_else(|| invalid("header"))?,
+        );
+        let pcs = ColorSpaceSignature::from_signature(
+            data.bytes(20, 4).ok_or_else(|| invalid("header"))?,
+        );
+        let rendering_intent = match data.u32(64) {
+            Some(0) => ColorRenderingIntent::Perceptual,
+            Some(2) => ColorRenderingIntent::Saturation,
+            Some(3) => ColorRenderingIntent::AbsoluteColorimetric,
+            _ => ColorRenderingIntent::RelativeColorimetric,
+        };
+        let mut profile_id = [0; 16];
+        profile_id.copy_from_slice(data.bytes(84, 16).ok_or_else(|| invalid("header"))?);
+
+        let tag_count = data.u32(128).ok_or_else(|| invalid("tag table"))? as usize;
+        let mut tags = Vec::new();
+        for i in 0..tag_count.min(data.0.len() / 12) {
+            let entry = 132 + 12 * i;
+            let signature = data.signature(entry).ok_or_else(|| invalid("tag table"))?;
+            let offset = data.u32(entry + 4).ok_or_else(|| invalid("tag table"))? as usize;
+            let size = data.u32(entry + 8).ok_or_else(|| invalid("tag table"))? as usize;
+            let tag = data
+                .bytes(offset, size)
+                .ok_or_else(|| invalid("tag table"))?;
+            tags.push((signature, tag));
+        }
+        let tag = |signature: &str| {
+            tags.iter()
+                .find(|(tag, _)| tag == signature)
+                .map(|(_, data)| Data(data))
+        };
+
+        let components = color_space
+            .components()
+            .ok_or_else(|| invalid("color space"))?;
+        let lut = |signature: &str| -> Result<Option<Transform>, Error> {
+            match tag(signature) {
+                Some(data) => Ok(Some(Transform::Lut(
+                    data.lut(components).ok_or_else(|| invalid(signature))?,
+                ))),
+                None => Ok(None),
+            }
+        };
+        let mut transforms = [lut("A2B0")?, lut("A2B1")?, lut("A2B2")?];
+
+        let trc = match (
+            &color_space,
+            tag("kTRC"),
+            tag("rTRC"),
+            tag("gTRC"),
+            tag("bTRC"),
+        ) {
+            (ColorSpaceSignature::Gray, Some(k), ..) => Some(Transform::GrayTrc(
+                k.curve().ok_or_else(|| invalid("kTRC"))?.0,
+            )),
+            (ColorSpaceSignature::Rgb, _, Some(r), Some(g), Some(b)) => {
+                let column = |signature: &str| {
+                    tag(signature)
+                        .and_then(|data| data.xyz())
+                        .ok_or_else(|| invalid(signature))
+                };
+                let (red, green, blue) = (column("rXYZ")?, column("gXYZ")?, column("bXYZ")?);
+
+                Some(Transform::MatrixTrc {
+                    curves: [
+                        r.curve().ok_or_else(|| invalid("rTRC"))?.0,
+                        g.curve().ok_or_else(|| invalid("gTRC"))?.0,
+                        b.curve().ok_or_else(|| invalid("bTRC"))?.0,
+                    ],
+                    matrix: [
+                        [red[0], green[0], blue[0]],
+                        [red[1], green[1], blue[1]],
+                        [red[2], green[2], blue[2]],
+                    ],
+                })
+            }
+            _ => None,
+        };
+        for transform in transforms.iter_mut() {
+            if transform.is_none() {
+                *transform = trc.clone();
+            }
+        }
+
+        Ok(IccProfile {
+            version,
+            device_class,
+            color_space,
+            pcs,
+            rendering_intent,
+            cmm: data.signature(4).unwrap_or_default(),
+            manufacturer: data.signature(48).unwrap_or_default(),
+            creator: data.signature(80).unwrap_or_default(),
+            profile_id,
+            description: tag("desc").and_then(|data| data.text()),
+            copyright: tag("cprt").and_then(|data| data.text()),
+            media_white_point: tag("wtpt").and_then(|data| data.xyz()),
+            transforms,
+        })
+    }
+
+    /// The number of components of the device colors the profile describes.
+    pub fn components(&self) -> Option<usize> {
+        self.color_space.components()
+    }
+
+    /// The version as it's usually written, e.g. `4.3.0`.
+    pub fn version_string(&self) -> String {
+        format!("{}.{}.{}", self.version.0, self.version.1, self.version.2)
+    }
+
+    /// Converts a device color to CIE XYZ relative to the D50 white of the profile connection
+    /// space, using the transform for the rendering intent. Absolute colorimetric gives XYZ
+    /// relative to the profile's media white instead. `None` if the profile has no transform
+    /// that can be used.
+    pub fn to_xyz(&self, color: &[f32], intent: ColorRenderingIntent) -> Option<[f32; 3]> {
+        let preferred = match intent {
+            ColorRenderingIntent::Perceptual => 0,
+            ColorRenderingIntent::RelativeColorimetric
+            | ColorRenderingIntent::AbsoluteColorimetric => 1,
+            ColorRenderingIntent::Saturation => 2,
+        };
+        // Only the perceptual transform is required, so fall back to it.
+        let transform = self.transforms[preferred]
+            .as_ref()
+            .or_else(|| self.transforms[0].as_ref())?;
+
+        let xyz = match transform {
+            Transform::GrayTrc(curve) => {
+                let y = curve.eval(color.first().copied().unwrap_or(0.0));
+                [D50[0] * y, D50[1] * y, D50[2] * y]
+            }
+            Transform::MatrixTrc { curves, matrix } => {
+                let linear = [
+                    curves[0].eval(color.first().copied().unwrap_or(0.0)),
+                    curves[1].eval(color.get(1).copied().unwrap_or(0.0)),
+                    curves[2].eval(color.get(2).copied().unwrap_or(0.0)),
+                ];
+                let row = |i: usize| {
+                    matrix[i][0] * linear[0] + matrix[i][1] * linear[1] + matrix[i][2] * linear[2]
+                };
+                [row(0), row(1), row(2)]
+            }
+            Transform::Lut(lut) => {
+                let pcs = lut.eval(color);
+                if pcs.len() != 3 {
+                    return None;
+                }
+                self.decode_pcs(&pcs, lut.encoding)?
+            }
+        };
+
+        match (intent, self.media_white_point) {
+            (ColorRenderingIntent::AbsoluteColorimetric, Some(white)) => Some([
+                xyz[0] * white[0] / D50[0],
+                xyz[1] * white[1] / D50[1],
+                xyz[2] * white[2] / D50[2],
+            ]),
+            _ => Some(xyz),
+        }
+    }
+
+    /// Converts normalized PCS values from a lookup table to XYZ.
+    fn decode_pcs(&self, pcs: &[f32], encoding: PcsEncoding) -> Option<[f32; 3]> {
+        match self.pcs {
+            ColorSpaceSignature::Xyz => {
+                // 1.0 is encoded as 0x8000, and the encoding goes up to 0xFFFF.
+                let scale = 65535.0 / 32768.0;
+                Some([pcs[0] * scale, pcs[1] * scale, pcs[2] * scale])
+            }
+            ColorSpaceSignature::Lab => {
+                // Version 2 16-bit Lab encodes L = 100 as 0xFF00.
+                let scale = match encoding {
+                    PcsEncoding::Lut16 => 65535.0 / 65280.0,
+                    PcsEncoding::Lut8 | PcsEncoding::AToB => 1.0,
+                };
+                let l = pcs[0] * scale * 100.0;
+                let a = pcs[1] * scale * 255.0 - 128.0;
+                let b = pcs[2] * scale * 255.0 - 128.0;
+
+                let m = (l + 16.0) / 116.0;
+                let g = |x: f32| {
+                    if x >= 6.0 / 29.0 {
+                        x * x * x
+                    } else {
+                        108.0 / 841.0 * (x - 4.0 / 29.0)
+                    }
+                };
+                Some([
+                    D50[0] * g(m + a / 500.0),
+                    D50[1] * g(m),
+                    D50[2] * g(m - b / 200.0),
+                ])
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Big endian reads from profile data that fail at the end of the data rather than panicking.
+#[derive(Clone, Copy)]
+struct Data<'a>(&'a [u8]);
+
+impl<'a> Data<'a> {
+    fn bytes(&self, offset: usize, len: usize) -> Option<&'a [u8]> {
+        self.0.get(offset..offset.checked_add(len)?)
+    }
+
+    fn u8(&self, offset: usize) -> Option<u8> {
+        self.0.get(offset).copied()
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.bytes(offset, 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.bytes(offset, 4)?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn s15_fixed16(&self, offset: usize) -> Option<f32> {
+        Some(self.u32(offset)? as i32 as f32 / 65536.0)
+    }
+
+    fn signature(&self, offset: usize) -> Option<String> {
+        Some(String::from_utf8_lossy(self.bytes(offset, 4)?).into_owned())
+    }
+
+    fn from(&self, offset: usize) -> Option<Data<'a>> {
+        self.0.get(offset..).map(Data)
+    }
+
+    /// An `XYZType` tag.
+    fn xyz(&self) -> Option<[f32; 3]> {
+        if self.bytes(0, 4)? != b"XYZ " {
+            return None;
+        }
+        Some([
+            self.s15_fixed16(8)?,
+            self.s15_fixed16(12)?,
+            self.s15_fixed16(16)?,
+        ])
+    }
+
+    /// A `textType`, `textDescriptionType` or the first record of a `multiLocalizedUnicodeType`
+    /// tag.
+    fn text(&self) -> Option<String> {
+        let text = match self.bytes(0, 4)? {
+            b"text" => String::from_utf8_lossy(self.0.get(8..)?).into_owned(),
+            b"desc" => {
+                let len = self.u32(8)? as usize;
+                String::from_utf8_lossy(self.bytes(12, len)?).into_owned()
+            }
+            b"mluc" => {
+                if self.u32(8)? == 0 {
+                    return None;
+                }
+                let len = self.u32(20)? as usize;
+                let offset = self.u32(24)? as usize;
+                let units = self
+                    .bytes(offset, len)?
+                    .chunks_exact(2)
+                    .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                    .collect::<Vec<_>>();
+                String::from_utf16_lossy(&units)
+            }
+            _ => return None,
+        };
+
+        Some(text.trim_end_matches('\0').to_string())
+    }
+
+    /// A `curveType` or `parametricCurveType`, and its size padded to four bytes.
+    fn curve(&self) -> Option<(Curve, usize)> {
+        match self.bytes(0, 4)? {
+            b"curv" => {
+                let count = self.u32(8)? as usize;
+                let curve = match count {
+                    0 => Curve::Identity,
+                    1 => Curve::Gamma(f32::from(self.u16(12)?) / 256.0),
+                    count => Curve::Table(
+                        (0..count)
+                            .map(|i| Some(f32::from(self.u16(12 + 2 * i)?) / 65535.0))
+                            .collect::<Option<_>>()?,
+                    ),
+                };
+                Some((curve, (12 + 2 * count).div_ceil(4) * 4))
+            }
+            b"para" => {
+                let function = self.u16(8)?;
+                let count = match function {
+                    0 => 1,
+                    1 => 3,
+                    2 => 4,
+                    3 => 5,
+                    4 => 7,
+                    _ => return None,
+                };
+                let parameters = (0..count)
+                    .map(|i| self.s15_fixed16(12 + 4 * i))
+                    .collect::<Option<_>>()?;
+                Some((
+                    Curve::Parametric {
+                        function,
+                        parameters,
+                    },
+                    12 + 4 * count,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// A sequence of `count` curves, as used in `lutAToBType`.
+    fn curves(&self, count: usize) -> Option<Vec<Curve>> {
+        let mut offset = 0;
+        (0..count)
+            .map(|_| {
+                let (curve, size) = self.from(offset)?.curve()?;
+                offset += size;
+                Some(curve)
+            })
+            .collect()
+    }
+
+    fn clut(
+        &self,
+        offset: usize,
+        grid_points: Vec<usize>,
+        outputs: usize,
+        bytes_per_value: usize,
+    ) -> Option<Clut> {
+        if grid_points.len() > MAX_CLUT_INPUTS || grid_points.contains(&0) || outputs == 0 {
+            return None;
+        }
+        let size = grid_points
+            .iter()
+            .try_fold(outputs, |size, points| size.checked_mul(*points))
+            .filter(|size| *size <= MAX_CLUT_SIZE)?;
+
+        let values = (0..size)
+            .map(|i| match bytes_per_value {
+                1 => Some(f32::from(self.u8(offset + i)?) / 255.0),
+                _ => Some(f32::from(self.u16(offset + 2 * i)?) / 65535.0),
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Clut {
+            grid_points,
+            outputs,
+            values,
+        })
+    }
+
+    /// A `lut8Type`, `lut16Type` or `lutAToBType` tag.
+    fn lut(&self, components: usize) -> Option<Lut> {
+        let inputs = usize::from(self.u8(8)?);
+        let outputs = usize::from(self.u8(9)?);
+        if inputs != components || outputs != 3 {
+            return None;
+        }
+
+        match self.bytes(0, 4)? {
+            signature @ b"mft1" | signature @ b"mft2" => {
+                let lut8 = signature == b"mft1";
+                let grid_points = usize::from(self.u8(10)?);
+                let (bytes, input_entries, output_entries, mut offset) = if lut8 {
+                    (1, 256, 256, 48)
+                } else {
+                    (
+                        2,
+                        usize::from(self.u16(48)?),
+                        usize::from(self.u16(50)?),
+                        52,
+                    )
+                };
+
+                let mut table = |count: usize, entries: usize| -> Option<Vec<Curve>> {
+                    (0..count)
+                        .map(|_| {
+                            let values = (0..entries)
+                                .map(|i| match bytes {
+                                    1 => Some(f32::from(self.u8(offset + i)?) / 255.0),
+                                    _ => Some(f32::from(self.u16(offset + 2 * i)?) / 65535.0),
+                                })
+                                .collect::<Option<_>>()?;
+                            offset += entries * bytes;
+                            Some(Curve::Table(values))
+                        })
+                        .collect()
+                };
+
+                let a_curves = table(inputs, input_entries)?;
+                let clut = self.clut(offset, vec![grid_points; inputs], outputs, bytes)?;
+                offset += clut.values.len() * bytes;
+                let table = |count: usize, entries: usize| -> Option<Vec<Curve>> {
+                    (0..count)
+                        .map(|i| {
+                            let start = offset + i * entries * bytes;
+                            let values = (0..entries)
+                                .map(|j| match bytes {
+                                    1 => Some(f32::from(self.u8(start + j)?) / 255.0),
+                                    _ => Some(f32::from(self.u16(start + 2 * j)?) / 65535.0),
+                                })
+                                .collect::<Option<_>>()?;
+                            Some(Curve::Table(values))
+                        })
+                        .collect()
+                };
+                let b_curves = table(outputs, output_entries)?;
+
+                Some(Lut {
+                    encoding: if lut8 {
+                        PcsEncoding::Lut8
+                    } else {
+                        PcsEncoding::Lut16
+                    },
+                    a_curves,
+                    clut: Some(clut),
+                    m_curves: Vec::new(),
+                    matrix: None,
+                    b_curves,
+                })
+            }
+            b"mAB " => {
+                let element = |offset: usize| -> Option<Option<Data>> {
+                    match self.u32(offset)? as usize {
+                        0 => Some(None),
+                        offset => Some(Some(self.from(offset)?)),
+                    }
+                };
+
+                let b_curves =
+                    element(12)?.map_or(Some(Vec::new()), |data| data.curves(outputs))?;
+                let m_curves =
+                    element(20)?.map_or(Some(Vec::new()), |data| data.curves(outputs))?;
+                let a_curves = element(28)?.map_or(Some(Vec::new()), |data| data.curves(inputs))?;
+                let matrix = match element(16)? {
+                    Some(data) => {
+                        let value = |i: usize| data.s15_fixed16(4 * i);
+                        let mut matrix = [[0.0; 3]; 3];
+                        for (i, row) in matrix.iter_mut().enumerate() {
+                            for (j, value_ij) in row.iter_mut().enumerate() {
+                                *value_ij = value(3 * i + j)?;
+                            }
+                        }
+                        Some((matrix, [value(9)?, value(10)?, value(11)?]))
+                    }
+                    None => None,
+                };
+                let clut = match element(24)? {
+                    Some(data) => {
+                        let grid_points = (0..inputs)
+                            .map(|i| data.u8(i).map(usize::from))
+                            .collect::<Option<Vec<_>>>()?;
+                        let bytes = usize::from(data.u8(16)?);
+                        Some(data.clut(20, grid_points, outputs, bytes)?)
+                    }
+                    None => None,
+                };
+
+                Some(Lut {
+                    encoding: PcsEncoding::AToB,
+                    a_curves,
+                    clut,
+                    m_curves,
+                    matrix,
+                    b_curves,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s15_fixed16(value: f32) -> [u8; 4] {
+        ((value * 65536.0).round() as i32).to_be_bytes()
+    }
+
+    fn xyz(value: [f32; 3]) -> Vec<u8> {
+        let mut tag = b"XYZ \0\0\0\0".to_vec();
+        for component in &value {
+            tag.extend_from_slice(&s15_fixed16(*component));
+        }
+        tag
+    }
+
+    /// A display profile with the sRGB primaries adapted to D50 and linear curves.
+    fn linear_rgb_profile() -> Vec<u8> {
+        let mut gamma = b"curv\0\0\0\0".to_vec();
+        gamma.extend_from_slice(&1u32.to_be_bytes());
+        gamma.extend_from_slice(&[1, 0, 0, 0]);
+        let mut description = b"desc\0\0\0\0".to_vec();
+        description.extend_from_slice(&7u32.to_be_bytes());
+        description.extend_from_slice(b"Linear\0\0");
+
+        profile(
+            b"RGB ",
+            b"XYZ ",
+            vec![
+                (b"desc", description),
+                (b"wtpt", xyz(D50)),
+                (b"rXYZ", xyz([0.4361, 0.2225, 0.0139])),
+                (b"gXYZ", xyz([0.3851, 0.7169, 0.0971])),
+                (b"bXYZ", xyz([0.1431, 0.0606, 0.7141])),
+                (b"rTRC", gamma.clone()),
+                (b"gTRC", gamma.clone()),
+                (b"bTRC", gamma),
+            ],
+        )
+    }
+
+    /// A version 2.1 display profile of `color_space` with the given tags.
+    fn profile(color_space: &[u8; 4], pcs: &[u8; 4], tags: Vec<(&[u8; 4], Vec<u8>)>) -> Vec<u8> {
+        let mut profile = vec![0; 128];
+        profile[8] = 2;
+        profile[9] = 0x10;
+        profile[12..16].copy_from_slice(b"mntr");
+        profile[16..20].copy_from_slice(color_space);
+        profile[20..24].copy_from_slice(pcs);
+        profile[36..40].copy_from_slice(b"acsp");
+        profile.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+
+        let mut offset = 132 + 12 * tags.len();
+        for (signature, tag) in &tags {
+            profile.extend_from_slice(*signature);
+            profile.extend_from_slice(&(offset as u32).to_be_bytes());
+            profile.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+            offset += tag.len();
+        }
+        for (_, tag) in tags {
+            profile.extend_from_slice(&tag);
+        }
+        profile
+    }
+
+    #[test]
+    fn parses_matrix_trc_profile() {
+        let profile = IccProfile::parse(&linear_rgb_profile()).unwrap();
+
+        assert_eq!(profile.version_string(), "2.1.0");
+        assert_eq!(profile.device_class, DeviceClass::Display);
+        assert_eq!(profile.components(), Some(3));
+        assert_eq!(profile.description.as_deref(), Some("Linear"));
+
+        let white = profile
+            .to_xyz(&[1.0, 1.0, 1.0], ColorRenderingIntent::Perceptual)
+            .unwrap();
+        assert!(white.iter().zip(&D50).all(|(a, b)| (a - b).abs() < 1e-3));
+
+        let half = profile
+            .to_xyz(&[0.0, 0.5, 0.0], ColorRenderingIntent::Perceptual)
+            .unwrap();
+        assert!((half[1] - 0.7169 / 2.0).abs() < 1e-3);
+
+        assert!(IccProfile::parse(&[0; 64]).is_err());
+    }
+
+    #[test]
+    fn reads_color_space_signatures() {
+        assert_eq!(
+            ColorSpaceSignature::from_signature(b"6CLR").components(),
+            Some(6)
+        );
+        assert_eq!(
+            ColorSpaceSignature::from_signature(b"\xFFCLR"),
+            ColorSpaceSignature::Other("\u{FFFD}CLR".to_string(), None)
+        );
+        assert!(IccProfile::parse(&profile(b"\xFFCLR", b"XYZ ", Vec::new())).is_err());
+    }
+
+    /// An identity `curveType`.
+    fn identity() -> Vec<u8> {
+        b"curv\0\0\0\0\0\0\0\0".to_vec()
+    }
+
+    fn close(a: [f32; 3], b: [f32; 3]) -> bool {
+        a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 2e-3)
+    }
+
+    fn gray_xyz(profile: &IccProfile, gray: f32) -> [f32; 3] {
+        profile
+            .to_xyz(&[gray], ColorRenderingIntent::Perceptual)
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_gray_trc_profile() {
+        let mut gamma = b"curv\0\0\0\0".to_vec();
+        gamma.extend_from_slice(&1u32.to_be_bytes());
+        gamma.extend_from_slice(&[2, 0, 0, 0]);
+        let profile =
+            IccProfile::parse(&profile(b"GRAY", b"XYZ ", vec![(b"kTRC", gamma)])).unwrap();
+
+        assert_eq!(profile.components(), Some(1));
+        assert!(close(gray_xyz(&profile, 1.0), D50));
+        assert!(close(
+            gray_xyz(&profile, 0.5),
+            [D50[0] / 4.0, 0.25, D50[2] / 4.0]
+        ));
+    }
+
+    #[test]
+    fn parses_lut8_profile_with_lab_pcs() {
+        let mut lut = b"mft1\0\0\0\0".to_vec();
+        lut.extend_from_slice(&[1, 3, 2, 0]);
+        lut.extend(vec![0; 36]);
+        lut.extend((0..=255).map(|i| i as u8));
+        // Black and the D50 white, as L*, a* and b*.
+        lut.extend_from_slice(&[0, 128, 128, 255, 128, 128]);
+        for _ in 0..3 {
+            lut.extend((0..=255).map(|i| i as u8));
+        }
+        let profile = IccProfile::parse(&profile(b"GRAY", b"Lab ", vec![(b"A2B0", lut)])).unwrap();
+
+        assert!(matches!(
+            &profile.transforms[0],
+            Some(Transform::Lut(Lut {
+                encoding: PcsEncoding::Lut8,
+                ..
+            }))
+        ));
+        assert!(close(gray_xyz(&profile, 1.0), D50));
+        assert!(close(gray_xyz(&profile, 0.0), [0.0; 3]));
+        // L* = 50 is a luminance of about 0.184.
+        assert!((gray_xyz(&profile, 0.5)[1] - 0.184).abs() < 5e-3);
+    }
+
+    #[test]
+    fn parses_lut16_profile_with_version_2_lab_encoding() {
+        let u16s = |values: &[u16]| {
+            values
+                .iter()
+                .flat_map(|value| value.to_be_bytes().to_vec())
+                .collect::<Vec<_>>()
+        };
+        let mut lut = b"mft2\0\0\0\0".to_vec();
+        lut.extend_from_slice(&[1, 3, 2, 0]);
+        lut.extend(vec![0; 36]);
+        lut.extend(u16s(&[2, 2]));
+        lut.extend(u16s(&[0, 0xffff]));
+        // Version 2 encodes L* = 100 as 0xFF00 and a* = b* = 0 as 0x8000.
+        lut.extend(u16s(&[0, 0x8000, 0x8000, 0xff00, 0x8000, 0x8000]));
+        lut.extend(u16s(&[0, 0xffff, 0, 0xffff, 0, 0xffff]));
+        let profile = IccProfile::parse(&profile(b"GRAY", b"Lab ", vec![(b"A2B0", lut)])).unwrap();
+
+        assert!(close(gray_xyz(&profile, 1.0), D50));
+        assert!(close(gray_xyz(&profile, 0.0), [0.0; 3]));
+    }
+
+    #[test]
+    fn parses_lut_a_to_b_profile() {
+        let mut lut = b"mAB \0\0\0\0".to_vec();
+        lut.extend_from_slice(&[1, 3, 0, 0]);
+        // The offsets of the B curves, matrix, M curves, CLUT and A curves.
+        for offset in &[32u32, 68, 116, 152, 184] {
+            lut.extend_from_slice(&offset.to_be_bytes());
+        }
+        for _ in 0..3 {
+            lut.extend(identity());
+        }
+        // Doubles each channel.
+        for value in &[2.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0] {
+            lut.extend_from_slice(&s15_fixed16(*value));
+        }
+        for _ in 0..3 {
+            lut.extend(identity());
+        }
+        let mut grid_points = vec![0; 16];
+        grid_points[0] = 2;
+        lut.extend(grid_points);
+        lut.extend_from_slice(&[2, 0, 0, 0]);
+        // Black and half of the D50 white, with 1.0 encoded as 0x8000.
+        lut.extend(vec![0; 6]);
+        for component in &D50 {
+            let value = (component / 2.0 * 32768.0).round() as u16;
+            lut.extend_from_slice(&value.to_be_bytes());
+        }
+        lut.extend(identity());
+        let profile = IccProfile::parse(&profile(b"GRAY", b"XYZ ", vec![(b"A2B0", lut)])).unwrap();
+
+        assert!(matches!(
+            &profile.transforms[0],
+            Some(Transform::Lut(Lut {
+                encoding: PcsEncoding::AToB,
+                matrix: Some(_),
+                ..
+            }))
+        ));
+        assert!(close(gray_xyz(&profile, 1.0), D50));
+        assert!(close(gray_xyz(&profile, 0.0), [0.0; 3]));
+    }
+}
@@ -0,0 +1,1449 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+use pdf::{
+    object::Resolve,
+    primitive::{Dictionary, Primitive},
+};
+
+use crate::{
+    color::{Color, ColorSpace},
+    document::{get, get_bool, get_integer, get_numbers, stream_data, Location, RawPage},
+    error::Error,
+    font::Font,
+    geometry::{Matrix, Rect},
+    graphics_state::GraphicsState,
+    painting::{walk_content, walk_pattern_cell, ContentVisitor, Step},
+    pattern::{PaintType, Pattern, TilingPattern},
+    raster::{FillRule, Mask, Path},
+    shading::Shading,
+    text_state::{Glyph, TextState},
+    ColorRenderingIntent, Name, Operation, TextOrGlyphPositioning, TextRenderingMode,
+};
+
+/// The most pixels a page is rasterized with, the resolution is lowered for larger pages.
+const MAX_PIXELS: f32 = (1 << 24) as f32;
+
+/// The most cells of a tiling pattern that are painted for one fill.
+const MAX_TILES: usize = 4096;
+
+/// The most inks whose coverage is measured. Each takes four bytes a pixel, so that a page
+/// naming many spot inks can't exhaust memory; the inks past it are only listed.
+const MAX_INKS: usize = 16;
+
+/// A printing ink: one of the four process inks or a named spot ink.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Ink {
+    Cyan,
+    Magenta,
+    Yellow,
+    Black,
+    Spot(String),
+}
+
+impl Ink {
+    pub const PROCESS: [Ink; 4] = [Ink::Cyan, Ink::Magenta, Ink::Yellow, Ink::Black];
+
+    /// The inks a Separation or DeviceN colorant name stands for: process colorant names are
+    /// the process inks, `All` is every process ink and `None` is no ink.
+    pub fn from_colorant(name: &str) -> Vec<Ink> {
+        match name {
+            "Cyan" => vec![Ink::Cyan],
+            "Magenta" => vec![Ink::Magenta],
+            "Yellow" => vec![Ink::Yellow],
+            "Black" => vec![Ink::Black],
+            "All" => Ink::PROCESS.to_vec(),
+            "None" => Vec::new(),
+            name => vec![Ink::Spot(name.to_string())],
+        }
+    }
+}
+
+impl fmt::Display for Ink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ink::Cyan => write!(f, "Cyan"),
+            Ink::Magenta => write!(f, "Magenta"),
+            Ink::Yellow => write!(f, "Yellow"),
+            Ink::Black => write!(f, "Black"),
+            Ink::Spot(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// The tints of the inks a color is printed with, leaving out inks with no tint. Separation and
+/// DeviceN colorants are printed on their own inks, and other colors are converted to process
+/// inks with `ColorSpace::to_cmyk`.
+pub fn color_inks(
+    color_space: &ColorSpace,
+    components: &[f32],
+    intent: ColorRenderingIntent,
+) -> Vec<(Ink, f32)> {
+    let tint = |i: usize| components.get(i).copied().unwrap_or(0.0).clamp(0.0, 1.0);
+
+    let mut inks = match color_space {
+        ColorSpace::Separation { name, .. } => Ink::from_colorant(name)
+            .into_iter()
+            .map(|ink| (ink, tint(0)))
+            .collect(),
+        ColorSpace::DeviceN { names, .. } => names
+            .iter()
+            .enumerate()
+            .flat_map(|(i, name)| {
+                Ink::from_colorant(name)
+                    .into_iter()
+                    .map(move |ink| (ink, tint(i)))
+            })
+            .collect(),
+        ColorSpace::Indexed { .. } => match color_space.lookup(components) {
+            Some((base, color)) => color_inks(base, &color, intent),
+            None => Vec::new(),
+        },
+        ColorSpace::ICCBased {
+            profile: None,
+            alternate,
+            ..
+        } => color_inks(alternate, components, intent),
+        color_space => match color_space.to_cmyk(components, intent) {
+            Some(cmyk) => Ink::PROCESS.iter().cloned().zip(cmyk).collect(),
+            None => Vec::new(),
+        },
+    };
+
+    inks.retain(|(_, tint)| *tint > 0.0);
+    inks
+}
+
+/// The inks that painting a color with overprint set replaces, section 8.6.7 of ISO 32000-1.
+/// Separation and DeviceN colors replace their own colorants, and DeviceCMYK colors with
+/// overprint mode 1 only replace the inks they give a nonzero tint. Colors in any other space
+/// are converted to process inks and replace all of them.
+pub fn overprinted_inks(color_space: &ColorSpace, components: &[f32], mode: i32) -> Vec<Ink> {
+    match color_space {
+        ColorSpace::DeviceCMYK if mode == 1 => Ink::PROCESS
+            .iter()
+            .zip(components)
+            .filter(|(_, tint)| **tint != 0.0)
+            .map(|(ink, _)| ink.clone())
+            .collect(),
+        ColorSpace::Separation { .. } | ColorSpace::DeviceN { .. } => color_space_inks(color_space),
+        _ => Ink::PROCESS.to_vec(),
+    }
+}
+
+/// Every ink a color space can print with, such as the inks of an image in it.
+pub fn color_space_inks(color_space: &ColorSpace) -> Vec<Ink> {
+    match color_space {
+        ColorSpace::Separation { name, .. } => Ink::from_colorant(name),
+        ColorSpace::DeviceN { names, .. } => names
+            .iter()
+            .flat_map(|name| Ink::from_colorant(name))
+            .collect(),
+        ColorSpace::Indexed { base, .. } => color_space_inks(base),
+        ColorSpace::ICCBased {
+            profile: None,
+            alternate,
+            ..
+        } => color_space_inks(alternate),
+        ColorSpace::DeviceGray | ColorSpace::CalGray { .. } => vec![Ink::Black],
+        ColorSpace::ICCBased { components: 1, .. } => vec![Ink::Black],
+        ColorSpace::Pattern { .. } => Vec::new(),
+        _ => Ink::PROCESS.to_vec(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InkOptions {
+    /// The resolution pages are rasterized at, in pixels per inch.
+    pub resolution: f32,
+    /// The total area coverage over which areas are flagged, as a percentage. 300% is a common
+    /// limit for coated paper.
+    pub ink_limit: f32,
+    /// Whether objects painted with overprint set leave the inks they don't paint as they are,
+    /// as they would on press, instead of knocking them out.
+    pub simulate_overprint: bool,
+}
+
+impl Default for InkOptions {
+    fn default() -> Self {
+        InkOptions {
+            resolution: 72.0,
+            ink_limit: 300.0,
+            simulate_overprint: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InkCoverage {
+    pub ink: Ink,
+    /// The area covered by the ink, weighted by its tint, as a percentage of the page.
+    pub coverage: f32,
+}
+
+/// The inks a page is printed with and how much of them it takes, from rasterizing its content.
+///
+/// Paths, shadings, shading and tiling patterns, text and images are rasterized, each painted
+/// area knocking out the inks beneath it. Text is painted over the boxes of its glyphs, which
+/// overstates the area it covers, and images are sampled at each pixel they cover. Text in a font
+/// that can't be loaded, images whose samples can't be decoded and the inks a page uses past the
+/// sixteenth are only listed, and set `unmeasured_area`. Dash patterns are ignored and strokes are
+/// painted solid. Transparency isn't taken into account, and overprinting is only taken into
+/// account with `InkOptions::simulate_overprint`. Form XObjects and pattern cells are painted
+/// within the limits of `painting::walk_content`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InkReport {
+    /// Every ink the page uses, process inks first and then spot inks by name.
+    pub inks: Vec<InkCoverage>,
+    /// The highest total area coverage of any pixel, as a percentage.
+    pub max_total_coverage: f32,
+    /// The areas where the total area coverage is over the ink limit, in the page's default
+    /// coordinate space.
+    pub over_limit: Vec<Rect>,
+    /// Whether something was painted whose area couldn't be measured, so that the coverage can
+    /// be higher than reported.
+    pub unmeasured_area: bool,
+}
+
+impl InkReport {
+    /// Rasterizes the page's `/CropBox`, or `/MediaBox` if there's none.
+    pub fn analyze<R: Resolve>(
+        resolve: &R,
+        page: &RawPage,
+        options: &InkOptions,
+    ) -> Result<Self, Error> {
+        let page_box = match get_numbers(resolve, &page.dictionary, "CropBox")? {
+            Some(crop_box) => Some(crop_box),
+            None => get_numbers(resolve, &page.dictionary, "MediaBox")?,
+        }
+        .and_then(|page_box| Rect::from_slice(&page_box))
+        .unwrap_or_else(|| Rect::new(0.0, 0.0, 612.0, 792.0));
+
+        let area = page_box.width() * page_box.height();
+        let mut scale = options.resolution / 72.0;
+        if area * scale * scale > MAX_PIXELS {
+            scale = (MAX_PIXELS / area).sqrt();
+        }
+        let width = ((page_box.width() * scale).ceil() as usize).max(1);
+        let height = ((page_box.height() * scale).ceil() as usize).max(1);
+        let device = Matrix::translate(-page_box.x_min, -page_box.y_max)
+            * Matrix::new(scale, 0.0, 0.0, -scale, 0.0, 0.0);
+
+        let mut painter = Painter {
+            resolve,
+            device,
+            width,
+            height,
+            simulate_overprint: options.simulate_overprint,
+            planes: BTreeMap::new(),
+            unmeasured: BTreeSet::new(),
+            unmeasured_area: false,
+            levels: vec![Level::new(Matrix::IDENTITY, None, None)],
+        };
+        let operations = page
+            .content_parser(resolve)?
+            .collect::<Result<Vec<_>, _>>()?;
+        walk_content(
+            resolve,
+            &operations,
+            GraphicsState::default(),
+            &page.resources(resolve)?,
+            vec![Location::Page(page.number)],
+            &mut painter,
+        )?;
+
+        Ok(painter.report(options.ink_limit))
+    }
+}
+
+/// A content stream being painted: a page, Form XObject or pattern cell.
+struct Level {
+    /// Maps the default coordinate space of the content to the page's, which is where patterns
+    /// used in the content are placed.
+    base: Matrix,
+    clip: Option<Mask>,
+    /// The clips saved by `q`.
+    saved_clips: Vec<Option<Mask>>,
+    path: Path,
+    /// The rule of a `W` or `W*` that takes effect when the path is painted.
+    pending_clip: Option<FillRule>,
+    /// The color uncolored tiling pattern cells are painted with.
+    uncolored: Option<Color>,
+    /// The text matrices. The text state parameters are the graphics state's.
+    text: TextState,
+    fonts: HashMap<String, Font>,
+    /// The entries of the inline image whose data is being read, from its `ID`.
+    inline_image: Option<Dictionary>,
+}
+
+impl Level {
+    fn new(base: Matrix, clip: Option<Mask>, uncolored: Option<Color>) -> Self {
+        Level {
+            base,
+            clip,
+            saved_clips: Vec::new(),
+            path: Path::new(),
+            pending_clip: None,
+            uncolored,
+            text: TextState::default(),
+            fonts: HashMap::new(),
+            inline_image: None,
+        }
+    }
+}
+
+struct Painter<'a, R> {
+    resolve: &'a R,
+    /// Maps the page's default coordinate space to pixels.
+    device: Matrix,
+    width: usize,
+    height: usize,
+    simulate_overprint: bool,
+    /// The tint of each ink at each pixel.
+    planes: BTreeMap<Ink, Vec<f32>>,
+    /// The inks of text and images whose area couldn't be measured.
+    unmeasured: BTreeSet<Ink>,
+    unmeasured_area: bool,
+    /// The content streams being painted, the page's first.
+    levels: Vec<Level>,
+}
+
+impl<'a, R: Resolve> ContentVisitor for Painter<'a, R> {
+    fn operation(&mut self, step: &Step) -> Result<(), Error> {
+        let mut level = self.levels.pop().expect("the page's content stream");
+        if level.path.apply(step.operation) {
+            self.levels.push(level);
+            return Ok(());
+        }
+
+        let state = step.state;
+        let mut text = TextState {
+            matrix: level.text.matrix,
+            line_matrix: level.text.line_matrix,
+            ..state.text.clone()
+        };
+        text.update(step.operation);
+
+        let (fill, stroke) = match step.operation {
+            Operation::FillPathUsingNonZeroWindingNumberRule
+            | Operation::ObsoleteFillPathUsingNonZeroWindingMumberRule => {
+                (Some(FillRule::NonZeroWinding), false)
+            }
+            Operation::FillPathUsingEvenOddRule => (Some(FillRule::EvenOdd), false),
+            Operation::FillAndStrokePathUsingNonZeroWindingNumber => {
+                (Some(FillRule::NonZeroWinding), true)
+            }
+            Operation::FillAndStrokePathUsingEvenOddRule => (Some(FillRule::EvenOdd), true),
+            Operation::CloseFillAndStrokePathUsingNonZeroWindingNumber => {
+                level.path.close();
+                (Some(FillRule::NonZeroWinding), true)
+            }
+            Operation::CloseFillAndStrokePathUsingEvenOddRule => {
+                level.path.close();
+                (Some(FillRule::EvenOdd), true)
+            }
+            Operation::StrokePath => (None, true),
+            Operation::CloseAndStrokePath => {
+                level.path.close();
+                (None, true)
+            }
+            _ => (None, false),
+        };
+
+        if let Some(rule) = fill {
+            let mut mask = self.fill_mask(&level.path, &state.ctm, rule);
+            intersect(&mut mask, &level.clip);
+            let overprint = self.overprint(state.fill_overprint, state);
+            self.paint(&mask, &state.fill_color, overprint, step, &level)?;
+        }
+        if stroke {
+            let mut mask = self.stroke_mask(&level.path, state);
+            intersect(&mut mask, &level.clip);
+            let overprint = self.overprint(state.stroke_overprint, state);
+            self.paint(&mask, &state.stroke_color, overprint, step, &level)?;
+        }
+
+        match step.operation {
+            Operation::SaveGraphicsState => level.saved_clips.push(level.clip.clone()),
+            Operation::RestoreGraphicsState => {
+                if let Some(saved) = level.saved_clips.pop() {
+                    level.clip = saved;
+                }
+            }
+            Operation::SetClippingPathUsingNonZeroWindingNumberRule => {
+                level.pending_clip = Some(FillRule::NonZeroWinding)
+            }
+            Operation::SetClippingPathUsingEvenOddRule => {
+                level.pending_clip = Some(FillRule::EvenOdd)
+            }
+            Operation::PaintAreaDefinedByShadingPattern(Name(name)) => {
+                let shading = Shading::from_resources(self.resolve, step.resources, name)?;
+                let mask = level
+                    .clip
+                    .clone()
+                    .unwrap_or_else(|| Mask::full(self.width, self.height));
+                let overprint = self.overprint(state.fill_overprint, state);
+                self.paint_shading(&mask, &shading, &state.ctm, false, overprint, state);
+            }
+            Operation::InvokeNamedXObject(_) => self.invoke(step, &level)?,
+            Operation::BeginInlineImageData(entries) => {
+                let mut dictionary = Dictionary::new();
+                for entry in entries.chunks(2) {
+                    if let [Primitive::Name(key), value] = entry {
+                        dictionary.insert(expand_abbreviation(key).to_string(), value.clone());
+                    }
+                }
+                level.inline_image = Some(dictionary);
+            }
+            Operation::EndInlineImageObject(data) => {
+                let dictionary = level.inline_image.take().unwrap_or_else(Dictionary::new);
+                // The data of filtered inline images isn't decoded.
+                let image = if dictionary.contains_key("Filter") {
+                    None
+                } else {
+                    let color_space = match dictionary.get("ColorSpace") {
+                        Some(color_space) => {
+                            ColorSpace::from_inline_image(self.resolve, step.resources, color_space)
+                                .ok()
+                        }
+                        None => None,
+                    };
+                    Image::new(self.resolve, &dictionary, color_space, data.to_vec())?
+                };
+                self.paint_image(image, &dictionary, step, &level)?;
+            }
+            Operation::ShowText(_)
+            | Operation::ShowTextAllowingIndividualGlyphPositioning(_)
+            | Operation::MoveToNextLineAndShowText(_)
+            | Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText { .. }
+            | Operation::Unknown { .. }
+                if shows_text(step.operation) =>
+            {
+                let (fill, stroke) = match state.text.rendering_mode {
+                    TextRenderingMode::FillText
+                    | TextRenderingMode::FillTextAndAddToPathForClipping => (true, false),
+                    TextRenderingMode::StrokeText
+                    | TextRenderingMode::StrokeTextAndAddToPathForClipping => (false, true),
+                    TextRenderingMode::FillThenStrokeText
+                    | TextRenderingMode::FillThenStrokeTextAndAddToPathForClipping => (true, true),
+                    TextRenderingMode::Invisible | TextRenderingMode::AddTextToPathForClipping => {
+                        (false, false)
+                    }
+                };
+                if fill || stroke {
+                    let boxes = text
+                        .load_font(self.resolve, step.resources, &mut level.fonts)
+                        .ok()
+                        .and_then(|font| glyph_boxes(&mut text, font, step));
+                    match boxes {
+                        Some(boxes) => {
+                            let mut mask =
+                                self.fill_mask(&boxes, &Matrix::IDENTITY, FillRule::NonZeroWinding);
+                            intersect(&mut mask, &level.clip);
+                            if fill {
+                                let overprint = self.overprint(state.fill_overprint, state);
+                                self.paint(&mask, &state.fill_color, overprint, step, &level)?;
+                            }
+                            if stroke {
+                                let overprint = self.overprint(state.stroke_overprint, state);
+                                self.paint(&mask, &state.stroke_color, overprint, step, &level)?;
+                            }
+                        }
+                        None => {
+                            self.unmeasured_area = true;
+                            if fill {
+                                self.list(&state.fill_color, state, &level);
+                            }
+                            if stroke {
+                                self.list(&state.stroke_color, state, &level);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        level.text = text;
+
+        // Any path painting operation ends the path, after which a pending clip takes effect.
+        if fill.is_some()
+            || stroke
+            || matches!(step.operation, Operation::EndPathWithoutFillingOrStroking)
+        {
+            if let Some(rule) = level.pending_clip.take() {
+                let mask = self.fill_mask(&level.path, &state.ctm, rule);
+                intersect_with(&mut level.clip, mask);
+            }
+            level.path = Path::new();
+        }
+
+        self.levels.push(level);
+        Ok(())
+    }
+
+    /// Forms are painted within their `/BBox`, with patterns placed in their own space.
+    fn begin_form(&mut self, step: &Step) -> Result<(), Error> {
+        let level = self.levels.last().expect("the page's content stream");
+        let dictionary = match step.xobject {
+            Some(stream) => &stream.info,
+            None => return Ok(()),
+        };
+
+        let ctm = match get_numbers(self.resolve, dictionary, "Matrix")? {
+            Some(matrix) => {
+                Matrix::from_slice(&matrix).ok_or_else(|| Error::InvalidEntry("Matrix".into()))?
+            }
+            None => Matrix::IDENTITY,
+        } * step.state.ctm;
+
+        let mut clip = level.clip.clone();
+        if let Some(bbox) =
+            get_numbers(self.resolve, dictionary, "BBox")?.and_then(|bbox| Rect::from_slice(&bbox))
+        {
+            let mut path = Path::new();
+            path.rectangle(bbox.x_min, bbox.y_min, bbox.width(), bbox.height());
+            intersect_with(
+                &mut clip,
+                self.fill_mask(&path, &ctm, FillRule::NonZeroWinding),
+            );
+        }
+
+        let uncolored = level.uncolored.clone();
+        self.levels.push(Level::new(ctm, clip, uncolored));
+        Ok(())
+    }
+
+    fn end_form(&mut self, _step: &Step) -> Result<(), Error> {
+        self.levels.pop();
+        Ok(())
+    }
+}
+
+impl<'a, R: Resolve> Painter<'a, R> {
+    fn fill_mask(&self, path: &Path, ctm: &Matrix, rule: FillRule) -> Mask {
+        Mask::fill(
+            &path.transform(&(*ctm * self.device)),
+            rule,
+            self.width,
+            self.height,
+        )
+    }
+
+    /// Strokes are at least a pixel wide, as the thinnest line a device can draw is.
+    fn stroke_mask(&self, path: &Path, state: &GraphicsState) -> Mask {
+        let matrix = state.ctm * self.device;
+        let scale = matrix.determinant().abs().sqrt();
+        let width = if scale > 0.0 {
+            state.line_width.max(1.0 / scale)
+        } else {
+            state.line_width
+        };
+
+        let outline = path.stroke(width, state.line_cap, state.line_join);
+        Mask::fill(
+            &outline.transform(&matrix),
+            FillRule::NonZeroWinding,
+            self.width,
+            self.height,
+        )
+    }
+
+    /// The overprint mode to paint with, if overprint is set and being simulated.
+    fn overprint(&self, overprint: bool, state: &GraphicsState) -> Option<i32> {
+        if self.simulate_overprint && overprint {
+            Some(state.overprint_mode)
+        } else {
+            None
+        }
+    }
+
+    /// Paints the pixels of `mask` in a color, which may be a pattern, for the operation of
+    /// `step`.
+    fn paint(
+        &mut self,
+        mask: &Mask,
+        color: &Color,
+        overprint: Option<i32>,
+        step: &Step,
+        level: &Level,
+    ) -> Result<(), Error> {
+        let state = step.state;
+        let color = level.uncolored.as_ref().unwrap_or(color);
+        let name = match (&color.color_space, &color.pattern) {
+            (ColorSpace::Pattern { .. }, Some(name)) => name,
+            (ColorSpace::Pattern { .. }, None) => return Ok(()),
+            (color_space, _) => {
+                self.paint_flat(mask, color_space, &color.components, overprint, state);
+                return Ok(());
+            }
+        };
+
+        match Pattern::from_resources(self.resolve, step.resources, name)? {
+            Pattern::Shading(pattern) => {
+                let matrix = pattern.matrix * level.base;
+                self.paint_shading(mask, &pattern.shading, &matrix, true, overprint, state);
+                Ok(())
+            }
+            Pattern::Tiling(pattern) => {
+                let uncolored = match (&color.color_space, pattern.paint_type) {
+                    (ColorSpace::Pattern { underlying }, PaintType::Uncolored) => Some(Color::new(
+                        underlying
+                            .as_deref()
+                            .cloned()
+                            .unwrap_or(ColorSpace::DeviceGray),
+                        color.components.clone(),
+                    )),
+                    _ => None,
+                };
+                self.paint_tiles(mask, name, &pattern, uncolored, overprint, step, level)
+            }
+        }
+    }
+
+    /// Paints every pixel of `mask` in the same color.
+    fn paint_flat(
+        &mut self,
+        mask: &Mask,
+        color_space: &ColorSpace,
+        components: &[f32],
+        overprint: Option<i32>,
+        state: &GraphicsState,
+    ) {
+        let inks = color_inks(color_space, components, state.rendering_intent);
+        let replaced = overprint.map(|mode| overprinted_inks(color_space, components, mode));
+        for (x, y) in mask.pixels() {
+            self.paint_pixel(x, y, &inks, replaced.as_deref());
+        }
+    }
+
+    /// Paints a shading over `mask`, with `matrix` mapping shading space to the page. Shading
+    /// patterns also paint their background where the shading itself doesn't.
+    fn paint_shading(
+        &mut self,
+        mask: &Mask,
+        shading: &Shading,
+        matrix: &Matrix,
+        background: bool,
+        overprint: Option<i32>,
+        state: &GraphicsState,
+    ) {
+        let inverse = match (*matrix * self.device).inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+
+        for (x, y) in mask.pixels() {
+            let (u, v) = inverse.transform(x as f32 + 0.5, y as f32 + 0.5);
+            let color = match shading.color_at(u, v) {
+                Some(color) => color,
+                None => match &shading.background {
+                    Some(color) if background => color.clone(),
+                    _ => continue,
+                },
+            };
+
+            let inks = color_inks(&shading.color_space, &color, state.rendering_intent);
+            let replaced =
+                overprint.map(|mode| overprinted_inks(&shading.color_space, &color, mode));
+            self.paint_pixel(x, y, &inks, replaced.as_deref());
+        }
+    }
+
+    /// Paints the cells of a tiling pattern that fall in `mask`, until there are too many
+    /// nested in each other or the content walked is past its limit.
+    #[allow(clippy::too_many_arguments)]
+    fn paint_tiles(
+        &mut self,
+        mask: &Mask,
+        name: &str,
+        pattern: &TilingPattern,
+        uncolored: Option<Color>,
+        overprint: Option<i32>,
+        step: &Step,
+        level: &Level,
+    ) -> Result<(), Error> {
+        if pattern.x_step == 0.0 || pattern.y_step == 0.0 {
+            return Ok(());
+        }
+        let state = step.state;
+
+        let matrix = pattern.matrix * level.base;
+        let (area, inverse) = match (mask.bounds(), (matrix * self.device).inverse()) {
+            (Some(bounds), Some(inverse)) => (bounds, inverse),
+            _ => return Ok(()),
+        };
+        let area = Rect::new(area.0 as f32, area.1 as f32, area.2 as f32, area.3 as f32)
+            .transform(&inverse);
+
+        // The steps whose cells overlap the painted area.
+        let steps = |min: f32, max: f32, cell_min: f32, cell_max: f32, step: f32| {
+            let (a, b) = ((min - cell_max) / step, (max - cell_min) / step);
+            (a.min(b).floor() as i64, a.max(b).ceil() as i64)
+        };
+        let (i_min, i_max) = steps(
+            area.x_min,
+            area.x_max,
+            pattern.bbox.x_min,
+            pattern.bbox.x_max,
+            pattern.x_step,
+        );
+        let (j_min, j_max) = steps(
+            area.y_min,
+            area.y_max,
+            pattern.bbox.y_min,
+            pattern.bbox.y_max,
+            pattern.y_step,
+        );
+
+        // Tiny steps put the ends of the spans at the limits of `i64`.
+        let span = |min: i64, max: i64| i128::from(max) - i128::from(min) + 1;
+        let tiles = span(i_min, i_max).saturating_mul(span(j_min, j_max));
+        if tiles > MAX_TILES as i128 {
+            // Too many cells to paint one by one, uncolored patterns are painted as a flat area
+            // of their color and colored ones are left out.
+            if let Some(color) = &uncolored {
+                self.paint_flat(
+                    mask,
+                    &color.color_space,
+                    &color.components,
+                    overprint,
+                    state,
+                );
+            }
+            return Ok(());
+        }
+
+        for i in i_min..=i_max {
+            for j in j_min..=j_max {
+                let cell = Matrix::translate(i as f32 * pattern.x_step, j as f32 * pattern.y_step)
+                    * matrix;
+
+                let mut bbox = Path::new();
+                bbox.rectangle(
+                    pattern.bbox.x_min,
+                    pattern.bbox.y_min,
+                    pattern.bbox.width(),
+                    pattern.bbox.height(),
+                );
+                let mut clip = self.fill_mask(&bbox, &cell, FillRule::NonZeroWinding);
+                clip.intersect(mask);
+                if clip.count() == 0 {
+                    continue;
+                }
+
+                let initial = GraphicsState {
+                    ctm: cell,
+                    ..GraphicsState::default()
+                };
+                self.levels
+                    .push(Level::new(cell, Some(clip), uncolored.clone()));
+                let walked =
+                    walk_pattern_cell(self.resolve, step, name, pattern, initial, &mut *self);
+                self.levels.pop();
+                if !walked? {
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Paints an image. Forms are painted by the walk itself.
+    fn invoke(&mut self, step: &Step, level: &Level) -> Result<(), Error> {
+        let stream = match step.xobject {
+            Some(stream) => stream,
+            None => return Ok(()),
+        };
+        let dictionary = &stream.info;
+
+        match get(self.resolve, dictionary, "Subtype")? {
+            Some(Primitive::Name(subtype)) if subtype == "Image" => {
+                let color_space = match get(self.resolve, dictionary, "ColorSpace")? {
+                    Some(color_space) => {
+                        ColorSpace::from_primitive(self.resolve, &color_space).ok()
+                    }
+                    None => None,
+                };
+                let image = match stream_data(self.resolve, stream) {
+                    Ok(data) => Image::new(self.resolve, dictionary, color_space, data)?,
+                    Err(_) => None,
+                };
+                self.paint_image(image, dictionary, step, level)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Paints an image over the pixels of its unit square, or only lists its inks if its samples
+    /// couldn't be decoded. Stencil masks paint the samples they mark in the nonstroking color.
+    fn paint_image(
+        &mut self,
+        image: Option<Image>,
+        dictionary: &Dictionary,
+        step: &Step,
+        level: &Level,
+    ) -> Result<(), Error> {
+        let state = step.state;
+        let image = match image {
+            Some(image) => image,
+            None => {
+                self.unmeasured_area = true;
+                if get_bool(self.resolve, dictionary, "ImageMask")? == Some(true) {
+                    self.list(&state.fill_color, state, level);
+                } else if let Some(color_space) = get(self.resolve, dictionary, "ColorSpace")? {
+                    if let Ok(color_space) = ColorSpace::from_primitive(self.resolve, &color_space)
+                    {
+                        self.unmeasured.extend(color_space_inks(&color_space));
+                    }
+                }
+                return Ok(());
+            }
+        };
+
+        let inverse = match (state.ctm * self.device).inverse() {
+            Some(inverse) => inverse,
+            None => return Ok(()),
+        };
+        let mut square = Path::new();
+        square.rectangle(0.0, 0.0, 1.0, 1.0);
+        let mut mask = self.fill_mask(&square, &state.ctm, FillRule::NonZeroWinding);
+        intersect(&mut mask, &level.clip);
+
+        // Image space has its first row at the top of the unit square.
+        let sample = |x: usize, y: usize| {
+            let (u, v) = inverse.transform(x as f32 + 0.5, y as f32 + 0.5);
+            let column = (u * image.width as f32)
+                .floor()
+                .clamp(0.0, (image.width - 1) as f32);
+            let row = ((1.0 - v) * image.height as f32)
+                .floor()
+                .clamp(0.0, (image.height - 1) as f32);
+            image.sample(column as usize, row as usize)
+        };
+        let overprint = self.overprint(state.fill_overprint, state);
+
+        match &image.color_space {
+            None => {
+                let mut marked = Mask::new(self.width, self.height);
+                for (x, y) in mask.pixels() {
+                    if sample(x, y)[0] < 0.5 {
+                        marked.set(x, y, true);
+                    }
+                }
+                self.paint(&marked, &state.fill_color, overprint, step, level)
+            }
+            Some(color_space) => {
+                for (x, y) in mask.pixels() {
+                    let color = sample(x, y);
+                    let inks = color_inks(color_space, &color, state.rendering_intent);
+                    let replaced =
+                        overprint.map(|mode| overprinted_inks(color_space, &color, mode));
+                    self.paint_pixel(x, y, &inks, replaced.as_deref());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Records the inks of a color without painting anything.
+    fn list(&mut self, color: &Color, state: &GraphicsState, level: &Level) {
+        let color = level.uncolored.as_ref().unwrap_or(color);
+        let inks = color_inks(
+            &color.color_space,
+            &color.components,
+            state.rendering_intent,
+        );
+        self.unmeasured.extend(inks.into_iter().map(|(ink, _)| ink));
+    }
+
+    /// Paints a pixel with the given inks, knocking out any other inks there unless only the
+    /// `replaced` inks are overprinted.
+    fn paint_pixel(&mut self, x: usize, y: usize, inks: &[(Ink, f32)], replaced: Option<&[Ink]>) {
+        let index = y * self.width + x;
+        match replaced {
+            Some(replaced) => {
+                for ink in replaced {
+                    if let Some(plane) = self.planes.get_mut(ink) {
+                        plane[index] = 0.0;
+                    }
+                }
+            }
+            None => {
+                for plane in self.planes.values_mut() {
+                    plane[index] = 0.0;
+                }
+            }
+        }
+
+        for (ink, tint) in inks {
+            match self.planes.get_mut(ink) {
+                Some(plane) => plane[index] = *tint,
+                None if self.planes.len() >= MAX_INKS => {
+                    self.unmeasured.insert(ink.clone());
+                    self.unmeasured_area = true;
+                }
+                None => {
+                    let mut plane = vec![0.0; self.width * self.height];
+                    plane[index] = *tint;
+                    self.planes.insert(ink.clone(), plane);
+                }
+            }
+        }
+    }
+
+    fn report(&self, ink_limit: f32) -> InkReport {
+        let pixels = (self.width * self.height) as f32;
+        let mut inks: BTreeMap<_, _> = self
+            .unmeasured
+            .iter()
+            .map(|ink| (ink.clone(), 0.0))
+            .collect();
+        for (ink, plane) in &self.planes {
+            inks.insert(ink.clone(), plane.iter().sum::<f32>() / pixels * 100.0);
+        }
+
+        let mut total = vec![0.0; self.width * self.height];
+        for plane in self.planes.values() {
+            for (total, tint) in total.iter_mut().zip(plane) {
+                *total += tint * 100.0;
+            }
+        }
+
+        let mut over = Mask::new(self.width, self.height);
+        for (i, total) in total.iter().enumerate() {
+            if *total > ink_limit {
+                over.set(i % self.width, i / self.width, true);
+            }
+        }
+
+        let to_page = self.device.inverse().unwrap_or(Matrix::IDENTITY);
+        let over_limit = regions(&mut over)
+            .into_iter()
+            .map(|(x_min, y_min, x_max, y_max)| {
+                Rect::new(x_min as f32, y_min as f32, x_max as f32, y_max as f32)
+                    .transform(&to_page)
+            })
+            .collect();
+
+        InkReport {
+            inks: inks
+                .into_iter()
+                .filter(|(ink, coverage)| *coverage > 0.0 || self.unmeasured.contains(ink))
+                .map(|(ink, coverage)| InkCoverage { ink, coverage })
+                .collect(),
+            max_total_coverage: total.into_iter().fold(0.0, f32::max),
+            over_limit,
+            unmeasured_area: self.unmeasured_area,
+        }
+    }
+}
+
+/// An image's samples, with what's needed to read the color of each.
+struct Image {
+    width: usize,
+    height: usize,
+    /// The color space of the samples, `None` for stencil masks.
+    color_space: Option<ColorSpace>,
+    components: usize,
+    bits: usize,
+    /// The range each component's samples are mapped to, from `/Decode`.
+    decode: Vec<(f32, f32)>,
+    /// The length of a row of samples in bytes.
+    row: usize,
+    data: Vec<u8>,
+}
+
+impl Image {
+    /// Reads an image from its dictionary, in which inline images' abbreviations are expanded,
+    /// and its decoded data. `None` if there's too little data for its size, or it isn't known
+    /// how to read the samples.
+    fn new<R: Resolve>(
+        resolve: &R,
+        dictionary: &Dictionary,
+        color_space: Option<ColorSpace>,
+        data: Vec<u8>,
+    ) -> Result<Option<Self>, Error> {
+        let (width, height) = match (
+            get_integer(resolve, dictionary, "Width")?,
+            get_integer(resolve, dictionary, "Height")?,
+        ) {
+            (Some(width), Some(height)) if width > 0 && height > 0 => {
+                (width as usize, height as usize)
+            }
+            _ => return Ok(None),
+        };
+
+        let (color_space, bits) = if get_bool(resolve, dictionary, "ImageMask")? == Some(true) {
+            (None, 1)
+        } else {
+            match (
+                color_space,
+                get_integer(resolve, dictionary, "BitsPerComponent")?,
+            ) {
+                (Some(color_space), Some(bits)) if matches!(bits, 1 | 2 | 4 | 8 | 16) => {
+                    (Some(color_space), bits as usize)
+                }
+                _ => return Ok(None),
+            }
+        };
+        let components = color_space.as_ref().map_or(1, ColorSpace::components);
+
+        let decode = match get_numbers(resolve, dictionary, "Decode")? {
+            Some(decode) if decode.len() == 2 * components => decode
+                .chunks_exact(2)
+                .map(|range| (range[0], range[1]))
+                .collect(),
+            _ => match &color_space {
+                Some(ColorSpace::Indexed { .. }) => vec![(0.0, ((1 << bits) - 1) as f32)],
+                Some(color_space) => color_space.ranges(),
+                None => vec![(0.0, 1.0)],
+            },
+        };
+
+        let row = match width
+            .checked_mul(components)
+            .and_then(|bits_per_row| bits_per_row.checked_mul(bits))
+        {
+            Some(bits_per_row) => bits_per_row.div_ceil(8),
+            None => return Ok(None),
+        };
+        if !matches!(row.checked_mul(height), Some(size) if data.len() >= size) {
+            return Ok(None);
+        }
+
+        Ok(Some(Image {
+            width,
+            height,
+            color_space,
+            components,
+            bits,
+            decode,
+            row,
+            data,
+        }))
+    }
+
+    /// The decoded components of the sample at a column and row.
+    fn sample(&self, column: usize, row: usize) -> Vec<f32> {
+        let max = ((1u32 << self.bits) - 1) as f32;
+
+        (0..self.components)
+            .map(|i| {
+                let bit = row * self.row * 8 + (column * self.components + i) * self.bits;
+                let byte = bit / 8;
+                let value = match self.bits {
+                    16 => u32::from(self.data[byte]) << 8 | u32::from(self.data[byte + 1]),
+                    8 => u32::from(self.data[byte]),
+                    bits => u32::from(self.data[byte] >> (8 - bits - bit % 8)) & ((1 << bits) - 1),
+                };
+                let (min, max_value) = self.decode.get(i).copied().unwrap_or((0.0, 1.0));
+                min + value as f32 * (max_value - min) / max
+            })
+            .collect()
+    }
+}
+
+/// The full key of an inline image entry given by its abbreviation.
+fn expand_abbreviation(key: &str) -> &str {
+    match key {
+        "BPC" => "BitsPerComponent",
+        "CS" => "ColorSpace",
+        "D" => "Decode",
+        "DP" => "DecodeParms",
+        "F" => "Filter",
+        "H" => "Height",
+        "IM" => "ImageMask",
+        "I" => "Interpolate",
+        "W" => "Width",
+        key => key,
+    }
+}
+
+/// Whether an operation shows text, including text showing operators whose strings couldn't be
+/// typed.
+fn shows_text(operation: &Operation) -> bool {
+    match operation {
+        Operation::Unknown { operator, .. } => matches!(*operator, "Tj" | "TJ" | "'" | "\""),
+        Operation::ShowText(_)
+        | Operation::ShowTextAllowingIndividualGlyphPositioning(_)
+        | Operation::MoveToNextLineAndShowText(_)
+        | Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText { .. } => true,
+        _ => false,
+    }
+}
+
+/// The boxes of the glyphs a text showing operation shows, as a path in the page's space, moving
+/// the text position past them. `None` for strings that couldn't be typed.
+fn glyph_boxes(text: &mut TextState, font: &Font, step: &Step) -> Option<Path> {
+    let mut boxes = Path::new();
+    let ctm = &step.state.ctm;
+    let mut add = |glyph: Glyph| {
+        let [first, rest @ ..] = glyph.quad;
+        boxes.move_to(first.0, first.1);
+        for (x, y) in rest {
+            boxes.line_to(x, y);
+        }
+        boxes.close();
+    };
+
+    match step.operation {
+        Operation::ShowText(string)
+        | Operation::MoveToNextLineAndShowText(string)
+        | Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText { text: string, .. } => {
+            text.show(font, string.as_bytes(), ctm, &mut add)
+        }
+        Operation::ShowTextAllowingIndividualGlyphPositioning(array) => {
+            for item in array {
+                match item {
+                    TextOrGlyphPositioning::Text(string) => {
+                        text.show(font, string.as_bytes(), ctm, &mut add)
+                    }
+                    TextOrGlyphPositioning::GlyphPositioning(offset) => text.adjust(font, *offset),
+                }
+            }
+        }
+        _ => return None,
+    }
+
+    Some(boxes)
+}
+
+fn intersect(mask: &mut Mask, clip: &Option<Mask>) {
+    if let Some(clip) = clip {
+        mask.intersect(clip);
+    }
+}
+
+fn intersect_with(clip: &mut Option<Mask>, mask: Mask) {
+    match clip {
+        Some(clip) => clip.intersect(&mask),
+        None => *clip = Some(mask),
+    }
+}
+
+/// The bounds of each connected region of covered pixels, clearing the mask.
+fn regions(mask: &mut Mask) -> Vec<(usize, usize, usize, usize)> {
+    let mut regions = Vec::new();
+
+    for y in 0..mask.height() {
+        for x in 0..mask.width() {
+            if !mask.get(x, y) {
+                continue;
+            }
+
+            let mut bounds = (x, y, x + 1, y + 1);
+            let mut pending = vec![(x, y)];
+            mask.set(x, y, false);
+            while let Some((x, y)) = pending.pop() {
+                bounds = (
+                    bounds.0.min(x),
+                    bounds.1.min(y),
+                    bounds.2.max(x + 1),
+                    bounds.3.max(y + 1),
+                );
+
+                let neighbors = [
+                    (x.wrapping_sub(1), y),
+                    (x + 1, y),
+                    (x, y.wrapping_sub(1)),
+                    (x, y + 1),
+                ];
+                for (x, y) in neighbors {
+                    if mask.get(x, y) {
+                        mask.set(x, y, false);
+                        pending.push((x, y));
+                    }
+                }
+            }
+
+            regions.push(bounds);
+        }
+    }
+
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::{
+        object::NoResolve,
+        primitive::{Dictionary, PdfStream},
+    };
+
+    use super::*;
+    use crate::fixtures::{self, dictionary, name};
+
+    /// A square page of `size`, see [`fixtures::page`].
+    fn page(resources: Dictionary, size: i32, content: &[u8]) -> RawPage {
+        let mut page = fixtures::page(resources, content);
+        page.dictionary.insert(
+            "MediaBox".to_string(),
+            Primitive::Array(
+                [0, 0, size, size]
+                    .iter()
+                    .map(|n| Primitive::Integer(*n))
+                    .collect(),
+            ),
+        );
+        page
+    }
+
+    #[test]
+    fn measures_ink_coverage() {
+        // A full page of 40% black, with a square of rich black over a quarter of it.
+        let page = page(
+            Dictionary::new(),
+            100,
+            b"0 0 0 0.4 k 0 0 100 100 re f 1 1 1 1 k 0 0 50 50 re f",
+        );
+
+        let report = InkReport::analyze(&NoResolve, &page, &InkOptions::default()).unwrap();
+
+        let coverage = |ink: Ink| {
+            report
+                .inks
+                .iter()
+                .find(|coverage| coverage.ink == ink)
+                .map(|coverage| coverage.coverage)
+        };
+        assert_eq!(report.inks.len(), 4);
+        assert!((coverage(Ink::Cyan).unwrap() - 25.0).abs() < 0.1);
+        assert!((coverage(Ink::Black).unwrap() - 55.0).abs() < 0.1);
+        assert_eq!(report.max_total_coverage, 400.0);
+        assert_eq!(report.over_limit, vec![Rect::new(0.0, 0.0, 50.0, 50.0)]);
+    }
+
+    #[test]
+    fn simulates_overprint() {
+        let mut ext_g_state = Dictionary::new();
+        ext_g_state.insert("op".to_string(), Primitive::Boolean(true));
+        ext_g_state.insert("OPM".to_string(), Primitive::Integer(1));
+        let mut ext_g_states = Dictionary::new();
+        ext_g_states.insert("GS0".to_string(), Primitive::Dictionary(ext_g_state));
+        let mut resources = Dictionary::new();
+        resources.insert("ExtGState".to_string(), Primitive::Dictionary(ext_g_states));
+
+        // Yellow overprinted by black, and cyan by white.
+        let page = page(
+            resources,
+            10,
+            b"0 0 1 0 k 0 0 10 5 re f 1 0 0 0 k 0 5 10 5 re f \
+                /GS0 gs 0 0 0 1 k 0 0 10 5 re f 0 0 0 0 k 0 5 10 5 re f",
+        );
+
+        let options = InkOptions {
+            simulate_overprint: true,
+            ..InkOptions::default()
+        };
+        let report = InkReport::analyze(&NoResolve, &page, &options).unwrap();
+        let coverages: Vec<_> = report
+            .inks
+            .iter()
+            .map(|coverage| (coverage.ink.clone(), coverage.coverage))
+            .collect();
+        assert_eq!(
+            coverages,
+            vec![(Ink::Cyan, 50.0), (Ink::Yellow, 50.0), (Ink::Black, 50.0)]
+        );
+
+        let report = InkReport::analyze(&NoResolve, &page, &InkOptions::default()).unwrap();
+        assert_eq!(report.inks.len(), 1);
+        assert_eq!(report.max_total_coverage, 100.0);
+    }
+
+    #[test]
+    fn bounds_nested_content() {
+        // A form painting itself eight times, padded so that the limit on the content walked
+        // stops it long before the nesting limit would.
+        let mut data = b"/Fm0 Do ".repeat(8);
+        data.resize(1 << 20, b' ');
+        let mut form = Dictionary::new();
+        form.insert("Subtype".to_string(), Primitive::Name("Form".to_string()));
+        let mut xobjects = Dictionary::new();
+        xobjects.insert(
+            "Fm0".to_string(),
+            Primitive::Stream(PdfStream { info: form, data }),
+        );
+        let mut resources = Dictionary::new();
+        resources.insert("XObject".to_string(), Primitive::Dictionary(xobjects));
+        let page = page(resources, 10, b"0 0 0 1 k 0 0 10 10 re f /Fm0 Do");
+
+        let report = InkReport::analyze(&NoResolve, &page, &InkOptions::default()).unwrap();
+        assert_eq!(report.max_total_coverage, 100.0);
+    }
+
+    #[test]
+    fn measures_text() {
+        let page = page(
+            fixtures::courier_resources(),
+            100,
+            b"1 1 1 1 k BT /F1 20 Tf 10 10 Td (ABC) Tj ET",
+        );
+
+        let report = InkReport::analyze(&NoResolve, &page, &InkOptions::default()).unwrap();
+        assert_eq!(report.max_total_coverage, 400.0);
+        assert!(!report.over_limit.is_empty());
+        assert!(!report.unmeasured_area);
+
+        // Text in a font that can't be loaded is only listed.
+        let page = page(
+            Dictionary::new(),
+            100,
+            b"1 1 1 1 k BT /F1 20 Tf 10 10 Td (ABC) Tj ET",
+        );
+
+        let report = InkReport::analyze(&NoResolve, &page, &InkOptions::default()).unwrap();
+        assert_eq!(report.inks.len(), 4);
+        assert_eq!(report.max_total_coverage, 0.0);
+        assert!(report.unmeasured_area);
+    }
+
+    #[test]
+    fn measures_images() {
+        // Two samples, rich black on the left and cyan on the right.
+        let mut image = Dictionary::new();
+        image.insert("Subtype".to_string(), Primitive::Name("Image".to_string()));
+        image.insert("Width".to_string(), Primitive::Integer(2));
+        image.insert("Height".to_string(), Primitive::Integer(1));
+        image.insert(
+            "ColorSpace".to_string(),
+            Primitive::Name("DeviceCMYK".to_string()),
+        );
+        image.insert("BitsPerComponent".to_string(), Primitive::Integer(8));
+        let mut xobjects = Dictionary::new();
+        xobjects.insert(
+            "Im0".to_string(),
+            Primitive::Stream(PdfStream {
+                info: image,
+                data: vec![255, 255, 255, 255, 255, 0, 0, 0],
+            }),
+        );
+        let mut resources = Dictionary::new();
+        resources.insert("XObject".to_string(), Primitive::Dictionary(xobjects));
+        let page = page(resources, 10, b"10 0 0 10 0 0 cm /Im0 Do");
+
+        let report = InkReport::analyze(&NoResolve, &page, &InkOptions::default()).unwrap();
+        let coverages: Vec<_> = report
+            .inks
+            .iter()
+            .map(|coverage| (coverage.ink.clone(), coverage.coverage))
+            .collect();
+        assert_eq!(
+            coverages,
+            vec![
+                (Ink::Cyan, 100.0),
+                (Ink::Magenta, 50.0),
+                (Ink::Yellow, 50.0),
+                (Ink::Black, 50.0)
+            ]
+        );
+        assert_eq!(report.over_limit, vec![Rect::new(0.0, 0.0, 5.0, 10.0)]);
+        assert!(!report.unmeasured_area);
+
+        // An inline stencil mask, and an inline image whose data isn't decoded.
+        let page = page(
+            Dictionary::new(),
+            10,
+            b"0 0 0 1 k q 10 0 0 10 0 0 cm BI /W 2 /H 1 /IM true ID \x7f EI Q \
+                q 10 0 0 10 0 0 cm BI /W 1 /H 1 /CS /CMYK /BPC 8 /F /AHx ID 00000000> EI Q",
+        );
+
+        let report = InkReport::analyze(&NoResolve, &page, &InkOptions::default()).unwrap();
+        assert_eq!(report.inks.len(), 4);
+        assert_eq!(report.max_total_coverage, 100.0);
+        assert!(report.unmeasured_area);
+    }
+
+    #[test]
+    fn bounds_tiny_pattern_steps() {
+        let mut info = Dictionary::new();
+        info.insert("PatternType".to_string(), Primitive::Integer(1));
+        info.insert("PaintType".to_string(), Primitive::Integer(2));
+        info.insert("TilingType".to_string(), Primitive::Integer(1));
+        info.insert(
+            "BBox".to_string(),
+            Primitive::Array(vec![
+                Primitive::Integer(0),
+                Primitive::Integer(0),
+                Primitive::Integer(10),
+                Primitive::Integer(10),
+            ]),
+        );
+        info.insert("XStep".to_string(), Primitive::Number(1e-20));
+        info.insert("YStep".to_string(), Primitive::Integer(10));
+        let mut patterns = Dictionary::new();
+        patterns.insert(
+            "P0".to_string(),
+            Primitive::Stream(PdfStream {
+                info,
+                data: b"0 0 10 10 re f".to_vec(),
+            }),
+        );
+        let mut color_spaces = Dictionary::new();
+        color_spaces.insert(
+            "CS0".to_string(),
+            Primitive::Array(vec![
+                Primitive::Name("Pattern".to_string()),
+                Primitive::Name("DeviceCMYK".to_string()),
+            ]),
+        );
+        let mut resources = Dictionary::new();
+        resources.insert("Pattern".to_string(), Primitive::Dictionary(patterns));
+        resources.insert(
+            "ColorSpace".to_string(),
+            Primitive::Dictionary(color_spaces),
+        );
+        let page = page(resources, 10, b"/CS0 cs 0 0 0 1 /P0 scn 0 0 10 10 re f");
+
+        // Far too many cells, so the pattern is painted as a flat area of its color.
+        let report = InkReport::analyze(&NoResolve, &page, &InkOptions::default()).unwrap();
+        assert_eq!(report.max_total_coverage, 100.0);
+    }
+
+    #[test]
+    fn bounds_the_inks_measured() {
+        // A square of each of twenty spot inks.
+        let mut color_spaces = Dictionary::new();
+        let mut content = Vec::new();
+        for i in 0..20 {
+            color_spaces.insert(
+                format!("CS{}", i),
+                Primitive::Array(vec![
+                    name("Separation"),
+                    name(&format!("Spot{}", i)),
+                    name("DeviceCMYK"),
+                    dictionary(vec![
+                        ("FunctionType", Primitive::Integer(2)),
+                        (
+                            "Domain",
+                            Primitive::Array(vec![Primitive::Integer(0), Primitive::Integer(1)]),
+                        ),
+                        ("N", Primitive::Integer(1)),
+                    ]),
+                ]),
+            );
+            content.extend(format!("/CS{} cs 1 sc {} 0 1 1 re f ", i, i).bytes());
+        }
+        let mut resources = Dictionary::new();
+        resources.insert(
+            "ColorSpace".to_string(),
+            Primitive::Dictionary(color_spaces),
+        );
+        let page = page(resources, 20, &content);
+
+        let report = InkReport::analyze(&NoResolve, &page, &InkOptions::default()).unwrap();
+        assert_eq!(report.inks.len(), 20);
+        assert_eq!(
+            report
+                .inks
+                .iter()
+                .filter(|coverage| coverage.coverage > 0.0)
+                .count(),
+            MAX_INKS
+        );
+        assert!(report.unmeasured_area);
+    }
+}
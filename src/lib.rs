@@ -1,21 +1,60 @@
-use std::borrow::Cow;
-
-use pdf::{content::Operation as PdfOperation, primitive::Primitive};
-
-pub struct Name<'src>(&'src str);
-
+use std::{borrow::Cow, ops::Range};
+
+use pdf::{
+    content::Operation as PdfOperation,
+    primitive::{Dictionary, Primitive},
+};
+
+pub mod color;
+pub mod diff;
+pub mod document;
+pub mod error;
+pub mod ext_g_state;
+#[cfg(test)]
+mod fixtures;
+pub mod font;
+pub mod function;
+pub mod geometry;
+pub mod graphics_state;
+pub mod icc;
+pub mod ink;
+pub mod marked_content;
+pub mod optimize;
+pub mod optional_content;
+pub mod overprint;
+pub mod painting;
+pub mod parser;
+pub mod pattern;
+pub mod postscript;
+pub mod raster;
+pub mod redact;
+pub mod search;
+pub mod shading;
+pub mod structure;
+pub mod text;
+pub mod text_state;
+pub mod transparency;
+pub mod visit;
+pub mod writer;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Name<'src>(pub &'src str);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum LineCapStyle {
     ButtCap,
     RoundCap,
     ProjectingSquareCap,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum LineJoinStyle {
     MiterJoin,
     RoundJoin,
     BevelJoin,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TextRenderingMode {
     FillText,
     StrokeText,
@@ -27,17 +66,28 @@ pub enum TextRenderingMode {
     AddTextToPathForClipping,
 }
 
+/// The property list of a `BDC` or `DP` operation, given inline or as the name of a
+/// `/Properties` resource.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PropertyList<'src> {
+    Inline(&'src Dictionary),
+    Named(Name<'src>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum TextOrGlyphPositioning<'src> {
     Text(Cow<'src, str>),
     GlyphPositioning(f32),
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum UntypedColor {
     DeviceGrayCalGrayOrIndexed(f32),
     DeviceRGBCalRGBOrLab(f32, f32, f32),
     DeviceCMYK(f32, f32, f32, f32),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ColorRenderingIntent {
     AbsoluteColorimetric,
     RelativeColorimetric,
@@ -45,14 +95,18 @@ pub enum ColorRenderingIntent {
     Perceptual,
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum Operation<'src> {
     CloseFillAndStrokePathUsingNonZeroWindingNumber,
     FillAndStrokePathUsingNonZeroWindingNumber,
     CloseFillAndStrokePathUsingEvenOddRule,
     FillAndStrokePathUsingEvenOddRule,
-    BeginMarkedContentSequenceWithPropertyList,
+    BeginMarkedContentSequenceWithPropertyList {
+        tag: Name<'src>,
+        properties: PropertyList<'src>,
+    },
     BeginInlineImageObject,
-    BeginMarkedContentSequence,
+    BeginMarkedContentSequence(Name<'src>),
     BeginTextObject,
     BeginCompatibilitySection,
     AppendCurvedSegmentToPath {
@@ -83,8 +137,12 @@ pub enum Operation<'src> {
         ury: f32,
     },
     InvokeNamedXObject(Name<'src>),
-    DefineMarkedContentPointWithPropertyList,
-    EndInlineImageObject,
+    DefineMarkedContentPointWithPropertyList {
+        tag: Name<'src>,
+        properties: PropertyList<'src>,
+    },
+    /// The data of an inline image, between `ID` and `EI`.
+    EndInlineImageObject(&'src [u8]),
     EndMarkedContentSequence,
     EndTextObject,
     EndCompatibilitySection,
@@ -96,7 +154,8 @@ pub enum Operation<'src> {
     SetParametersFromGraphicsStateParameterDictionary(Name<'src>),
     CloseSubpath,
     SetFlatnessTolerance(i32),
-    BeginInlineImageData,
+    /// The entries of an inline image's dictionary, as alternating keys and values.
+    BeginInlineImageData(&'src [Primitive]),
     SetLineJoinStyle(LineJoinStyle),
     SetLineCapStyle(LineCapStyle),
     SetCMYKColorForStrokingOperations(f32, f32, f32, f32),
@@ -183,6 +242,10 @@ pub enum Operation<'src> {
         operator: &'src str,
         operands: &'src [Primitive],
     },
+    Malformed {
+        byte_range: Range<usize>,
+        reason: &'src str,
+    },
 }
 
 trait PrimitiveExt {
@@ -199,6 +262,14 @@ impl PrimitiveExt for Primitive {
     }
 }
 
+fn property_list(properties: &Primitive) -> Option<PropertyList<'_>> {
+    match properties {
+        Primitive::Dictionary(dictionary) => Some(PropertyList::Inline(dictionary)),
+        Primitive::Name(name) => Some(PropertyList::Named(Name(name))),
+        _ => None,
+    }
+}
+
 pub fn normalize_operation(operation: &PdfOperation) -> Operation {
     let PdfOperation { operator, operands } = operation;
 
@@ -207,9 +278,15 @@ pub fn normalize_operation(operation: &PdfOperation) -> Operation {
         ("B", _) => Operation::FillAndStrokePathUsingNonZeroWindingNumber,
         ("b*", _) => Operation::CloseFillAndStrokePathUsingEvenOddRule,
         ("B*", _) => Operation::FillAndStrokePathUsingEvenOddRule,
-        ("BDC", []) => Operation::BeginMarkedContentSequenceWithPropertyList,
+        ("BDC", [Primitive::Name(tag), properties]) => match property_list(properties) {
+            Some(properties) => Operation::BeginMarkedContentSequenceWithPropertyList {
+                tag: Name(tag),
+                properties,
+            },
+            None => Operation::Unknown { operator, operands },
+        },
         ("BI", []) => Operation::BeginInlineImageObject,
-        ("BMC", []) => Operation::BeginMarkedContentSequence,
+        ("BMC", [Primitive::Name(tag)]) => Operation::BeginMarkedContentSequence(Name(tag)),
         ("BT", _) => Operation::BeginTextObject,
         ("BX", []) => Operation::BeginCompatibilitySection,
         ("c", ns) => {
@@ -297,8 +374,14 @@ pub fn normalize_operation(operation: &PdfOperation) -> Operation {
             }
         }
         ("Do", [Primitive::Name(name)]) => Operation::InvokeNamedXObject(Name(name)),
-        ("DP", []) => Operation::DefineMarkedContentPointWithPropertyList,
-        ("EI", []) => Operation::EndInlineImageObject,
+        ("DP", [Primitive::Name(tag), properties]) => match property_list(properties) {
+            Some(properties) => Operation::DefineMarkedContentPointWithPropertyList {
+                tag: Name(tag),
+                properties,
+            },
+            None => Operation::Unknown { operator, operands },
+        },
+        ("EI", [Primitive::String(data)]) => Operation::EndInlineImageObject(data.as_bytes()),
         ("EMC", []) => Operation::EndMarkedContentSequence,
         ("ET", _) => Operation::EndTextObject,
         ("EX", []) => Operation::EndCompatibilitySection,
@@ -320,7 +403,7 @@ pub fn normalize_operation(operation: &PdfOperation) -> Operation {
         }
         ("h", _) => Operation::CloseSubpath,
         ("i", [Primitive::Integer(flatness)]) => Operation::SetFlatnessTolerance(*flatness),
-        ("ID", []) => Operation::BeginInlineImageData,
+        ("ID", entries) => Operation::BeginInlineImageData(entries),
         ("j", [Primitive::Integer(0)]) => Operation::SetLineJoinStyle(LineJoinStyle::MiterJoin),
         ("j", [Primitive::Integer(1)]) => Operation::SetLineJoinStyle(LineJoinStyle::RoundJoin),
         ("j", [Primitive::Integer(2)]) => Operation::SetLineJoinStyle(LineJoinStyle::BevelJoin),
@@ -626,11 +709,7 @@ pub fn normalize_operation(operation: &PdfOperation) -> Operation {
             let ns = ns.iter().filter_map(|n| n.try_to_f()).collect::<Vec<_>>();
 
             if let [a, b, c, d, e, f] = ns.as_slice() {
-                if *a == 1.0 && *b == 0.0 && *c == 0.0 && *d == 1.0 {
-                    Operation::MoveTextPosition { x: *e, y: *f }
-                } else {
-                    Operation::SetTextMatrixAndTextLineMatrix(*a, *b, *c, *d, *e, *f)
-                }
+                Operation::SetTextMatrixAndTextLineMatrix(*a, *b, *c, *d, *e, *f)
             } else {
                 Operation::Unknown { operator, operands }
             }
@@ -721,8 +800,27 @@ pub fn normalize_operation(operation: &PdfOperation) -> Operation {
 
 #[cfg(test)]
 mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::parser::ContentStreamParser;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn keeps_translating_text_matrices() {
+        let content = b"BT 2 0 0 2 0 0 Tm 1 0 0 1 72 700 Tm ET";
+        let operations = ContentStreamParser::new(Cursor::new(&content[..]))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert!(matches!(
+            normalize_operation(&operations[2]),
+            Operation::SetTextMatrixAndTextLineMatrix(a, b, c, d, e, f)
+                if (a, b, c, d, e, f) == (1.0, 0.0, 0.0, 1.0, 72.0, 700.0)
+        ));
+    }
 }
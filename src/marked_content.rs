@@ -0,0 +1,190 @@
+use std::{ops::Range, sync::Arc};
+
+use pdf::{
+    object::Resolve,
+    primitive::{Dictionary, Primitive},
+};
+
+use crate::{
+    document::get_resource, error::Error, Name, Operation, PropertyList, TextOrGlyphPositioning,
+};
+
+impl<'src> PropertyList<'src> {
+    /// The property list dictionary, looking named ones up in the `/Properties` resources.
+    pub fn resolve<R: Resolve>(
+        &self,
+        resolve: &R,
+        resources: &Dictionary,
+    ) -> Result<Dictionary, Error> {
+        match self {
+            PropertyList::Inline(dictionary) => Ok((*dictionary).clone()),
+            PropertyList::Named(Name(name)) => {
+                match get_resource(resolve, resources, "Properties", name)? {
+                    Primitive::Dictionary(dictionary) => Ok(dictionary),
+                    _ => Err(Error::InvalidEntry(format!("Properties /{}", name))),
+                }
+            }
+        }
+    }
+}
+
+/// A marked content sequence, from its `BMC` or `BDC` operation to the matching `EMC`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MarkedContent {
+    pub tag: String,
+    /// The property list of a `BDC` operation.
+    pub properties: Option<Dictionary>,
+    /// The indices of the operations from `BMC` or `BDC` to `EMC`, both included. A sequence
+    /// that is never closed runs to the end of the content.
+    pub operations: Range<usize>,
+    /// The number of sequences the sequence is nested in.
+    pub depth: usize,
+    /// The text shown in the whole content, shared by all its sequences.
+    shown: Arc<str>,
+    /// The part of `shown` shown in the sequence.
+    text: Range<usize>,
+}
+
+impl MarkedContent {
+    /// `/MCID`, which links the sequence to an element of the structure tree.
+    pub fn mcid(&self) -> Option<i32> {
+        match self.properties.as_ref()?.get("MCID") {
+            Some(Primitive::Integer(mcid)) => Some(*mcid),
+            _ => None,
+        }
+    }
+
+    /// The text shown in the sequence, including in nested sequences. It's the raw bytes of the
+    /// shown strings read as UTF-8, not decoded with the font's encoding or `/ToUnicode` map, so
+    /// it's only the actual text for fonts whose character codes are ASCII or UTF-8.
+    pub fn text(&self) -> &str {
+        &self.shown[self.text.clone()]
+    }
+
+    /// The marked content sequences of a content stream, in the order they begin. Unbalanced
+    /// `EMC` operations are ignored.
+    pub fn collect<R: Resolve>(
+        resolve: &R,
+        resources: &Dictionary,
+        operations: &[Operation],
+    ) -> Result<Vec<MarkedContent>, Error> {
+        let mut sequences: Vec<MarkedContent> = Vec::new();
+        let mut open: Vec<usize> = Vec::new();
+        // Nested sequences share the text of the sequences they're in, rather than each having a
+        // copy of it.
+        let mut shown = String::new();
+
+        for (index, operation) in operations.iter().enumerate() {
+            let (tag, properties) = match operation {
+                Operation::BeginMarkedContentSequence(Name(tag)) => (tag, None),
+                Operation::BeginMarkedContentSequenceWithPropertyList {
+                    tag: Name(tag),
+                    properties,
+                } => (tag, Some(properties.resolve(resolve, resources)?)),
+                Operation::EndMarkedContentSequence => {
+                    if let Some(sequence) = open.pop() {
+                        sequences[sequence].operations.end = index + 1;
+                        sequences[sequence].text.end = shown.len();
+                    }
+                    continue;
+                }
+                operation => {
+                    if let Some(text) = shown_text(operation) {
+                        shown.push_str(&text);
+                    }
+                    continue;
+                }
+            };
+
+            open.push(sequences.len());
+            sequences.push(MarkedContent {
+                tag: tag.to_string(),
+                properties,
+                operations: index..operations.len(),
+                depth: open.len() - 1,
+                shown: Arc::from(""),
+                text: shown.len()..usize::MAX,
+            });
+        }
+
+        // Sequences that are never closed run to the end of the text.
+        let shown: Arc<str> = Arc::from(shown);
+        for sequence in &mut sequences {
+            sequence.text.end = sequence.text.end.min(shown.len());
+            sequence.shown = Arc::clone(&shown);
+        }
+
+        Ok(sequences)
+    }
+}
+
+/// The text shown by a text showing operation.
+pub(crate) fn shown_text(operation: &Operation) -> Option<String> {
+    match operation {
+        Operation::ShowText(text)
+        | Operation::MoveToNextLineAndShowText(text)
+        | Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText { text, .. } => {
+            Some(text.to_string())
+        }
+        Operation::ShowTextAllowingIndividualGlyphPositioning(array) => Some(
+            array
+                .iter()
+                .filter_map(|item| match item {
+                    TextOrGlyphPositioning::Text(text) => Some(&**text),
+                    TextOrGlyphPositioning::GlyphPositioning(_) => None,
+                })
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use pdf::object::NoResolve;
+
+    use super::*;
+    use crate::{normalize_operation, parser::ContentStreamParser};
+
+    #[test]
+    fn collects_nested_sequences() {
+        let mut properties = Dictionary::new();
+        properties.insert("MCID".to_string(), Primitive::Integer(7));
+        let mut named = Dictionary::new();
+        named.insert("P0".to_string(), Primitive::Dictionary(properties));
+        let mut resources = Dictionary::new();
+        resources.insert("Properties".to_string(), Primitive::Dictionary(named));
+
+        let content = b"/Span << /MCID 3 >> BDC (Hello) Tj /Em /P0 BDC [( wor) -20 (ld)] TJ EMC \
+                        EMC /Artifact BMC (1) Tj EMC";
+        let operations = ContentStreamParser::new(Cursor::new(&content[..]))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let operations: Vec<_> = operations.iter().map(normalize_operation).collect();
+
+        let sequences = MarkedContent::collect(&NoResolve, &resources, &operations).unwrap();
+        let summary: Vec<_> = sequences
+            .iter()
+            .map(|sequence| {
+                (
+                    sequence.tag.as_str(),
+                    sequence.mcid(),
+                    sequence.operations.clone(),
+                    sequence.depth,
+                    sequence.text(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("Span", Some(3), 0..6, 0, "Hello world"),
+                ("Em", Some(7), 2..5, 1, " world"),
+                ("Artifact", None, 6..9, 0, "1"),
+            ]
+        );
+    }
+}
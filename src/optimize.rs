@@ -0,0 +1,496 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{geometry::Matrix, text_state::TextState, Operation};
+
+/// A graphics state parameter set by state operators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Parameter {
+    LineWidth,
+    LineCap,
+    LineJoin,
+    MiterLimit,
+    Dash,
+    RenderingIntent,
+    Flatness,
+    StrokeColorSpace,
+    StrokeColor,
+    FillColorSpace,
+    FillColor,
+    CharacterSpacing,
+    WordSpacing,
+    HorizontalScaling,
+    Leading,
+    Font,
+    TextRenderingMode,
+    TextRise,
+}
+
+/// The parameters a graphics state parameter dictionary can set.
+const EXT_G_STATE_PARAMETERS: &[Parameter] = &[
+    Parameter::LineWidth,
+    Parameter::LineCap,
+    Parameter::LineJoin,
+    Parameter::MiterLimit,
+    Parameter::Dash,
+    Parameter::RenderingIntent,
+    Parameter::Flatness,
+    Parameter::Font,
+];
+
+/// The parameters an operation sets, if it's a state operator that does nothing else. Color
+/// space operators also set the color, to the initial color of the space.
+fn parameters(operation: &Operation) -> &'static [Parameter] {
+    use Parameter::*;
+
+    match operation {
+        Operation::SetLineWidth(_) => &[LineWidth],
+        Operation::SetLineCapStyle(_) => &[LineCap],
+        Operation::SetLineJoinStyle(_) => &[LineJoin],
+        Operation::SetMiterLimit(_) => &[MiterLimit],
+        Operation::SetLineDashPattern { .. } => &[Dash],
+        Operation::SetColorRenderingIntent(_) => &[RenderingIntent],
+        Operation::SetFlatnessTolerance(_) => &[Flatness],
+        Operation::SetColorSpaceForStrokingOperations(_)
+        | Operation::SetGrayLevelForStrokingOperations(_)
+        | Operation::SetRGBColorForStrokingOperations(..)
+        | Operation::SetCMYKColorForStrokingOperations(..) => &[StrokeColorSpace, StrokeColor],
+        Operation::SetColorForStrokingOperations(_)
+        | Operation::SetColorForStrokingOperationsICCBasedAndSpecialColorSpaces { .. } => {
+            &[StrokeColor]
+        }
+        Operation::SetColorSpaceForNonStrokingOperations(_)
+        | Operation::SetGrayLevelForNonStrokingOperations(_)
+        | Operation::SetRGBColorForNonStrokingOperations(..)
+        | Operation::SetCMYKColorForNonStrokingOperations(..) => &[FillColorSpace, FillColor],
+        Operation::SetColorForNonStrokingOperations(_)
+        | Operation::SetColorForNonStrokingOperationsICCBasedAndSpecialColorSpaces { .. } => {
+            &[FillColor]
+        }
+        Operation::SetCharacterSpacing(_) => &[CharacterSpacing],
+        Operation::SetWordSpacing(_) => &[WordSpacing],
+        Operation::SetHorizontalTextScaling(_) => &[HorizontalScaling],
+        Operation::SetTextLeading(_) => &[Leading],
+        Operation::SetTextFontAndSize { .. } => &[Font],
+        Operation::SetTextRenderingMode(_) => &[TextRenderingMode],
+        Operation::SetTextRise(_) => &[TextRise],
+        _ => &[],
+    }
+}
+
+/// Whether an operation leaves the graphics state parameters alone and doesn't paint with them.
+fn is_neutral(operation: &Operation) -> bool {
+    matches!(
+        operation,
+        Operation::BeginNewSubpath { .. }
+            | Operation::AppendStraightLineSegmentToPath { .. }
+            | Operation::AppendCurvedSegmentToPath { .. }
+            | Operation::AppendCurvedSegmentToPathInitialPointReplicated { .. }
+            | Operation::AppendCurvedSegmentToPathFinalPointReplicated { .. }
+            | Operation::AppendRectangleToPath { .. }
+            | Operation::CloseSubpath
+            | Operation::ConcatenateMatrixToCurrentTransformationMatrix(..)
+            | Operation::BeginTextObject
+            | Operation::MoveTextPosition { .. }
+            | Operation::MoveTextPositionAndSetLeading { .. }
+            | Operation::SetTextMatrixAndTextLineMatrix(..)
+            | Operation::BeginMarkedContentSequence(_)
+            | Operation::BeginMarkedContentSequenceWithPropertyList { .. }
+            | Operation::EndMarkedContentSequence
+            | Operation::DefineMarkedContentPoint(_)
+            | Operation::DefineMarkedContentPointWithPropertyList { .. }
+    )
+}
+
+/// Whether an operation shows text, which operators that couldn't be typed are taken to do.
+fn shows_text(operation: &Operation) -> bool {
+    matches!(
+        operation,
+        Operation::ShowText(_)
+            | Operation::ShowTextAllowingIndividualGlyphPositioning(_)
+            | Operation::MoveToNextLineAndShowText(_)
+            | Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText { .. }
+            | Operation::Unknown { .. }
+            | Operation::Malformed { .. }
+    )
+}
+
+/// Removes operations from a content stream that don't change what it paints, to make it
+/// smaller:
+///
+/// - state operators that set a parameter to the value it already has, or whose value is
+///   replaced or restored by `Q` before anything is painted with it
+/// - `q Q` pairs with nothing but state operators between them
+/// - `BT ET` pairs that don't show any text
+/// - `cm` operators that don't change the matrix, with adjacent ones merged into one
+///
+/// It also replaces `Tm` by `Td` where that sets exactly the same text matrix. Nothing is
+/// assumed about the graphics state at the start, but the operations are taken to be the whole
+/// content of a page or a Form XObject, so state set at the end is dropped.
+pub fn optimize<'src>(mut operations: Vec<Operation<'src>>) -> Vec<Operation<'src>> {
+    operations = shorten_text_matrices(operations);
+
+    loop {
+        let length = operations.len();
+        operations = merge_matrices(operations);
+        operations = remove_unchanged_state(operations);
+        operations = remove_unused_state(operations);
+        operations = remove_empty_text_objects(operations);
+        operations = remove_empty_saves(operations);
+        if operations.len() == length {
+            return operations;
+        }
+    }
+}
+
+/// Merges adjacent `cm` operators and drops identity ones.
+fn merge_matrices(operations: Vec<Operation>) -> Vec<Operation> {
+    let mut out: Vec<Operation> = Vec::with_capacity(operations.len());
+    for operation in operations {
+        let matrix = match operation {
+            Operation::ConcatenateMatrixToCurrentTransformationMatrix(a, b, c, d, e, f) => {
+                Matrix::new(a, b, c, d, e, f)
+            }
+            operation => {
+                out.push(operation);
+                continue;
+            }
+        };
+
+        // The later matrix applies first.
+        let matrix = match out.last() {
+            Some(Operation::ConcatenateMatrixToCurrentTransformationMatrix(a, b, c, d, e, f)) => {
+                let previous = Matrix::new(*a, *b, *c, *d, *e, *f);
+                out.pop();
+                matrix * previous
+            }
+            _ => matrix,
+        };
+        if matrix != Matrix::IDENTITY {
+            let Matrix { a, b, c, d, e, f } = matrix;
+            out.push(Operation::ConcatenateMatrixToCurrentTransformationMatrix(
+                a, b, c, d, e, f,
+            ));
+        }
+    }
+
+    out
+}
+
+/// Drops state operators that set parameters to the values they already have.
+fn remove_unchanged_state(operations: Vec<Operation>) -> Vec<Operation> {
+    let mut out = Vec::with_capacity(operations.len());
+    let mut known: HashMap<Parameter, Operation> = HashMap::new();
+    let mut saved = Vec::new();
+
+    for operation in operations {
+        let parameters = parameters(&operation);
+        if !parameters.is_empty() {
+            if parameters
+                .iter()
+                .all(|parameter| known.get(parameter) == Some(&operation))
+            {
+                continue;
+            }
+            for parameter in parameters {
+                known.insert(*parameter, operation.clone());
+            }
+            out.push(operation);
+            continue;
+        }
+
+        match &operation {
+            Operation::SaveGraphicsState => saved.push(known.clone()),
+            Operation::RestoreGraphicsState => known = saved.pop().unwrap_or_default(),
+            Operation::SetParametersFromGraphicsStateParameterDictionary(_) => {
+                for parameter in EXT_G_STATE_PARAMETERS {
+                    known.remove(parameter);
+                }
+            }
+            Operation::MoveTextPositionAndSetLeading { y, .. } => {
+                known.insert(Parameter::Leading, Operation::SetTextLeading(-y));
+            }
+            Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText {
+                word_spacing,
+                character_spacing,
+                ..
+            } => {
+                known.insert(
+                    Parameter::WordSpacing,
+                    Operation::SetWordSpacing(*word_spacing),
+                );
+                known.insert(
+                    Parameter::CharacterSpacing,
+                    Operation::SetCharacterSpacing(*character_spacing),
+                );
+            }
+            // Operators that couldn't be typed could be setting anything.
+            Operation::Unknown { .. } | Operation::Malformed { .. } => known.clear(),
+            _ => {}
+        }
+        out.push(operation);
+    }
+
+    out
+}
+
+/// Drops state operators whose values are replaced, restored by `Q` or left at the end of the
+/// content before anything could use them.
+fn remove_unused_state(operations: Vec<Operation>) -> Vec<Operation> {
+    // The unused operators setting each parameter, for each level of `q`.
+    let mut levels: Vec<HashMap<Parameter, usize>> = vec![HashMap::new()];
+    // How many of the parameters each unused operator sets are still unused.
+    let mut unused: HashMap<usize, usize> = HashMap::new();
+    let mut removed = HashSet::new();
+
+    for (index, operation) in operations.iter().enumerate() {
+        let parameters = parameters(operation);
+        if !parameters.is_empty() {
+            let level = levels.last_mut().expect("there's always a level");
+            for parameter in parameters {
+                if let Some(previous) = level.insert(*parameter, index) {
+                    discard(&mut unused, &mut removed, previous);
+                }
+            }
+            unused.insert(index, parameters.len());
+            continue;
+        }
+
+        match operation {
+            Operation::SaveGraphicsState => {
+                levels.last_mut().expect("there's always a level").clear();
+                levels.push(HashMap::new());
+            }
+            Operation::RestoreGraphicsState if levels.len() > 1 => {
+                for previous in levels.pop().expect("checked").values() {
+                    discard(&mut unused, &mut removed, *previous);
+                }
+            }
+            operation if is_neutral(operation) => {}
+            _ => levels.last_mut().expect("there's always a level").clear(),
+        }
+    }
+    for level in &levels {
+        for previous in level.values() {
+            discard(&mut unused, &mut removed, *previous);
+        }
+    }
+
+    operations
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !removed.contains(index))
+        .map(|(_, operation)| operation)
+        .collect()
+}
+
+/// Counts one of the parameters an operator sets as replaced, removing the operator when all of
+/// them are.
+fn discard(unused: &mut HashMap<usize, usize>, removed: &mut HashSet<usize>, index: usize) {
+    if let Some(count) = unused.get_mut(&index) {
+        *count -= 1;
+        if *count == 0 {
+            removed.insert(index);
+        }
+    }
+}
+
+/// Drops `BT` and `ET` pairs that don't show text, along with their text positioning operators.
+/// The other operators between them are kept, since text state parameters outlast text objects.
+/// Text clipping modes don't clip without glyphs.
+fn remove_empty_text_objects(operations: Vec<Operation>) -> Vec<Operation> {
+    let mut out = Vec::with_capacity(operations.len());
+    let mut text_object: Option<Vec<Operation>> = None;
+
+    for operation in operations {
+        let mut operations = match text_object.take() {
+            Some(operations) => operations,
+            None if operation == Operation::BeginTextObject => {
+                text_object = Some(vec![operation]);
+                continue;
+            }
+            None => {
+                out.push(operation);
+                continue;
+            }
+        };
+
+        if shows_text(&operation) {
+            out.append(&mut operations);
+            out.push(operation);
+        } else if operation == Operation::EndTextObject {
+            out.extend(
+                operations
+                    .into_iter()
+                    .filter_map(|operation| match operation {
+                        Operation::BeginTextObject
+                        | Operation::MoveTextPosition { .. }
+                        | Operation::SetTextMatrixAndTextLineMatrix(..)
+                        | Operation::MoveToStartOfNextTextLine => None,
+                        // `TD` also sets the leading.
+                        Operation::MoveTextPositionAndSetLeading { y, .. } => {
+                            Some(Operation::SetTextLeading(-y))
+                        }
+                        operation => Some(operation),
+                    }),
+            );
+        } else {
+            operations.push(operation);
+            text_object = Some(operations);
+        }
+    }
+    out.extend(text_object.into_iter().flatten());
+
+    out
+}
+
+/// Drops `q Q` pairs with nothing but state operators between them, which the `Q` undoes.
+fn remove_empty_saves(operations: Vec<Operation>) -> Vec<Operation> {
+    let mut out: Vec<Operation> = Vec::with_capacity(operations.len());
+    // Where each unmatched `q` is in `out`, and whether anything but state was set since.
+    let mut saves: Vec<(usize, bool)> = Vec::new();
+
+    for operation in operations {
+        match operation {
+            Operation::SaveGraphicsState => {
+                saves.push((out.len(), false));
+                out.push(operation);
+            }
+            Operation::RestoreGraphicsState => match saves.pop() {
+                Some((start, false)) => out.truncate(start),
+                Some((_, true)) => {
+                    if let Some(outer) = saves.last_mut() {
+                        outer.1 = true;
+                    }
+                    out.push(operation);
+                }
+                None => out.push(operation),
+            },
+            operation => {
+                let state = !parameters(&operation).is_empty()
+                    || matches!(
+                        operation,
+                        Operation::ConcatenateMatrixToCurrentTransformationMatrix(..)
+                            | Operation::SetParametersFromGraphicsStateParameterDictionary(_)
+                    );
+                if !state {
+                    if let Some(save) = saves.last_mut() {
+                        save.1 = true;
+                    }
+                }
+                out.push(operation);
+            }
+        }
+    }
+
+    out
+}
+
+/// Replaces `Tm` by `Td` where translating the text line matrix gives exactly the same matrix.
+fn shorten_text_matrices(operations: Vec<Operation>) -> Vec<Operation> {
+    let mut text = TextState::default();
+    // Whether the text line matrix is known, which it is from the start of a text object.
+    let mut known = false;
+
+    operations
+        .into_iter()
+        .map(|operation| {
+            let operation = match operation {
+                Operation::SetTextMatrixAndTextLineMatrix(a, b, c, d, e, f) if known => {
+                    let matrix = Matrix::new(a, b, c, d, e, f);
+                    match translation(&text.line_matrix, &matrix) {
+                        Some((x, y)) => Operation::MoveTextPosition { x, y },
+                        None => operation,
+                    }
+                }
+                operation => operation,
+            };
+
+            match &operation {
+                Operation::BeginTextObject => known = true,
+                Operation::Unknown { .. } | Operation::Malformed { .. } => known = false,
+                _ => {}
+            }
+            text.update(&operation);
+            operation
+        })
+        .collect()
+}
+
+/// The translation that moves `from` to `to`, if it's exact.
+fn translation(from: &Matrix, to: &Matrix) -> Option<(f32, f32)> {
+    let linear = Matrix::new(from.a, from.b, from.c, from.d, 0.0, 0.0).inverse()?;
+    let (x, y) = linear.transform_vector(to.e - from.e, to.f - from.f);
+
+    if Matrix::translate(x, y) * *from == *to {
+        Some((x, y))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{writer::to_content, Name, UntypedColor};
+
+    #[test]
+    fn removes_redundant_operators() {
+        let operations = vec![
+            Operation::SetLineWidth(2.0),
+            Operation::SetLineWidth(3.0),
+            Operation::SetRGBColorForNonStrokingOperations(1.0, 0.0, 0.0),
+            Operation::SaveGraphicsState,
+            Operation::ConcatenateMatrixToCurrentTransformationMatrix(2.0, 0.0, 0.0, 2.0, 0.0, 0.0),
+            Operation::SetLineWidth(5.0),
+            Operation::RestoreGraphicsState,
+            Operation::ConcatenateMatrixToCurrentTransformationMatrix(
+                1.0, 0.0, 0.0, 1.0, 10.0, 0.0,
+            ),
+            Operation::ConcatenateMatrixToCurrentTransformationMatrix(0.5, 0.0, 0.0, 0.5, 0.0, 0.0),
+            Operation::SetRGBColorForNonStrokingOperations(1.0, 0.0, 0.0),
+            Operation::AppendRectangleToPath {
+                x: 0.0,
+                y: 0.0,
+                width: 10.0,
+                height: 10.0,
+            },
+            Operation::FillPathUsingNonZeroWindingNumberRule,
+            Operation::BeginTextObject,
+            Operation::SetTextFontAndSize {
+                font: "F1",
+                size: 12.0,
+            },
+            Operation::MoveTextPosition { x: 5.0, y: 5.0 },
+            Operation::EndTextObject,
+            Operation::BeginTextObject,
+            Operation::SetTextMatrixAndTextLineMatrix(1.0, 0.0, 0.0, 1.0, 72.0, 700.0),
+            Operation::ShowText("a".into()),
+            Operation::SetTextMatrixAndTextLineMatrix(1.0, 0.0, 0.0, 1.0, 72.0, 688.0),
+            Operation::ShowText("b".into()),
+            Operation::EndTextObject,
+            Operation::BeginTextObject,
+            Operation::SetTextLeading(12.0),
+            Operation::MoveToStartOfNextTextLine,
+            Operation::SetTextLeading(0.0),
+            Operation::ShowText("x".into()),
+            Operation::MoveTextPositionAndSetLeading { x: 0.0, y: -20.0 },
+            Operation::SetTextLeading(0.0),
+            Operation::MoveToStartOfNextTextLine,
+            Operation::ShowText("y".into()),
+            Operation::EndTextObject,
+            Operation::SetColorSpaceForNonStrokingOperations(Name("CS0")),
+            Operation::SetColorForNonStrokingOperations(UntypedColor::DeviceGrayCalGrayOrIndexed(
+                0.5,
+            )),
+            Operation::StrokePath,
+            Operation::SetLineWidth(4.0),
+        ];
+
+        assert_eq!(
+            String::from_utf8(to_content(&optimize(operations))).unwrap(),
+            "3 w\n1 0 0 rg\n.5 0 0 .5 10 0 cm\n0 0 10 10 re\nf\n/F1 12 Tf\n\
+             BT\n72 700 Td\n(a) Tj\n0 -12 Td\n(b) Tj\nET\n\
+             BT\n12 TL\nT*\n0 TL\n(x) Tj\n0 -20 TD\n0 TL\nT*\n(y) Tj\nET\n/CS0 cs\n.5 sc\nS\n"
+        );
+    }
+}
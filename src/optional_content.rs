@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+
+use pdf::{
+    object::{PlainRef, Resolve},
+    primitive::{Dictionary, Primitive},
+};
+
+use crate::{
+    document::{self, get, get_dictionary, get_name, get_resource, get_text, has_type},
+    error::Error,
+    Name, Operation, PropertyList,
+};
+
+/// How deeply visibility expressions can be nested.
+const MAX_DEPTH: usize = 32;
+/// How many groups and nested expressions a visibility expression can have in all, counting
+/// those it refers to more than once each time, so that shared or cyclic references can't make
+/// its evaluation explode.
+const MAX_NODES: usize = 4096;
+
+/// An optional content group, a layer that content can be shown or hidden with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptionalContentGroup {
+    pub reference: PlainRef,
+    /// `/Name`
+    pub name: String,
+    /// `/Intent`, `View` if it isn't given.
+    pub intent: Vec<String>,
+}
+
+/// `/BaseState` of an optional content configuration.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BaseState {
+    On,
+    Off,
+    /// The groups keep their state from the default configuration.
+    Unchanged,
+}
+
+/// An optional content configuration dictionary, the default `/D` or one of `/Configs`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptionalContentConfig {
+    /// `/Name`
+    pub name: Option<String>,
+    pub base_state: BaseState,
+    /// `/ON`, the groups turned on regardless of the base state.
+    pub on: Vec<PlainRef>,
+    /// `/OFF`, the groups turned off regardless of the base state.
+    pub off: Vec<PlainRef>,
+}
+
+/// A document's optional content, `/OCProperties` in the catalog, section 8.11 of ISO 32000-1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptionalContent {
+    /// `/OCGs`, every optional content group in the document.
+    pub groups: Vec<OptionalContentGroup>,
+    /// `/D`
+    pub default: OptionalContentConfig,
+    /// `/Configs`
+    pub configs: Vec<OptionalContentConfig>,
+}
+
+/// Which optional content groups are on, which decides what content is visible.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OptionalContentState {
+    groups: HashMap<PlainRef, bool>,
+}
+
+impl OptionalContent {
+    /// Reads the optional content of a document, `None` if it doesn't have any.
+    pub fn load<R: Resolve>(resolve: &R, catalog: &Dictionary) -> Result<Option<Self>, Error> {
+        let properties = match get_dictionary(resolve, catalog, "OCProperties")? {
+            Some(properties) => properties,
+            None => return Ok(None),
+        };
+
+        let mut groups = Vec::new();
+        for reference in references(resolve, &properties, "OCGs")? {
+            let group = match document::resolve(resolve, &Primitive::Reference(reference))? {
+                Primitive::Dictionary(group) => group,
+                _ => return Err(Error::InvalidEntry("OCGs".into())),
+            };
+            let intent = match get(resolve, &group, "Intent")? {
+                Some(Primitive::Name(intent)) => vec![intent],
+                Some(Primitive::Array(intents)) => intents
+                    .iter()
+                    .filter_map(|intent| match intent {
+                        Primitive::Name(intent) => Some(intent.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => vec!["View".to_string()],
+            };
+
+            groups.push(OptionalContentGroup {
+                reference,
+                name: get_text(resolve, &group, "Name")?.unwrap_or_default(),
+                intent,
+            });
+        }
+
+        let default = match get_dictionary(resolve, &properties, "D")? {
+            Some(config) => read_config(resolve, &config)?,
+            None => return Err(Error::MissingEntry("D".into())),
+        };
+
+        let mut configs = Vec::new();
+        if let Some(Primitive::Array(array)) = get(resolve, &properties, "Configs")? {
+            for config in &array {
+                match document::resolve(resolve, config)? {
+                    Primitive::Dictionary(config) => configs.push(read_config(resolve, &config)?),
+                    _ => return Err(Error::InvalidEntry("Configs".into())),
+                }
+            }
+        }
+
+        Ok(Some(OptionalContent {
+            groups,
+            default,
+            configs,
+        }))
+    }
+
+    /// The first group with the given name.
+    pub fn group(&self, name: &str) -> Option<&OptionalContentGroup> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    /// The state of the groups under the default configuration.
+    pub fn default_state(&self) -> OptionalContentState {
+        let mut state = OptionalContentState {
+            groups: self
+                .groups
+                .iter()
+                .map(|group| (group.reference, true))
+                .collect(),
+        };
+        state.apply(&self.default);
+        state
+    }
+
+    /// The state of the groups under the configuration with the given name, which starts from
+    /// the default configuration.
+    pub fn state(&self, name: &str) -> Option<OptionalContentState> {
+        let config = self
+            .configs
+            .iter()
+            .find(|config| config.name.as_deref() == Some(name))?;
+
+        let mut state = self.default_state();
+        state.apply(config);
+        Some(state)
+    }
+}
+
+impl OptionalContentState {
+    fn apply(&mut self, config: &OptionalContentConfig) {
+        let base = match config.base_state {
+            BaseState::On => Some(true),
+            BaseState::Off => Some(false),
+            BaseState::Unchanged => None,
+        };
+        if let Some(base) = base {
+            for on in self.groups.values_mut() {
+                *on = base;
+            }
+        }
+
+        for group in &config.on {
+            self.groups.insert(*group, true);
+        }
+        for group in &config.off {
+            self.groups.insert(*group, false);
+        }
+    }
+
+    /// Whether a group is on. Groups that aren't listed in `/OCGs` are on.
+    pub fn is_on(&self, group: PlainRef) -> bool {
+        self.groups.get(&group).copied().unwrap_or(true)
+    }
+
+    pub fn set(&mut self, group: PlainRef, on: bool) {
+        self.groups.insert(group, on);
+    }
+
+    /// Turns every group off but the given one, to extract a single layer.
+    pub fn only(&mut self, group: PlainRef) {
+        for on in self.groups.values_mut() {
+            *on = false;
+        }
+        self.groups.insert(group, true);
+    }
+
+    /// Whether content is visible under an `/OC` entry or property list, which is an optional
+    /// content group or an optional content membership dictionary.
+    pub fn is_visible<R: Resolve>(&self, resolve: &R, oc: &Primitive) -> Result<bool, Error> {
+        let dictionary = match document::resolve(resolve, oc)? {
+            Primitive::Dictionary(dictionary) => dictionary,
+            Primitive::Null => return Ok(true),
+            _ => return Err(Error::InvalidEntry("OC".into())),
+        };
+
+        if !has_type(&dictionary, "OCMD") {
+            // Groups are told apart by their reference, a direct one can't be in `/OCGs`.
+            return Ok(match oc {
+                Primitive::Reference(group) => self.is_on(*group),
+                _ => true,
+            });
+        }
+
+        // A visibility expression takes precedence over the groups and policy.
+        if let Some(expression) = dictionary.get("VE") {
+            return self.expression(resolve, expression, 0, &mut 0);
+        }
+
+        let groups = match dictionary.get("OCGs") {
+            Some(Primitive::Reference(group)) => match resolve.resolve(*group)? {
+                Primitive::Array(groups) => groups,
+                _ => vec![Primitive::Reference(*group)],
+            },
+            Some(Primitive::Array(groups)) => groups.clone(),
+            _ => Vec::new(),
+        };
+        let states: Vec<bool> = groups
+            .iter()
+            .filter_map(|group| match group {
+                Primitive::Reference(group) => Some(self.is_on(*group)),
+                _ => None,
+            })
+            .collect();
+        if states.is_empty() {
+            return Ok(true);
+        }
+
+        Ok(
+            match get_name(resolve, &dictionary, "P")?
+                .as_deref()
+                .unwrap_or("AnyOn")
+            {
+                "AllOn" => states.iter().all(|on| *on),
+                "AnyOn" => states.iter().any(|on| *on),
+                "AnyOff" => states.iter().any(|on| !on),
+                "AllOff" => states.iter().all(|on| !on),
+                _ => return Err(Error::InvalidEntry("P".into())),
+            },
+        )
+    }
+
+    /// Evaluates a `/VE` visibility expression, an array of `/And`, `/Or` or `/Not` followed by
+    /// groups and nested expressions.
+    fn expression<R: Resolve>(
+        &self,
+        resolve: &R,
+        expression: &Primitive,
+        depth: usize,
+        nodes: &mut usize,
+    ) -> Result<bool, Error> {
+        *nodes += 1;
+        if depth >= MAX_DEPTH || *nodes > MAX_NODES {
+            return Err(Error::InvalidEntry("VE".into()));
+        }
+
+        let operands = match expression {
+            Primitive::Reference(group) => match resolve.resolve(*group)? {
+                Primitive::Array(array) => array,
+                _ => return Ok(self.is_on(*group)),
+            },
+            Primitive::Array(array) => array.clone(),
+            _ => return Err(Error::InvalidEntry("VE".into())),
+        };
+
+        let (operator, operands) = match operands.split_first() {
+            Some((Primitive::Name(operator), operands)) => (operator.as_str(), operands),
+            _ => return Err(Error::InvalidEntry("VE".into())),
+        };
+        let mut values = operands
+            .iter()
+            .map(|operand| self.expression(resolve, operand, depth + 1, nodes));
+
+        match operator {
+            "And" => values.try_fold(true, |all, value| Ok(all & value?)),
+            "Or" => values.try_fold(false, |any, value| Ok(any | value?)),
+            "Not" if operands.len() == 1 => Ok(!values.next().unwrap()?),
+            _ => Err(Error::InvalidEntry("VE".into())),
+        }
+    }
+
+    /// Whether each operation of a content stream is visible. Marked content sequences tagged
+    /// `/OC` are hidden along with everything in them, including their `BDC` and `EMC`, when
+    /// their optional content is off, and so are XObjects with an `/OC` entry.
+    pub fn visible_operations<R: Resolve>(
+        &self,
+        resolve: &R,
+        resources: &Dictionary,
+        operations: &[Operation],
+    ) -> Result<Vec<bool>, Error> {
+        let mut visible = Vec::with_capacity(operations.len());
+        let mut depth = 0;
+        // The depth of the outermost hidden sequence.
+        let mut hidden: Option<usize> = None;
+
+        for operation in operations {
+            match operation {
+                Operation::BeginMarkedContentSequence(_) => depth += 1,
+                Operation::BeginMarkedContentSequenceWithPropertyList {
+                    tag: Name(tag),
+                    properties,
+                } => {
+                    depth += 1;
+                    if hidden.is_none()
+                        && *tag == "OC"
+                        && !self
+                            .is_visible(resolve, &property_list(resolve, resources, properties)?)?
+                    {
+                        hidden = Some(depth);
+                    }
+                }
+                Operation::EndMarkedContentSequence => {
+                    visible.push(hidden.is_none());
+                    if hidden == Some(depth) {
+                        hidden = None;
+                    }
+                    depth = depth.saturating_sub(1);
+                    continue;
+                }
+                Operation::InvokeNamedXObject(Name(name)) if hidden.is_none() => {
+                    let xobject = match get_resource(resolve, resources, "XObject", name)? {
+                        Primitive::Stream(stream) => stream.info,
+                        _ => return Err(Error::InvalidEntry(format!("XObject /{}", name))),
+                    };
+                    let oc = xobject.get("OC").cloned().unwrap_or(Primitive::Null);
+                    visible.push(self.is_visible(resolve, &oc)?);
+                    continue;
+                }
+                _ => {}
+            }
+
+            visible.push(hidden.is_none());
+        }
+
+        Ok(visible)
+    }
+
+    /// The operations of a content stream that are visible, see `visible_operations`.
+    pub fn filter<'src, R: Resolve>(
+        &self,
+        resolve: &R,
+        resources: &Dictionary,
+        operations: Vec<Operation<'src>>,
+    ) -> Result<Vec<Operation<'src>>, Error> {
+        let visible = self.visible_operations(resolve, resources, &operations)?;
+
+        Ok(operations
+            .into_iter()
+            .zip(visible)
+            .filter_map(|(operation, visible)| if visible { Some(operation) } else { None })
+            .collect())
+    }
+}
+
+/// The property list of an `/OC` sequence, with named ones looked up in the `/Properties`
+/// resources. The entry itself isn't resolved, since groups are told apart by their reference.
+fn property_list<R: Resolve>(
+    resolve: &R,
+    resources: &Dictionary,
+    properties: &PropertyList,
+) -> Result<Primitive, Error> {
+    match properties {
+        PropertyList::Inline(dictionary) => Ok(Primitive::Dictionary((*dictionary).clone())),
+        PropertyList::Named(Name(name)) => get_dictionary(resolve, resources, "Properties")?
+            .and_then(|properties| properties.get(name).cloned())
+            .ok_or_else(|| Error::MissingEntry(format!("Properties /{}", name))),
+    }
+}
+
+/// Reads a configuration dictionary, whose `/BaseState` is `ON` if it isn't given.
+fn read_config<R: Resolve>(
+    resolve: &R,
+    config: &Dictionary,
+) -> Result<OptionalContentConfig, Error> {
+    let base_state = match get_name(resolve, config, "BaseState")?.as_deref() {
+        Some("ON") => BaseState::On,
+        Some("OFF") => BaseState::Off,
+        Some("Unchanged") => BaseState::Unchanged,
+        Some(_) => return Err(Error::InvalidEntry("BaseState".into())),
+        None => BaseState::On,
+    };
+
+    Ok(OptionalContentConfig {
+        name: get_text(resolve, config, "Name")?,
+        base_state,
+        on: references(resolve, config, "ON")?,
+        off: references(resolve, config, "OFF")?,
+    })
+}
+
+/// The references in an array valued `key`, which is how groups are listed.
+fn references<R: Resolve>(
+    resolve: &R,
+    dictionary: &Dictionary,
+    key: &str,
+) -> Result<Vec<PlainRef>, Error> {
+    Ok(match get(resolve, dictionary, key)? {
+        Some(Primitive::Array(array)) => array
+            .iter()
+            .filter_map(|primitive| match primitive {
+                Primitive::Reference(reference) => Some(*reference),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use pdf::primitive::PdfString;
+
+    use super::*;
+    use crate::{
+        fixtures::{dictionary, name, Objects},
+        normalize_operation,
+        parser::ContentStreamParser,
+    };
+
+    fn reference(id: u64) -> Primitive {
+        Primitive::Reference(PlainRef { id, gen: 0 })
+    }
+
+    #[test]
+    fn filters_hidden_layers() {
+        let group = |title: &str| {
+            dictionary(vec![
+                ("Type", name("OCG")),
+                (
+                    "Name",
+                    Primitive::String(PdfString::new(title.as_bytes().to_vec())),
+                ),
+            ])
+        };
+
+        let objects = Objects(
+            vec![
+                (1, group("Walls")),
+                (2, group("Doors")),
+                (
+                    3,
+                    dictionary(vec![
+                        ("Type", name("OCMD")),
+                        (
+                            "VE",
+                            Primitive::Array(vec![
+                                name("And"),
+                                reference(1),
+                                Primitive::Array(vec![name("Not"), reference(2)]),
+                            ]),
+                        ),
+                    ]),
+                ),
+                (
+                    4,
+                    dictionary(vec![
+                        ("W", reference(1)),
+                        ("D", reference(2)),
+                        ("M", reference(3)),
+                    ]),
+                ),
+            ]
+            .into_iter()
+            .map(|(id, object)| (PlainRef { id, gen: 0 }, object))
+            .collect(),
+        );
+
+        let catalog = dictionary(vec![(
+            "OCProperties",
+            dictionary(vec![
+                ("OCGs", Primitive::Array(vec![reference(1), reference(2)])),
+                (
+                    "D",
+                    dictionary(vec![("OFF", Primitive::Array(vec![reference(2)]))]),
+                ),
+                (
+                    "Configs",
+                    Primitive::Array(vec![dictionary(vec![
+                        ("Name", Primitive::String(PdfString::new(b"Doors".to_vec()))),
+                        ("ON", Primitive::Array(vec![reference(2)])),
+                    ])]),
+                ),
+            ]),
+        )]);
+        let catalog = match catalog {
+            Primitive::Dictionary(catalog) => catalog,
+            _ => unreachable!(),
+        };
+        let resources = match dictionary(vec![("Properties", reference(4))]) {
+            Primitive::Dictionary(resources) => resources,
+            _ => unreachable!(),
+        };
+
+        let content = b"/OC /W BDC 0 0 m /OC /D BDC 1 1 m EMC EMC /OC /M BDC 2 2 m EMC 3 3 m";
+        let operations = ContentStreamParser::new(Cursor::new(&content[..]))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let operations: Vec<_> = operations.iter().map(normalize_operation).collect();
+
+        let content = OptionalContent::load(&objects, &catalog).unwrap().unwrap();
+        let walls = content.group("Walls").unwrap().reference;
+        assert_eq!(content.configs[0].base_state, BaseState::On);
+
+        let default = content.default_state();
+        assert_eq!(
+            default
+                .visible_operations(&objects, &resources, &operations)
+                .unwrap(),
+            vec![true, true, false, false, false, true, true, true, true, true]
+        );
+
+        let doors = content.state("Doors").unwrap();
+        assert_eq!(
+            doors
+                .visible_operations(&objects, &resources, &operations)
+                .unwrap(),
+            vec![true, true, true, true, true, true, false, false, false, true]
+        );
+
+        let mut only_walls = doors;
+        only_walls.only(walls);
+        let visible = only_walls.filter(&objects, &resources, operations).unwrap();
+        assert_eq!(visible.len(), 7);
+    }
+
+    #[test]
+    fn rejects_cyclic_visibility_expressions() {
+        let objects = Objects(
+            vec![
+                (
+                    1,
+                    Primitive::Array(vec![name("And"), reference(1), reference(1)]),
+                ),
+                (
+                    2,
+                    dictionary(vec![("Type", name("OCMD")), ("VE", reference(1))]),
+                ),
+            ]
+            .into_iter()
+            .map(|(id, object)| (PlainRef { id, gen: 0 }, object))
+            .collect(),
+        );
+
+        let state = OptionalContentState::default();
+        assert!(state.is_visible(&objects, &reference(2)).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use pdf::object::Resolve;
+
+use crate::{
+    color::{Color, ColorSpace},
+    document::{Location, RawPage},
+    error::Error,
+    graphics_state::GraphicsState,
+    ink::{color_inks, Ink},
+    painting::{for_each_painting, PaintKind, Painting},
+};
+
+/// Tints at least this high count as solid.
+const SOLID: f32 = 0.995;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverprintIssue {
+    /// A white object set to overprint, which disappears on press.
+    WhiteOverprint,
+    /// A solid black object that knocks out the inks beneath it instead of overprinting them,
+    /// which shows gaps around it when the plates are misregistered.
+    BlackKnockout,
+}
+
+/// An object painted by a content stream, with the overprint parameters it's painted with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaintedObject {
+    /// The content stream the object is painted by, such as `page 3 > XObject /Fm1`.
+    pub path: Vec<Location>,
+    /// The index of the painting operation in its content stream.
+    pub index: usize,
+    pub kind: PaintKind,
+    /// The color the object is painted in, `None` for shadings and images.
+    pub color: Option<Color>,
+    /// `OP` for strokes, `op` for everything else.
+    pub overprint: bool,
+    /// `OPM`
+    pub overprint_mode: i32,
+    pub issue: Option<OverprintIssue>,
+}
+
+/// The objects on a page that overprint or have an overprint issue.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OverprintReport {
+    pub objects: Vec<PaintedObject>,
+}
+
+impl OverprintReport {
+    /// Walks the page's content and the Form XObjects it paints, following the graphics state
+    /// that each painting operation is done with.
+    pub fn analyze<R: Resolve>(resolve: &R, page: &RawPage) -> Result<Self, Error> {
+        let mut report = OverprintReport::default();
+        for_each_painting(resolve, page, |painting| {
+            report.record(&painting);
+            Ok(())
+        })?;
+
+        Ok(report)
+    }
+
+    /// The objects with an issue.
+    pub fn issues(&self) -> impl Iterator<Item = &PaintedObject> {
+        self.objects.iter().filter(|object| object.issue.is_some())
+    }
+
+    /// Records a painted object if it overprints or has an issue.
+    fn record(&mut self, painting: &Painting) {
+        let state = painting.state;
+        let (color, overprint) = match painting.kind {
+            PaintKind::Stroke | PaintKind::StrokeText => {
+                (Some(&state.stroke_color), state.stroke_overprint)
+            }
+            PaintKind::Fill | PaintKind::FillText | PaintKind::ImageMask => {
+                (Some(&state.fill_color), state.fill_overprint)
+            }
+            PaintKind::Shading | PaintKind::Image => (None, state.fill_overprint),
+            // The objects in a form are passed on their own.
+            PaintKind::Form => return,
+        };
+
+        let issue = color.and_then(|color| issue(color, overprint, state));
+        if !overprint && issue.is_none() {
+            return;
+        }
+
+        self.objects.push(PaintedObject {
+            path: painting.path.to_vec(),
+            index: painting.index,
+            kind: painting.kind,
+            color: color.cloned(),
+            overprint,
+            overprint_mode: state.overprint_mode,
+            issue,
+        });
+    }
+}
+
+fn issue(color: &Color, overprint: bool, state: &GraphicsState) -> Option<OverprintIssue> {
+    match &color.color_space {
+        ColorSpace::Pattern { .. } => return None,
+        ColorSpace::Separation { name, .. } if name == "None" => return None,
+        _ => {}
+    }
+
+    let inks = color_inks(
+        &color.color_space,
+        &color.components,
+        state.rendering_intent,
+    );
+    match inks.as_slice() {
+        [] if overprint => Some(OverprintIssue::WhiteOverprint),
+        [(Ink::Black, tint)] if !overprint && *tint >= SOLID => Some(OverprintIssue::BlackKnockout),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::{
+        object::NoResolve,
+        primitive::{Dictionary, Primitive},
+    };
+
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn finds_overprint_issues() {
+        let mut ext_g_state = Dictionary::new();
+        ext_g_state.insert("OP".to_string(), Primitive::Boolean(true));
+        ext_g_state.insert("OPM".to_string(), Primitive::Integer(1));
+        let mut ext_g_states = Dictionary::new();
+        ext_g_states.insert("GS0".to_string(), Primitive::Dictionary(ext_g_state));
+        let mut resources = Dictionary::new();
+        resources.insert("ExtGState".to_string(), Primitive::Dictionary(ext_g_states));
+
+        let page = fixtures::page(
+            resources,
+            b"0 g 0 0 10 10 re f q /GS0 gs 1 g 0 0 10 10 re f 0 0 1 0 k 0 0 5 5 re f Q",
+        );
+
+        let report = OverprintReport::analyze(&NoResolve, &page).unwrap();
+        let objects: Vec<_> = report
+            .objects
+            .iter()
+            .map(|object| (object.index, object.overprint, object.issue))
+            .collect();
+
+        assert_eq!(
+            objects,
+            vec![
+                (2, false, Some(OverprintIssue::BlackKnockout)),
+                (7, true, Some(OverprintIssue::WhiteOverprint)),
+                (10, true, None),
+            ]
+        );
+        assert_eq!(report.issues().count(), 2);
+        assert_eq!(report.objects[2].overprint_mode, 1);
+    }
+}
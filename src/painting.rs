@@ -0,0 +1,557 @@
+use std::{cell::Cell, io::Cursor};
+
+use pdf::{
+    content::Operation as PdfOperation,
+    object::Resolve,
+    primitive::{Dictionary, PdfStream, Primitive},
+};
+
+use crate::{
+    document::{
+        get_bool, get_dictionary, get_name, get_numbers, get_resource, recovered_operations,
+        stream_data, Location, RawPage,
+    },
+    error::Error,
+    geometry::Matrix,
+    graphics_state::{GraphicsState, GraphicsStateStack},
+    normalize_operation,
+    parser::ContentStreamParser,
+    pattern::TilingPattern,
+    Name, Operation, TextRenderingMode,
+};
+
+/// How deeply Form XObjects and pattern cells can be nested in each other.
+const MAX_DEPTH: usize = 12;
+
+/// How many bytes of Form XObject and pattern cell content a walk goes through in all. Forms are
+/// walked each time they're painted, so without a limit a few forms painting each other many
+/// times could take time exponential in `MAX_DEPTH`.
+const MAX_FORM_CONTENT: usize = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PaintKind {
+    Fill,
+    Stroke,
+    FillText,
+    StrokeText,
+    /// An area painted by `sh`.
+    Shading,
+    Image,
+    /// A stencil mask image, painted in the nonstroking color.
+    ImageMask,
+    /// A Form XObject, whose own content is walked right after it.
+    Form,
+}
+
+impl PaintKind {
+    /// Whether the object is painted with the stroking color and alpha.
+    pub fn is_stroke(self) -> bool {
+        matches!(self, PaintKind::Stroke | PaintKind::StrokeText)
+    }
+}
+
+/// Something painted by a content stream, and the graphics state it's painted with.
+pub struct Painting<'a> {
+    /// The content stream of the painting operation, such as `page 3 > XObject /Fm1`.
+    pub path: &'a [Location],
+    /// The index of the painting operation in its content stream.
+    pub index: usize,
+    pub kind: PaintKind,
+    pub state: &'a GraphicsState,
+    /// The stream dictionary of the painted image or form, or the entries of an inline image,
+    /// which may use abbreviated keys.
+    pub xobject: Option<&'a Dictionary>,
+}
+
+/// Calls `f` with everything painted by a page's content and the Form XObjects it paints, in
+/// painting order. Operations that fill and stroke a path are passed as a fill and then a
+/// stroke, and text shown in a mode that neither fills nor strokes isn't passed at all. Inline
+/// images are passed at their `EI`.
+pub fn for_each_painting<R, F>(resolve: &R, page: &RawPage, f: F) -> Result<(), Error>
+where
+    R: Resolve,
+    F: FnMut(Painting) -> Result<(), Error>,
+{
+    let operations = page.operations(resolve)?;
+
+    walk_content(
+        resolve,
+        &operations,
+        GraphicsState::default(),
+        &page.resources(resolve)?,
+        vec![Location::Page(page.number)],
+        &mut Painter {
+            resolve,
+            f,
+            inline_image: None,
+        },
+    )
+}
+
+/// An operation of a content stream walked by [`walk_content`].
+pub struct Step<'a> {
+    /// The content stream of the operation, such as `page 3 > XObject /Fm1`.
+    pub path: &'a [Location],
+    /// The index of the operation in its content stream.
+    pub index: usize,
+    pub operation: &'a Operation<'a>,
+    /// The graphics state before the operation.
+    pub state: &'a GraphicsState,
+    /// The resources of the content stream.
+    pub resources: &'a Dictionary,
+    /// The XObject painted by a `Do` operation.
+    pub xobject: Option<&'a PdfStream>,
+    /// How many more bytes of nested content the walk can go through.
+    budget: &'a Cell<usize>,
+}
+
+/// Receives the operations of the content walked by [`walk_content`].
+pub trait ContentVisitor {
+    /// Called with each operation, in content stream order.
+    fn operation(&mut self, step: &Step) -> Result<(), Error>;
+
+    /// Called before the content of the Form XObject painted by `step` is walked, right after
+    /// `operation` is called with it. Forms that are too deeply nested, or past the limit on
+    /// how much content is walked, are skipped without calling this.
+    fn begin_form(&mut self, _step: &Step) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called after the content of the Form XObject painted by `step` has been walked.
+    fn end_form(&mut self, _step: &Step) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Walks a content stream and the Form XObjects it paints, keeping track of the graphics state.
+/// The content of each form is walked right after the `Do` painting it, starting with the
+/// graphics state at that `Do`.
+pub fn walk_content<R, V>(
+    resolve: &R,
+    operations: &[PdfOperation],
+    initial: GraphicsState,
+    resources: &Dictionary,
+    path: Vec<Location>,
+    visitor: &mut V,
+) -> Result<(), Error>
+where
+    R: Resolve,
+    V: ContentVisitor,
+{
+    let budget = Cell::new(MAX_FORM_CONTENT);
+    walk(
+        resolve, operations, initial, resources, path, &budget, visitor,
+    )
+}
+
+/// Walks the content of a tiling pattern's cell painted by the operation of `step`, as the
+/// content of a Form XObject painted there would be: starting with the `initial` graphics state,
+/// under the same nesting limit and from the same budget. Returns `false` without walking the
+/// cell if it's past either of them.
+pub fn walk_pattern_cell<R, V>(
+    resolve: &R,
+    step: &Step,
+    name: &str,
+    pattern: &TilingPattern,
+    initial: GraphicsState,
+    visitor: &mut V,
+) -> Result<bool, Error>
+where
+    R: Resolve,
+    V: ContentVisitor,
+{
+    if step.path.len() > MAX_DEPTH {
+        return Ok(false);
+    }
+    match step.budget.get().checked_sub(pattern.content_length) {
+        Some(remaining) => step.budget.set(remaining),
+        None => return Ok(false),
+    }
+
+    let mut path = step.path.to_vec();
+    path.push(Location::Pattern(name.to_string()));
+    walk(
+        resolve,
+        &pattern.content,
+        initial,
+        &pattern.resources,
+        path,
+        step.budget,
+        visitor,
+    )?;
+
+    Ok(true)
+}
+
+fn walk<R, V>(
+    resolve: &R,
+    operations: &[PdfOperation],
+    initial: GraphicsState,
+    resources: &Dictionary,
+    path: Vec<Location>,
+    budget: &Cell<usize>,
+    visitor: &mut V,
+) -> Result<(), Error>
+where
+    R: Resolve,
+    V: ContentVisitor,
+{
+    let mut stack = GraphicsStateStack::new(initial);
+
+    for (index, operation) in operations.iter().map(normalize_operation).enumerate() {
+        let xobject = match &operation {
+            // XObjects that are missing or aren't streams paint nothing, the walk goes on.
+            Operation::InvokeNamedXObject(Name(name)) => {
+                match get_resource(resolve, resources, "XObject", name) {
+                    Ok(Primitive::Stream(stream)) => Some(stream),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let state = stack.current();
+        let step = Step {
+            path: &path,
+            index,
+            operation: &operation,
+            state,
+            resources,
+            xobject: xobject.as_ref(),
+            budget,
+        };
+        visitor.operation(&step)?;
+
+        if let (Some(stream), Operation::InvokeNamedXObject(Name(name))) = (&xobject, &operation) {
+            let dictionary = &stream.info;
+            if path.len() <= MAX_DEPTH
+                && get_name(resolve, dictionary, "Subtype")?.as_deref() == Some("Form")
+            {
+                // Forms whose content can't be decoded paint nothing either.
+                let walked = stream_data(resolve, stream).ok().and_then(|data| {
+                    let remaining = budget.get().checked_sub(data.len())?;
+                    Some((remaining, data))
+                });
+                if let Some((remaining, data)) = walked {
+                    budget.set(remaining);
+
+                    let matrix = match get_numbers(resolve, dictionary, "Matrix")? {
+                        Some(matrix) => Matrix::from_slice(&matrix)
+                            .ok_or_else(|| Error::InvalidEntry("Matrix".into()))?,
+                        None => Matrix::IDENTITY,
+                    };
+                    let form =
+                        recovered_operations(ContentStreamParser::new(Cursor::new(&data[..])))?;
+                    let form_resources = get_dictionary(resolve, dictionary, "Resources")?
+                        .unwrap_or_else(|| resources.clone());
+
+                    let mut initial = GraphicsState {
+                        ctm: matrix * state.ctm,
+                        ..state.clone()
+                    };
+                    // A transparency group's alpha, blend mode and soft mask apply to the group
+                    // as a whole, its content starts with them at their initial values.
+                    if is_transparency_group(resolve, dictionary)? {
+                        let defaults = GraphicsState::default();
+                        initial.stroke_alpha = defaults.stroke_alpha;
+                        initial.fill_alpha = defaults.fill_alpha;
+                        initial.blend_mode = defaults.blend_mode;
+                        initial.soft_mask = defaults.soft_mask;
+                    }
+
+                    let mut form_path = path.clone();
+                    form_path.push(Location::XObject(name.to_string()));
+
+                    visitor.begin_form(&step)?;
+                    walk(
+                        resolve,
+                        &form,
+                        initial,
+                        &form_resources,
+                        form_path,
+                        budget,
+                        visitor,
+                    )?;
+                    visitor.end_form(&step)?;
+                }
+            }
+        }
+
+        stack.update(&operation, resources, resolve)?;
+    }
+
+    Ok(())
+}
+
+/// Passes what's painted by the content walked to a `for_each_painting` callback.
+struct Painter<'a, R, F> {
+    resolve: &'a R,
+    f: F,
+    /// The entries of the inline image whose data is being read, from its `ID`.
+    inline_image: Option<Dictionary>,
+}
+
+impl<'a, R, F> Painter<'a, R, F>
+where
+    R: Resolve,
+    F: FnMut(Painting) -> Result<(), Error>,
+{
+    fn paint(
+        &mut self,
+        step: &Step,
+        kind: PaintKind,
+        xobject: Option<&Dictionary>,
+    ) -> Result<(), Error> {
+        (self.f)(Painting {
+            path: step.path,
+            index: step.index,
+            kind,
+            state: step.state,
+            xobject,
+        })
+    }
+}
+
+impl<'a, R, F> ContentVisitor for Painter<'a, R, F>
+where
+    R: Resolve,
+    F: FnMut(Painting) -> Result<(), Error>,
+{
+    fn operation(&mut self, step: &Step) -> Result<(), Error> {
+        let text_mode = step.state.text.rendering_mode;
+
+        match step.operation {
+            Operation::FillPathUsingNonZeroWindingNumberRule
+            | Operation::ObsoleteFillPathUsingNonZeroWindingMumberRule
+            | Operation::FillPathUsingEvenOddRule => self.paint(step, PaintKind::Fill, None)?,
+            Operation::StrokePath | Operation::CloseAndStrokePath => {
+                self.paint(step, PaintKind::Stroke, None)?
+            }
+            Operation::FillAndStrokePathUsingNonZeroWindingNumber
+            | Operation::FillAndStrokePathUsingEvenOddRule
+            | Operation::CloseFillAndStrokePathUsingNonZeroWindingNumber
+            | Operation::CloseFillAndStrokePathUsingEvenOddRule => {
+                self.paint(step, PaintKind::Fill, None)?;
+                self.paint(step, PaintKind::Stroke, None)?;
+            }
+            Operation::ShowText(_)
+            | Operation::ShowTextAllowingIndividualGlyphPositioning(_)
+            | Operation::MoveToNextLineAndShowText(_)
+            | Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText { .. } => {
+                if matches!(
+                    text_mode,
+                    TextRenderingMode::FillText
+                        | TextRenderingMode::FillThenStrokeText
+                        | TextRenderingMode::FillTextAndAddToPathForClipping
+                        | TextRenderingMode::FillThenStrokeTextAndAddToPathForClipping
+                ) {
+                    self.paint(step, PaintKind::FillText, None)?;
+                }
+                if matches!(
+                    text_mode,
+                    TextRenderingMode::StrokeText
+                        | TextRenderingMode::FillThenStrokeText
+                        | TextRenderingMode::StrokeTextAndAddToPathForClipping
+                        | TextRenderingMode::FillThenStrokeTextAndAddToPathForClipping
+                ) {
+                    self.paint(step, PaintKind::StrokeText, None)?;
+                }
+            }
+            Operation::PaintAreaDefinedByShadingPattern(_) => {
+                self.paint(step, PaintKind::Shading, None)?
+            }
+            Operation::InvokeNamedXObject(_) => {
+                let dictionary = match step.xobject {
+                    Some(stream) => &stream.info,
+                    None => return Ok(()),
+                };
+                if get_name(self.resolve, dictionary, "Subtype")?.as_deref() == Some("Image") {
+                    if get_bool(self.resolve, dictionary, "ImageMask")? == Some(true) {
+                        self.paint(step, PaintKind::ImageMask, Some(dictionary))?;
+                    } else {
+                        self.paint(step, PaintKind::Image, Some(dictionary))?;
+                    }
+                }
+            }
+            Operation::BeginInlineImageData(entries) => {
+                let mut dictionary = Dictionary::new();
+                for entry in entries.chunks(2) {
+                    if let [Primitive::Name(key), value] = entry {
+                        dictionary.insert(key.clone(), value.clone());
+                    }
+                }
+                self.inline_image = Some(dictionary);
+            }
+            Operation::EndInlineImageObject(_) => {
+                let dictionary = self.inline_image.take().unwrap_or_else(Dictionary::new);
+                let mask = match get_bool(self.resolve, &dictionary, "IM")? {
+                    Some(mask) => Some(mask),
+                    None => get_bool(self.resolve, &dictionary, "ImageMask")?,
+                };
+                let kind = if mask == Some(true) {
+                    PaintKind::ImageMask
+                } else {
+                    PaintKind::Image
+                };
+                self.paint(step, kind, Some(&dictionary))?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn begin_form(&mut self, step: &Step) -> Result<(), Error> {
+        self.paint(
+            step,
+            PaintKind::Form,
+            step.xobject.map(|stream| &stream.info),
+        )
+    }
+}
+
+/// Whether a Form XObject is a transparency group, with a `/Group` whose `/S` is `Transparency`.
+pub fn is_transparency_group<R: Resolve>(resolve: &R, form: &Dictionary) -> Result<bool, Error> {
+    Ok(match get_dictionary(resolve, form, "Group")? {
+        Some(group) => get_name(resolve, &group, "S")?.as_deref() == Some("Transparency"),
+        None => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::object::NoResolve;
+
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn limits_the_form_content_walked() {
+        // A form painting itself twice, padded so that a few walks of it use up the limit.
+        let mut data = b"/Fm0 Do /Fm0 Do 0 0 1 1 re f".to_vec();
+        data.resize(MAX_FORM_CONTENT / 8, b' ');
+        let mut form = Dictionary::new();
+        form.insert("Subtype".to_string(), Primitive::Name("Form".to_string()));
+        let mut xobjects = Dictionary::new();
+        xobjects.insert(
+            "Fm0".to_string(),
+            Primitive::Stream(PdfStream { info: form, data }),
+        );
+        let mut resources = Dictionary::new();
+        resources.insert("XObject".to_string(), Primitive::Dictionary(xobjects));
+        let page = fixtures::page(resources, b"/Fm0 Do");
+
+        let mut kinds = Vec::new();
+        for_each_painting(&NoResolve, &page, |painting| {
+            kinds.push((painting.path.len(), painting.kind));
+            Ok(())
+        })
+        .unwrap();
+
+        // The forms are walked depth first, until eight of them have used up the limit.
+        let depths: Vec<_> = kinds
+            .iter()
+            .filter(|(_, kind)| *kind == PaintKind::Form)
+            .map(|(depth, _)| *depth)
+            .collect();
+        assert_eq!(depths, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(kinds.len(), 16);
+    }
+
+    #[test]
+    fn keeps_the_text_rendering_mode_in_the_graphics_state() {
+        let mut form = Dictionary::new();
+        form.insert("Subtype".to_string(), Primitive::Name("Form".to_string()));
+        let mut xobjects = Dictionary::new();
+        xobjects.insert(
+            "Fm0".to_string(),
+            Primitive::Stream(PdfStream {
+                info: form,
+                data: b"BT (In form) Tj ET".to_vec(),
+            }),
+        );
+        let mut resources = fixtures::courier_resources();
+        resources.insert("XObject".to_string(), Primitive::Dictionary(xobjects));
+        // Invisible text is restored to filled by `Q`, and forms inherit the mode.
+        let page = fixtures::page(
+            resources,
+            b"BT /F1 12 Tf q 3 Tr Q (Filled) Tj 1 Tr ET /Fm0 Do 3 Tr /Fm0 Do",
+        );
+
+        let mut kinds = Vec::new();
+        for_each_painting(&NoResolve, &page, |painting| {
+            kinds.push((painting.index, painting.kind));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            kinds,
+            [
+                (5, PaintKind::FillText),
+                (8, PaintKind::Form),
+                (1, PaintKind::StrokeText),
+                (10, PaintKind::Form),
+            ]
+        );
+    }
+
+    #[test]
+    fn paints_inline_images() {
+        let page = fixtures::page(
+            Dictionary::new(),
+            b"BI /W 1 /H 1 /IM true ID \x00 EI BI /W 1 /H 1 /CS /G /BPC 8 ID \x80 EI",
+        );
+
+        let mut kinds = Vec::new();
+        for_each_painting(&NoResolve, &page, |painting| {
+            kinds.push((painting.index, painting.kind, painting.xobject.cloned()));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            kinds
+                .iter()
+                .map(|(index, kind, _)| (*index, *kind))
+                .collect::<Vec<_>>(),
+            [(2, PaintKind::ImageMask), (5, PaintKind::Image)]
+        );
+        assert_eq!(
+            kinds[1].2.as_ref().unwrap().get("CS"),
+            Some(&Primitive::Name("G".to_string()))
+        );
+    }
+
+    #[test]
+    fn walks_past_damaged_content_and_missing_xobjects() {
+        let mut form = Dictionary::new();
+        form.insert("Subtype".to_string(), Primitive::Name("Form".to_string()));
+        let mut xobjects = Dictionary::new();
+        xobjects.insert(
+            "Fm0".to_string(),
+            Primitive::Stream(PdfStream {
+                info: form,
+                data: b") 0 0 m 0 0 1 1 re f".to_vec(),
+            }),
+        );
+        let mut resources = Dictionary::new();
+        resources.insert("XObject".to_string(), Primitive::Dictionary(xobjects));
+        let page = fixtures::page(resources, b"/Fm9 Do ) 0 0 m 0 0 1 1 re f /Fm0 Do");
+
+        let mut kinds = Vec::new();
+        for_each_painting(&NoResolve, &page, |painting| {
+            kinds.push((painting.path.len(), painting.index, painting.kind));
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            kinds,
+            [
+                (1, 2, PaintKind::Fill),
+                (1, 3, PaintKind::Form),
+                (2, 1, PaintKind::Fill),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1165 @@
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    error::Error,
+    fmt,
+    io::{self, Read},
+    mem,
+    ops::Range,
+};
+
+use pdf::{
+    content::Operation as PdfOperation,
+    primitive::{Dictionary, PdfString, Primitive},
+};
+
+use crate::{normalize_operation, Operation};
+
+/// How many bytes the parser reads from its streams at a time, unless told otherwise.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// How many bytes of a token the parser buffers before treating it as a syntax error, so that an
+/// unterminated string or comment can't make the parser read the rest of the stream into memory.
+const MAX_TOKEN_LENGTH: usize = 1024 * 1024;
+
+/// How many bytes an operation's operands, or the data of an inline image, can span before the
+/// parser treats it as a syntax error, so that operands without an operator or an inline image
+/// without an `EI` can't make the parser hold the rest of the stream in memory.
+const MAX_OPERATION_LENGTH: usize = 4 * 1024 * 1024;
+
+/// How deeply arrays and dictionaries can be nested in an operand.
+const MAX_NESTING_DEPTH: usize = 64;
+
+/// Where an operation came from: the index of the content stream it starts in, for pages with
+/// multiple `/Contents` streams, and its byte range in that decoded stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub stream: usize,
+    pub byte_range: Range<usize>,
+}
+
+/// A value, usually an operation, with the span it was parsed from if known.
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Option<Span>,
+}
+
+impl<T> Spanned<T> {
+    /// Wraps a value that wasn't parsed by this crate, such as an operation from
+    /// `pdf::content::Content`.
+    pub fn unspanned(value: T) -> Self {
+        Spanned { value, span: None }
+    }
+}
+
+/// Every operator defined for content streams. The parser uses these to find a plausible place to
+/// resume after a syntax error.
+pub const OPERATORS: &[&str] = &[
+    "b", "B", "b*", "B*", "BDC", "BI", "BMC", "BT", "BX", "c", "cm", "CS", "cs", "d", "d0", "d1",
+    "Do", "DP", "EI", "EMC", "ET", "EX", "f", "F", "f*", "G", "g", "gs", "h", "i", "ID", "j", "J",
+    "K", "k", "l", "m", "M", "MP", "n", "q", "Q", "re", "RG", "rg", "ri", "s", "S", "SC", "sc",
+    "SCN", "scn", "sh", "T*", "Tc", "Td", "TD", "Tf", "Tj", "TJ", "TL", "Tm", "Tr", "Ts", "Tw",
+    "Tz", "v", "w", "W", "W*", "y", "'", "\"",
+];
+
+#[derive(Debug)]
+pub enum ParseError {
+    Io(io::Error),
+    /// A syntax error, at a byte range of the content stream with index `stream`.
+    Syntax {
+        stream: usize,
+        byte_range: Range<usize>,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Io(error) => write!(f, "Unable to read content stream: {}", error),
+            ParseError::Syntax {
+                stream,
+                byte_range,
+                reason,
+            } => write!(
+                f,
+                "Syntax error at bytes {}..{} of stream {}: {}",
+                byte_range.start, byte_range.end, stream, reason
+            ),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Io(error) => Some(error),
+            ParseError::Syntax { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(error: io::Error) -> Self {
+        ParseError::Io(error)
+    }
+}
+
+enum Token {
+    Object(Primitive),
+    Operator(String),
+    ArrayStart,
+    ArrayEnd,
+    DictStart,
+    DictEnd,
+}
+
+enum Lexed {
+    /// A token and its position, relative to the start of the lexed bytes.
+    Token(Token, Range<usize>),
+    Incomplete,
+    End,
+    /// A reason and the position where lexing failed, relative to the start of the lexed bytes.
+    Error(String, usize),
+}
+
+enum Container {
+    Array(Vec<Primitive>),
+    Dict(Vec<Primitive>),
+}
+
+/// Parses a decoded content stream incrementally.
+///
+/// Bytes are pulled from the reader a chunk at a time and only the unconsumed part of the
+/// current chunk, the operands of the operation being built and the data of an inline image
+/// are held in memory. Operations spanning more than a few megabytes, and operands nested too
+/// deeply, are syntax errors.
+///
+/// A page's content may be split over several streams, which are parsed as if they were
+/// concatenated. Byte ranges in errors and [`Span`]s are relative to the start of the stream the
+/// operation begins in.
+///
+/// By default parsing stops at the first syntax error. With [`with_recovery`] the error is
+/// reported and parsing resumes after the next operator, so that the rest of a damaged stream can
+/// still be read.
+///
+/// [`with_recovery`]: ContentStreamParser::with_recovery
+pub struct ContentStreamParser<R> {
+    streams: VecDeque<R>,
+    stream_starts: Vec<usize>,
+    chunk_size: usize,
+    recover: bool,
+    done: bool,
+    buf: Vec<u8>,
+    pos: usize,
+    offset: usize,
+    eof: bool,
+    operation_start: Option<usize>,
+    skip_to_operator: bool,
+    operands: Vec<Primitive>,
+    containers: Vec<Container>,
+    inline_image_length: Option<Option<usize>>,
+}
+
+impl<R: Read> ContentStreamParser<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_chunk_size(reader, DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(reader: R, chunk_size: usize) -> Self {
+        Self::from_streams(vec![reader], chunk_size)
+    }
+
+    /// Parses the streams of a page with multiple `/Contents` streams, in order.
+    pub fn from_streams<I>(streams: I, chunk_size: usize) -> Self
+    where
+        I: IntoIterator<Item = R>,
+    {
+        ContentStreamParser {
+            streams: streams.into_iter().collect(),
+            stream_starts: vec![0],
+            chunk_size: chunk_size.max(1),
+            recover: false,
+            done: false,
+            buf: Vec::new(),
+            pos: 0,
+            offset: 0,
+            eof: false,
+            operation_start: None,
+            skip_to_operator: true,
+            operands: Vec::new(),
+            containers: Vec::new(),
+            inline_image_length: None,
+        }
+    }
+
+    /// Keep parsing after syntax errors, yielding each damaged region as a
+    /// [`ParseError::Syntax`] and resuming after the next plausible operator.
+    pub fn with_recovery(mut self) -> Self {
+        self.recover = true;
+        self
+    }
+
+    /// The offset in the concatenated content streams of the next byte to be parsed.
+    pub fn offset(&self) -> usize {
+        self.offset + self.pos
+    }
+
+    /// Calls `f` with every operation in the stream, normalized as it is parsed.
+    ///
+    /// When recovering from errors, damaged regions are passed to `f` as
+    /// [`Operation::Malformed`].
+    pub fn for_each_operation<F>(&mut self, mut f: F) -> Result<(), ParseError>
+    where
+        F: FnMut(Operation),
+    {
+        self.for_each_spanned_operation(|operation| f(operation.value))
+    }
+
+    /// Like [`for_each_operation`], but also passes where each operation came from.
+    ///
+    /// [`for_each_operation`]: ContentStreamParser::for_each_operation
+    pub fn for_each_spanned_operation<F>(&mut self, mut f: F) -> Result<(), ParseError>
+    where
+        F: FnMut(Spanned<Operation>),
+    {
+        while let Some(operation) = self.next_spanned() {
+            match operation {
+                Ok(Spanned { value, span }) => f(Spanned {
+                    value: normalize_operation(&value),
+                    span,
+                }),
+                Err(ParseError::Syntax {
+                    stream,
+                    byte_range,
+                    reason,
+                }) if self.recover => {
+                    let span = Span {
+                        stream,
+                        byte_range: byte_range.clone(),
+                    };
+                    f(Spanned {
+                        value: Operation::Malformed {
+                            byte_range,
+                            reason: &reason,
+                        },
+                        span: Some(span),
+                    })
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses the next operation along with where it came from.
+    pub fn next_spanned(&mut self) -> Option<Result<Spanned<PdfOperation>, ParseError>> {
+        if self.done {
+            return None;
+        }
+
+        match self.next_operation() {
+            Ok(Some(operation)) => {
+                let start = self.operation_start.take().unwrap_or_else(|| self.offset());
+                let span = self.span(start..self.offset());
+
+                Some(Ok(Spanned {
+                    value: operation,
+                    span: Some(span),
+                }))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(ParseError::Syntax {
+                byte_range, reason, ..
+            }) if self.recover => {
+                let start = byte_range.start;
+                self.operation_start = None;
+
+                match self.resynchronize() {
+                    Ok(end) => {
+                        let Span { stream, byte_range } = self.span(start..end);
+                        Some(Err(ParseError::Syntax {
+                            stream,
+                            byte_range,
+                            reason,
+                        }))
+                    }
+                    Err(error) => {
+                        self.done = true;
+                        Some(Err(error))
+                    }
+                }
+            }
+            Err(ParseError::Syntax {
+                byte_range, reason, ..
+            }) => {
+                self.done = true;
+                let Span { stream, byte_range } = self.span(byte_range);
+                Some(Err(ParseError::Syntax {
+                    stream,
+                    byte_range,
+                    reason,
+                }))
+            }
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+        }
+    }
+
+    /// Maps a range of the concatenated streams to the stream it starts in.
+    fn span(&self, byte_range: Range<usize>) -> Span {
+        let stream = self
+            .stream_starts
+            .iter()
+            .rposition(|start| *start <= byte_range.start)
+            .unwrap_or(0);
+        let start = self.stream_starts[stream];
+
+        Span {
+            stream,
+            byte_range: byte_range.start - start..byte_range.end - start,
+        }
+    }
+
+    fn fill(&mut self) -> Result<bool, ParseError> {
+        if self.eof {
+            return Ok(false);
+        }
+
+        self.buf.drain(..self.pos);
+        self.offset += self.pos;
+        self.pos = 0;
+
+        // Read at least as much as is already buffered, so a token spanning many chunks is lexed
+        // a logarithmic rather than linear number of times.
+        let len = self.buf.len();
+        self.buf.resize(len + self.chunk_size.max(len), 0);
+        let read = loop {
+            let reader = match self.streams.front_mut() {
+                Some(reader) => reader,
+                None => break 0,
+            };
+
+            match reader.read(&mut self.buf[len..]) {
+                Ok(0) => {
+                    self.streams.pop_front();
+                    if !self.streams.is_empty() {
+                        // Streams are separated by white-space when they're concatenated.
+                        self.buf[len] = b'\n';
+                        self.stream_starts.push(self.offset + len + 1);
+                        break 1;
+                    }
+                }
+                Ok(read) => break read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    self.buf.truncate(len);
+                    return Err(error.into());
+                }
+            }
+        };
+        self.buf.truncate(len + read);
+
+        if read == 0 {
+            self.eof = true;
+        }
+
+        Ok(read > 0)
+    }
+
+    /// An error at a range of the concatenated streams, which `next_spanned` maps to the stream it
+    /// starts in.
+    fn syntax_error(&self, reason: impl Into<String>) -> ParseError {
+        let offset = self.offset();
+
+        ParseError::Syntax {
+            stream: 0,
+            byte_range: self.operation_start.unwrap_or(offset)..offset,
+            reason: reason.into(),
+        }
+    }
+
+    fn next_operation(&mut self) -> Result<Option<PdfOperation>, ParseError> {
+        loop {
+            if let Some(length) = self.inline_image_length {
+                match find_inline_image_end(&self.buf[self.pos..], length, self.eof) {
+                    Some((data, consumed)) => {
+                        let data = self.buf[self.pos + data.start..self.pos + data.end].to_vec();
+                        self.operation_start = Some(self.offset());
+                        self.pos += consumed;
+                        self.inline_image_length = None;
+
+                        return Ok(Some(PdfOperation {
+                            operator: "EI".into(),
+                            operands: vec![Primitive::String(PdfString::new(data))],
+                        }));
+                    }
+                    None if self.eof => {
+                        self.operation_start = Some(self.offset());
+                        self.pos = self.buf.len();
+                        return Err(self.syntax_error("unterminated inline image"));
+                    }
+                    None if self.buf.len() - self.pos >= MAX_OPERATION_LENGTH => {
+                        self.operation_start = Some(self.offset());
+                        self.pos = self.buf.len();
+                        return Err(self.syntax_error(format!(
+                            "inline image longer than {} bytes",
+                            MAX_OPERATION_LENGTH
+                        )));
+                    }
+                    None => {
+                        self.fill()?;
+                        continue;
+                    }
+                }
+            }
+
+            // Skip white-space here so that it's never buffered as part of an incomplete token.
+            while self.pos < self.buf.len() && is_whitespace(self.buf[self.pos]) {
+                self.pos += 1;
+            }
+
+            let token = match lex(&self.buf[self.pos..], self.eof) {
+                Lexed::Token(token, range) => {
+                    if self.operands.is_empty() && self.containers.is_empty() {
+                        self.operation_start = Some(self.offset() + range.start);
+                    }
+                    self.pos += range.end;
+                    token
+                }
+                Lexed::Incomplete if self.buf.len() - self.pos >= MAX_TOKEN_LENGTH => {
+                    if self.operation_start.is_none() {
+                        self.operation_start = Some(self.offset());
+                    }
+                    return Err(
+                        self.syntax_error(format!("token longer than {} bytes", MAX_TOKEN_LENGTH))
+                    );
+                }
+                Lexed::Incomplete => {
+                    self.fill()?;
+                    continue;
+                }
+                Lexed::End => {
+                    self.pos = self.buf.len();
+                    if !self.containers.is_empty() {
+                        return Err(self.syntax_error("unterminated array or dictionary"));
+                    }
+                    if !self.operands.is_empty() {
+                        return Err(self.syntax_error("operands without an operator"));
+                    }
+
+                    return Ok(None);
+                }
+                Lexed::Error(reason, at) => {
+                    if self.operation_start.is_none() {
+                        self.operation_start = Some(self.offset() + at);
+                    }
+                    self.pos += at;
+                    return Err(self.syntax_error(reason));
+                }
+            };
+
+            if let Some(start) = self.operation_start {
+                if self.offset() - start > MAX_OPERATION_LENGTH {
+                    return Err(self.syntax_error(format!(
+                        "operation longer than {} bytes",
+                        MAX_OPERATION_LENGTH
+                    )));
+                }
+            }
+
+            match token {
+                Token::Object(object) => self.push(object),
+                Token::ArrayStart | Token::DictStart
+                    if self.containers.len() >= MAX_NESTING_DEPTH =>
+                {
+                    return Err(self.syntax_error(format!(
+                        "arrays or dictionaries nested more than {} deep",
+                        MAX_NESTING_DEPTH
+                    )));
+                }
+                Token::ArrayStart => self.containers.push(Container::Array(Vec::new())),
+                Token::DictStart => self.containers.push(Container::Dict(Vec::new())),
+                Token::ArrayEnd => match self.containers.pop() {
+                    Some(Container::Array(items)) => self.push(Primitive::Array(items)),
+                    _ => return Err(self.syntax_error("unexpected ]")),
+                },
+                Token::DictEnd => match self.containers.pop() {
+                    Some(Container::Dict(items)) => {
+                        let dictionary = to_dictionary(items)
+                            .ok_or_else(|| self.syntax_error("malformed dictionary"))?;
+                        self.push(Primitive::Dictionary(dictionary));
+                    }
+                    _ => return Err(self.syntax_error("unexpected >>")),
+                },
+                Token::Operator(operator) => {
+                    if !self.containers.is_empty() {
+                        // The operator ends the damaged operation, so there's nothing to skip.
+                        self.skip_to_operator = false;
+                        return Err(self.syntax_error(format!(
+                            "operator {} inside an array or dictionary",
+                            operator
+                        )));
+                    }
+
+                    let operands = mem::take(&mut self.operands);
+                    if operator == "ID" {
+                        self.inline_image_length = Some(inline_image_length(&operands));
+                    }
+
+                    return Ok(Some(PdfOperation { operator, operands }));
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, object: Primitive) {
+        match self.containers.last_mut() {
+            Some(Container::Array(items)) | Some(Container::Dict(items)) => items.push(object),
+            None => self.operands.push(object),
+        }
+    }
+
+    /// Discards the operation being built and skips ahead to just after the next known operator,
+    /// returning the offset parsing resumes from.
+    fn resynchronize(&mut self) -> Result<usize, ParseError> {
+        self.operands.clear();
+        self.containers.clear();
+        self.inline_image_length = None;
+
+        if !self.skip_to_operator {
+            self.skip_to_operator = true;
+            return Ok(self.offset());
+        }
+
+        let longest_operator = OPERATORS.iter().map(|operator| operator.len()).max();
+
+        // Always skip at least one byte so that the same error can't be hit again.
+        let mut at_boundary = false;
+        loop {
+            if self.pos >= self.buf.len() && !self.fill()? {
+                return Ok(self.offset());
+            }
+
+            if at_boundary {
+                let len = loop {
+                    let rest = &self.buf[self.pos..];
+                    let len = rest.iter().take_while(|c| is_regular(**c)).count();
+                    if len < rest.len() || self.eof || Some(len) > longest_operator {
+                        break len;
+                    }
+                    self.fill()?;
+                };
+
+                let token = &self.buf[self.pos..self.pos + len];
+                if len > 0
+                    && OPERATORS
+                        .iter()
+                        .any(|operator| operator.as_bytes() == token)
+                {
+                    self.pos += len;
+                    return Ok(self.offset());
+                }
+            }
+
+            // Operators follow white-space or the end of an operand, as in `]TJ` or `)Tj`, but not
+            // the start of a name.
+            let c = self.buf[self.pos];
+            at_boundary = is_whitespace(c) || (is_delimiter(c) && c != b'/');
+            self.pos += 1;
+        }
+    }
+}
+
+impl<R: Read> Iterator for ContentStreamParser<R> {
+    type Item = Result<PdfOperation, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_spanned()
+            .map(|operation| operation.map(|operation| operation.value))
+    }
+}
+
+fn to_dictionary(items: Vec<Primitive>) -> Option<Dictionary> {
+    let mut dictionary = Dictionary::new();
+    let mut items = items.into_iter();
+    while let Some(key) = items.next() {
+        let value = items.next()?;
+        if let Primitive::Name(key) = key {
+            dictionary.insert(key, value);
+        } else {
+            return None;
+        }
+    }
+
+    Some(dictionary)
+}
+
+/// The `/L` or `/Length` of an inline image. A negative length is ignored, so the data is taken
+/// to end at `EI` as if it had none.
+fn inline_image_length(operands: &[Primitive]) -> Option<usize> {
+    operands.chunks(2).find_map(|pair| match pair {
+        [Primitive::Name(key), Primitive::Integer(length)] if key == "L" || key == "Length" => {
+            usize::try_from(*length).ok()
+        }
+        _ => None,
+    })
+}
+
+/// Finds the end of inline image data that starts right after the `ID` operator, returning the
+/// range of the data and the number of bytes up to and including `EI`.
+fn find_inline_image_end(
+    buf: &[u8],
+    length: Option<usize>,
+    eof: bool,
+) -> Option<(Range<usize>, usize)> {
+    // A single white-space character separates `ID` from the data.
+    let start: usize = match buf.first() {
+        Some(c) if is_whitespace(*c) => 1,
+        Some(_) => 0,
+        None => return None,
+    };
+
+    if let Some(length) = length {
+        let end = start.saturating_add(length);
+        let rest = &buf[end.min(buf.len())..];
+        let skipped = rest.iter().take_while(|c| is_whitespace(**c)).count();
+        if rest[skipped..].starts_with(b"EI") {
+            return Some((start..end, end + skipped + 2));
+        }
+        if rest.len() < skipped + 2 && !eof {
+            return None;
+        }
+    }
+
+    let mut i = start;
+    while i + 2 <= buf.len() {
+        if buf[i..].starts_with(b"EI")
+            && i > start
+            && is_whitespace(buf[i - 1])
+            && match buf.get(i + 2) {
+                Some(c) => is_whitespace(*c) || is_delimiter(*c),
+                None => eof,
+            }
+        {
+            return Some((start..i - 1, i + 2));
+        }
+        i += 1;
+    }
+
+    None
+}
+
+pub(crate) fn is_whitespace(c: u8) -> bool {
+    matches!(c, b'\0' | b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+pub(crate) fn is_delimiter(c: u8) -> bool {
+    matches!(
+        c,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+fn is_regular(c: u8) -> bool {
+    !is_whitespace(c) && !is_delimiter(c)
+}
+
+fn lex(buf: &[u8], eof: bool) -> Lexed {
+    let mut i = 0;
+
+    loop {
+        while i < buf.len() && is_whitespace(buf[i]) {
+            i += 1;
+        }
+
+        if i < buf.len() && buf[i] == b'%' {
+            match buf[i..].iter().position(|c| *c == b'\r' || *c == b'\n') {
+                Some(end) => i += end,
+                None if eof => i = buf.len(),
+                None => return Lexed::Incomplete,
+            }
+        } else {
+            break;
+        }
+    }
+
+    let rest = &buf[i..];
+    let first = match rest.first() {
+        Some(c) => *c,
+        None if eof => return Lexed::End,
+        None => return Lexed::Incomplete,
+    };
+
+    let lexed = match first {
+        b'[' => Lexed::Token(Token::ArrayStart, 0..1),
+        b']' => Lexed::Token(Token::ArrayEnd, 0..1),
+        b'<' => match rest.get(1) {
+            Some(b'<') => Lexed::Token(Token::DictStart, 0..2),
+            Some(_) => lex_hex_string(rest, eof),
+            None if eof => Lexed::Error("unterminated hex string".into(), 0),
+            None => Lexed::Incomplete,
+        },
+        b'>' => match rest.get(1) {
+            Some(b'>') => Lexed::Token(Token::DictEnd, 0..2),
+            Some(_) => Lexed::Error("unexpected >".into(), 0),
+            None if eof => Lexed::Error("unexpected >".into(), 0),
+            None => Lexed::Incomplete,
+        },
+        b'(' => lex_literal_string(rest, eof),
+        b')' => Lexed::Error("unbalanced )".into(), 0),
+        b'{' | b'}' => Lexed::Error(format!("unexpected {}", first as char), 0),
+        b'/' => {
+            let len = rest[1..].iter().take_while(|c| is_regular(**c)).count() + 1;
+            if len == rest.len() && !eof {
+                Lexed::Incomplete
+            } else {
+                match decode_name(&rest[1..len]) {
+                    Some(name) => Lexed::Token(Token::Object(Primitive::Name(name)), 0..len),
+                    None => Lexed::Error("invalid name".into(), 0),
+                }
+            }
+        }
+        _ => {
+            let len = rest.iter().take_while(|c| is_regular(**c)).count();
+            if len == rest.len() && !eof {
+                Lexed::Incomplete
+            } else {
+                match regular_token(&rest[..len]) {
+                    Some(token) => Lexed::Token(token, 0..len),
+                    None => Lexed::Error("unexpected binary data".into(), 0),
+                }
+            }
+        }
+    };
+
+    match lexed {
+        Lexed::Token(token, range) => Lexed::Token(token, i + range.start..i + range.end),
+        Lexed::Error(reason, at) => Lexed::Error(reason, i + at),
+        lexed => lexed,
+    }
+}
+
+fn regular_token(bytes: &[u8]) -> Option<Token> {
+    // Operators are always printable ASCII, anything else is stray binary data.
+    if !bytes.iter().all(|c| c.is_ascii_graphic()) {
+        return None;
+    }
+    let text = String::from_utf8_lossy(bytes);
+
+    let token = match &*text {
+        "true" => Token::Object(Primitive::Boolean(true)),
+        "false" => Token::Object(Primitive::Boolean(false)),
+        "null" => Token::Object(Primitive::Null),
+        _ => parse_number(&text)
+            .map(Token::Object)
+            .unwrap_or_else(|| Token::Operator(text.into_owned())),
+    };
+
+    Some(token)
+}
+
+fn parse_number(text: &str) -> Option<Primitive> {
+    let digits = text.trim_start_matches(&['+', '-'][..]);
+    if digits.is_empty()
+        || text.len() - digits.len() > 1
+        || !digits.bytes().all(|c| c.is_ascii_digit() || c == b'.')
+        || digits.bytes().filter(|c| *c == b'.').count() > 1
+        || digits == "."
+    {
+        return None;
+    }
+
+    if !digits.contains('.') {
+        if let Ok(integer) = text.trim_start_matches('+').parse::<i32>() {
+            return Some(Primitive::Integer(integer));
+        }
+    }
+
+    let number = if digits.ends_with('.') {
+        text.trim_end_matches('.').parse::<f32>()
+    } else {
+        text.parse::<f32>()
+    };
+
+    number.ok().map(Primitive::Number)
+}
+
+fn decode_name(bytes: &[u8]) -> Option<String> {
+    let mut name = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'#' {
+            let hex = bytes.get(i + 1..i + 3)?;
+            name.push(hex_value(hex[0])? << 4 | hex_value(hex[1])?);
+            i += 3;
+        } else {
+            name.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    Some(String::from_utf8_lossy(&name).into_owned())
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+fn lex_hex_string(rest: &[u8], eof: bool) -> Lexed {
+    let end = match rest.iter().position(|c| *c == b'>') {
+        Some(end) => end,
+        None if eof => return Lexed::Error("unterminated hex string".into(), 0),
+        None => return Lexed::Incomplete,
+    };
+
+    let mut digits = Vec::with_capacity(end);
+    for c in &rest[1..end] {
+        if is_whitespace(*c) {
+            continue;
+        }
+        match hex_value(*c) {
+            Some(digit) => digits.push(digit),
+            None => return Lexed::Error("invalid hex string".into(), 0),
+        }
+    }
+    if digits.len() % 2 != 0 {
+        digits.push(0);
+    }
+
+    let data = digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect();
+    Lexed::Token(
+        Token::Object(Primitive::String(PdfString::new(data))),
+        0..end + 1,
+    )
+}
+
+fn lex_literal_string(rest: &[u8], eof: bool) -> Lexed {
+    let mut data = Vec::new();
+    let mut depth = 1;
+    let mut i = 1;
+
+    while i < rest.len() {
+        match rest[i] {
+            b'(' => {
+                depth += 1;
+                data.push(b'(');
+            }
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Lexed::Token(
+                        Token::Object(Primitive::String(PdfString::new(data))),
+                        0..i + 1,
+                    );
+                }
+                data.push(b')');
+            }
+            b'\r' => {
+                data.push(b'\n');
+                if rest.get(i + 1) == Some(&b'\n') {
+                    i += 1;
+                } else if i + 1 == rest.len() && !eof {
+                    return Lexed::Incomplete;
+                }
+            }
+            b'\\' => {
+                i += 1;
+                // Escapes are at most four bytes long, wait for all of them.
+                if rest.len() < i + 3 && !eof {
+                    return Lexed::Incomplete;
+                }
+                match rest.get(i) {
+                    Some(b'n') => data.push(b'\n'),
+                    Some(b'r') => data.push(b'\r'),
+                    Some(b't') => data.push(b'\t'),
+                    Some(b'b') => data.push(b'\x08'),
+                    Some(b'f') => data.push(b'\x0c'),
+                    Some(b'\r') => {
+                        if rest.get(i + 1) == Some(&b'\n') {
+                            i += 1;
+                        }
+                    }
+                    Some(b'\n') => {}
+                    Some(c @ b'0'..=b'7') => {
+                        let mut value = u32::from(c - b'0');
+                        for _ in 0..2 {
+                            match rest.get(i + 1) {
+                                Some(c @ b'0'..=b'7') => {
+                                    value = value * 8 + u32::from(c - b'0');
+                                    i += 1;
+                                }
+                                _ => break,
+                            }
+                        }
+                        data.push(value as u8);
+                    }
+                    Some(c) => data.push(*c),
+                    None => break,
+                }
+            }
+            c => data.push(c),
+        }
+        i += 1;
+    }
+
+    if eof {
+        Lexed::Error("unterminated string".into(), 0)
+    } else {
+        Lexed::Incomplete
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &[u8], chunk_size: usize) -> Vec<PdfOperation> {
+        ContentStreamParser::with_chunk_size(content, chunk_size)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn tokens_spanning_chunks() {
+        let content = b"q 1 0 0 1 72.5 -10 cm /F1 12 Tf (Hello \\(world\\)) Tj [(A) -120 (B)] TJ Q";
+
+        for chunk_size in &[1, 2, 3, 7, 1024] {
+            let operations = parse(content, *chunk_size);
+            let operators = operations
+                .iter()
+                .map(|operation| operation.operator.as_str())
+                .collect::<Vec<_>>();
+
+            assert_eq!(operators, ["q", "cm", "Tf", "Tj", "TJ", "Q"]);
+            assert_eq!(operations[1].operands[4], Primitive::Number(72.5));
+            assert_eq!(
+                operations[3].operands[0],
+                Primitive::String(PdfString::new(b"Hello (world)".to_vec()))
+            );
+        }
+    }
+
+    #[test]
+    fn inline_image_data() {
+        let content = b"BI /W 2 /H 1 /BPC 8 /CS /G ID \x00EI\xff EI Q";
+
+        for chunk_size in &[1, 4, 1024] {
+            let operations = parse(content, *chunk_size);
+            let operators = operations
+                .iter()
+                .map(|operation| operation.operator.as_str())
+                .collect::<Vec<_>>();
+
+            assert_eq!(operators, ["BI", "ID", "EI", "Q"]);
+            assert_eq!(operations[1].operands.len(), 8);
+            assert_eq!(
+                operations[2].operands[0],
+                Primitive::String(PdfString::new(b"\x00EI\xff".to_vec()))
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_negative_inline_image_lengths() {
+        let operations = parse(b"BI /L -1 ID \x00 EI Q", 1024);
+
+        assert_eq!(operations.len(), 4);
+        assert_eq!(
+            operations[2].operands[0],
+            Primitive::String(PdfString::new(b"\x00".to_vec()))
+        );
+    }
+
+    #[test]
+    fn recovers_from_malformed_tokens() {
+        let content = b"1 0 0 RG [(a) (b) Tj ) 0 0 m 5 5 l \xff\xfe 10 10 l S (unterminated";
+
+        let mut operations = Vec::new();
+        ContentStreamParser::with_chunk_size(&content[..], 3)
+            .with_recovery()
+            .for_each_operation(|operation| {
+                operations.push(match operation {
+                    Operation::Malformed { byte_range, .. } => Some(byte_range),
+                    _ => None,
+                })
+            })
+            .unwrap();
+
+        assert_eq!(
+            operations,
+            [
+                None,
+                Some(9..20),
+                Some(21..28),
+                None,
+                Some(35..45),
+                None,
+                Some(48..61)
+            ]
+        );
+    }
+
+    #[test]
+    fn bounds_malformed_tokens() {
+        let mut content = b"q \xff[(a) -20 (x)]TJ (c) Tj (".to_vec();
+        content.extend(vec![b'd'; 2 * MAX_TOKEN_LENGTH]);
+        content.extend_from_slice(b") Tj Q");
+
+        let mut operations = Vec::new();
+        ContentStreamParser::with_chunk_size(&content[..], 7)
+            .with_recovery()
+            .for_each_operation(|operation| {
+                operations.push(match operation {
+                    Operation::Malformed { byte_range, .. } => Err(byte_range),
+                    Operation::ShowText(text) => Ok(text.into_owned()),
+                    _ => Ok(String::new()),
+                })
+            })
+            .unwrap();
+
+        let end = content.len() - 2;
+        assert_eq!(
+            operations,
+            [
+                Ok(String::new()),
+                Err(2..18),
+                Ok("c".into()),
+                Err(26..end),
+                Ok(String::new())
+            ]
+        );
+    }
+
+    #[test]
+    fn bounds_operations() {
+        let mut content = b"q ".to_vec();
+        content.extend(b"0 ".repeat(MAX_OPERATION_LENGTH / 2 + 1));
+        content.extend_from_slice(b"Tj Q ");
+        content.extend(b"[".repeat(MAX_NESTING_DEPTH + 1));
+        content.extend_from_slice(b" Tj Q BI /W 1 ID ");
+        content.extend(vec![b'x'; 2 * MAX_OPERATION_LENGTH]);
+        content.extend_from_slice(b"\nEI Q");
+
+        let mut operators = Vec::new();
+        ContentStreamParser::with_chunk_size(&content[..], 64 * 1024)
+            .with_recovery()
+            .for_each_operation(|operation| {
+                operators.push(match operation {
+                    Operation::Malformed { .. } => "malformed",
+                    Operation::SaveGraphicsState => "q",
+                    Operation::RestoreGraphicsState => "Q",
+                    Operation::BeginInlineImageObject => "BI",
+                    Operation::BeginInlineImageData(_) => "ID",
+                    _ => "other",
+                })
+            })
+            .unwrap();
+
+        assert_eq!(
+            operators,
+            [
+                "q",
+                "malformed",
+                "Q",
+                "malformed",
+                "Q",
+                "BI",
+                "ID",
+                "malformed",
+                "Q"
+            ]
+        );
+    }
+
+    #[test]
+    fn spans_malformed_tokens_in_later_streams() {
+        let streams: Vec<&[u8]> = vec![b"q 1 0 0 1 0 0 cm", b" 0 g ) 0 0 m Q", b"1 2 ("];
+
+        let mut spans = Vec::new();
+        let mut parser = ContentStreamParser::from_streams(streams.clone(), 4).with_recovery();
+        parser
+            .for_each_spanned_operation(|operation| {
+                if let Operation::Malformed { .. } = operation.value {
+                    spans.push(operation.span.unwrap());
+                }
+            })
+            .unwrap();
+
+        assert_eq!(
+            spans,
+            [
+                Span {
+                    stream: 1,
+                    byte_range: 5..12
+                },
+                Span {
+                    stream: 2,
+                    byte_range: 0..5
+                },
+            ]
+        );
+
+        match ContentStreamParser::from_streams(streams, 4).find_map(Result::err) {
+            Some(ParseError::Syntax {
+                stream, byte_range, ..
+            }) => assert_eq!((stream, byte_range), (1, 5..5)),
+            error => panic!("expected a syntax error, got {:?}", error),
+        }
+    }
+
+    #[test]
+    fn spans_across_streams() {
+        let streams: Vec<&[u8]> = vec![b"q 1 0 0 1 0 0 cm", b"  0 g 0 0 10 10 re f Q"];
+
+        let mut spans = Vec::new();
+        ContentStreamParser::from_streams(streams, 4)
+            .for_each_spanned_operation(|operation| spans.push(operation.span.unwrap()))
+            .unwrap();
+
+        assert_eq!(
+            spans,
+            [
+                Span {
+                    stream: 0,
+                    byte_range: 0..1
+                },
+                Span {
+                    stream: 0,
+                    byte_range: 2..16
+                },
+                Span {
+                    stream: 1,
+                    byte_range: 2..5
+                },
+                Span {
+                    stream: 1,
+                    byte_range: 6..18
+                },
+                Span {
+                    stream: 1,
+                    byte_range: 19..20
+                },
+                Span {
+                    stream: 1,
+                    byte_range: 21..22
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use std::io::Cursor;
+
+use pdf::{
+    content::Operation as PdfOperation,
+    object::Resolve,
+    primitive::{Dictionary, Primitive},
+};
+
+use crate::{
+    document::{
+        get, get_dictionary, get_integer, get_number, get_numbers, get_resource,
+        recovered_operations, stream_data,
+    },
+    error::Error,
+    ext_g_state::ExtGState,
+    geometry::{Matrix, Rect},
+    normalize_operation,
+    parser::ContentStreamParser,
+    shading::Shading,
+    Name, Operation,
+};
+
+/// A pattern dictionary, section 8.7.3 of ISO 32000-1.
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Tiling(TilingPattern),
+    Shading(Box<ShadingPattern>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PaintType {
+    /// The pattern's content specifies its own colors.
+    Colored,
+    /// The pattern's content is painted in the color given with the pattern by `SCN` or `scn`.
+    Uncolored,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TilingType {
+    ConstantSpacing,
+    NoDistortion,
+    ConstantSpacingAndFasterTiling,
+}
+
+#[derive(Clone, Debug)]
+pub struct TilingPattern {
+    pub paint_type: PaintType,
+    pub tiling_type: TilingType,
+    /// The pattern cell, in pattern space.
+    pub bbox: Rect,
+    pub x_step: f32,
+    pub y_step: f32,
+    /// Maps pattern space to the default coordinate space of the pattern's parent content.
+    pub matrix: Matrix,
+    pub resources: Dictionary,
+    /// The operations of the pattern cell's content stream, see `operations` for them typed.
+    pub content: Vec<PdfOperation>,
+    /// The length of the pattern cell's content stream, in bytes.
+    pub content_length: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShadingPattern {
+    pub shading: Shading,
+    /// Maps pattern space to the default coordinate space of the pattern's parent content.
+    pub matrix: Matrix,
+    pub ext_g_state: Option<ExtGState>,
+}
+
+/// The pattern selected as the current color by `SCN` or `scn`.
+#[derive(Clone, Debug)]
+pub struct PatternColor {
+    pub pattern: Pattern,
+    /// For uncolored tiling patterns, the color to paint the pattern with, in the underlying
+    /// color space of the current Pattern color space.
+    pub components: Option<Vec<f32>>,
+}
+
+impl Pattern {
+    /// Looks up a pattern in the `/Pattern` resources.
+    pub fn from_resources<R: Resolve>(
+        resolve: &R,
+        resources: &Dictionary,
+        name: &str,
+    ) -> Result<Self, Error> {
+        Self::from_primitive(resolve, &get_resource(resolve, resources, "Pattern", name)?)
+    }
+
+    /// Parses a tiling pattern stream or shading pattern dictionary.
+    pub fn from_primitive<R: Resolve>(resolve: &R, primitive: &Primitive) -> Result<Self, Error> {
+        match crate::document::resolve(resolve, primitive)? {
+            Primitive::Stream(stream) => {
+                let dictionary = &stream.info;
+                if get_integer(resolve, dictionary, "PatternType")? != Some(1) {
+                    return Err(Error::InvalidEntry("PatternType".into()));
+                }
+
+                let paint_type = match get_integer(resolve, dictionary, "PaintType")? {
+                    Some(1) => PaintType::Colored,
+                    Some(2) => PaintType::Uncolored,
+                    _ => return Err(Error::InvalidEntry("PaintType".into())),
+                };
+                let tiling_type = match get_integer(resolve, dictionary, "TilingType")? {
+                    Some(1) => TilingType::ConstantSpacing,
+                    Some(2) => TilingType::NoDistortion,
+                    Some(3) => TilingType::ConstantSpacingAndFasterTiling,
+                    _ => return Err(Error::InvalidEntry("TilingType".into())),
+                };
+                let bbox = get_numbers(resolve, dictionary, "BBox")?
+                    .and_then(|bbox| Rect::from_slice(&bbox))
+                    .ok_or_else(|| Error::InvalidEntry("BBox".into()))?;
+                let x_step = get_number(resolve, dictionary, "XStep")?
+                    .ok_or_else(|| Error::MissingEntry("XStep".into()))?;
+                let y_step = get_number(resolve, dictionary, "YStep")?
+                    .ok_or_else(|| Error::MissingEntry("YStep".into()))?;
+
+                let data = stream_data(resolve, &stream)?;
+                // Damaged cell content is read as far as it can be, like page content.
+                let content =
+                    recovered_operations(ContentStreamParser::new(Cursor::new(&data[..])))?;
+
+                Ok(Pattern::Tiling(TilingPattern {
+                    paint_type,
+                    tiling_type,
+                    bbox,
+                    x_step,
+                    y_step,
+                    matrix: matrix(resolve, dictionary)?,
+                    resources: get_dictionary(resolve, dictionary, "Resources")?
+                        .unwrap_or_else(Dictionary::new),
+                    content,
+                    content_length: data.len(),
+                }))
+            }
+            Primitive::Dictionary(dictionary) => {
+                if get_integer(resolve, &dictionary, "PatternType")? != Some(2) {
+                    return Err(Error::InvalidEntry("PatternType".into()));
+                }
+
+                let shading = get(resolve, &dictionary, "Shading")?
+                    .ok_or_else(|| Error::MissingEntry("Shading".into()))?;
+                let ext_g_state = match get_dictionary(resolve, &dictionary, "ExtGState")? {
+                    Some(ext_g_state) => Some(ExtGState::from_dictionary(resolve, &ext_g_state)?),
+                    None => None,
+                };
+
+                Ok(Pattern::Shading(Box::new(ShadingPattern {
+                    shading: Shading::from_primitive(resolve, &shading)?,
+                    matrix: matrix(resolve, &dictionary)?,
+                    ext_g_state,
+                })))
+            }
+            _ => Err(Error::InvalidEntry("Pattern".into())),
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix {
+        match self {
+            Pattern::Tiling(pattern) => pattern.matrix,
+            Pattern::Shading(pattern) => pattern.matrix,
+        }
+    }
+}
+
+impl TilingPattern {
+    /// The typed operations of the pattern cell's content.
+    pub fn operations(&self) -> impl Iterator<Item = Operation<'_>> {
+        self.content.iter().map(normalize_operation)
+    }
+}
+
+impl PatternColor {
+    /// Resolves the pattern selected by an `SCN` or `scn` operation with a pattern name, `None`
+    /// for any other operation.
+    pub fn from_operation<R: Resolve>(
+        resolve: &R,
+        resources: &Dictionary,
+        operation: &Operation,
+    ) -> Result<Option<Self>, Error> {
+        let (cs, name) = match operation {
+            Operation::SetColorForStrokingOperationsICCBasedAndSpecialColorSpaces {
+                cs,
+                name: Some(Name(name)),
+            }
+            | Operation::SetColorForNonStrokingOperationsICCBasedAndSpecialColorSpaces {
+                cs,
+                name: Some(Name(name)),
+            } => (cs, name),
+            _ => return Ok(None),
+        };
+
+        let pattern = Pattern::from_resources(resolve, resources, name)?;
+        let components = match &pattern {
+            Pattern::Tiling(TilingPattern {
+                paint_type: PaintType::Uncolored,
+                ..
+            }) => {
+                if cs.is_empty() {
+                    return Err(Error::InvalidEntry(format!(
+                        "Pattern /{} needs color components",
+                        name
+                    )));
+                }
+                Some(cs.clone())
+            }
+            _ => None,
+        };
+
+        Ok(Some(PatternColor {
+            pattern,
+            components,
+        }))
+    }
+}
+
+fn matrix<R: Resolve>(resolve: &R, dictionary: &Dictionary) -> Result<Matrix, Error> {
+    match get_numbers(resolve, dictionary, "Matrix")? {
+        Some(matrix) => {
+            Matrix::from_slice(&matrix).ok_or_else(|| Error::InvalidEntry("Matrix".into()))
+        }
+        None => Ok(Matrix::IDENTITY),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::{object::NoResolve, primitive::PdfStream};
+
+    use super::*;
+
+    #[test]
+    fn resolves_uncolored_tiling_pattern() {
+        let mut info = Dictionary::new();
+        info.insert("PatternType".to_string(), Primitive::Integer(1));
+        info.insert("PaintType".to_string(), Primitive::Integer(2));
+        info.insert("TilingType".to_string(), Primitive::Integer(1));
+        info.insert(
+            "BBox".to_string(),
+            Primitive::Array(
+                [0, 0, 10, 10]
+                    .iter()
+                    .map(|n| Primitive::Integer(*n))
+                    .collect(),
+            ),
+        );
+        info.insert("XStep".to_string(), Primitive::Integer(10));
+        info.insert("YStep".to_string(), Primitive::Integer(10));
+
+        // The cell content starts with a damaged operation, which is skipped.
+        let mut patterns = Dictionary::new();
+        patterns.insert(
+            "P0".to_string(),
+            Primitive::Stream(PdfStream {
+                info,
+                data: b") 0 0 m 0 0 5 5 re f".to_vec(),
+            }),
+        );
+        let mut resources = Dictionary::new();
+        resources.insert("Pattern".to_string(), Primitive::Dictionary(patterns));
+
+        let operation = Operation::SetColorForNonStrokingOperationsICCBasedAndSpecialColorSpaces {
+            cs: vec![1.0, 0.0, 0.0],
+            name: Some(Name("P0")),
+        };
+        let color = PatternColor::from_operation(&NoResolve, &resources, &operation)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(color.components, Some(vec![1.0, 0.0, 0.0]));
+        match color.pattern {
+            Pattern::Tiling(pattern) => {
+                assert_eq!(pattern.x_step, 10.0);
+                assert!(matches!(
+                    pattern.operations().nth(1),
+                    Some(Operation::FillPathUsingNonZeroWindingNumberRule)
+                ));
+            }
+            Pattern::Shading(_) => panic!("expected a tiling pattern"),
+        }
+    }
+}
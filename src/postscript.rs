@@ -0,0 +1,660 @@
+//! The subset of PostScript used by Type 4 functions, section 7.10.5 of ISO 32000-1.
+
+use std::{error::Error as StdError, fmt};
+
+/// The most values the operand stack can hold, the limit given in Annex C of ISO 32000-1.
+const MAX_STACK_DEPTH: usize = 100;
+/// The most instructions a program can have, counting those in nested procedures.
+const MAX_PROGRAM_LENGTH: usize = 65_536;
+/// How deeply `if` and `ifelse` procedures can be nested.
+const MAX_NESTING_DEPTH: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    Integer(i32),
+    Real(f32),
+    Boolean(bool),
+}
+
+impl Value {
+    fn to_f(self) -> Result<f32, CalculatorError> {
+        match self {
+            Value::Integer(integer) => Ok(integer as f32),
+            Value::Real(real) => Ok(real),
+            Value::Boolean(_) => Err(CalculatorError::TypeCheck),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Abs,
+    Add,
+    Atan,
+    Ceiling,
+    Cos,
+    Cvi,
+    Cvr,
+    Div,
+    Exp,
+    Floor,
+    Idiv,
+    Ln,
+    Log,
+    Mod,
+    Mul,
+    Neg,
+    Round,
+    Sin,
+    Sqrt,
+    Sub,
+    Truncate,
+    And,
+    Bitshift,
+    Eq,
+    Ge,
+    Gt,
+    Le,
+    Lt,
+    Ne,
+    Not,
+    Or,
+    Xor,
+    Copy,
+    Dup,
+    Exch,
+    Index,
+    Pop,
+    Roll,
+}
+
+impl Operator {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "abs" => Operator::Abs,
+            "add" => Operator::Add,
+            "atan" => Operator::Atan,
+            "ceiling" => Operator::Ceiling,
+            "cos" => Operator::Cos,
+            "cvi" => Operator::Cvi,
+            "cvr" => Operator::Cvr,
+            "div" => Operator::Div,
+            "exp" => Operator::Exp,
+            "floor" => Operator::Floor,
+            "idiv" => Operator::Idiv,
+            "ln" => Operator::Ln,
+            "log" => Operator::Log,
+            "mod" => Operator::Mod,
+            "mul" => Operator::Mul,
+            "neg" => Operator::Neg,
+            "round" => Operator::Round,
+            "sin" => Operator::Sin,
+            "sqrt" => Operator::Sqrt,
+            "sub" => Operator::Sub,
+            "truncate" => Operator::Truncate,
+            "and" => Operator::And,
+            "bitshift" => Operator::Bitshift,
+            "eq" => Operator::Eq,
+            "ge" => Operator::Ge,
+            "gt" => Operator::Gt,
+            "le" => Operator::Le,
+            "lt" => Operator::Lt,
+            "ne" => Operator::Ne,
+            "not" => Operator::Not,
+            "or" => Operator::Or,
+            "xor" => Operator::Xor,
+            "copy" => Operator::Copy,
+            "dup" => Operator::Dup,
+            "exch" => Operator::Exch,
+            "index" => Operator::Index,
+            "pop" => Operator::Pop,
+            "roll" => Operator::Roll,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instruction {
+    Push(Value),
+    Operator(Operator),
+    If(Vec<Instruction>),
+    IfElse(Vec<Instruction>, Vec<Instruction>),
+}
+
+/// Why a program couldn't be parsed or run, named after the PostScript errors.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CalculatorError {
+    /// The code isn't a valid program.
+    Syntax(String),
+    /// The program is longer or more deeply nested than the limits allow.
+    LimitCheck,
+    StackOverflow,
+    StackUnderflow,
+    /// An operand has the wrong type.
+    TypeCheck,
+    /// An operand is outside the range an operator accepts.
+    RangeCheck,
+    /// The result of an operation isn't a finite number, e.g. a division by zero.
+    UndefinedResult,
+}
+
+impl fmt::Display for CalculatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CalculatorError::Syntax(reason) => write!(f, "Syntax error: {}", reason),
+            CalculatorError::LimitCheck => write!(f, "Program too large"),
+            CalculatorError::StackOverflow => write!(f, "Stack overflow"),
+            CalculatorError::StackUnderflow => write!(f, "Stack underflow"),
+            CalculatorError::TypeCheck => write!(f, "Operand has the wrong type"),
+            CalculatorError::RangeCheck => write!(f, "Operand out of range"),
+            CalculatorError::UndefinedResult => write!(f, "Undefined result"),
+        }
+    }
+}
+
+impl StdError for CalculatorError {}
+
+/// A parsed Type 4 function program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+}
+
+impl Program {
+    /// Parses a program, which is a single procedure in braces.
+    pub fn parse(code: &[u8]) -> Result<Self, CalculatorError> {
+        let mut tokens = tokenize(code)?.into_iter();
+        let mut length = 0;
+
+        match tokens.next() {
+            Some(Token::Open) => {}
+            _ => return Err(CalculatorError::Syntax("expected {".into())),
+        }
+        let instructions = parse_procedure(&mut tokens, 0, &mut length)?;
+
+        if tokens.next().is_some() {
+            return Err(CalculatorError::Syntax("code after the program".into()));
+        }
+
+        Ok(Program { instructions })
+    }
+
+    /// Runs the program with the inputs on the stack, returning the stack afterwards as
+    /// numbers, bottom first.
+    pub fn run(&self, input: &[f32]) -> Result<Vec<f32>, CalculatorError> {
+        let mut stack = Stack { values: Vec::new() };
+        for value in input {
+            stack.push(Value::Real(*value))?;
+        }
+
+        execute(&self.instructions, &mut stack)?;
+
+        stack.values.into_iter().map(Value::to_f).collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Value(Value),
+    Operator(Operator),
+    If,
+    IfElse,
+}
+
+fn tokenize(code: &[u8]) -> Result<Vec<Token>, CalculatorError> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < code.len() {
+        match code[i] {
+            b'{' => tokens.push(Token::Open),
+            b'}' => tokens.push(Token::Close),
+            b'%' => {
+                while i < code.len() && code[i] != b'\n' && code[i] != b'\r' {
+                    i += 1;
+                }
+            }
+            byte if byte.is_ascii_whitespace() || byte == 0 => {}
+            _ => {
+                let start = i;
+                while i < code.len()
+                    && !code[i].is_ascii_whitespace()
+                    && !matches!(code[i], 0 | b'{' | b'}' | b'%')
+                {
+                    i += 1;
+                }
+                let word = String::from_utf8_lossy(&code[start..i]);
+                tokens.push(token(&word)?);
+                continue;
+            }
+        }
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+fn token(word: &str) -> Result<Token, CalculatorError> {
+    Ok(match word {
+        "true" => Token::Value(Value::Boolean(true)),
+        "false" => Token::Value(Value::Boolean(false)),
+        "if" => Token::If,
+        "ifelse" => Token::IfElse,
+        word => match Operator::from_name(word) {
+            Some(operator) => Token::Operator(operator),
+            None => match word.parse::<i32>() {
+                Ok(integer) => Token::Value(Value::Integer(integer)),
+                Err(_) => match word.parse::<f32>() {
+                    Ok(real) if real.is_finite() => Token::Value(Value::Real(real)),
+                    _ => return Err(CalculatorError::Syntax(format!("unknown token {}", word))),
+                },
+            },
+        },
+    })
+}
+
+/// Parses the instructions of a procedure whose opening brace has been read.
+fn parse_procedure<I: Iterator<Item = Token>>(
+    tokens: &mut I,
+    depth: usize,
+    length: &mut usize,
+) -> Result<Vec<Instruction>, CalculatorError> {
+    if depth > MAX_NESTING_DEPTH {
+        return Err(CalculatorError::LimitCheck);
+    }
+
+    let mut instructions = Vec::new();
+    // Procedures that haven't been consumed by an `if` or `ifelse` yet.
+    let mut procedures: Vec<Vec<Instruction>> = Vec::new();
+
+    loop {
+        *length += 1;
+        if *length > MAX_PROGRAM_LENGTH {
+            return Err(CalculatorError::LimitCheck);
+        }
+
+        let token = tokens
+            .next()
+            .ok_or_else(|| CalculatorError::Syntax("unterminated procedure".into()))?;
+
+        if !procedures.is_empty() && !matches!(token, Token::Open | Token::If | Token::IfElse) {
+            return Err(CalculatorError::Syntax(
+                "procedure not followed by if or ifelse".into(),
+            ));
+        }
+
+        match token {
+            Token::Open => procedures.push(parse_procedure(tokens, depth + 1, length)?),
+            Token::Close => return Ok(instructions),
+            Token::Value(value) => instructions.push(Instruction::Push(value)),
+            Token::Operator(operator) => instructions.push(Instruction::Operator(operator)),
+            Token::If => match (procedures.pop(), procedures.is_empty()) {
+                (Some(procedure), true) => instructions.push(Instruction::If(procedure)),
+                _ => return Err(CalculatorError::Syntax("if needs one procedure".into())),
+            },
+            Token::IfElse => match (procedures.pop(), procedures.pop(), procedures.is_empty()) {
+                (Some(otherwise), Some(then), true) => {
+                    instructions.push(Instruction::IfElse(then, otherwise))
+                }
+                _ => {
+                    return Err(CalculatorError::Syntax(
+                        "ifelse needs two procedures".into(),
+                    ))
+                }
+            },
+        }
+    }
+}
+
+struct Stack {
+    values: Vec<Value>,
+}
+
+impl Stack {
+    fn push(&mut self, value: Value) -> Result<(), CalculatorError> {
+        if self.values.len() >= MAX_STACK_DEPTH {
+            return Err(CalculatorError::StackOverflow);
+        }
+        self.values.push(value);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Value, CalculatorError> {
+        self.values.pop().ok_or(CalculatorError::StackUnderflow)
+    }
+
+    fn pop_f(&mut self) -> Result<f32, CalculatorError> {
+        self.pop()?.to_f()
+    }
+
+    fn pop_integer(&mut self) -> Result<i32, CalculatorError> {
+        match self.pop()? {
+            Value::Integer(integer) => Ok(integer),
+            _ => Err(CalculatorError::TypeCheck),
+        }
+    }
+
+    fn pop_bool(&mut self) -> Result<bool, CalculatorError> {
+        match self.pop()? {
+            Value::Boolean(boolean) => Ok(boolean),
+            _ => Err(CalculatorError::TypeCheck),
+        }
+    }
+
+    /// Pushes a real result, which has to be finite.
+    fn push_real(&mut self, real: f32) -> Result<(), CalculatorError> {
+        if !real.is_finite() {
+            return Err(CalculatorError::UndefinedResult);
+        }
+        self.push(Value::Real(real))
+    }
+
+    /// A count operand for `copy`, `index` and `roll`, which can't be more than the number of
+    /// values on the stack below it.
+    fn pop_count(&mut self) -> Result<usize, CalculatorError> {
+        let count = self.pop_integer()?;
+        if count < 0 {
+            return Err(CalculatorError::RangeCheck);
+        }
+        let count = count as usize;
+        if count > self.values.len() {
+            return Err(CalculatorError::StackUnderflow);
+        }
+        Ok(count)
+    }
+}
+
+fn execute(instructions: &[Instruction], stack: &mut Stack) -> Result<(), CalculatorError> {
+    for instruction in instructions {
+        match instruction {
+            Instruction::Push(value) => stack.push(*value)?,
+            Instruction::Operator(operator) => apply(*operator, stack)?,
+            Instruction::If(procedure) => {
+                if stack.pop_bool()? {
+                    execute(procedure, stack)?;
+                }
+            }
+            Instruction::IfElse(then, otherwise) => {
+                if stack.pop_bool()? {
+                    execute(then, stack)?;
+                } else {
+                    execute(otherwise, stack)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies an arithmetic operator that keeps integers as integers unless they overflow.
+fn arithmetic(
+    stack: &mut Stack,
+    integer: fn(i32, i32) -> Option<i32>,
+    real: fn(f32, f32) -> f32,
+) -> Result<(), CalculatorError> {
+    let b = stack.pop()?;
+    let a = stack.pop()?;
+
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => match integer(a, b) {
+            Some(result) => stack.push(Value::Integer(result)),
+            None => stack.push_real(real(a as f32, b as f32)),
+        },
+        (a, b) => stack.push_real(real(a.to_f()?, b.to_f()?)),
+    }
+}
+
+/// Applies a rounding operator, which leaves integers unchanged.
+fn rounding(stack: &mut Stack, real: fn(f32) -> f32) -> Result<(), CalculatorError> {
+    match stack.pop()? {
+        Value::Integer(integer) => stack.push(Value::Integer(integer)),
+        Value::Real(value) => stack.push_real(real(value)),
+        Value::Boolean(_) => Err(CalculatorError::TypeCheck),
+    }
+}
+
+/// Applies a comparison, which takes two numbers, or two booleans for `eq` and `ne`.
+fn comparison(
+    stack: &mut Stack,
+    compare: fn(f32, f32) -> bool,
+    equality: bool,
+) -> Result<(), CalculatorError> {
+    let b = stack.pop()?;
+    let a = stack.pop()?;
+
+    let result = match (a, b) {
+        (Value::Boolean(a), Value::Boolean(b)) if equality => {
+            compare(a as i32 as f32, b as i32 as f32)
+        }
+        (a, b) => compare(a.to_f()?, b.to_f()?),
+    };
+    stack.push(Value::Boolean(result))
+}
+
+/// Applies a logical operator, which is bitwise for integers.
+fn logical(
+    stack: &mut Stack,
+    integer: fn(i32, i32) -> i32,
+    boolean: fn(bool, bool) -> bool,
+) -> Result<(), CalculatorError> {
+    let b = stack.pop()?;
+    let a = stack.pop()?;
+
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => stack.push(Value::Integer(integer(a, b))),
+        (Value::Boolean(a), Value::Boolean(b)) => stack.push(Value::Boolean(boolean(a, b))),
+        _ => Err(CalculatorError::TypeCheck),
+    }
+}
+
+fn apply(operator: Operator, stack: &mut Stack) -> Result<(), CalculatorError> {
+    match operator {
+        Operator::Add => arithmetic(stack, i32::checked_add, |a, b| a + b),
+        Operator::Sub => arithmetic(stack, i32::checked_sub, |a, b| a - b),
+        Operator::Mul => arithmetic(stack, i32::checked_mul, |a, b| a * b),
+        Operator::Div => {
+            let b = stack.pop_f()?;
+            let a = stack.pop_f()?;
+            stack.push_real(a / b)
+        }
+        Operator::Idiv | Operator::Mod => {
+            let b = stack.pop_integer()?;
+            let a = stack.pop_integer()?;
+            let result = if operator == Operator::Idiv {
+                a.checked_div(b)
+            } else {
+                a.checked_rem(b)
+            };
+            stack.push(Value::Integer(
+                result.ok_or(CalculatorError::UndefinedResult)?,
+            ))
+        }
+        Operator::Neg | Operator::Abs => match stack.pop()? {
+            Value::Integer(integer) => {
+                let result = if operator == Operator::Neg {
+                    integer.checked_neg()
+                } else {
+                    integer.checked_abs()
+                };
+                match result {
+                    Some(result) => stack.push(Value::Integer(result)),
+                    None => stack.push_real(-(integer as f32)),
+                }
+            }
+            Value::Real(real) if operator == Operator::Neg => stack.push_real(-real),
+            Value::Real(real) => stack.push_real(real.abs()),
+            Value::Boolean(_) => Err(CalculatorError::TypeCheck),
+        },
+        Operator::Ceiling => rounding(stack, f32::ceil),
+        Operator::Floor => rounding(stack, f32::floor),
+        // PostScript rounds halves up, rather than away from zero.
+        Operator::Round => rounding(stack, |real| (real + 0.5).floor()),
+        Operator::Truncate => rounding(stack, f32::trunc),
+        Operator::Cvi => {
+            let real = stack.pop_f()?.trunc();
+            if real < i32::MIN as f32 || real > i32::MAX as f32 {
+                return Err(CalculatorError::RangeCheck);
+            }
+            stack.push(Value::Integer(real as i32))
+        }
+        Operator::Cvr => {
+            let real = stack.pop_f()?;
+            stack.push_real(real)
+        }
+        Operator::Sqrt => {
+            let real = stack.pop_f()?;
+            if real < 0.0 {
+                return Err(CalculatorError::RangeCheck);
+            }
+            stack.push_real(real.sqrt())
+        }
+        // Angles are in degrees.
+        Operator::Sin => {
+            let degrees = stack.pop_f()?;
+            stack.push_real(degrees.to_radians().sin())
+        }
+        Operator::Cos => {
+            let degrees = stack.pop_f()?;
+            stack.push_real(degrees.to_radians().cos())
+        }
+        Operator::Atan => {
+            let denominator = stack.pop_f()?;
+            let numerator = stack.pop_f()?;
+            if numerator == 0.0 && denominator == 0.0 {
+                return Err(CalculatorError::UndefinedResult);
+            }
+            let degrees = numerator.atan2(denominator).to_degrees();
+            stack.push_real(if degrees < 0.0 {
+                degrees + 360.0
+            } else {
+                degrees
+            })
+        }
+        Operator::Exp => {
+            let exponent = stack.pop_f()?;
+            let base = stack.pop_f()?;
+            stack.push_real(base.powf(exponent))
+        }
+        Operator::Ln | Operator::Log => {
+            let real = stack.pop_f()?;
+            if real <= 0.0 {
+                return Err(CalculatorError::RangeCheck);
+            }
+            stack.push_real(if operator == Operator::Ln {
+                real.ln()
+            } else {
+                real.log10()
+            })
+        }
+        Operator::Eq => comparison(stack, |a, b| a == b, true),
+        Operator::Ne => comparison(stack, |a, b| a != b, true),
+        Operator::Ge => comparison(stack, |a, b| a >= b, false),
+        Operator::Gt => comparison(stack, |a, b| a > b, false),
+        Operator::Le => comparison(stack, |a, b| a <= b, false),
+        Operator::Lt => comparison(stack, |a, b| a < b, false),
+        Operator::And => logical(stack, |a, b| a & b, |a, b| a && b),
+        Operator::Or => logical(stack, |a, b| a | b, |a, b| a || b),
+        Operator::Xor => logical(stack, |a, b| a ^ b, |a, b| a ^ b),
+        Operator::Not => match stack.pop()? {
+            Value::Integer(integer) => stack.push(Value::Integer(!integer)),
+            Value::Boolean(boolean) => stack.push(Value::Boolean(!boolean)),
+            Value::Real(_) => Err(CalculatorError::TypeCheck),
+        },
+        Operator::Bitshift => {
+            let shift = stack.pop_integer()?;
+            let integer = stack.pop_integer()?;
+            let result = match shift {
+                0..=31 => integer << shift,
+                -31..=-1 => ((integer as u32) >> -shift) as i32,
+                _ => 0,
+            };
+            stack.push(Value::Integer(result))
+        }
+        Operator::Dup => {
+            let value = *stack.values.last().ok_or(CalculatorError::StackUnderflow)?;
+            stack.push(value)
+        }
+        Operator::Exch => {
+            let b = stack.pop()?;
+            let a = stack.pop()?;
+            stack.push(b)?;
+            stack.push(a)
+        }
+        Operator::Pop => stack.pop().map(|_| ()),
+        Operator::Copy => {
+            let count = stack.pop_count()?;
+            let start = stack.values.len() - count;
+            for i in start..start + count {
+                let value = stack.values[i];
+                stack.push(value)?;
+            }
+            Ok(())
+        }
+        Operator::Index => {
+            let index = stack.pop_count()?;
+            let value = *stack
+                .values
+                .iter()
+                .rev()
+                .nth(index)
+                .ok_or(CalculatorError::RangeCheck)?;
+            stack.push(value)
+        }
+        Operator::Roll => {
+            let shift = stack.pop_integer()?;
+            let count = stack.pop_count()?;
+            if count > 0 {
+                let start = stack.values.len() - count;
+                let shift = shift.rem_euclid(count as i32) as usize;
+                stack.values[start..].rotate_right(shift);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_programs() {
+        // A spot function from the specification.
+        let program =
+            Program::parse(b"{ 360 mul sin 2 div exch 360 mul sin 2 div add % comment\n }")
+                .unwrap();
+        let output = program.run(&[0.25, 0.0]).unwrap();
+        assert!((output[0] - 0.5).abs() < 1e-6);
+
+        let program = Program::parse(b"{ dup 0.5 gt { 1 exch sub } if 3 1 roll 2 copy }").unwrap();
+        assert_eq!(
+            program.run(&[1.0, 2.0, 0.75]).unwrap(),
+            vec![0.25, 1.0, 2.0, 1.0, 2.0]
+        );
+
+        let program = Program::parse(b"{ 0 eq { 1 } { 7 3 idiv } ifelse }").unwrap();
+        assert_eq!(program.run(&[0.0]).unwrap(), vec![1.0]);
+        assert_eq!(program.run(&[1.0]).unwrap(), vec![2.0]);
+
+        assert_eq!(
+            Program::parse(b"{ 1 0 div }").unwrap().run(&[]),
+            Err(CalculatorError::UndefinedResult)
+        );
+        assert_eq!(
+            Program::parse(b"{ add }").unwrap().run(&[1.0]),
+            Err(CalculatorError::StackUnderflow)
+        );
+        assert_eq!(
+            Program::parse(b"{ 1 1 copy 2 copy 4 copy 8 copy 16 copy 32 copy 64 copy }")
+                .unwrap()
+                .run(&[]),
+            Err(CalculatorError::StackOverflow)
+        );
+        assert!(Program::parse(b"{ { 1 } }").is_err());
+        assert!(Program::parse(b"{ 1 foo }").is_err());
+    }
+}
@@ -0,0 +1,476 @@
+use std::f32::consts::PI;
+
+use crate::{
+    geometry::{Matrix, Rect},
+    LineCapStyle, LineJoinStyle, Operation,
+};
+
+/// The number of line segments curves are flattened into.
+const CURVE_SEGMENTS: usize = 16;
+
+/// The number of sides of the polygons round caps and joins are drawn with.
+const CIRCLE_SEGMENTS: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FillRule {
+    NonZeroWinding,
+    EvenOdd,
+}
+
+/// A path built by path construction operations, with its curves flattened into line segments.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path {
+    pub subpaths: Vec<Subpath>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Subpath {
+    pub points: Vec<(f32, f32)>,
+    pub closed: bool,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Path::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subpaths
+            .iter()
+            .all(|subpath| subpath.points.is_empty())
+    }
+
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        self.subpaths.push(Subpath {
+            points: vec![(x, y)],
+            closed: false,
+        });
+    }
+
+    pub fn line_to(&mut self, x: f32, y: f32) {
+        match self.subpaths.last_mut() {
+            Some(subpath) if !subpath.closed => subpath.points.push((x, y)),
+            // After `h` the current point is the start of the closed subpath, and a new subpath
+            // starts there.
+            Some(subpath) => {
+                let start = subpath.points[0];
+                self.subpaths.push(Subpath {
+                    points: vec![start, (x, y)],
+                    closed: false,
+                });
+            }
+            None => self.move_to(x, y),
+        }
+    }
+
+    pub fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32) {
+        let (x0, y0) = match self.current_point() {
+            Some(point) => point,
+            None => return self.move_to(x3, y3),
+        };
+
+        for i in 1..=CURVE_SEGMENTS {
+            let t = i as f32 / CURVE_SEGMENTS as f32;
+            let s = 1.0 - t;
+            let (a, b, c, d) = (s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t);
+            self.line_to(
+                a * x0 + b * x1 + c * x2 + d * x3,
+                a * y0 + b * y1 + c * y2 + d * y3,
+            );
+        }
+    }
+
+    pub fn close(&mut self) {
+        if let Some(subpath) = self.subpaths.last_mut() {
+            subpath.closed = true;
+        }
+    }
+
+    pub fn rectangle(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.move_to(x, y);
+        self.line_to(x + width, y);
+        self.line_to(x + width, y + height);
+        self.line_to(x, y + height);
+        self.close();
+    }
+
+    pub fn current_point(&self) -> Option<(f32, f32)> {
+        let subpath = self.subpaths.last()?;
+        if subpath.closed {
+            subpath.points.first().copied()
+        } else {
+            subpath.points.last().copied()
+        }
+    }
+
+    /// Applies a path construction operation, returning whether `operation` was one.
+    pub fn apply(&mut self, operation: &Operation) -> bool {
+        match *operation {
+            Operation::BeginNewSubpath { x, y } => self.move_to(x, y),
+            Operation::AppendStraightLineSegmentToPath { x, y } => self.line_to(x, y),
+            Operation::AppendCurvedSegmentToPath {
+                x1,
+                y1,
+                x2,
+                y2,
+                x3,
+                y3,
+            } => self.curve_to(x1, y1, x2, y2, x3, y3),
+            Operation::AppendCurvedSegmentToPathInitialPointReplicated { x2, y2, x3, y3 } => {
+                let (x1, y1) = self.current_point().unwrap_or((x2, y2));
+                self.curve_to(x1, y1, x2, y2, x3, y3)
+            }
+            Operation::AppendCurvedSegmentToPathFinalPointReplicated { x1, y1, x3, y3 } => {
+                self.curve_to(x1, y1, x3, y3, x3, y3)
+            }
+            Operation::AppendRectangleToPath {
+                x,
+                y,
+                width,
+                height,
+            } => self.rectangle(x, y, width, height),
+            Operation::CloseSubpath => self.close(),
+            _ => return false,
+        }
+
+        true
+    }
+
+    pub fn transform(&self, matrix: &Matrix) -> Path {
+        Path {
+            subpaths: self
+                .subpaths
+                .iter()
+                .map(|subpath| Subpath {
+                    points: subpath
+                        .points
+                        .iter()
+                        .map(|(x, y)| matrix.transform(*x, *y))
+                        .collect(),
+                    closed: subpath.closed,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn bounds(&self) -> Option<Rect> {
+        let mut points = self.subpaths.iter().flat_map(|subpath| &subpath.points);
+        let (x, y) = points.next()?;
+
+        Some(points.fold(Rect::new(*x, *y, *x, *y), |rect, (x, y)| Rect {
+            x_min: rect.x_min.min(*x),
+            y_min: rect.y_min.min(*y),
+            x_max: rect.x_max.max(*x),
+            y_max: rect.y_max.max(*y),
+        }))
+    }
+
+    /// The outline of the path stroked with a solid line, as polygons that cover the stroke when
+    /// filled with the nonzero winding number rule. Miter joins are drawn beveled.
+    pub fn stroke(&self, width: f32, cap: LineCapStyle, join: LineJoinStyle) -> Path {
+        let radius = width / 2.0;
+        let mut outline = Path::new();
+
+        for subpath in &self.subpaths {
+            let mut points = subpath.points.clone();
+            points.dedup();
+            if subpath.closed && points.len() > 1 && points.first() == points.last() {
+                points.pop();
+            }
+
+            if points.len() == 1 {
+                // A zero length subpath only paints its caps.
+                let (x, y) = points[0];
+                match cap {
+                    LineCapStyle::ButtCap => {}
+                    LineCapStyle::RoundCap => outline.polygon(circle(x, y, radius)),
+                    LineCapStyle::ProjectingSquareCap => outline.polygon(vec![
+                        (x - radius, y - radius),
+                        (x + radius, y - radius),
+                        (x + radius, y + radius),
+                        (x - radius, y + radius),
+                    ]),
+                }
+                continue;
+            }
+
+            let closed = subpath.closed && points.len() > 2;
+            let mut segments: Vec<_> = points.windows(2).map(|w| (w[0], w[1])).collect();
+            if closed {
+                segments.push((points[points.len() - 1], points[0]));
+            }
+
+            if !closed && cap == LineCapStyle::ProjectingSquareCap {
+                let first = &mut segments[0];
+                first.0 = extend(first.1, first.0, radius);
+                let last = segments.len() - 1;
+                let last = &mut segments[last];
+                last.1 = extend(last.0, last.1, radius);
+            }
+
+            for (start, end) in &segments {
+                let (nx, ny) = normal(*start, *end, radius);
+                outline.polygon(vec![
+                    (start.0 + nx, start.1 + ny),
+                    (end.0 + nx, end.1 + ny),
+                    (end.0 - nx, end.1 - ny),
+                    (start.0 - nx, start.1 - ny),
+                ]);
+            }
+
+            let joins = if closed {
+                segments.len()
+            } else {
+                segments.len() - 1
+            };
+            for i in 0..joins {
+                let (previous, next) = (segments[i], segments[(i + 1) % segments.len()]);
+                let (x, y) = previous.1;
+                match join {
+                    LineJoinStyle::RoundJoin => outline.polygon(circle(x, y, radius)),
+                    LineJoinStyle::MiterJoin | LineJoinStyle::BevelJoin => {
+                        let (ax, ay) = normal(previous.0, previous.1, radius);
+                        let (bx, by) = normal(next.0, next.1, radius);
+                        outline.polygon(vec![(x, y), (x + ax, y + ay), (x + bx, y + by)]);
+                        outline.polygon(vec![(x, y), (x - ax, y - ay), (x - bx, y - by)]);
+                    }
+                }
+            }
+
+            if !closed && cap == LineCapStyle::RoundCap {
+                let (first, last) = (points[0], points[points.len() - 1]);
+                outline.polygon(circle(first.0, first.1, radius));
+                outline.polygon(circle(last.0, last.1, radius));
+            }
+        }
+
+        outline
+    }
+
+    /// Adds a closed polygon, oriented counterclockwise so that overlapping polygons add up
+    /// under the nonzero winding number rule.
+    fn polygon(&mut self, mut points: Vec<(f32, f32)>) {
+        let area: f32 = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+            .sum();
+        if area < 0.0 {
+            points.reverse();
+        }
+
+        self.subpaths.push(Subpath {
+            points,
+            closed: true,
+        });
+    }
+}
+
+/// A perpendicular to the segment from `start` to `end` with the given length.
+fn normal(start: (f32, f32), end: (f32, f32), length: f32) -> (f32, f32) {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let norm = dx.hypot(dy);
+    if norm == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    (-dy / norm * length, dx / norm * length)
+}
+
+/// Moves `end` further away from `start` by `length`.
+fn extend(start: (f32, f32), end: (f32, f32), length: f32) -> (f32, f32) {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let norm = dx.hypot(dy);
+    if norm == 0.0 {
+        return end;
+    }
+
+    (end.0 + dx / norm * length, end.1 + dy / norm * length)
+}
+
+fn circle(x: f32, y: f32, radius: f32) -> Vec<(f32, f32)> {
+    (0..CIRCLE_SEGMENTS)
+        .map(|i| {
+            let angle = 2.0 * PI * i as f32 / CIRCLE_SEGMENTS as f32;
+            (x + radius * angle.cos(), y + radius * angle.sin())
+        })
+        .collect()
+}
+
+/// A bitmap of the pixels an area covers. Pixel `(x, y)` is the square from `(x, y)` to
+/// `(x + 1, y + 1)` in device space, and is covered if its center is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mask {
+    width: usize,
+    height: usize,
+    data: Vec<bool>,
+}
+
+impl Mask {
+    /// A mask covering no pixels.
+    pub fn new(width: usize, height: usize) -> Self {
+        Mask {
+            width,
+            height,
+            data: vec![false; width * height],
+        }
+    }
+
+    /// A mask covering every pixel.
+    pub fn full(width: usize, height: usize) -> Self {
+        Mask {
+            width,
+            height,
+            data: vec![true; width * height],
+        }
+    }
+
+    /// The pixels covered by filling `path`, which is given in device space. Open subpaths are
+    /// closed.
+    pub fn fill(path: &Path, rule: FillRule, width: usize, height: usize) -> Self {
+        let mut mask = Mask::new(width, height);
+
+        // The edges crossing the center line of each row, with their direction.
+        let mut rows: Vec<Vec<(f32, i32)>> = vec![Vec::new(); height];
+        for subpath in &path.subpaths {
+            let points = &subpath.points;
+            let edges = points.iter().zip(points.iter().cycle().skip(1));
+
+            for (&(x0, y0), &(x1, y1)) in edges.take(points.len()) {
+                if y0 == y1 || ![x0, y0, x1, y1].iter().all(|v| v.is_finite()) {
+                    continue;
+                }
+
+                let (direction, top, bottom) = if y0 < y1 {
+                    (1, (x0, y0), (x1, y1))
+                } else {
+                    (-1, (x1, y1), (x0, y0))
+                };
+                let first = (top.1 - 0.5).ceil().max(0.0) as usize;
+                let last = ((bottom.1 - 0.5).ceil().max(0.0) as usize).min(height);
+
+                for (row, crossings) in rows.iter_mut().enumerate().take(last).skip(first) {
+                    let y = row as f32 + 0.5;
+                    let x = top.0 + (y - top.1) * (bottom.0 - top.0) / (bottom.1 - top.1);
+                    crossings.push((x, direction));
+                }
+            }
+        }
+
+        for (row, mut crossings) in rows.into_iter().enumerate() {
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                let inside = match rule {
+                    FillRule::NonZeroWinding => winding != 0,
+                    FillRule::EvenOdd => winding % 2 != 0,
+                };
+                if !inside {
+                    continue;
+                }
+
+                let start = (pair[0].0 - 0.5).ceil().max(0.0) as usize;
+                let end = ((pair[1].0 - 0.5).ceil().max(0.0) as usize).min(width);
+                if start < end {
+                    let offset = row * width;
+                    mask.data[offset + start..offset + end].fill(true);
+                }
+            }
+        }
+
+        mask
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.data[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, covered: bool) {
+        if x < self.width && y < self.height {
+            self.data[y * self.width + x] = covered;
+        }
+    }
+
+    /// Keeps only the pixels also covered by `other`, which should have the same size.
+    pub fn intersect(&mut self, other: &Mask) {
+        for (pixel, other) in self.data.iter_mut().zip(&other.data) {
+            *pixel &= *other;
+        }
+    }
+
+    /// The number of pixels covered.
+    pub fn count(&self) -> usize {
+        self.data.iter().filter(|pixel| **pixel).count()
+    }
+
+    /// The covered pixels, row by row.
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.data
+            .iter()
+            .enumerate()
+            .filter(|(_, pixel)| **pixel)
+            .map(move |(i, _)| (i % self.width, i / self.width))
+    }
+
+    /// The smallest rectangle of pixels containing every covered pixel, as
+    /// `(x_min, y_min, x_max, y_max)` with the maximums exclusive.
+    pub fn bounds(&self) -> Option<(usize, usize, usize, usize)> {
+        self.pixels().fold(None, |bounds, (x, y)| match bounds {
+            None => Some((x, y, x + 1, y + 1)),
+            Some((x_min, y_min, x_max, y_max)) => {
+                Some((x_min.min(x), y_min, x_max.max(x + 1), y_max.max(y + 1)))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_and_strokes_paths() {
+        let mut path = Path::new();
+        path.rectangle(2.0, 2.0, 4.0, 4.0);
+        path.rectangle(3.0, 3.0, 2.0, 2.0);
+
+        assert_eq!(
+            Mask::fill(&path, FillRule::NonZeroWinding, 10, 10).count(),
+            16
+        );
+        assert_eq!(Mask::fill(&path, FillRule::EvenOdd, 10, 10).count(), 12);
+
+        let mut line = Path::new();
+        line.move_to(1.0, 5.0);
+        line.line_to(9.0, 5.0);
+        let butt = line.stroke(2.0, LineCapStyle::ButtCap, LineJoinStyle::MiterJoin);
+        let square = line.stroke(
+            2.0,
+            LineCapStyle::ProjectingSquareCap,
+            LineJoinStyle::MiterJoin,
+        );
+
+        assert_eq!(
+            Mask::fill(&butt, FillRule::NonZeroWinding, 10, 10).count(),
+            16
+        );
+        assert_eq!(
+            Mask::fill(&square, FillRule::NonZeroWinding, 10, 10).count(),
+            20
+        );
+        assert_eq!(
+            Mask::fill(&butt, FillRule::NonZeroWinding, 10, 10).bounds(),
+            Some((1, 4, 9, 6))
+        );
+    }
+}
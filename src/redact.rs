@@ -0,0 +1,1474 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    f32::consts::SQRT_2,
+    io::Cursor,
+    mem,
+    ops::Range,
+};
+
+use pdf::{
+    object::Resolve,
+    primitive::{Dictionary, PdfStream, Primitive},
+};
+
+use crate::{
+    color::ColorSpace,
+    document::{
+        get, get_bool, get_dictionary, get_integer, get_name, get_numbers, get_resource,
+        stream_data, RawPage,
+    },
+    error::Error,
+    font::Font,
+    geometry::{Matrix, Rect},
+    graphics_state::{GraphicsState, GraphicsStateStack},
+    normalize_operation,
+    parser::ContentStreamParser,
+    raster::{FillRule, Path},
+    text::REPLACEMENT_KEYS,
+    text_state::TextState,
+    writer::to_content,
+    LineJoinStyle, Name, Operation, PropertyList, TextOrGlyphPositioning,
+};
+
+/// How far the clipping paths that keep shadings out of the regions reach, in default user space
+/// units. Pages can't be larger than 14400 units.
+const FAR: f32 = 100_000.0;
+
+/// The content of a page with the content inside some regions removed.
+pub struct RedactedPage {
+    /// The page's new content stream.
+    pub content: Vec<u8>,
+    /// The redacted copies of the page's `/XObject` resources that `content` paints, by the
+    /// name it paints them with, to be added to the page's `/XObject` resources: images with the
+    /// redacted pixels set to zero, and Form XObjects with their content redacted. An XObject
+    /// painted in several places gets a copy for each place that touches a region. The streams
+    /// aren't compressed.
+    pub xobjects: HashMap<String, PdfStream>,
+    /// The page's `/XObject` resources that `content` no longer paints, each place painting them
+    /// having been redacted, so that they can be removed from the page's resources.
+    pub unused: Vec<String>,
+}
+
+/// Operations with the content inside some regions removed.
+pub struct Redaction<'src> {
+    pub operations: Vec<Operation<'src>>,
+    /// The `Do` operations that paint a redacted copy of their XObject instead, by index in
+    /// `operations`, with the name of the copy.
+    pub renamed: HashMap<usize, String>,
+    /// The `BDC` operations whose property list is replaced by this inline one, by index in
+    /// `operations`. Their own has an `/ActualText`, `/Alt` or `/E` that would spell out the
+    /// text removed from the sequence.
+    pub properties: HashMap<usize, Dictionary>,
+    /// The redacted copies of XObjects, as in `RedactedPage`.
+    pub xobjects: HashMap<String, PdfStream>,
+    /// The XObjects no longer painted, as in `RedactedPage`.
+    pub unused: Vec<String>,
+}
+
+impl<'src> Redaction<'src> {
+    /// Serializes the operations as a content stream, with the renamed `Do` operations painting
+    /// the copies and the replaced property lists.
+    pub fn to_content(&self) -> Vec<u8> {
+        let operations: Vec<_> = self
+            .operations
+            .iter()
+            .enumerate()
+            .map(|(index, operation)| match self.renamed.get(&index) {
+                Some(name) => Operation::InvokeNamedXObject(Name(name)),
+                None => with_properties(index, operation, &self.properties),
+            })
+            .collect();
+        to_content(&operations)
+    }
+}
+
+/// Removes the text, paths, images and shadings inside the regions, given in default user space,
+/// from a page's content.
+///
+/// Glyphs touching a region are removed from the strings showing them and replaced by offsets
+/// so the rest of the text stays in place. Text in a font whose glyph widths are only
+/// approximated is removed whole when it could reach a region. Filled areas and stroked lines
+/// are cut along the regions' edges. Image pixels in a region are set to zero, which is black for
+/// most color spaces, and Form XObjects are redacted themselves. Inline images, and images and
+/// forms inside other forms, are removed entirely when they touch a region. Soft masks aren't
+/// redacted.
+///
+/// The `/ActualText`, `/Alt` and `/E` of the marked content sequences that glyphs or XObjects are
+/// removed from are taken out of their property list, which is then given inline.
+pub fn redact_page<R: Resolve>(
+    resolve: &R,
+    page: &RawPage,
+    regions: &[Rect],
+) -> Result<RedactedPage, Error> {
+    let operations = page
+        .content_parser(resolve)?
+        .collect::<Result<Vec<_>, _>>()?;
+    let operations = operations.iter().map(normalize_operation).collect();
+
+    let redaction = redact(resolve, &page.resources(resolve)?, operations, regions)?;
+    Ok(RedactedPage {
+        content: redaction.to_content(),
+        xobjects: redaction.xobjects,
+        unused: redaction.unused,
+    })
+}
+
+/// Redacts the operations of a page's content, as `redact_page` does.
+pub fn redact<'src, R: Resolve>(
+    resolve: &R,
+    resources: &Dictionary,
+    operations: Vec<Operation<'src>>,
+    regions: &[Rect],
+) -> Result<Redaction<'src>, Error> {
+    let mut redactor = Redactor {
+        resolve,
+        regions,
+        xobjects: HashMap::new(),
+        placements: Vec::new(),
+        renamed: HashMap::new(),
+        page_resources: true,
+        kept: HashSet::new(),
+        redacted: HashSet::new(),
+        shared: false,
+    };
+    let (operations, properties) =
+        redactor.redact(resources, operations, Matrix::IDENTITY, true)?;
+
+    let mut unused: Vec<_> = if redactor.shared {
+        Vec::new()
+    } else {
+        redactor
+            .redacted
+            .difference(&redactor.kept)
+            .cloned()
+            .collect()
+    };
+    unused.sort_unstable();
+
+    Ok(Redaction {
+        operations,
+        renamed: redactor.renamed,
+        properties,
+        xobjects: redactor.xobjects,
+        unused,
+    })
+}
+
+/// How a path painting operation paints its path.
+#[derive(Clone, Copy)]
+struct Painting {
+    fill: Option<FillRule>,
+    stroke: bool,
+    close: bool,
+}
+
+impl Painting {
+    fn of(operation: &Operation) -> Option<Self> {
+        let (fill, stroke, close) = match operation {
+            Operation::FillPathUsingNonZeroWindingNumberRule
+            | Operation::ObsoleteFillPathUsingNonZeroWindingMumberRule => {
+                (Some(FillRule::NonZeroWinding), false, false)
+            }
+            Operation::FillPathUsingEvenOddRule => (Some(FillRule::EvenOdd), false, false),
+            Operation::StrokePath => (None, true, false),
+            Operation::CloseAndStrokePath => (None, true, true),
+            Operation::FillAndStrokePathUsingNonZeroWindingNumber => {
+                (Some(FillRule::NonZeroWinding), true, false)
+            }
+            Operation::FillAndStrokePathUsingEvenOddRule => (Some(FillRule::EvenOdd), true, false),
+            Operation::CloseFillAndStrokePathUsingNonZeroWindingNumber => {
+                (Some(FillRule::NonZeroWinding), true, true)
+            }
+            Operation::CloseFillAndStrokePathUsingEvenOddRule => {
+                (Some(FillRule::EvenOdd), true, true)
+            }
+            Operation::EndPathWithoutFillingOrStroking => (None, false, false),
+            _ => return None,
+        };
+
+        Some(Painting {
+            fill,
+            stroke,
+            close,
+        })
+    }
+}
+
+/// What redaction does with a `Do` operation.
+enum Invocation {
+    Kept,
+    Removed,
+    /// The `Do` paints the redacted copy of its XObject with this name instead.
+    Renamed(String),
+}
+
+/// A marked content sequence open in the content being redacted.
+struct OpenSequence {
+    /// The index of its `BDC` operation in the redacted content, `None` for `BMC`.
+    begin: Option<usize>,
+    /// Whether glyphs or XObjects were removed from it.
+    touched: bool,
+}
+
+/// A string or an offset of a text showing operation.
+enum Piece<'a> {
+    Text(&'a [u8]),
+    Offset(f32),
+}
+
+struct Redactor<'a, R> {
+    resolve: &'a R,
+    regions: &'a [Rect],
+    xobjects: HashMap<String, PdfStream>,
+    /// The XObjects painted by the page's content that were copied to be redacted, with the
+    /// matrix they were painted with and the name of the copy.
+    placements: Vec<(String, Matrix, String)>,
+    renamed: HashMap<usize, String>,
+    /// Whether the content being redacted looks its resources up in the page's.
+    page_resources: bool,
+    /// The page's XObjects painted as they are somewhere, and the ones redacted or removed
+    /// somewhere.
+    kept: HashSet<String>,
+    redacted: HashSet<String>,
+    /// Whether a form painted as it is has no resources of its own, so that it may paint any of
+    /// the page's XObjects.
+    shared: bool,
+}
+
+impl<'a, R: Resolve> Redactor<'a, R> {
+    /// Redacts a content stream, returning the property lists that replace those of its `BDC`
+    /// operations as in `Redaction`. Forms and images are only redacted in place in the page's
+    /// own content, in forms they're removed when they touch a region.
+    fn redact<'src>(
+        &mut self,
+        resources: &Dictionary,
+        operations: Vec<Operation<'src>>,
+        ctm: Matrix,
+        page: bool,
+    ) -> Result<(Vec<Operation<'src>>, HashMap<usize, Dictionary>), Error> {
+        let mut out = Vec::with_capacity(operations.len());
+        let mut stack = GraphicsStateStack::new(GraphicsState {
+            ctm,
+            ..GraphicsState::default()
+        });
+        let (mut text, mut saved_text) = (TextState::default(), Vec::new());
+        let mut fonts = HashMap::new();
+        let (mut path, mut path_operations, mut clip) = (Path::new(), Vec::new(), None);
+        let mut inline_image = Vec::new();
+        let (mut sequences, mut properties) = (Vec::new(), HashMap::new());
+
+        for operation in operations {
+            // Inline images are held back until their data ends, as they're kept or dropped whole.
+            if !inline_image.is_empty() || operation == Operation::BeginInlineImageObject {
+                let end = matches!(operation, Operation::EndInlineImageObject(_));
+                inline_image.push(operation);
+                if end {
+                    if self.touches(&unit_square(&stack.current().ctm)) {
+                        inline_image.clear();
+                    } else {
+                        out.append(&mut inline_image);
+                    }
+                }
+                continue;
+            }
+
+            if path.apply(&operation) {
+                path_operations.push(operation);
+                continue;
+            }
+            if let Some(painting) = Painting::of(&operation) {
+                self.paint(
+                    &mut out,
+                    operation,
+                    painting,
+                    mem::take(&mut path_operations),
+                    mem::take(&mut path),
+                    clip.take(),
+                    stack.current(),
+                );
+                continue;
+            }
+
+            match &operation {
+                Operation::BeginMarkedContentSequence(_) => sequences.push(OpenSequence {
+                    begin: None,
+                    touched: false,
+                }),
+                Operation::BeginMarkedContentSequenceWithPropertyList { .. } => {
+                    sequences.push(OpenSequence {
+                        begin: Some(out.len()),
+                        touched: false,
+                    })
+                }
+                Operation::EndMarkedContentSequence => {
+                    if let Some(sequence) = sequences.pop() {
+                        self.strip(&out, resources, sequence, &mut properties)?;
+                    }
+                }
+                _ => {}
+            }
+
+            match &operation {
+                Operation::SetClippingPathUsingNonZeroWindingNumberRule
+                | Operation::SetClippingPathUsingEvenOddRule => {
+                    clip = Some(operation);
+                    continue;
+                }
+                Operation::ShowText(_)
+                | Operation::ShowTextAllowingIndividualGlyphPositioning(_)
+                | Operation::MoveToNextLineAndShowText(_)
+                | Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText { .. } => {
+                    text.update(&operation);
+                    let font = text.load_font(self.resolve, resources, &mut fonts)?;
+                    if self.show(&mut out, operation, &mut text, font, stack.current()) {
+                        touch(&mut sequences);
+                    }
+                    continue;
+                }
+                Operation::Unknown { operator, operands }
+                    if matches!(*operator, "Tj" | "TJ" | "'" | "\"") =>
+                {
+                    let font = text.load_font(self.resolve, resources, &mut fonts)?;
+                    if self.show_unknown(
+                        &mut out,
+                        operator,
+                        operands,
+                        &mut text,
+                        font,
+                        stack.current(),
+                    ) {
+                        touch(&mut sequences);
+                    }
+                    continue;
+                }
+                Operation::InvokeNamedXObject(Name(name)) => {
+                    let invocation = self.invoke(resources, name, &stack.current().ctm, page)?;
+                    if self.page_resources {
+                        match invocation {
+                            Invocation::Kept => self.kept.insert(name.to_string()),
+                            _ => self.redacted.insert(name.to_string()),
+                        };
+                    }
+                    match invocation {
+                        Invocation::Kept => {}
+                        Invocation::Removed => {
+                            touch(&mut sequences);
+                            continue;
+                        }
+                        Invocation::Renamed(copy) => {
+                            touch(&mut sequences);
+                            self.renamed.insert(out.len(), copy);
+                        }
+                    }
+                    out.push(operation);
+                    continue;
+                }
+                Operation::PaintAreaDefinedByShadingPattern(_) if !self.regions.is_empty() => {
+                    // Nothing is painted with a singular matrix.
+                    if let Some(inverse) = stack.current().ctm.inverse() {
+                        out.push(Operation::SaveGraphicsState);
+                        for region in self.regions {
+                            exclude(&mut out, region, &inverse);
+                        }
+                        out.push(operation);
+                        out.push(Operation::RestoreGraphicsState);
+                    }
+                    continue;
+                }
+                Operation::SaveGraphicsState => saved_text.push(text.clone()),
+                Operation::RestoreGraphicsState => {
+                    if let Some(saved) = saved_text.pop() {
+                        text = saved;
+                    }
+                }
+                _ => text.update(&operation),
+            }
+
+            stack.update(&operation, resources, self.resolve)?;
+            out.push(operation);
+        }
+
+        // Sequences left open run to the end of the content.
+        for sequence in sequences {
+            self.strip(&out, resources, sequence, &mut properties)?;
+        }
+
+        Ok((out, properties))
+    }
+
+    /// Takes the `/ActualText`, `/Alt` and `/E` out of the property list of a sequence that
+    /// glyphs or XObjects were removed from, since they would spell out what was removed.
+    fn strip(
+        &self,
+        out: &[Operation],
+        resources: &Dictionary,
+        sequence: OpenSequence,
+        properties: &mut HashMap<usize, Dictionary>,
+    ) -> Result<(), Error> {
+        let index = match sequence {
+            OpenSequence {
+                begin: Some(index),
+                touched: true,
+            } => index,
+            _ => return Ok(()),
+        };
+
+        if let Operation::BeginMarkedContentSequenceWithPropertyList {
+            properties: list, ..
+        } = &out[index]
+        {
+            let mut dictionary = list.resolve(self.resolve, resources)?;
+            let mut stripped = false;
+            for key in &REPLACEMENT_KEYS {
+                stripped |= dictionary.remove(key).is_some();
+            }
+            if stripped {
+                properties.insert(index, dictionary);
+            }
+        }
+        Ok(())
+    }
+
+    /// Paints a path with the parts in the regions cut out. A clipping path set with it is kept
+    /// as it was, since it doesn't paint anything itself.
+    #[allow(clippy::too_many_arguments)]
+    fn paint<'src>(
+        &self,
+        out: &mut Vec<Operation<'src>>,
+        operation: Operation<'src>,
+        painting: Painting,
+        path_operations: Vec<Operation<'src>>,
+        mut path: Path,
+        clip: Option<Operation<'src>>,
+        state: &GraphicsState,
+    ) {
+        if painting.close {
+            path.close();
+        }
+        let page_path = path.transform(&state.ctm);
+        let margin = stroke_margin(state);
+        let fill_touches = painting.fill.is_some() && self.path_touches(&page_path, 0.0);
+        let stroke_touches = painting.stroke && self.path_touches(&page_path, margin);
+
+        let inverse = match state.ctm.inverse() {
+            Some(inverse) if fill_touches || stroke_touches => inverse,
+            _ => {
+                out.extend(path_operations);
+                out.extend(clip);
+                out.push(operation);
+                return;
+            }
+        };
+
+        if let Some(rule) = painting.fill {
+            if fill_touches {
+                let mut polygons: Vec<_> = page_path
+                    .subpaths
+                    .iter()
+                    .map(|subpath| subpath.points.clone())
+                    .collect();
+                for region in self.regions {
+                    polygons = polygons
+                        .iter()
+                        .flat_map(|polygon| subtract(polygon, region))
+                        .collect();
+                }
+                if !polygons.is_empty() {
+                    for polygon in &polygons {
+                        append_polyline(out, polygon, true, &inverse);
+                    }
+                    out.push(fill_operation(rule));
+                }
+            } else {
+                out.extend(path_operations.iter().cloned());
+                out.push(fill_operation(rule));
+            }
+        }
+
+        if painting.stroke {
+            if stroke_touches {
+                let mut lines: Vec<_> = page_path
+                    .subpaths
+                    .iter()
+                    .map(|subpath| (subpath.points.clone(), subpath.closed))
+                    .collect();
+                for region in self.regions {
+                    let region = expand(region, margin);
+                    lines = lines
+                        .iter()
+                        .flat_map(|(points, closed)| cut(points, *closed, &region))
+                        .collect();
+                }
+                if !lines.is_empty() {
+                    for (points, closed) in &lines {
+                        append_polyline(out, points, *closed, &inverse);
+                    }
+                    out.push(Operation::StrokePath);
+                }
+            } else {
+                out.extend(path_operations.iter().cloned());
+                out.push(if painting.close {
+                    Operation::CloseAndStrokePath
+                } else {
+                    Operation::StrokePath
+                });
+            }
+        }
+
+        if let Some(clip) = clip {
+            out.extend(path_operations);
+            out.push(clip);
+            out.push(Operation::EndPathWithoutFillingOrStroking);
+        }
+    }
+
+    fn path_touches(&self, path: &Path, margin: f32) -> bool {
+        matches!(path.bounds(), Some(bounds) if self.touches(&expand(&bounds, margin)))
+    }
+
+    /// Shows text with the glyphs in the regions replaced by offsets of the same width, returning
+    /// whether any were. Glyphs that share a UTF-8 character with a removed glyph are removed
+    /// with it.
+    fn show<'src>(
+        &self,
+        out: &mut Vec<Operation<'src>>,
+        operation: Operation<'src>,
+        text: &mut TextState,
+        font: &Font,
+        state: &GraphicsState,
+    ) -> bool {
+        let array = {
+            let pieces = match &operation {
+                Operation::ShowText(string)
+                | Operation::MoveToNextLineAndShowText(string)
+                | Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText {
+                    text: string,
+                    ..
+                } => vec![Piece::Text(string.as_bytes())],
+                Operation::ShowTextAllowingIndividualGlyphPositioning(array) => array
+                    .iter()
+                    .map(|item| match item {
+                        TextOrGlyphPositioning::Text(string) => Piece::Text(string.as_bytes()),
+                        TextOrGlyphPositioning::GlyphPositioning(offset) => Piece::Offset(*offset),
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            let font_size = text.font_size;
+            let mut layouts = self.layout(&pieces, text, font, &state.ctm);
+            if !layouts.iter().flatten().any(|glyph| glyph.removed) {
+                None
+            } else {
+                let mut array = Vec::new();
+                for (piece, glyphs) in pieces.iter().zip(&mut layouts) {
+                    match piece {
+                        Piece::Offset(offset) => push_offset(&mut array, *offset),
+                        Piece::Text(bytes) => {
+                            whole_characters(bytes, glyphs);
+                            rebuild(&mut array, bytes, glyphs, font_size);
+                        }
+                    }
+                }
+                Some(array)
+            }
+        };
+
+        let array = match array {
+            Some(array) => array,
+            None => {
+                out.push(operation);
+                return false;
+            }
+        };
+        match operation {
+            Operation::MoveToNextLineAndShowText(_) => {
+                out.push(Operation::MoveToStartOfNextTextLine)
+            }
+            Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText {
+                word_spacing,
+                character_spacing,
+                ..
+            } => out.extend(vec![
+                Operation::SetWordSpacing(word_spacing),
+                Operation::SetCharacterSpacing(character_spacing),
+                Operation::MoveToStartOfNextTextLine,
+            ]),
+            _ => {}
+        }
+        out.push(Operation::ShowTextAllowingIndividualGlyphPositioning(array));
+        true
+    }
+
+    /// Shows text whose strings aren't UTF-8, which can't be split, so it's replaced by an offset
+    /// as a whole when any of its glyphs is in a region, returning whether it was.
+    fn show_unknown<'src>(
+        &self,
+        out: &mut Vec<Operation<'src>>,
+        operator: &'src str,
+        operands: &'src [Primitive],
+        text: &mut TextState,
+        font: &Font,
+        state: &GraphicsState,
+    ) -> bool {
+        let unchanged = Operation::Unknown { operator, operands };
+        let mut moves = Vec::new();
+        if operator == "\"" {
+            match operands {
+                [word_spacing, character_spacing, _] => {
+                    let (word_spacing, character_spacing) =
+                        match (number(word_spacing), number(character_spacing)) {
+                            (Some(word_spacing), Some(character_spacing)) => {
+                                (word_spacing, character_spacing)
+                            }
+                            _ => {
+                                out.push(unchanged);
+                                return false;
+                            }
+                        };
+                    moves.push(Operation::SetWordSpacing(word_spacing));
+                    moves.push(Operation::SetCharacterSpacing(character_spacing));
+                }
+                _ => {
+                    out.push(unchanged);
+                    return false;
+                }
+            }
+        }
+        if operator == "'" || operator == "\"" {
+            moves.push(Operation::MoveToStartOfNextTextLine);
+        }
+        for operation in &moves {
+            text.update(operation);
+        }
+
+        let pieces: Vec<_> = match (operator, operands.last()) {
+            ("TJ", Some(Primitive::Array(array))) => array
+                .iter()
+                .filter_map(|item| match item {
+                    Primitive::String(string) => Some(Piece::Text(string.as_bytes())),
+                    _ => number(item).map(Piece::Offset),
+                })
+                .collect(),
+            ("TJ", _) => {
+                out.push(unchanged);
+                return false;
+            }
+            (_, Some(Primitive::String(string))) => vec![Piece::Text(string.as_bytes())],
+            _ => {
+                out.push(unchanged);
+                return false;
+            }
+        };
+
+        let font_size = text.font_size;
+        let layouts = self.layout(&pieces, text, font, &state.ctm);
+        if !layouts.iter().flatten().any(|glyph| glyph.removed) {
+            out.push(unchanged);
+            return false;
+        }
+
+        let mut array = Vec::new();
+        for (piece, glyphs) in pieces.iter().zip(&layouts) {
+            match piece {
+                Piece::Offset(offset) => push_offset(&mut array, *offset),
+                Piece::Text(_) => {
+                    for glyph in glyphs {
+                        push_offset(&mut array, offset(glyph.advance, font_size));
+                    }
+                }
+            }
+        }
+        out.extend(moves);
+        out.push(Operation::ShowTextAllowingIndividualGlyphPositioning(array));
+        true
+    }
+
+    /// Lays out the glyphs of each string, marking the ones touching a region. When the font's
+    /// glyph positions are only approximated, where the glyphs are isn't known well enough to
+    /// remove some of them, so all of them are removed when the strings could reach a region
+    /// even with the widest glyphs.
+    fn layout(
+        &self,
+        pieces: &[Piece],
+        text: &mut TextState,
+        font: &Font,
+        ctm: &Matrix,
+    ) -> Vec<Vec<LaidOutGlyph>> {
+        let widest = if font.is_approximate() {
+            let mut probe = text.clone();
+            self.place(pieces, &mut probe, &font.widest(), ctm)
+                .iter()
+                .flatten()
+                .any(|glyph| glyph.removed)
+        } else {
+            false
+        };
+
+        let mut layouts = self.place(pieces, text, font, ctm);
+        if font.is_approximate() && (widest || layouts.iter().flatten().any(|glyph| glyph.removed))
+        {
+            for glyph in layouts.iter_mut().flatten() {
+                glyph.removed = true;
+            }
+        }
+        layouts
+    }
+
+    /// Lays out the glyphs of each string with the font's widths.
+    fn place(
+        &self,
+        pieces: &[Piece],
+        text: &mut TextState,
+        font: &Font,
+        ctm: &Matrix,
+    ) -> Vec<Vec<LaidOutGlyph>> {
+        pieces
+            .iter()
+            .map(|piece| {
+                let mut glyphs = Vec::new();
+                match piece {
+                    Piece::Text(bytes) => text.show(font, bytes, ctm, |glyph| {
+                        glyphs.push(LaidOutGlyph {
+                            removed: self.touches(&glyph.bounds()),
+                            bytes: glyph.bytes,
+                            advance: glyph.advance,
+                        })
+                    }),
+                    Piece::Offset(offset) => text.adjust(font, *offset),
+                }
+                glyphs
+            })
+            .collect()
+    }
+
+    /// Redacts an XObject painted by `Do`. XObjects in the page's own content are redacted in a
+    /// copy for the place they're painted at, so that the same XObject painted elsewhere isn't
+    /// redacted where it doesn't touch a region.
+    fn invoke(
+        &mut self,
+        resources: &Dictionary,
+        name: &str,
+        ctm: &Matrix,
+        page: bool,
+    ) -> Result<Invocation, Error> {
+        if let Some((.., copy)) = self
+            .placements
+            .iter()
+            .find(|(original, placement, _)| page && original == name && placement == ctm)
+        {
+            return Ok(Invocation::Renamed(copy.clone()));
+        }
+
+        let stream = match get_resource(self.resolve, resources, "XObject", name)? {
+            Primitive::Stream(stream) => stream,
+            _ => return Err(Error::InvalidEntry(format!("XObject /{}", name))),
+        };
+
+        match get_name(self.resolve, &stream.info, "Subtype")?.as_deref() {
+            Some("Image") => {
+                if !self.touches(&unit_square(ctm)) {
+                    return Ok(Invocation::Kept);
+                }
+                if !page {
+                    return Ok(Invocation::Removed);
+                }
+                match self.mask_image(&stream, ctm)? {
+                    Some(image) => self.copy(resources, name, ctm, image),
+                    None => Ok(Invocation::Removed),
+                }
+            }
+            Some("Form") => {
+                let matrix = get_numbers(self.resolve, &stream.info, "Matrix")?
+                    .and_then(|matrix| Matrix::from_slice(&matrix))
+                    .unwrap_or(Matrix::IDENTITY);
+                let form_ctm = matrix * *ctm;
+                let bbox = get_numbers(self.resolve, &stream.info, "BBox")?
+                    .and_then(|bbox| Rect::from_slice(&bbox));
+                // Forms without a bounding box are taken to reach everywhere.
+                if matches!(bbox, Some(bbox) if !self.touches(&bbox.transform(&form_ctm))) {
+                    if self.page_resources && stream.info.get("Resources").is_none() {
+                        self.shared = true;
+                    }
+                    return Ok(Invocation::Kept);
+                }
+                if !page {
+                    return Ok(Invocation::Removed);
+                }
+
+                let data = stream_data(self.resolve, &stream)?;
+                let form = ContentStreamParser::new(Cursor::new(&data[..]))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let form = form.iter().map(normalize_operation).collect();
+                let own_resources = get_dictionary(self.resolve, &stream.info, "Resources")?;
+                let page_resources = self.page_resources;
+                self.page_resources = page_resources && own_resources.is_none();
+                let form_resources = own_resources.unwrap_or_else(|| resources.clone());
+
+                let (form, properties) = self.redact(&form_resources, form, form_ctm, false)?;
+                self.page_resources = page_resources;
+                let form: Vec<_> = form
+                    .iter()
+                    .enumerate()
+                    .map(|(index, operation)| with_properties(index, operation, &properties))
+                    .collect();
+                self.copy(resources, name, ctm, with_data(&stream, to_content(&form)))
+            }
+            _ => Ok(Invocation::Kept),
+        }
+    }
+
+    /// Adds the redacted copy of an XObject painted at `ctm`, named so that it doesn't clash
+    /// with the XObjects in `resources` or the other copies.
+    fn copy(
+        &mut self,
+        resources: &Dictionary,
+        name: &str,
+        ctm: &Matrix,
+        stream: PdfStream,
+    ) -> Result<Invocation, Error> {
+        let xobjects =
+            get_dictionary(self.resolve, resources, "XObject")?.unwrap_or_else(Dictionary::new);
+        let copy = (1..)
+            .map(|n| format!("{}R{}", name, n))
+            .find(|copy| xobjects.get(copy).is_none() && !self.xobjects.contains_key(copy))
+            .expect("an unused name");
+
+        self.xobjects.insert(copy.clone(), stream);
+        self.placements.push((name.to_string(), *ctm, copy.clone()));
+        Ok(Invocation::Renamed(copy))
+    }
+
+    /// The image with the samples of the pixels touching a region set to zero, or to the value
+    /// that doesn't paint for image masks. `None` if the samples can't be decoded.
+    fn mask_image(&self, stream: &PdfStream, ctm: &Matrix) -> Result<Option<PdfStream>, Error> {
+        let info = &stream.info;
+        let (width, height) = match (
+            get_integer(self.resolve, info, "Width")?,
+            get_integer(self.resolve, info, "Height")?,
+        ) {
+            (Some(width), Some(height)) if width > 0 && height > 0 => {
+                (width as usize, height as usize)
+            }
+            _ => return Ok(None),
+        };
+
+        let (components, bits, value) = if get_bool(self.resolve, info, "ImageMask")? == Some(true)
+        {
+            let inverted = matches!(
+                get_numbers(self.resolve, info, "Decode")?.as_deref(),
+                Some([first, _]) if *first == 1.0
+            );
+            (1, 1, !inverted)
+        } else {
+            let color_space = match get(self.resolve, info, "ColorSpace")? {
+                Some(color_space) => ColorSpace::from_primitive(self.resolve, &color_space).ok(),
+                None => None,
+            };
+            match (
+                color_space,
+                get_integer(self.resolve, info, "BitsPerComponent")?,
+            ) {
+                (Some(color_space), Some(bits)) if matches!(bits, 1 | 2 | 4 | 8 | 16) => {
+                    (color_space.components(), bits as usize, false)
+                }
+                _ => return Ok(None),
+            }
+        };
+
+        let mut data = match stream_data(self.resolve, stream) {
+            Ok(data) => data,
+            Err(_) => return Ok(None),
+        };
+        let row = match width
+            .checked_mul(components)
+            .and_then(|bits_per_row| bits_per_row.checked_mul(bits))
+        {
+            Some(bits_per_row) => bits_per_row.div_ceil(8),
+            None => return Ok(None),
+        };
+        if !matches!(row.checked_mul(height), Some(size) if data.len() >= size) {
+            return Ok(None);
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                // Image space has its first row at the top of the unit square.
+                let pixel = Rect::new(
+                    x as f32 / width as f32,
+                    1.0 - (y + 1) as f32 / height as f32,
+                    (x + 1) as f32 / width as f32,
+                    1.0 - y as f32 / height as f32,
+                );
+                if self.touches(&pixel.transform(ctm)) {
+                    let start = y * row * 8 + x * components * bits;
+                    for bit in start..start + components * bits {
+                        let mask = 0x80 >> (bit % 8);
+                        if value {
+                            data[bit / 8] |= mask;
+                        } else {
+                            data[bit / 8] &= !mask;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Some(with_data(stream, data)))
+    }
+
+    fn touches(&self, bounds: &Rect) -> bool {
+        self.regions.iter().any(|region| overlaps(region, bounds))
+    }
+}
+
+/// Whether two rectangles share any point, including along their edges.
+fn overlaps(a: &Rect, b: &Rect) -> bool {
+    a.x_min <= b.x_max && b.x_min <= a.x_max && a.y_min <= b.y_max && b.y_min <= a.y_max
+}
+
+/// The bounds of the unit square images are painted in.
+fn unit_square(ctm: &Matrix) -> Rect {
+    Rect::new(0.0, 0.0, 1.0, 1.0).transform(ctm)
+}
+
+/// A glyph of a shown string, and whether it's removed.
+struct LaidOutGlyph {
+    bytes: Range<usize>,
+    advance: f32,
+    removed: bool,
+}
+
+/// Removes the glyphs whose bytes share a UTF-8 character with a removed glyph's, so that what's
+/// left of the string is still UTF-8.
+fn whole_characters(bytes: &[u8], glyphs: &mut [LaidOutGlyph]) {
+    let string = match std::str::from_utf8(bytes) {
+        Ok(string) => string,
+        Err(_) => return glyphs.iter_mut().for_each(|glyph| glyph.removed = true),
+    };
+
+    loop {
+        let mut removed = vec![false; bytes.len()];
+        for glyph in glyphs.iter().filter(|glyph| glyph.removed) {
+            removed[glyph.bytes.clone()]
+                .iter_mut()
+                .for_each(|byte| *byte = true);
+        }
+        for (start, character) in string.char_indices() {
+            let end = start + character.len_utf8();
+            if removed[start..end].contains(&true) {
+                removed[start..end].iter_mut().for_each(|byte| *byte = true);
+            }
+        }
+
+        let mut changed = false;
+        for glyph in glyphs.iter_mut().filter(|glyph| !glyph.removed) {
+            if removed[glyph.bytes.clone()].contains(&true) {
+                glyph.removed = true;
+                changed = true;
+            }
+        }
+        if !changed {
+            return;
+        }
+    }
+}
+
+/// Appends the kept runs of a string to a `TJ` array, with offsets in place of removed glyphs.
+fn rebuild(
+    array: &mut Vec<TextOrGlyphPositioning<'static>>,
+    bytes: &[u8],
+    glyphs: &[LaidOutGlyph],
+    font_size: f32,
+) {
+    let mut run: Option<(Range<usize>, f32)> = None;
+    let flush = |array: &mut Vec<_>, run: &mut Option<(Range<usize>, f32)>| {
+        if let Some((range, advance)) = run.take() {
+            match std::str::from_utf8(&bytes[range]) {
+                Ok(text) => array.push(TextOrGlyphPositioning::Text(Cow::Owned(text.to_string()))),
+                Err(_) => push_offset(array, offset(advance, font_size)),
+            }
+        }
+    };
+
+    for glyph in glyphs {
+        if glyph.removed {
+            flush(array, &mut run);
+            push_offset(array, offset(glyph.advance, font_size));
+        } else {
+            run = Some(match run.take() {
+                Some((range, advance)) => (range.start..glyph.bytes.end, advance + glyph.advance),
+                None => (glyph.bytes.clone(), glyph.advance),
+            });
+        }
+    }
+    flush(array, &mut run);
+}
+
+/// Appends a `TJ` offset, adding it to an offset the array already ends with.
+fn push_offset(array: &mut Vec<TextOrGlyphPositioning>, offset: f32) {
+    match array.last_mut() {
+        Some(TextOrGlyphPositioning::GlyphPositioning(last)) => *last += offset,
+        _ => array.push(TextOrGlyphPositioning::GlyphPositioning(offset)),
+    }
+}
+
+/// The `TJ` offset that moves the text position as far as a glyph's advance.
+fn offset(advance: f32, font_size: f32) -> f32 {
+    if font_size == 0.0 {
+        0.0
+    } else {
+        -advance * 1000.0 / font_size
+    }
+}
+
+fn number(primitive: &Primitive) -> Option<f32> {
+    match primitive {
+        Primitive::Integer(integer) => Some(*integer as f32),
+        Primitive::Number(number) => Some(*number),
+        _ => None,
+    }
+}
+
+/// Marks the open sequences as having had content removed from them.
+fn touch(sequences: &mut [OpenSequence]) {
+    sequences
+        .iter_mut()
+        .for_each(|sequence| sequence.touched = true);
+}
+
+/// The operation, or the `BDC` operation with the property list replacing its own.
+fn with_properties<'a>(
+    index: usize,
+    operation: &Operation<'a>,
+    properties: &'a HashMap<usize, Dictionary>,
+) -> Operation<'a> {
+    match (operation, properties.get(&index)) {
+        (Operation::BeginMarkedContentSequenceWithPropertyList { tag, .. }, Some(properties)) => {
+            Operation::BeginMarkedContentSequenceWithPropertyList {
+                tag: *tag,
+                properties: PropertyList::Inline(properties),
+            }
+        }
+        _ => operation.clone(),
+    }
+}
+
+/// A copy of a stream with new, unfiltered data.
+fn with_data(stream: &PdfStream, data: Vec<u8>) -> PdfStream {
+    let mut info = stream.info.clone();
+    info.remove("Filter");
+    info.remove("DecodeParms");
+    info.insert("Length".to_string(), Primitive::Integer(data.len() as i32));
+
+    PdfStream { info, data }
+}
+
+fn fill_operation<'src>(rule: FillRule) -> Operation<'src> {
+    match rule {
+        FillRule::NonZeroWinding => Operation::FillPathUsingNonZeroWindingNumberRule,
+        FillRule::EvenOdd => Operation::FillPathUsingEvenOddRule,
+    }
+}
+
+/// How far a stroke can reach from its path in page space, with its caps and miter joins.
+fn stroke_margin(state: &GraphicsState) -> f32 {
+    let ctm = &state.ctm;
+    // The Frobenius norm is at least as large as the most the matrix stretches a distance by.
+    let scale = (ctm.a * ctm.a + ctm.b * ctm.b + ctm.c * ctm.c + ctm.d * ctm.d).sqrt();
+    // A zero width line is as thin as the device can draw, taken to be a unit wide.
+    let half_width = (state.line_width * scale).max(1.0) / 2.0;
+    let reach = match state.line_join {
+        LineJoinStyle::MiterJoin => state.miter_limit.max(SQRT_2),
+        _ => SQRT_2,
+    };
+
+    half_width * reach
+}
+
+fn expand(rect: &Rect, margin: f32) -> Rect {
+    Rect {
+        x_min: rect.x_min - margin,
+        y_min: rect.y_min - margin,
+        x_max: rect.x_max + margin,
+        y_max: rect.y_max + margin,
+    }
+}
+
+/// Appends the path construction operations for a polyline given in page space.
+fn append_polyline<'src>(
+    out: &mut Vec<Operation<'src>>,
+    points: &[(f32, f32)],
+    closed: bool,
+    inverse: &Matrix,
+) {
+    for (i, (x, y)) in points.iter().enumerate() {
+        let (x, y) = inverse.transform(*x, *y);
+        out.push(if i == 0 {
+            Operation::BeginNewSubpath { x, y }
+        } else {
+            Operation::AppendStraightLineSegmentToPath { x, y }
+        });
+    }
+    if closed {
+        out.push(Operation::CloseSubpath);
+    }
+}
+
+/// Intersects the clipping path with the outside of a region.
+fn exclude(out: &mut Vec<Operation>, region: &Rect, inverse: &Matrix) {
+    for piece in &outside(region) {
+        let corners = [
+            (piece.x_min, piece.y_min),
+            (piece.x_max, piece.y_min),
+            (piece.x_max, piece.y_max),
+            (piece.x_min, piece.y_max),
+        ];
+        append_polyline(out, &corners, true, inverse);
+    }
+    out.push(Operation::SetClippingPathUsingNonZeroWindingNumberRule);
+    out.push(Operation::EndPathWithoutFillingOrStroking);
+}
+
+/// The outside of a region, up to `FAR`, split into rectangles to its left and right and
+/// below and above it.
+fn outside(region: &Rect) -> [Rect; 4] {
+    [
+        Rect::new(-FAR, -FAR, region.x_min, FAR),
+        Rect::new(region.x_max, -FAR, FAR, FAR),
+        Rect::new(region.x_min, -FAR, region.x_max, region.y_min),
+        Rect::new(region.x_min, region.y_max, region.x_max, FAR),
+    ]
+}
+
+/// The parts of a polygon outside a region, as polygons that fill the same area with either
+/// fill rule: clipping keeps the polygon's orientation and the parts don't overlap.
+fn subtract(polygon: &[(f32, f32)], region: &Rect) -> Vec<Vec<(f32, f32)>> {
+    if polygon.len() < 3 {
+        return Vec::new();
+    }
+    if !overlaps(region, &polyline_bounds(polygon)) {
+        return vec![polygon.to_vec()];
+    }
+
+    outside(region)
+        .iter()
+        .map(|piece| {
+            let polygon = clip(polygon, |(x, _)| x >= piece.x_min, |t| t.0, piece.x_min);
+            let polygon = clip(&polygon, |(x, _)| x <= piece.x_max, |t| t.0, piece.x_max);
+            let polygon = clip(&polygon, |(_, y)| y >= piece.y_min, |t| t.1, piece.y_min);
+            clip(&polygon, |(_, y)| y <= piece.y_max, |t| t.1, piece.y_max)
+        })
+        .filter(|polygon| area(polygon) != 0.0)
+        .collect()
+}
+
+/// Clips a polygon to the half plane where `inside` holds, whose edge is where `coordinate` is
+/// `edge` (Sutherland-Hodgman).
+fn clip<I, C>(polygon: &[(f32, f32)], inside: I, coordinate: C, edge: f32) -> Vec<(f32, f32)>
+where
+    I: Fn((f32, f32)) -> bool,
+    C: Fn((f32, f32)) -> f32,
+{
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (i, &current) in polygon.iter().enumerate() {
+        let previous = polygon[(i + polygon.len() - 1) % polygon.len()];
+        let crossing = || {
+            let t = (edge - coordinate(previous)) / (coordinate(current) - coordinate(previous));
+            lerp(previous, current, t)
+        };
+        match (inside(previous), inside(current)) {
+            (true, true) => clipped.push(current),
+            (true, false) => clipped.push(crossing()),
+            (false, true) => {
+                clipped.push(crossing());
+                clipped.push(current);
+            }
+            (false, false) => {}
+        }
+    }
+
+    clipped
+}
+
+/// Twice the signed area of a polygon.
+fn area(polygon: &[(f32, f32)]) -> f32 {
+    (0..polygon.len())
+        .map(|i| {
+            let (x0, y0) = polygon[i];
+            let (x1, y1) = polygon[(i + 1) % polygon.len()];
+            x0 * y1 - x1 * y0
+        })
+        .sum()
+}
+
+/// The parts of a stroked subpath outside a region, as open polylines. Subpaths that don't
+/// touch the region are kept whole, closed or not.
+fn cut(points: &[(f32, f32)], closed: bool, region: &Rect) -> Vec<(Vec<(f32, f32)>, bool)> {
+    match points {
+        [] => return Vec::new(),
+        // A lone point still paints its caps.
+        [(x, y)] if region.contains(*x, *y) => return Vec::new(),
+        _ if !overlaps(region, &polyline_bounds(points)) => return vec![(points.to_vec(), closed)],
+        _ => {}
+    }
+
+    let mut points = points.to_vec();
+    if closed {
+        points.push(points[0]);
+    }
+
+    let (mut lines, mut line) = (Vec::new(), Vec::new());
+    for segment in points.windows(2) {
+        let (start, end) = (segment[0], segment[1]);
+        if line.is_empty() {
+            line.push(start);
+        }
+        match inside_segment(start, end, region) {
+            None => line.push(end),
+            Some((t0, t1)) => {
+                if t0 > 0.0 {
+                    line.push(lerp(start, end, t0));
+                }
+                if line.len() > 1 {
+                    lines.push((mem::take(&mut line), false));
+                }
+                line.clear();
+                if t1 < 1.0 {
+                    line.push(lerp(start, end, t1));
+                    line.push(end);
+                }
+            }
+        }
+    }
+    if line.len() > 1 {
+        lines.push((line, false));
+    }
+
+    lines
+}
+
+/// The part of a segment inside a region, as the range of its parameter (Liang-Barsky).
+fn inside_segment(start: (f32, f32), end: (f32, f32), region: &Rect) -> Option<(f32, f32)> {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let (mut t0, mut t1) = (0.0f32, 1.0f32);
+    for (p, q) in &[
+        (-dx, start.0 - region.x_min),
+        (dx, region.x_max - start.0),
+        (-dy, start.1 - region.y_min),
+        (dy, region.y_max - start.1),
+    ] {
+        if *p == 0.0 {
+            if *q < 0.0 {
+                return None;
+            }
+        } else if *p < 0.0 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+
+    if t0 < t1 {
+        Some((t0, t1))
+    } else {
+        None
+    }
+}
+
+fn lerp(start: (f32, f32), end: (f32, f32), t: f32) -> (f32, f32) {
+    (
+        start.0 + (end.0 - start.0) * t,
+        start.1 + (end.1 - start.1) * t,
+    )
+}
+
+fn polyline_bounds(points: &[(f32, f32)]) -> Rect {
+    points.iter().fold(
+        Rect::new(points[0].0, points[0].1, points[0].0, points[0].1),
+        |rect, (x, y)| Rect {
+            x_min: rect.x_min.min(*x),
+            y_min: rect.y_min.min(*y),
+            x_max: rect.x_max.max(*x),
+            y_max: rect.y_max.max(*y),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::{object::NoResolve, primitive::PdfString};
+
+    use super::*;
+    use crate::{
+        fixtures,
+        text::{extract_text, TextOptions},
+    };
+
+    #[test]
+    fn removes_glyphs_and_cuts_paths() {
+        let page = fixtures::page(
+            fixtures::courier_resources(),
+            b"BT /F1 10 Tf 100 700 Td (abc) Tj ET \
+              0 0 200 200 re f 108 160 1 1 re f 0 400 m 300 400 l S",
+        );
+
+        let redacted =
+            redact_page(&NoResolve, &page, &[Rect::new(107.0, 150.0, 110.0, 720.0)]).unwrap();
+        let content = String::from_utf8(redacted.content).unwrap();
+        let lines: Vec<_> = content.lines().collect();
+
+        assert!(lines.contains(&"[(a) -600 (c)] TJ"));
+        // The fill keeps the parts left, right and below the region.
+        assert_eq!(lines.iter().filter(|line| **line == "h").count(), 3);
+        assert!(!content.contains("108 160"));
+        // The stroke is cut in two, with room for its miter joins.
+        assert_eq!(lines.iter().filter(|line| line.ends_with(" m")).count(), 5);
+        assert!(lines.contains(&"117.07107 400 m"));
+    }
+
+    #[test]
+    fn removes_whole_strings_in_fonts_without_widths() {
+        let mut font = Dictionary::new();
+        font.insert(
+            "BaseFont".to_string(),
+            Primitive::Name("Helvetica".to_string()),
+        );
+        let mut fonts = Dictionary::new();
+        fonts.insert("F2".to_string(), Primitive::Dictionary(font));
+        let mut resources = Dictionary::new();
+        resources.insert("Font".to_string(), Primitive::Dictionary(fonts));
+        let page = fixtures::page(
+            resources,
+            b"BT /F2 10 Tf 100 700 Td (abc) Tj ET BT /F2 10 Tf 100 600 Td (def) Tj ET",
+        );
+
+        // The region is past where the average widths put the first string, but not past
+        // where wider glyphs would.
+        let redacted =
+            redact_page(&NoResolve, &page, &[Rect::new(120.0, 690.0, 125.0, 720.0)]).unwrap();
+        let content = String::from_utf8(redacted.content).unwrap();
+        let lines: Vec<_> = content.lines().collect();
+
+        assert!(!content.contains("abc"));
+        assert!(lines.contains(&"[ -1500 ] TJ"));
+        assert!(lines.contains(&"(def) Tj"));
+    }
+
+    #[test]
+    fn removes_whole_strings_in_vertical_fonts() {
+        let mut descendant = Dictionary::new();
+        descendant.insert("DW".to_string(), Primitive::Integer(1000));
+        let mut font = Dictionary::new();
+        font.insert("Subtype".to_string(), Primitive::Name("Type0".to_string()));
+        font.insert(
+            "Encoding".to_string(),
+            Primitive::Name("Identity-V".to_string()),
+        );
+        font.insert(
+            "DescendantFonts".to_string(),
+            Primitive::Array(vec![Primitive::Dictionary(descendant)]),
+        );
+        let mut fonts = Dictionary::new();
+        fonts.insert("F3".to_string(), Primitive::Dictionary(font));
+        let mut resources = Dictionary::new();
+        resources.insert("Font".to_string(), Primitive::Dictionary(fonts));
+        let page = fixtures::page(
+            resources,
+            b"BT /F3 10 Tf 100 700 Td <0001000200030004> Tj ET \
+              BT /F3 10 Tf 300 700 Td <0005000600070008> Tj ET",
+        );
+
+        // The region is below the start of the first string, where its third glyph is written,
+        // and far from where laying it out horizontally would put any of them.
+        let redacted =
+            redact_page(&NoResolve, &page, &[Rect::new(95.0, 672.0, 105.0, 678.0)]).unwrap();
+        let content = String::from_utf8(redacted.content).unwrap();
+        let lines: Vec<_> = content.lines().collect();
+
+        assert!(lines.contains(&"[ 4000 ] TJ"));
+        assert_eq!(lines.iter().filter(|line| line.ends_with(" Tj")).count(), 1);
+    }
+
+    #[test]
+    fn strips_replacement_text_from_redacted_sequences() {
+        let mut secret = Dictionary::new();
+        secret.insert(
+            "Alt".to_string(),
+            Primitive::String(PdfString::new(b"secret".to_vec())),
+        );
+        secret.insert("MCID".to_string(), Primitive::Integer(1));
+        let mut properties = Dictionary::new();
+        properties.insert("Secret".to_string(), Primitive::Dictionary(secret));
+        let mut resources = fixtures::courier_resources();
+        resources.insert("Properties".to_string(), Primitive::Dictionary(properties));
+
+        let page = fixtures::page(
+            resources.clone(),
+            b"BT /F1 10 Tf 100 700 Td /Span <</ActualText (secret)>> BDC (secret) Tj EMC \
+              0 -100 Td /Span /Secret BDC (secret) Tj EMC \
+              0 -100 Td /Span <</ActualText (kept)>> BDC (kept) Tj EMC ET",
+        );
+        let redacted =
+            redact_page(&NoResolve, &page, &[Rect::new(0.0, 550.0, 612.0, 720.0)]).unwrap();
+        let content = String::from_utf8(redacted.content).unwrap();
+
+        // The named property list is given inline, with its other entries.
+        assert!(content.contains("/Span <</MCID 1 >> BDC"));
+        assert_eq!(
+            extract_text(
+                &NoResolve,
+                &fixtures::page(resources, content.as_bytes()),
+                TextOptions::default()
+            )
+            .unwrap(),
+            "kept"
+        );
+    }
+
+    #[test]
+    fn redacts_a_copy_for_each_placement() {
+        let mut image = Dictionary::new();
+        image.insert("Subtype".to_string(), Primitive::Name("Image".to_string()));
+        image.insert("Width".to_string(), Primitive::Integer(2));
+        image.insert("Height".to_string(), Primitive::Integer(2));
+        image.insert(
+            "ColorSpace".to_string(),
+            Primitive::Name("DeviceGray".to_string()),
+        );
+        image.insert("BitsPerComponent".to_string(), Primitive::Integer(8));
+        let mut xobjects = Dictionary::new();
+        xobjects.insert(
+            "Im0".to_string(),
+            Primitive::Stream(PdfStream {
+                info: image,
+                data: vec![255; 4],
+            }),
+        );
+        let mut resources = Dictionary::new();
+        resources.insert("XObject".to_string(), Primitive::Dictionary(xobjects));
+        // The image is painted twice over the region and once away from it.
+        let page = fixtures::page(
+            resources,
+            b"q 10 0 0 10 0 0 cm /Im0 Do Q q 10 0 0 10 100 100 cm /Im0 Do Q \
+              q 10 0 0 10 0 0 cm /Im0 Do Q",
+        );
+
+        let redacted = redact_page(&NoResolve, &page, &[Rect::new(0.0, 0.0, 4.0, 4.0)]).unwrap();
+        let content = String::from_utf8(redacted.content).unwrap();
+        let painted: Vec<_> = content
+            .lines()
+            .filter(|line| line.ends_with("Do"))
+            .collect();
+
+        assert_eq!(painted, vec!["/Im0R1 Do", "/Im0 Do", "/Im0R1 Do"]);
+        assert_eq!(redacted.xobjects.len(), 1);
+        // Only the lower left pixel is in the region.
+        assert_eq!(redacted.xobjects["Im0R1"].data, vec![255, 255, 0, 255]);
+        assert!(redacted.unused.is_empty());
+
+        let redacted =
+            redact_page(&NoResolve, &page, &[Rect::new(0.0, 0.0, 200.0, 200.0)]).unwrap();
+        assert_eq!(redacted.unused, vec!["Im0".to_string()]);
+    }
+}
@@ -0,0 +1,466 @@
+use std::{collections::HashMap, ops::Range};
+
+use pdf::{
+    object::Resolve,
+    primitive::{Dictionary, Primitive},
+};
+use regex::Regex;
+
+use crate::{
+    document::{Location, RawPage},
+    error::Error,
+    font::Font,
+    geometry::Matrix,
+    graphics_state::GraphicsState,
+    painting::{walk_content, ContentVisitor, Step},
+    text::{breaks_line, MarkedText, TextOptions, WORD_GAP},
+    text_state::TextState,
+    Operation, TextOrGlyphPositioning,
+};
+
+/// A match of a search on a page.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    /// The matched text, with lines separated by newlines.
+    pub text: String,
+    /// The quadrilaterals covering the matched glyphs, one for each line the match spans, in
+    /// default user space: lower left, lower right, upper right and upper left.
+    pub quads: Vec<[(f32, f32); 4]>,
+    /// The indices of the operations showing the matched glyphs in `RawPage::operations`, in
+    /// order. Glyphs shown by a Form XObject belong to the `Do` painting it.
+    pub operations: Vec<usize>,
+}
+
+/// Finds a string in the text of a page. Any whitespace in `query` matches any whitespace in the
+/// page's text, including line breaks, so matches can span lines.
+pub fn search<R: Resolve>(
+    resolve: &R,
+    page: &RawPage,
+    query: &str,
+) -> Result<Vec<SearchHit>, Error> {
+    let pattern = query
+        .split_whitespace()
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(r"\s+");
+    if pattern.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let regex = Regex::new(&pattern).map_err(|error| Error::InvalidEntry(error.to_string()))?;
+    search_regex(resolve, page, &regex)
+}
+
+/// Finds the matches of a regular expression in the text of a page, which has the lines shown
+/// by the page separated by newlines and words separated by spaces. The text is the one
+/// `extract_text` gives with the default `TextOptions`: artifacts are left out, and glyphs
+/// replaced by an `/ActualText` are placed at the start of their replacement.
+pub fn search_regex<R: Resolve>(
+    resolve: &R,
+    page: &RawPage,
+    regex: &Regex,
+) -> Result<Vec<SearchHit>, Error> {
+    let operations = page.operations(resolve)?;
+
+    let mut layout = Layout {
+        resolve,
+        text: String::new(),
+        characters: Vec::new(),
+        line: 0,
+        line_break: false,
+        marked: MarkedText::new(TextOptions::default()),
+        replacement: 0,
+        streams: vec![Stream::default()],
+    };
+    walk_content(
+        resolve,
+        &operations,
+        GraphicsState::default(),
+        &page.resources(resolve)?,
+        vec![Location::Page(page.number)],
+        &mut layout,
+    )?;
+
+    Ok(regex
+        .find_iter(&layout.text)
+        .filter(|found| !found.as_str().is_empty())
+        .map(|found| layout.hit(found.range()))
+        .collect())
+}
+
+/// A character of a page's text and the glyph showing it.
+struct PlacedCharacter {
+    /// Where the character starts in the page's text.
+    start: usize,
+    quad: [(f32, f32); 4],
+    operation: usize,
+    line: usize,
+}
+
+/// The text of a page, with where each character is shown.
+struct Layout<'a, R> {
+    resolve: &'a R,
+    text: String,
+    characters: Vec<PlacedCharacter>,
+    /// Counts the lines, so that characters on different lines aren't covered by one quad.
+    line: usize,
+    /// Whether the next text starts on a new line.
+    line_break: bool,
+    marked: MarkedText,
+    /// Where the text of the last replaced sequence starts.
+    replacement: usize,
+    /// The content stream being walked, and the ones painting it.
+    streams: Vec<Stream>,
+}
+
+/// The text state of a content stream being laid out.
+#[derive(Default)]
+struct Stream {
+    text: TextState,
+    /// The text states saved by `q`.
+    saved_text: Vec<TextState>,
+    fonts: HashMap<String, Font>,
+    /// The operation of the page's content that paints the Form XObject this content belongs
+    /// to, if it's a form's.
+    form: Option<usize>,
+}
+
+impl<'a, R: Resolve> ContentVisitor for Layout<'a, R> {
+    fn operation(&mut self, step: &Step) -> Result<(), Error> {
+        let mut stream = self.streams.pop().expect("the page's content stream");
+        let Stream {
+            text,
+            saved_text,
+            fonts,
+            form,
+        } = &mut stream;
+        let (index, operation, resources) =
+            (form.unwrap_or(step.index), step.operation, step.resources);
+        let ctm = &step.state.ctm;
+        text.update(operation);
+
+        if let Some(replacement) = self.marked.update(self.resolve, step)? {
+            self.break_line();
+            self.replacement = self.text.len();
+            replacement
+                .chars()
+                .for_each(|character| self.append(character, None));
+        }
+        if breaks_line(operation) {
+            self.line_break = true;
+        }
+
+        match operation {
+            Operation::ShowText(string)
+            | Operation::MoveToNextLineAndShowText(string)
+            | Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText {
+                text: string, ..
+            } => {
+                let font = text.load_font(self.resolve, resources, fonts)?;
+                self.show(text, font, string, ctm, index);
+            }
+            Operation::ShowTextAllowingIndividualGlyphPositioning(array) => {
+                let font = text.load_font(self.resolve, resources, fonts)?;
+                for item in array {
+                    match item {
+                        TextOrGlyphPositioning::Text(string) => {
+                            self.show(text, font, string, ctm, index)
+                        }
+                        TextOrGlyphPositioning::GlyphPositioning(offset) => {
+                            text.adjust(font, *offset);
+                            if -offset >= WORD_GAP {
+                                self.push(' ', None);
+                            }
+                        }
+                    }
+                }
+            }
+            Operation::Unknown { operator, operands }
+                if matches!(*operator, "Tj" | "TJ" | "'" | "\"") =>
+            {
+                // Strings that aren't UTF-8 can't be searched, but still move the text position
+                // for the text after them.
+                self.skip(text, resources, fonts, operator, operands, step.state)?;
+            }
+            Operation::SaveGraphicsState => saved_text.push(text.clone()),
+            Operation::RestoreGraphicsState => {
+                if let Some(saved) = saved_text.pop() {
+                    *text = saved;
+                }
+            }
+            _ => {}
+        }
+
+        self.streams.push(stream);
+        Ok(())
+    }
+
+    /// The operations of a Form XObject are attributed to the operation of the page's content
+    /// painting it.
+    fn begin_form(&mut self, step: &Step) -> Result<(), Error> {
+        let form = self.streams.last().and_then(|stream| stream.form);
+        self.streams.push(Stream {
+            form: Some(form.unwrap_or(step.index)),
+            ..Stream::default()
+        });
+        self.marked.begin_form();
+        self.line_break = true;
+        Ok(())
+    }
+
+    fn end_form(&mut self, _step: &Step) -> Result<(), Error> {
+        self.streams.pop();
+        self.marked.end_form();
+        self.line_break = true;
+        Ok(())
+    }
+}
+
+impl<'a, R: Resolve> Layout<'a, R> {
+    /// Lays out a string, placing each of its characters at the glyph whose bytes it starts in,
+    /// or its glyphs at the start of the replacement standing in for it.
+    fn show(
+        &mut self,
+        text: &mut TextState,
+        font: &Font,
+        string: &str,
+        ctm: &Matrix,
+        operation: usize,
+    ) {
+        let mut glyphs = Vec::new();
+        text.show(font, string.as_bytes(), ctm, |glyph| glyphs.push(glyph));
+
+        if self.marked.is_replaced() {
+            for glyph in glyphs {
+                self.characters.push(PlacedCharacter {
+                    start: self.replacement,
+                    quad: glyph.quad,
+                    operation,
+                    line: self.line,
+                });
+            }
+            return;
+        }
+
+        let mut glyph = 0;
+        for (start, character) in string.char_indices() {
+            while glyph + 1 < glyphs.len() && glyphs[glyph + 1].bytes.start <= start {
+                glyph += 1;
+            }
+            let quad = glyphs.get(glyph).map(|glyph| glyph.quad);
+            self.push(character, quad.map(|quad| (quad, operation)));
+        }
+    }
+
+    /// Moves the text position past the strings of a text showing operation that couldn't be
+    /// typed.
+    fn skip(
+        &mut self,
+        text: &mut TextState,
+        resources: &Dictionary,
+        fonts: &mut HashMap<String, Font>,
+        operator: &str,
+        operands: &[Primitive],
+        state: &GraphicsState,
+    ) -> Result<(), Error> {
+        if operator == "'" || operator == "\"" {
+            text.update(&Operation::MoveToStartOfNextTextLine);
+        }
+        let font = text.load_font(self.resolve, resources, fonts)?;
+        let show = |text: &mut TextState, primitive: &Primitive| match primitive {
+            Primitive::String(string) => text.show(font, string.as_bytes(), &state.ctm, |_| {}),
+            Primitive::Integer(offset) => text.adjust(font, *offset as f32),
+            Primitive::Number(offset) => text.adjust(font, *offset),
+            _ => {}
+        };
+        match operands.last() {
+            Some(Primitive::Array(array)) if operator == "TJ" => {
+                array.iter().for_each(|item| show(text, item))
+            }
+            Some(string @ Primitive::String(_)) => show(text, string),
+            _ => {}
+        }
+
+        self.push(' ', None);
+        Ok(())
+    }
+
+    /// Appends a character to the page's text, unless it's left out by marked content.
+    fn push(&mut self, character: char, glyph: Option<([(f32, f32); 4], usize)>) {
+        if !self.marked.is_suppressed() {
+            self.append(character, glyph);
+        }
+    }
+
+    /// Appends a character to the page's text, starting a new line first if one is due.
+    fn append(&mut self, character: char, glyph: Option<([(f32, f32); 4], usize)>) {
+        self.break_line();
+        if character == ' ' && (self.text.is_empty() || self.text.ends_with(char::is_whitespace)) {
+            return;
+        }
+
+        if let Some((quad, operation)) = glyph {
+            self.characters.push(PlacedCharacter {
+                start: self.text.len(),
+                quad,
+                operation,
+                line: self.line,
+            });
+        }
+        self.text.push(character);
+    }
+
+    /// Starts a new line if one is due.
+    fn break_line(&mut self) {
+        if self.line_break {
+            if !self.text.is_empty() && !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+            self.line += 1;
+            self.line_break = false;
+        }
+    }
+
+    fn hit(&self, range: Range<usize>) -> SearchHit {
+        let first = self
+            .characters
+            .partition_point(|character| character.start < range.start);
+        let last = self
+            .characters
+            .partition_point(|character| character.start < range.end);
+
+        let mut quads: Vec<[(f32, f32); 4]> = Vec::new();
+        let mut operations: Vec<usize> = Vec::new();
+        let mut line = None;
+        for character in &self.characters[first..last] {
+            match quads.last_mut() {
+                Some(quad) if line == Some(character.line) => {
+                    quad[1] = character.quad[1];
+                    quad[2] = character.quad[2];
+                }
+                _ => quads.push(character.quad),
+            }
+            line = Some(character.line);
+            operations.push(character.operation);
+        }
+        operations.sort_unstable();
+        operations.dedup();
+
+        SearchHit {
+            text: self.text[range].to_string(),
+            quads,
+            operations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::{object::NoResolve, primitive::PdfStream};
+
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn finds_text_across_operations_and_lines() {
+        let page = fixtures::page(
+            fixtures::courier_resources(),
+            b"BT /F1 10 Tf 100 700 Td (Hello Wor) Tj [(ld) -300 (again)] TJ \
+              0 -12 Td (next) Tj ET",
+        );
+
+        let hits = search(&NoResolve, &page, "World").unwrap();
+        assert_eq!(
+            hits,
+            vec![SearchHit {
+                text: "World".to_string(),
+                quads: vec![[
+                    (136.0, 698.0),
+                    (166.0, 698.0),
+                    (166.0, 708.0),
+                    (136.0, 708.0)
+                ]],
+                operations: vec![3, 4],
+            }]
+        );
+
+        let hits = search(&NoResolve, &page, "again next").unwrap();
+        assert_eq!(hits[0].text, "again\nnext");
+        assert_eq!(hits[0].quads.len(), 2);
+        assert_eq!(hits[0].operations, vec![4, 6]);
+
+        let regex = Regex::new(r"W\w+").unwrap();
+        assert_eq!(
+            search_regex(&NoResolve, &page, &regex).unwrap()[0].text,
+            "World"
+        );
+    }
+
+    #[test]
+    fn skips_artifacts_and_finds_actual_text() {
+        let page = fixtures::page(
+            fixtures::courier_resources(),
+            b"BT /F1 10 Tf 100 700 Td /Artifact BMC (Header) Tj EMC \
+              /Span << /ActualText (fi) >> BDC (x) Tj EMC (ne) Tj ET",
+        );
+
+        assert!(search(&NoResolve, &page, "Header").unwrap().is_empty());
+        assert!(search(&NoResolve, &page, "xne").unwrap().is_empty());
+        assert_eq!(
+            search(&NoResolve, &page, "fine").unwrap(),
+            vec![SearchHit {
+                text: "fine".to_string(),
+                quads: vec![[
+                    (136.0, 698.0),
+                    (154.0, 698.0),
+                    (154.0, 708.0),
+                    (136.0, 708.0)
+                ]],
+                operations: vec![7, 9],
+            }]
+        );
+    }
+
+    #[test]
+    fn attributes_form_text_to_the_painting_operation() {
+        let mut form = Dictionary::new();
+        form.insert("Subtype".to_string(), Primitive::Name("Form".to_string()));
+        form.insert(
+            "Matrix".to_string(),
+            Primitive::Array(
+                [1, 0, 0, 1, 0, 100]
+                    .iter()
+                    .map(|n| Primitive::Integer(*n))
+                    .collect(),
+            ),
+        );
+        let mut xobjects = Dictionary::new();
+        xobjects.insert(
+            "Fm0".to_string(),
+            Primitive::Stream(PdfStream {
+                info: form,
+                data: b"BT /F1 10 Tf 100 600 Td (Form) Tj ET".to_vec(),
+            }),
+        );
+        let mut resources = fixtures::courier_resources();
+        resources.insert("XObject".to_string(), Primitive::Dictionary(xobjects));
+        let page = fixtures::page(
+            resources,
+            b"q 1 0 0 1 0 -50 cm /Fm0 Do Q BT /F1 10 Tf (Page) Tj ET",
+        );
+
+        let hits = search(&NoResolve, &page, "Form Page").unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].text, "Form\nPage");
+        assert_eq!(hits[0].operations, vec![2, 6]);
+        assert_eq!(
+            hits[0].quads[0],
+            [
+                (100.0, 648.0),
+                (124.0, 648.0),
+                (124.0, 658.0),
+                (100.0, 658.0)
+            ]
+        );
+    }
+}
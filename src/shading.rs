@@ -0,0 +1,920 @@
+use pdf::{
+    object::Resolve,
+    primitive::{Dictionary, Primitive},
+};
+
+use crate::{
+    color::ColorSpace,
+    document::{self, get, get_bool, get_integer, get_numbers, get_resource, stream_data},
+    error::Error,
+    function::{interpolate, BitReader, Function},
+    geometry::{Matrix, Rect},
+};
+
+/// The number of steps in each direction patches are divided into when sampling them.
+const PATCH_STEPS: usize = 16;
+
+/// The order of a patch's control points in a mesh data stream, as `(u, v)` indices. Coons
+/// patches only give the first twelve.
+const PATCH_POINT_ORDER: [(usize, usize); 16] = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (1, 3),
+    (2, 3),
+    (3, 3),
+    (3, 2),
+    (3, 1),
+    (3, 0),
+    (2, 0),
+    (1, 0),
+    (1, 1),
+    (1, 2),
+    (2, 2),
+    (2, 1),
+];
+
+/// A shading dictionary, section 8.7.4.3 of ISO 32000-1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shading {
+    /// `/ColorSpace`, which the shading's colors are given in.
+    pub color_space: ColorSpace,
+    /// `/Background`, only used by `sh` when painting a shading pattern.
+    pub background: Option<Vec<f32>>,
+    /// `/BBox`, in shading space.
+    pub bbox: Option<Rect>,
+    /// `/AntiAlias`
+    pub anti_alias: bool,
+    pub shading_type: ShadingType,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShadingType {
+    /// Type 1
+    FunctionBased {
+        domain: [f32; 4],
+        /// Maps the domain to shading space.
+        matrix: Matrix,
+        functions: Vec<Function>,
+    },
+    /// Type 2
+    Axial {
+        /// `[x0 y0 x1 y1]`
+        coords: [f32; 4],
+        domain: [f32; 2],
+        functions: Vec<Function>,
+        extend: [bool; 2],
+    },
+    /// Type 3
+    Radial {
+        /// `[x0 y0 r0 x1 y1 r1]`
+        coords: [f32; 6],
+        domain: [f32; 2],
+        functions: Vec<Function>,
+        extend: [bool; 2],
+    },
+    /// Type 4
+    FreeFormGouraud {
+        triangles: Vec<[Vertex; 3]>,
+        functions: Vec<Function>,
+    },
+    /// Type 5
+    LatticeFormGouraud {
+        vertices_per_row: usize,
+        /// The vertices, row by row.
+        vertices: Vec<Vertex>,
+        functions: Vec<Function>,
+    },
+    /// Type 6, with the interior control points of each patch computed so that it can be
+    /// treated as a tensor-product patch.
+    CoonsPatch {
+        patches: Vec<Patch>,
+        functions: Vec<Function>,
+    },
+    /// Type 7
+    TensorProductPatch {
+        patches: Vec<Patch>,
+        functions: Vec<Function>,
+    },
+}
+
+/// A vertex of a Gouraud shaded triangle mesh.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vertex {
+    pub x: f32,
+    pub y: f32,
+    /// Color components, or a single parametric value if the shading has functions.
+    pub color: Vec<f32>,
+}
+
+/// A tensor-product patch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Patch {
+    /// The control points, indexed by `[u][v]`.
+    pub points: [[(f32, f32); 4]; 4],
+    /// The colors of the corners `(0, 0)`, `(0, 1)`, `(1, 1)` and `(1, 0)`, in that order.
+    pub colors: [Vec<f32>; 4],
+}
+
+impl Shading {
+    /// Looks up the shading named by a `sh` operation in the `/Shading` resources.
+    pub fn from_resources<R: Resolve>(
+        resolve: &R,
+        resources: &Dictionary,
+        name: &str,
+    ) -> Result<Self, Error> {
+        Self::from_primitive(resolve, &get_resource(resolve, resources, "Shading", name)?)
+    }
+
+    /// Parses a shading dictionary, or a stream for the mesh based types.
+    pub fn from_primitive<R: Resolve>(resolve: &R, primitive: &Primitive) -> Result<Self, Error> {
+        let (dictionary, data) = match document::resolve(resolve, primitive)? {
+            Primitive::Dictionary(dictionary) => (dictionary, None),
+            Primitive::Stream(stream) => {
+                let data = stream_data(resolve, &stream)?;
+                (stream.info, Some(data))
+            }
+            _ => return Err(Error::InvalidEntry("Shading".into())),
+        };
+
+        let color_space = get(resolve, &dictionary, "ColorSpace")?
+            .ok_or_else(|| Error::MissingEntry("ColorSpace".into()))?;
+        let color_space = ColorSpace::from_primitive(resolve, &color_space)?;
+        let bbox = match get_numbers(resolve, &dictionary, "BBox")? {
+            Some(bbox) => {
+                Some(Rect::from_slice(&bbox).ok_or_else(|| Error::InvalidEntry("BBox".into()))?)
+            }
+            None => None,
+        };
+        let functions = Function::from_entry(resolve, &dictionary, "Function")?;
+
+        let shading_type = match (
+            get_integer(resolve, &dictionary, "ShadingType")?,
+            data.as_deref(),
+        ) {
+            (Some(1), _) => {
+                let domain = get_numbers(resolve, &dictionary, "Domain")?
+                    .unwrap_or_else(|| vec![0.0, 1.0, 0.0, 1.0]);
+                let matrix = match get_numbers(resolve, &dictionary, "Matrix")? {
+                    Some(matrix) => Matrix::from_slice(&matrix)
+                        .ok_or_else(|| Error::InvalidEntry("Matrix".into()))?,
+                    None => Matrix::IDENTITY,
+                };
+
+                ShadingType::FunctionBased {
+                    domain: array(&domain, "Domain")?,
+                    matrix,
+                    functions: required(functions)?,
+                }
+            }
+            (Some(2), _) => ShadingType::Axial {
+                coords: array(&numbers(resolve, &dictionary, "Coords")?, "Coords")?,
+                domain: domain(resolve, &dictionary)?,
+                functions: required(functions)?,
+                extend: extend(resolve, &dictionary)?,
+            },
+            (Some(3), _) => ShadingType::Radial {
+                coords: array(&numbers(resolve, &dictionary, "Coords")?, "Coords")?,
+                domain: domain(resolve, &dictionary)?,
+                functions: required(functions)?,
+                extend: extend(resolve, &dictionary)?,
+            },
+            (Some(shading_type @ 4..=7), Some(data)) => {
+                let components = if functions.is_empty() {
+                    color_space.components()
+                } else {
+                    1
+                };
+                let mut mesh = MeshReader::new(resolve, &dictionary, data, components)?;
+
+                match shading_type {
+                    4 => ShadingType::FreeFormGouraud {
+                        triangles: mesh.free_form_triangles()?,
+                        functions,
+                    },
+                    5 => {
+                        let vertices_per_row = get_integer(resolve, &dictionary, "VerticesPerRow")?
+                            .filter(|count| *count >= 2)
+                            .ok_or_else(|| Error::InvalidEntry("VerticesPerRow".into()))?;
+
+                        ShadingType::LatticeFormGouraud {
+                            vertices_per_row: vertices_per_row as usize,
+                            vertices: mesh.lattice_vertices(),
+                            functions,
+                        }
+                    }
+                    6 => ShadingType::CoonsPatch {
+                        patches: mesh.patches(false)?,
+                        functions,
+                    },
+                    _ => ShadingType::TensorProductPatch {
+                        patches: mesh.patches(true)?,
+                        functions,
+                    },
+                }
+            }
+            _ => return Err(Error::InvalidEntry("ShadingType".into())),
+        };
+
+        Ok(Shading {
+            color_space,
+            background: get_numbers(resolve, &dictionary, "Background")?,
+            bbox,
+            anti_alias: get_bool(resolve, &dictionary, "AntiAlias")?.unwrap_or(false),
+            shading_type,
+        })
+    }
+
+    /// The color at a point in shading space, in the shading's color space, or `None` if the
+    /// shading doesn't paint that point.
+    pub fn color_at(&self, x: f32, y: f32) -> Option<Vec<f32>> {
+        if let Some(bbox) = &self.bbox {
+            if !bbox.contains(x, y) {
+                return None;
+            }
+        }
+
+        match &self.shading_type {
+            ShadingType::FunctionBased {
+                domain,
+                matrix,
+                functions,
+            } => {
+                let (u, v) = matrix.inverse()?.transform(x, y);
+                let inside = u >= domain[0] && u <= domain[1] && v >= domain[2] && v <= domain[3];
+
+                if inside {
+                    Some(eval(functions, &[u, v]))
+                } else {
+                    None
+                }
+            }
+            ShadingType::Axial {
+                coords: [x0, y0, x1, y1],
+                domain,
+                functions,
+                extend,
+            } => {
+                let (dx, dy) = (x1 - x0, y1 - y0);
+                let length = dx * dx + dy * dy;
+                let s = if length == 0.0 {
+                    0.0
+                } else {
+                    (dx * (x - x0) + dy * (y - y0)) / length
+                };
+
+                parameter(s, domain, extend).map(|t| eval(functions, &[t]))
+            }
+            ShadingType::Radial {
+                coords,
+                domain,
+                functions,
+                extend,
+            } => radial_parameter(coords, extend, x, y)
+                .and_then(|s| parameter(s, domain, extend))
+                .map(|t| eval(functions, &[t])),
+            ShadingType::FreeFormGouraud {
+                triangles,
+                functions,
+            } => triangles
+                .iter()
+                .rev()
+                .find_map(|[a, b, c]| sample_triangle(a, b, c, x, y))
+                .map(|color| mesh_color(functions, color)),
+            ShadingType::LatticeFormGouraud {
+                vertices_per_row,
+                vertices,
+                functions,
+            } => {
+                let rows = vertices.chunks_exact(*vertices_per_row).collect::<Vec<_>>();
+
+                rows.windows(2)
+                    .rev()
+                    .find_map(|rows| {
+                        (0..vertices_per_row - 1).rev().find_map(|i| {
+                            let (a, b) = (&rows[0][i], &rows[0][i + 1]);
+                            let (c, d) = (&rows[1][i], &rows[1][i + 1]);
+
+                            sample_triangle(b, d, c, x, y)
+                                .or_else(|| sample_triangle(a, b, c, x, y))
+                        })
+                    })
+                    .map(|color| mesh_color(functions, color))
+            }
+            ShadingType::CoonsPatch { patches, functions }
+            | ShadingType::TensorProductPatch { patches, functions } => patches
+                .iter()
+                .rev()
+                .find_map(|patch| patch.sample(x, y))
+                .map(|color| mesh_color(functions, color)),
+        }
+    }
+}
+
+impl Patch {
+    pub fn point(&self, u: f32, v: f32) -> (f32, f32) {
+        let (bu, bv) = (bernstein(u), bernstein(v));
+        let mut point = (0.0, 0.0);
+
+        for (row, bu) in self.points.iter().zip(&bu) {
+            for ((x, y), bv) in row.iter().zip(&bv) {
+                point.0 += bu * bv * x;
+                point.1 += bu * bv * y;
+            }
+        }
+
+        point
+    }
+
+    /// The color at `(u, v)`, interpolated between the corner colors.
+    pub fn color(&self, u: f32, v: f32) -> Vec<f32> {
+        let [c00, c01, c11, c10] = &self.colors;
+
+        (0..c00.len())
+            .map(|k| {
+                (1.0 - u) * (1.0 - v) * c00[k]
+                    + (1.0 - u) * v * c01[k]
+                    + u * v * c11[k]
+                    + u * (1.0 - v) * c10[k]
+            })
+            .collect()
+    }
+
+    /// The color painted at `(x, y)`, found by dividing the patch into triangles.
+    fn sample(&self, x: f32, y: f32) -> Option<Vec<f32>> {
+        // A Bézier surface lies inside the bounding box of its control points.
+        let points = self.points.iter().flatten();
+        let (x_min, x_max) = points
+            .clone()
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), point| {
+                (min.min(point.0), max.max(point.0))
+            });
+        let (y_min, y_max) = points
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), point| {
+                (min.min(point.1), max.max(point.1))
+            });
+        if !Rect::new(x_min, y_min, x_max, y_max).contains(x, y) {
+            return None;
+        }
+
+        let step = 1.0 / PATCH_STEPS as f32;
+        let vertex = |i: usize, j: usize| {
+            let (u, v) = (i as f32 * step, j as f32 * step);
+            let (x, y) = self.point(u, v);
+            Vertex {
+                x,
+                y,
+                color: vec![u, v],
+            }
+        };
+
+        // Later parts of a patch are painted over earlier ones where it folds over itself.
+        for i in (0..PATCH_STEPS).rev() {
+            for j in (0..PATCH_STEPS).rev() {
+                let (a, b) = (vertex(i, j), vertex(i + 1, j));
+                let (c, d) = (vertex(i, j + 1), vertex(i + 1, j + 1));
+
+                if let Some(uv) =
+                    sample_triangle(&b, &d, &c, x, y).or_else(|| sample_triangle(&a, &b, &c, x, y))
+                {
+                    return Some(self.color(uv[0], uv[1]));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+fn bernstein(t: f32) -> [f32; 4] {
+    let s = 1.0 - t;
+    [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t]
+}
+
+/// Interpolates the colors of a triangle's vertices at `(x, y)`, if it's inside the triangle.
+fn sample_triangle(a: &Vertex, b: &Vertex, c: &Vertex, x: f32, y: f32) -> Option<Vec<f32>> {
+    let area = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+    if area == 0.0 {
+        return None;
+    }
+
+    let wb = ((x - a.x) * (c.y - a.y) - (c.x - a.x) * (y - a.y)) / area;
+    let wc = ((b.x - a.x) * (y - a.y) - (x - a.x) * (b.y - a.y)) / area;
+    let wa = 1.0 - wb - wc;
+
+    const EPSILON: f32 = 1e-5;
+    if wa < -EPSILON || wb < -EPSILON || wc < -EPSILON {
+        return None;
+    }
+
+    Some(
+        a.color
+            .iter()
+            .zip(&b.color)
+            .zip(&c.color)
+            .map(|((a, b), c)| wa * a + wb * b + wc * c)
+            .collect(),
+    )
+}
+
+/// Finds the largest `s` for which `(x, y)` is on the circle interpolated between the start and
+/// end circles of a radial shading, allowing `s` outside `[0, 1]` where the shading extends.
+fn radial_parameter(coords: &[f32; 6], extend: &[bool; 2], x: f32, y: f32) -> Option<f32> {
+    let [x0, y0, r0, x1, y1, r1] = *coords;
+    let (cdx, cdy, dr) = (x1 - x0, y1 - y0, r1 - r0);
+    let (pdx, pdy) = (x - x0, y - y0);
+
+    // |p - c(s)| = r(s), which is the quadratic a s² - 2 b s + c = 0.
+    let a = cdx * cdx + cdy * cdy - dr * dr;
+    let b = pdx * cdx + pdy * cdy + r0 * dr;
+    let c = pdx * pdx + pdy * pdy - r0 * r0;
+
+    let candidates = if a.abs() < 1e-6 {
+        if b == 0.0 {
+            return None;
+        }
+        vec![c / (2.0 * b)]
+    } else {
+        let discriminant = b * b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let (s1, s2) = ((b + root) / a, (b - root) / a);
+        vec![s1.max(s2), s1.min(s2)]
+    };
+
+    candidates
+        .into_iter()
+        .find(|s| r0 + s * dr >= 0.0 && (*s >= 0.0 || extend[0]) && (*s <= 1.0 || extend[1]))
+}
+
+/// Maps `s` along an axial or radial shading to the domain of its functions.
+fn parameter(s: f32, domain: &[f32; 2], extend: &[bool; 2]) -> Option<f32> {
+    let s = if s < 0.0 {
+        if !extend[0] {
+            return None;
+        }
+        0.0
+    } else if s > 1.0 {
+        if !extend[1] {
+            return None;
+        }
+        1.0
+    } else {
+        s
+    };
+
+    Some(domain[0] + s * (domain[1] - domain[0]))
+}
+
+/// Evaluates a shading's functions, either one function with all the outputs or one function for
+/// each output.
+fn eval(functions: &[Function], input: &[f32]) -> Vec<f32> {
+    match functions {
+        [function] => function.eval(input),
+        functions => functions
+            .iter()
+            .flat_map(|function| function.eval(input))
+            .collect(),
+    }
+}
+
+fn mesh_color(functions: &[Function], color: Vec<f32>) -> Vec<f32> {
+    if functions.is_empty() {
+        color
+    } else {
+        eval(functions, &color)
+    }
+}
+
+fn required(functions: Vec<Function>) -> Result<Vec<Function>, Error> {
+    if functions.is_empty() {
+        Err(Error::MissingEntry("Function".into()))
+    } else {
+        Ok(functions)
+    }
+}
+
+fn numbers<R: Resolve>(resolve: &R, dictionary: &Dictionary, key: &str) -> Result<Vec<f32>, Error> {
+    get_numbers(resolve, dictionary, key)?.ok_or_else(|| Error::MissingEntry(key.into()))
+}
+
+fn array<const N: usize>(values: &[f32], key: &str) -> Result<[f32; N], Error> {
+    let mut array = [0.0; N];
+    if values.len() != N {
+        return Err(Error::InvalidEntry(key.into()));
+    }
+    array.copy_from_slice(values);
+    Ok(array)
+}
+
+fn domain<R: Resolve>(resolve: &R, dictionary: &Dictionary) -> Result<[f32; 2], Error> {
+    match get_numbers(resolve, dictionary, "Domain")? {
+        Some(domain) => array(&domain, "Domain"),
+        None => Ok([0.0, 1.0]),
+    }
+}
+
+fn extend<R: Resolve>(resolve: &R, dictionary: &Dictionary) -> Result<[bool; 2], Error> {
+    match get(resolve, dictionary, "Extend")? {
+        Some(Primitive::Array(extend)) => match extend.as_slice() {
+            [Primitive::Boolean(start), Primitive::Boolean(end)] => Ok([*start, *end]),
+            _ => Err(Error::InvalidEntry("Extend".into())),
+        },
+        Some(_) => Err(Error::InvalidEntry("Extend".into())),
+        None => Ok([false, false]),
+    }
+}
+
+/// Reads the vertices and patches of a mesh shading's data stream, section 8.7.4.5.5 to
+/// 8.7.4.5.8 of ISO 32000-1.
+struct MeshReader<'a> {
+    reader: BitReader<'a>,
+    bits_per_coordinate: u32,
+    bits_per_component: u32,
+    bits_per_flag: u32,
+    decode: Vec<f32>,
+    components: usize,
+}
+
+impl<'a> MeshReader<'a> {
+    fn new<R: Resolve>(
+        resolve: &R,
+        dictionary: &Dictionary,
+        data: &'a [u8],
+        components: usize,
+    ) -> Result<Self, Error> {
+        let bits = |key: &str, valid: &[i32]| -> Result<u32, Error> {
+            match get_integer(resolve, dictionary, key)? {
+                Some(bits) if valid.contains(&bits) => Ok(bits as u32),
+                Some(_) => Err(Error::InvalidEntry(key.into())),
+                None => Err(Error::MissingEntry(key.into())),
+            }
+        };
+
+        let bits_per_coordinate = bits("BitsPerCoordinate", &[1, 2, 4, 8, 12, 16, 24, 32])?;
+        let bits_per_component = bits("BitsPerComponent", &[1, 2, 4, 8, 12, 16])?;
+        // Lattice-form meshes don't have flags.
+        let bits_per_flag = match get_integer(resolve, dictionary, "ShadingType")? {
+            Some(5) => 0,
+            _ => bits("BitsPerFlag", &[2, 4, 8])?,
+        };
+
+        let decode = numbers(resolve, dictionary, "Decode")?;
+        if decode.len() != 4 + 2 * components {
+            return Err(Error::InvalidEntry("Decode".into()));
+        }
+
+        Ok(MeshReader {
+            reader: BitReader::new(data),
+            bits_per_coordinate,
+            bits_per_component,
+            bits_per_flag,
+            decode,
+            components,
+        })
+    }
+
+    fn flag(&mut self) -> Option<u32> {
+        self.reader.read(self.bits_per_flag)
+    }
+
+    fn value(&mut self, bits: u32, decode_index: usize) -> Option<f32> {
+        let max = (2f64.powi(bits as i32) - 1.0) as f32;
+
+        Some(interpolate(
+            self.reader.read(bits)? as f32,
+            0.0,
+            max,
+            self.decode[2 * decode_index],
+            self.decode[2 * decode_index + 1],
+        ))
+    }
+
+    fn point(&mut self) -> Option<(f32, f32)> {
+        Some((
+            self.value(self.bits_per_coordinate, 0)?,
+            self.value(self.bits_per_coordinate, 1)?,
+        ))
+    }
+
+    fn color(&mut self) -> Option<Vec<f32>> {
+        (0..self.components)
+            .map(|i| self.value(self.bits_per_component, 2 + i))
+            .collect()
+    }
+
+    fn vertex(&mut self) -> Option<Vertex> {
+        let (x, y) = self.point()?;
+        let color = self.color()?;
+        Some(Vertex { x, y, color })
+    }
+
+    /// Reads a free-form mesh, where each vertex's flag says which edge of the previous triangle
+    /// it's joined to. Each vertex starts on a byte boundary.
+    fn free_form_triangles(&mut self) -> Result<Vec<[Vertex; 3]>, Error> {
+        let mut triangles: Vec<[Vertex; 3]> = Vec::new();
+        let mut pending = Vec::new();
+
+        while self.reader.has_data() {
+            let flag = self.flag();
+            let vertex = self.vertex();
+            self.reader.align();
+            let (flag, vertex) = match (flag, vertex) {
+                (Some(flag), Some(vertex)) => (flag, vertex),
+                // Padding at the end of the data.
+                _ => break,
+            };
+
+            if !pending.is_empty() || flag == 0 {
+                pending.push(vertex);
+                if pending.len() == 3 {
+                    let c = pending.pop().unwrap();
+                    let b = pending.pop().unwrap();
+                    let a = pending.pop().unwrap();
+                    triangles.push([a, b, c]);
+                }
+                continue;
+            }
+
+            let [a, b, c] = triangles
+                .last()
+                .ok_or_else(|| Error::InvalidEntry("mesh data".into()))?;
+            let triangle = match flag {
+                1 => [b.clone(), c.clone(), vertex],
+                2 => [a.clone(), c.clone(), vertex],
+                _ => return Err(Error::InvalidEntry("mesh data".into())),
+            };
+            triangles.push(triangle);
+        }
+
+        Ok(triangles)
+    }
+
+    fn lattice_vertices(&mut self) -> Vec<Vertex> {
+        let mut vertices = Vec::new();
+
+        while self.reader.has_data() {
+            match self.vertex() {
+                Some(vertex) => vertices.push(vertex),
+                None => break,
+            }
+        }
+
+        vertices
+    }
+
+    /// Reads Coons or tensor-product patches, where each patch's flag says which edge of the
+    /// previous patch it shares.
+    fn patches(&mut self, tensor: bool) -> Result<Vec<Patch>, Error> {
+        let mut patches: Vec<Patch> = Vec::new();
+        let point_count = if tensor { 16 } else { 12 };
+
+        while self.reader.has_data() {
+            let flag = match self.flag() {
+                Some(flag) => flag,
+                None => break,
+            };
+
+            let mut points = [[(0.0, 0.0); 4]; 4];
+            let mut colors: [Vec<f32>; 4] = Default::default();
+
+            let explicit = if flag == 0 {
+                0
+            } else {
+                let previous = patches
+                    .last()
+                    .ok_or_else(|| Error::InvalidEntry("mesh data".into()))?;
+                let p = &previous.points;
+                let c = &previous.colors;
+
+                let (edge, edge_colors) = match flag {
+                    1 => ([p[0][3], p[1][3], p[2][3], p[3][3]], [&c[1], &c[2]]),
+                    2 => ([p[3][3], p[3][2], p[3][1], p[3][0]], [&c[2], &c[3]]),
+                    3 => ([p[3][0], p[2][0], p[1][0], p[0][0]], [&c[3], &c[0]]),
+                    _ => return Err(Error::InvalidEntry("mesh data".into())),
+                };
+
+                for (i, point) in edge.iter().enumerate() {
+                    let (u, v) = PATCH_POINT_ORDER[i];
+                    points[u][v] = *point;
+                }
+                colors[0] = edge_colors[0].clone();
+                colors[1] = edge_colors[1].clone();
+                4
+            };
+
+            let mut complete = true;
+            for &(u, v) in &PATCH_POINT_ORDER[explicit..point_count] {
+                match self.point() {
+                    Some(point) => points[u][v] = point,
+                    None => complete = false,
+                }
+            }
+            for color in colors.iter_mut().skip(explicit / 2) {
+                match self.color() {
+                    Some(value) => *color = value,
+                    None => complete = false,
+                }
+            }
+            if !complete {
+                break;
+            }
+
+            if !tensor {
+                coons_interior(&mut points);
+            }
+
+            patches.push(Patch { points, colors });
+        }
+
+        Ok(patches)
+    }
+}
+
+/// Computes the interior control points that make a tensor-product patch equivalent to a Coons
+/// patch with the same boundary.
+fn coons_interior(p: &mut [[(f32, f32); 4]; 4]) {
+    let combine = |terms: &[(f32, (f32, f32))]| {
+        terms.iter().fold((0.0, 0.0), |(x, y), (weight, point)| {
+            (x + weight * point.0 / 9.0, y + weight * point.1 / 9.0)
+        })
+    };
+    let sum = |a: (f32, f32), b: (f32, f32)| (a.0 + b.0, a.1 + b.1);
+
+    p[1][1] = combine(&[
+        (-4.0, p[0][0]),
+        (6.0, sum(p[0][1], p[1][0])),
+        (-2.0, sum(p[0][3], p[3][0])),
+        (3.0, sum(p[3][1], p[1][3])),
+        (-1.0, p[3][3]),
+    ]);
+    p[1][2] = combine(&[
+        (-4.0, p[0][3]),
+        (6.0, sum(p[0][2], p[1][3])),
+        (-2.0, sum(p[0][0], p[3][3])),
+        (3.0, sum(p[3][2], p[1][0])),
+        (-1.0, p[3][0]),
+    ]);
+    p[2][2] = combine(&[
+        (-4.0, p[3][3]),
+        (6.0, sum(p[3][2], p[2][3])),
+        (-2.0, sum(p[3][0], p[0][3])),
+        (3.0, sum(p[0][2], p[2][0])),
+        (-1.0, p[0][0]),
+    ]);
+    p[2][1] = combine(&[
+        (-4.0, p[3][0]),
+        (6.0, sum(p[3][1], p[2][0])),
+        (-2.0, sum(p[3][3], p[0][0])),
+        (3.0, sum(p[0][1], p[2][3])),
+        (-1.0, p[0][3]),
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::object::NoResolve;
+
+    use super::*;
+
+    fn exponential(c0: f32, c1: f32) -> Primitive {
+        let mut function = Dictionary::new();
+        function.insert("FunctionType".to_string(), Primitive::Integer(2));
+        function.insert(
+            "Domain".to_string(),
+            Primitive::Array(vec![Primitive::Integer(0), Primitive::Integer(1)]),
+        );
+        function.insert(
+            "C0".to_string(),
+            Primitive::Array(vec![Primitive::Number(c0)]),
+        );
+        function.insert(
+            "C1".to_string(),
+            Primitive::Array(vec![Primitive::Number(c1)]),
+        );
+        function.insert("N".to_string(), Primitive::Integer(1));
+        Primitive::Dictionary(function)
+    }
+
+    #[test]
+    fn samples_axial_shading() {
+        let mut dictionary = Dictionary::new();
+        dictionary.insert("ShadingType".to_string(), Primitive::Integer(2));
+        dictionary.insert(
+            "ColorSpace".to_string(),
+            Primitive::Name("DeviceGray".into()),
+        );
+        dictionary.insert(
+            "Coords".to_string(),
+            Primitive::Array(
+                [0, 0, 100, 0]
+                    .iter()
+                    .map(|n| Primitive::Integer(*n))
+                    .collect(),
+            ),
+        );
+        dictionary.insert("Function".to_string(), exponential(0.2, 1.0));
+        dictionary.insert(
+            "Extend".to_string(),
+            Primitive::Array(vec![Primitive::Boolean(true), Primitive::Boolean(false)]),
+        );
+
+        let shading =
+            Shading::from_primitive(&NoResolve, &Primitive::Dictionary(dictionary)).unwrap();
+
+        assert_eq!(shading.color_at(50.0, 30.0), Some(vec![0.6]));
+        assert_eq!(shading.color_at(-10.0, 0.0), Some(vec![0.2]));
+        assert_eq!(shading.color_at(110.0, 0.0), None);
+    }
+
+    #[test]
+    fn samples_radial_shading() {
+        let shading = Shading {
+            color_space: ColorSpace::DeviceGray,
+            background: None,
+            bbox: None,
+            anti_alias: false,
+            shading_type: ShadingType::Radial {
+                coords: [0.0, 0.0, 0.0, 0.0, 0.0, 10.0],
+                domain: [0.0, 1.0],
+                functions: vec![
+                    Function::from_primitive(&NoResolve, &exponential(0.0, 1.0)).unwrap()
+                ],
+                extend: [false, false],
+            },
+        };
+
+        let color = shading.color_at(0.0, 5.0).unwrap();
+        assert!((color[0] - 0.5).abs() < 1e-5);
+        assert_eq!(shading.color_at(8.0, 8.0), None);
+    }
+
+    #[test]
+    fn samples_coons_patch() {
+        let mut patch = Patch {
+            points: [[(0.0, 0.0); 4]; 4],
+            colors: [vec![0.0], vec![0.0], vec![1.0], vec![1.0]],
+        };
+        for u in 0..4 {
+            for v in 0..4 {
+                patch.points[u][v] = (u as f32 * 10.0 / 3.0, v as f32 * 10.0 / 3.0);
+            }
+        }
+        let shading = Shading {
+            color_space: ColorSpace::DeviceGray,
+            background: None,
+            bbox: None,
+            anti_alias: false,
+            shading_type: ShadingType::CoonsPatch {
+                patches: vec![patch],
+                functions: Vec::new(),
+            },
+        };
+
+        let color = shading.color_at(2.5, 7.0).unwrap();
+        assert!((color[0] - 0.25).abs() < 1e-4);
+        assert_eq!(shading.color_at(11.0, 5.0), None);
+    }
+
+    #[test]
+    fn decodes_free_form_mesh() {
+        let mut dictionary = Dictionary::new();
+        dictionary.insert("ShadingType".to_string(), Primitive::Integer(4));
+        dictionary.insert("BitsPerCoordinate".to_string(), Primitive::Integer(8));
+        dictionary.insert("BitsPerComponent".to_string(), Primitive::Integer(8));
+        dictionary.insert("BitsPerFlag".to_string(), Primitive::Integer(8));
+        dictionary.insert(
+            "Decode".to_string(),
+            Primitive::Array(
+                [0, 255, 0, 255, 0, 1]
+                    .iter()
+                    .map(|n| Primitive::Integer(*n))
+                    .collect(),
+            ),
+        );
+        let mut reader = MeshReader::new(
+            &NoResolve,
+            &dictionary,
+            &[0, 0, 0, 0, 0, 100, 0, 255, 0, 0, 100, 255, 1, 100, 100, 0],
+            1,
+        )
+        .unwrap();
+
+        let triangles = reader.free_form_triangles().unwrap();
+        assert_eq!(triangles.len(), 2);
+        assert_eq!((triangles[1][0].x, triangles[1][2].y), (100.0, 100.0));
+        assert_eq!(
+            sample_triangle(
+                &triangles[0][0],
+                &triangles[0][1],
+                &triangles[0][2],
+                50.0,
+                0.0
+            ),
+            Some(vec![0.5])
+        );
+    }
+}
@@ -0,0 +1,673 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::TryFrom,
+};
+
+use pdf::{
+    object::{PlainRef, Resolve},
+    primitive::{Dictionary, Primitive},
+};
+
+use crate::{
+    document::{self, decode_text, get, get_dictionary, get_name, get_text, has_type, RawDocument},
+    error::Error,
+    marked_content::MarkedContent,
+    normalize_operation,
+};
+
+/// How deeply structure elements, and the nodes of the parent tree, can be nested.
+const MAX_DEPTH: usize = 64;
+
+/// How many role map entries are followed to reach a standard structure type.
+const MAX_ROLE_MAP_DEPTH: usize = 16;
+
+/// The standard structure types of section 14.8.4 of ISO 32000-1.
+const STANDARD_TYPES: &[&str] = &[
+    "Document",
+    "Part",
+    "Art",
+    "Sect",
+    "Div",
+    "BlockQuote",
+    "Caption",
+    "TOC",
+    "TOCI",
+    "Index",
+    "NonStruct",
+    "Private",
+    "P",
+    "H",
+    "H1",
+    "H2",
+    "H3",
+    "H4",
+    "H5",
+    "H6",
+    "L",
+    "LI",
+    "Lbl",
+    "LBody",
+    "Table",
+    "TR",
+    "TH",
+    "TD",
+    "THead",
+    "TBody",
+    "TFoot",
+    "Span",
+    "Quote",
+    "Note",
+    "Reference",
+    "BibEntry",
+    "Code",
+    "Link",
+    "Annot",
+    "Ruby",
+    "RB",
+    "RT",
+    "RP",
+    "Warichu",
+    "WT",
+    "WP",
+    "Figure",
+    "Formula",
+    "Form",
+];
+
+/// A document's logical structure, `/StructTreeRoot` in the catalog, section 14.7 of
+/// ISO 32000-1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructTree {
+    /// The top level structure elements, in logical order.
+    pub kids: Vec<StructElement>,
+    /// `/RoleMap`, mapping structure types to the types they stand for.
+    pub role_map: HashMap<String, String>,
+    /// `/ClassMap`, mapping attribute class names to their attribute objects.
+    pub class_map: HashMap<String, Vec<Dictionary>>,
+    /// `/ParentTree`, by `/StructParents` or `/StructParent` key.
+    pub parent_tree: BTreeMap<i32, ParentTreeEntry>,
+    /// The marked content sequences with an MCID in each page's content, by page number and
+    /// MCID.
+    pub marked_content: HashMap<(usize, i32), MarkedContent>,
+    /// The pages whose content or resources couldn't be read, so that their marked content is
+    /// missing from `marked_content`, by page number.
+    pub unreadable_pages: Vec<usize>,
+}
+
+/// A structure element, section 14.7.2 of ISO 32000-1.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StructElement {
+    pub reference: Option<PlainRef>,
+    /// `/S`, which may have to be mapped with the role map to a standard type.
+    pub struct_type: String,
+    /// `/ID`
+    pub id: Option<String>,
+    /// `/Pg`, as a page number.
+    pub page: Option<usize>,
+    /// `/T`
+    pub title: Option<String>,
+    /// `/Lang`
+    pub lang: Option<String>,
+    /// `/Alt`
+    pub alt: Option<String>,
+    /// `/E`
+    pub expansion: Option<String>,
+    /// `/ActualText`
+    pub actual_text: Option<String>,
+    /// `/C`, the attribute classes of the element.
+    pub classes: Vec<String>,
+    /// `/A`, the attribute objects of the element.
+    pub attributes: Vec<Dictionary>,
+    pub kids: Vec<StructKid>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StructKid {
+    Element(Box<StructElement>),
+    /// A marked content sequence, given by an MCID or a marked content reference.
+    MarkedContent {
+        /// The page the sequence is on, as a page number.
+        page: Option<usize>,
+        /// `/Stm`, for a sequence in a Form XObject rather than the page's own content.
+        stream: Option<PlainRef>,
+        mcid: i32,
+    },
+    /// An object reference to an annotation or XObject.
+    Object {
+        page: Option<usize>,
+        object: PlainRef,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParentTreeEntry {
+    /// The parent element of each marked content sequence in a page or content stream, by MCID.
+    Elements(Vec<Option<PlainRef>>),
+    /// The parent element of an annotation or XObject.
+    Element(PlainRef),
+}
+
+impl StructTree {
+    /// Reads the structure tree, `None` if the document isn't tagged. The marked content of each
+    /// page is collected from its own content, Form XObjects aren't looked into. Pages whose
+    /// content can't be read are listed in `unreadable_pages` rather than failing the whole tree.
+    pub fn load<R: Resolve>(resolve: &R, document: &RawDocument) -> Result<Option<Self>, Error> {
+        let root = match get_dictionary(resolve, &document.catalog, "StructTreeRoot")? {
+            Some(root) => root,
+            None => return Ok(None),
+        };
+
+        let pages: HashMap<PlainRef, usize> = document
+            .pages
+            .iter()
+            .filter_map(|page| Some((page.reference?, page.number)))
+            .collect();
+
+        let mut marked_content = HashMap::new();
+        let mut unreadable_pages = Vec::new();
+        for page in &document.pages {
+            let sequences = page.operations(resolve).and_then(|operations| {
+                let operations: Vec<_> = operations.iter().map(normalize_operation).collect();
+                MarkedContent::collect(resolve, &page.resources(resolve)?, &operations)
+            });
+
+            match sequences {
+                Ok(sequences) => {
+                    for sequence in sequences {
+                        if let Some(mcid) = sequence.mcid() {
+                            marked_content.insert((page.number, mcid), sequence);
+                        }
+                    }
+                }
+                Err(_) => unreadable_pages.push(page.number),
+            }
+        }
+
+        let mut reader = Reader {
+            resolve,
+            pages: &pages,
+            visited: HashSet::new(),
+        };
+        let kids = match root.get("K") {
+            Some(kids) => reader.elements(kids, None, 0)?,
+            None => Vec::new(),
+        };
+
+        let role_map = match get_dictionary(resolve, &root, "RoleMap")? {
+            Some(role_map) => role_map
+                .iter()
+                .filter_map(|(key, value)| match document::resolve(resolve, value) {
+                    Ok(Primitive::Name(role)) => Some((key.clone(), role)),
+                    _ => None,
+                })
+                .collect(),
+            None => HashMap::new(),
+        };
+
+        let mut class_map = HashMap::new();
+        if let Some(classes) = get_dictionary(resolve, &root, "ClassMap")? {
+            for (name, attributes) in classes.iter() {
+                class_map.insert(name.clone(), attribute_objects(resolve, attributes)?);
+            }
+        }
+
+        let mut parent_tree = BTreeMap::new();
+        if let Some(tree) = root.get("ParentTree") {
+            read_parent_tree(resolve, tree, &mut parent_tree, &mut HashSet::new(), 0)?;
+        }
+
+        Ok(Some(StructTree {
+            kids,
+            role_map,
+            class_map,
+            parent_tree,
+            marked_content,
+            unreadable_pages,
+        }))
+    }
+
+    /// Every structure element, in logical order: each element comes before its kids, and the
+    /// kids in the order of `/K`.
+    pub fn elements(&self) -> impl Iterator<Item = &StructElement> {
+        let mut elements = Vec::new();
+        let mut pending: Vec<&StructElement> = self.kids.iter().rev().collect();
+
+        while let Some(element) = pending.pop() {
+            elements.push(element);
+            pending.extend(element.kids.iter().rev().filter_map(|kid| match kid {
+                StructKid::Element(element) => Some(&**element),
+                _ => None,
+            }));
+        }
+
+        elements.into_iter()
+    }
+
+    /// The element with the given reference.
+    pub fn element(&self, reference: PlainRef) -> Option<&StructElement> {
+        self.elements()
+            .find(|element| element.reference == Some(reference))
+    }
+
+    /// The parent element of a marked content sequence, looked up in the parent tree by the
+    /// `/StructParents` of its page or content stream and its MCID.
+    pub fn parent(&self, struct_parents: i32, mcid: i32) -> Option<&StructElement> {
+        match self.parent_tree.get(&struct_parents)? {
+            ParentTreeEntry::Elements(parents) => {
+                self.element((*parents.get(usize::try_from(mcid).ok()?)?)?)
+            }
+            ParentTreeEntry::Element(_) => None,
+        }
+    }
+
+    /// The structure type an element stands for, following the role map to a standard type.
+    /// Types that don't map to a standard type are returned as the role map leaves them.
+    pub fn standard_type<'a>(&'a self, element: &'a StructElement) -> &'a str {
+        let mut struct_type = element.struct_type.as_str();
+
+        for _ in 0..MAX_ROLE_MAP_DEPTH {
+            if STANDARD_TYPES.contains(&struct_type) {
+                break;
+            }
+            match self.role_map.get(struct_type) {
+                Some(role) => struct_type = role,
+                None => break,
+            }
+        }
+
+        struct_type
+    }
+
+    /// The attribute objects of an element, its own and those of its classes.
+    pub fn attributes<'a>(&'a self, element: &'a StructElement) -> Vec<&'a Dictionary> {
+        let classes = element
+            .classes
+            .iter()
+            .filter_map(|class| self.class_map.get(class))
+            .flatten();
+
+        element.attributes.iter().chain(classes).collect()
+    }
+
+    /// The marked content sequence a kid refers to, if it's one on a page.
+    pub fn content(&self, kid: &StructKid) -> Option<&MarkedContent> {
+        match kid {
+            StructKid::MarkedContent {
+                page: Some(page),
+                stream: None,
+                mcid,
+            } => self.marked_content.get(&(*page, *mcid)),
+            _ => None,
+        }
+    }
+
+    /// The text of an element: its `/ActualText`, or else the text of its content in logical
+    /// order. Content text is the shown strings' raw bytes read as UTF-8, see
+    /// `MarkedContent::text`.
+    pub fn text(&self, element: &StructElement) -> String {
+        if let Some(actual_text) = &element.actual_text {
+            return actual_text.clone();
+        }
+
+        element
+            .kids
+            .iter()
+            .map(|kid| match kid {
+                StructKid::Element(element) => self.text(element),
+                kid => self
+                    .content(kid)
+                    .map(|content| content.text().to_string())
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+struct Reader<'a, R> {
+    resolve: &'a R,
+    pages: &'a HashMap<PlainRef, usize>,
+    visited: HashSet<PlainRef>,
+}
+
+impl<'a, R: Resolve> Reader<'a, R> {
+    /// The structure elements of a `/K` entry of the root, which can be one element or an array.
+    fn elements(
+        &mut self,
+        kids: &Primitive,
+        page: Option<usize>,
+        depth: usize,
+    ) -> Result<Vec<StructElement>, Error> {
+        Ok(self
+            .kids(kids, page, depth)?
+            .into_iter()
+            .filter_map(|kid| match kid {
+                StructKid::Element(element) => Some(*element),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// The kids of a `/K` entry, which can be one kid or an array of them.
+    fn kids(
+        &mut self,
+        kids: &Primitive,
+        page: Option<usize>,
+        depth: usize,
+    ) -> Result<Vec<StructKid>, Error> {
+        match document::resolve(self.resolve, kids)? {
+            Primitive::Array(array) => {
+                let mut kids = Vec::new();
+                for kid in &array {
+                    kids.extend(self.kid(kid, page, depth)?);
+                }
+                Ok(kids)
+            }
+            _ => Ok(self.kid(kids, page, depth)?.into_iter().collect()),
+        }
+    }
+
+    fn kid(
+        &mut self,
+        kid: &Primitive,
+        page: Option<usize>,
+        depth: usize,
+    ) -> Result<Option<StructKid>, Error> {
+        let reference = match kid {
+            Primitive::Reference(reference) => {
+                // A cycle in the tree, or an element that's a kid of two parents.
+                if !self.visited.insert(*reference) {
+                    return Ok(None);
+                }
+                Some(*reference)
+            }
+            _ => None,
+        };
+
+        let dictionary = match document::resolve(self.resolve, kid)? {
+            Primitive::Integer(mcid) => {
+                return Ok(Some(StructKid::MarkedContent {
+                    page,
+                    stream: None,
+                    mcid,
+                }))
+            }
+            Primitive::Dictionary(dictionary) => dictionary,
+            Primitive::Null => return Ok(None),
+            _ => return Err(Error::InvalidEntry("K".into())),
+        };
+        let page = self.page(&dictionary)?.or(page);
+
+        if has_type(&dictionary, "MCR") {
+            let mcid = match get(self.resolve, &dictionary, "MCID")? {
+                Some(Primitive::Integer(mcid)) => mcid,
+                _ => return Err(Error::MissingEntry("MCID".into())),
+            };
+            let stream = match dictionary.get("Stm") {
+                Some(Primitive::Reference(stream)) => Some(*stream),
+                _ => None,
+            };
+            return Ok(Some(StructKid::MarkedContent { page, stream, mcid }));
+        }
+
+        if has_type(&dictionary, "OBJR") {
+            return match dictionary.get("Obj") {
+                Some(Primitive::Reference(object)) => Ok(Some(StructKid::Object {
+                    page,
+                    object: *object,
+                })),
+                _ => Err(Error::MissingEntry("Obj".into())),
+            };
+        }
+
+        if depth >= MAX_DEPTH {
+            return Ok(None);
+        }
+
+        let kids = match dictionary.get("K") {
+            Some(kids) => self.kids(kids, page, depth + 1)?,
+            None => Vec::new(),
+        };
+
+        let classes = match get(self.resolve, &dictionary, "C")? {
+            Some(Primitive::Name(class)) => vec![class],
+            // Class names can be followed by revision numbers, which aren't kept.
+            Some(Primitive::Array(classes)) => classes
+                .iter()
+                .filter_map(|class| match class {
+                    Primitive::Name(class) => Some(class.clone()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let attributes = match dictionary.get("A") {
+            Some(attributes) => attribute_objects(self.resolve, attributes)?,
+            None => Vec::new(),
+        };
+        let id = match get(self.resolve, &dictionary, "ID")? {
+            Some(Primitive::String(id)) => Some(decode_text(id.as_bytes())),
+            _ => None,
+        };
+
+        Ok(Some(StructKid::Element(Box::new(StructElement {
+            reference,
+            struct_type: get_name(self.resolve, &dictionary, "S")?
+                .ok_or_else(|| Error::MissingEntry("S".into()))?,
+            id,
+            page,
+            title: get_text(self.resolve, &dictionary, "T")?,
+            lang: get_text(self.resolve, &dictionary, "Lang")?,
+            alt: get_text(self.resolve, &dictionary, "Alt")?,
+            expansion: get_text(self.resolve, &dictionary, "E")?,
+            actual_text: get_text(self.resolve, &dictionary, "ActualText")?,
+            classes,
+            attributes,
+            kids,
+        }))))
+    }
+
+    /// The page number of a `/Pg` entry.
+    fn page(&self, dictionary: &Dictionary) -> Result<Option<usize>, Error> {
+        match dictionary.get("Pg") {
+            Some(Primitive::Reference(page)) => Ok(self.pages.get(page).copied()),
+            Some(_) => Err(Error::InvalidEntry("Pg".into())),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The attribute objects of an `/A` entry or class map entry: one attribute object, or an array
+/// of them that may have revision numbers between them.
+fn attribute_objects<R: Resolve>(
+    resolve: &R,
+    attributes: &Primitive,
+) -> Result<Vec<Dictionary>, Error> {
+    let attribute = |primitive: &Primitive| match document::resolve(resolve, primitive)? {
+        Primitive::Dictionary(dictionary) => Ok(Some(dictionary)),
+        Primitive::Stream(stream) => Ok(Some(stream.info)),
+        _ => Ok(None),
+    };
+
+    match document::resolve(resolve, attributes)? {
+        Primitive::Array(array) => array
+            .iter()
+            .filter_map(|primitive| attribute(primitive).transpose())
+            .collect(),
+        primitive => Ok(attribute(&primitive)?.into_iter().collect()),
+    }
+}
+
+/// Reads the entries of a number tree node and its kids.
+fn read_parent_tree<R: Resolve>(
+    resolve: &R,
+    node: &Primitive,
+    entries: &mut BTreeMap<i32, ParentTreeEntry>,
+    visited: &mut HashSet<PlainRef>,
+    depth: usize,
+) -> Result<(), Error> {
+    if let Primitive::Reference(reference) = node {
+        if !visited.insert(*reference) {
+            return Ok(());
+        }
+    }
+    if depth >= MAX_DEPTH {
+        return Ok(());
+    }
+
+    let node = match document::resolve(resolve, node)? {
+        Primitive::Dictionary(node) => node,
+        _ => return Err(Error::InvalidEntry("ParentTree".into())),
+    };
+
+    if let Some(Primitive::Array(numbers)) = get(resolve, &node, "Nums")? {
+        for pair in numbers.chunks_exact(2) {
+            let key = match document::resolve(resolve, &pair[0])? {
+                Primitive::Integer(key) => key,
+                _ => return Err(Error::InvalidEntry("Nums".into())),
+            };
+
+            // The values are kept as references, which is how they identify elements.
+            let entry = match &pair[1] {
+                Primitive::Reference(reference) => match document::resolve(resolve, &pair[1])? {
+                    Primitive::Array(parents) => ParentTreeEntry::Elements(references(&parents)),
+                    _ => ParentTreeEntry::Element(*reference),
+                },
+                Primitive::Array(parents) => ParentTreeEntry::Elements(references(parents)),
+                _ => continue,
+            };
+            entries.insert(key, entry);
+        }
+    }
+
+    if let Some(Primitive::Array(kids)) = get(resolve, &node, "Kids")? {
+        for kid in &kids {
+            read_parent_tree(resolve, kid, entries, visited, depth + 1)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn references(array: &[Primitive]) -> Vec<Option<PlainRef>> {
+    array
+        .iter()
+        .map(|primitive| match primitive {
+            Primitive::Reference(reference) => Some(*reference),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::{
+        object::NoResolve,
+        primitive::{PdfStream, PdfString},
+    };
+
+    use super::*;
+    use crate::{
+        document::RawPage,
+        fixtures::{dictionary, name},
+    };
+
+    #[test]
+    fn links_elements_to_marked_content() {
+        let page_reference = PlainRef { id: 3, gen: 0 };
+        let heading = dictionary(vec![
+            ("S", name("Heading")),
+            ("Pg", Primitive::Reference(page_reference)),
+            ("K", Primitive::Integer(0)),
+        ]);
+        let paragraph = dictionary(vec![
+            ("S", name("P")),
+            ("Pg", Primitive::Reference(page_reference)),
+            (
+                "K",
+                Primitive::Array(vec![
+                    Primitive::Integer(1),
+                    dictionary(vec![
+                        ("S", name("Span")),
+                        (
+                            "ActualText",
+                            Primitive::String(PdfString::new(b"\xfe\xff\x00f\x00i".to_vec())),
+                        ),
+                        (
+                            "K",
+                            dictionary(vec![
+                                ("Type", name("MCR")),
+                                ("Pg", Primitive::Reference(page_reference)),
+                                ("MCID", Primitive::Integer(2)),
+                            ]),
+                        ),
+                    ]),
+                ]),
+            ),
+        ]);
+        let root = dictionary(vec![
+            ("Type", name("StructTreeRoot")),
+            (
+                "K",
+                dictionary(vec![
+                    ("S", name("Document")),
+                    ("K", Primitive::Array(vec![heading, paragraph])),
+                ]),
+            ),
+            ("RoleMap", dictionary(vec![("Heading", name("H1"))])),
+        ]);
+
+        let mut catalog = Dictionary::new();
+        catalog.insert("StructTreeRoot".to_string(), root);
+
+        let mut page = Dictionary::new();
+        page.insert(
+            "Contents".to_string(),
+            Primitive::Stream(PdfStream {
+                info: Dictionary::new(),
+                data: b"/H1 << /MCID 0 >> BDC (Title) Tj EMC /P << /MCID 1 >> BDC (Some ) Tj \
+                        EMC /Span << /MCID 2 >> BDC (\xef\xac\x81) Tj EMC (ne) Tj"
+                    .to_vec(),
+            }),
+        );
+        let mut unreadable = Dictionary::new();
+        unreadable.insert(
+            "Contents".to_string(),
+            Primitive::Stream(PdfStream {
+                info: Dictionary::new(),
+                data: b"/P /Missing BDC (Lost) Tj EMC".to_vec(),
+            }),
+        );
+        let document = RawDocument {
+            catalog,
+            pages: vec![
+                RawPage {
+                    number: 1,
+                    reference: Some(page_reference),
+                    dictionary: page,
+                },
+                RawPage {
+                    number: 2,
+                    reference: None,
+                    dictionary: unreadable,
+                },
+            ],
+        };
+
+        let tree = StructTree::load(&NoResolve, &document).unwrap().unwrap();
+        let elements: Vec<_> = tree
+            .elements()
+            .map(|element| (tree.standard_type(element), tree.text(element)))
+            .collect();
+
+        assert_eq!(
+            elements,
+            vec![
+                ("Document", "TitleSome fi".to_string()),
+                ("H1", "Title".to_string()),
+                ("P", "Some fi".to_string()),
+                ("Span", "fi".to_string()),
+            ]
+        );
+        assert_eq!(tree.unreadable_pages, vec![2]);
+    }
+}
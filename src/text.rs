@@ -0,0 +1,359 @@
+use pdf::{object::Resolve, primitive::Dictionary};
+
+use crate::{
+    document::{get_text, Location, RawPage},
+    error::Error,
+    graphics_state::GraphicsState,
+    painting::{walk_content, ContentVisitor, Step},
+    Name, Operation, TextOrGlyphPositioning,
+};
+
+/// `TJ` offsets, in thousandths of a text space unit, at least this far to the right are taken
+/// as a space between words.
+pub(crate) const WORD_GAP: f32 = 250.0;
+
+/// The marked content properties whose text replaces the text shown in a sequence, in order of
+/// preference.
+pub(crate) const REPLACEMENT_KEYS: [&str; 3] = ["ActualText", "Alt", "E"];
+
+/// How marked content is taken into account when extracting text.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextOptions {
+    /// Whether to extract the text of `/Artifact` sequences, such as running headers, footers
+    /// and page numbers.
+    pub artifacts: bool,
+    /// Whether the `/ActualText`, `/Alt` or `/E` of a marked content sequence, in that order of
+    /// preference, replaces the text shown in it.
+    pub replacements: bool,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            artifacts: false,
+            replacements: true,
+        }
+    }
+}
+
+/// Extracts the text of a page, in content stream order, including the text of the Form
+/// XObjects it paints. Lines are separated by newlines.
+pub fn extract_text<R: Resolve>(
+    resolve: &R,
+    page: &RawPage,
+    options: TextOptions,
+) -> Result<String, Error> {
+    let operations = page.operations(resolve)?;
+
+    let mut extractor = Extractor {
+        resolve,
+        marked: MarkedText::new(options),
+        text: String::new(),
+        line_break: false,
+    };
+    walk_content(
+        resolve,
+        &operations,
+        GraphicsState::default(),
+        &page.resources(resolve)?,
+        vec![Location::Page(page.number)],
+        &mut extractor,
+    )?;
+
+    Ok(extractor.text)
+}
+
+/// Whether the text shown by an operation, or after it, starts on a new line.
+pub(crate) fn breaks_line(operation: &Operation) -> bool {
+    match operation {
+        Operation::EndTextObject
+        | Operation::MoveToStartOfNextTextLine
+        | Operation::SetTextMatrixAndTextLineMatrix(..)
+        | Operation::MoveToNextLineAndShowText(_)
+        | Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText { .. } => true,
+        Operation::MoveTextPosition { y, .. }
+        | Operation::MoveTextPositionAndSetLeading { y, .. } => *y != 0.0,
+        // `'` and `"` with strings that aren't UTF-8.
+        Operation::Unknown { operator, .. } => matches!(*operator, "'" | "\""),
+        _ => false,
+    }
+}
+
+/// What happens to the text shown in a marked content sequence.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Sequence {
+    Shown,
+    /// Left out, being an artifact.
+    Artifact,
+    /// Stood in for by the sequence's replacement text.
+    Replaced,
+}
+
+/// Follows the marked content sequences of the content being walked, to tell which text is left
+/// out or replaced under `TextOptions`.
+pub(crate) struct MarkedText {
+    options: TextOptions,
+    /// The sequences the current operation is in, including the ones begun in the content
+    /// streams that paint the current one. Sequences in one whose text isn't shown take after
+    /// it.
+    sequences: Vec<Sequence>,
+    /// How many sequences were open when each Form XObject being walked was painted. Sequences
+    /// left open at the end of a form end with it.
+    bases: Vec<usize>,
+}
+
+impl MarkedText {
+    pub(crate) fn new(options: TextOptions) -> Self {
+        MarkedText {
+            options,
+            sequences: Vec::new(),
+            bases: Vec::new(),
+        }
+    }
+
+    /// Follows the marked content operators, returning the replacement text of a sequence
+    /// begun by `step`, which is to be shown in place of the text in it.
+    pub(crate) fn update<R: Resolve>(
+        &mut self,
+        resolve: &R,
+        step: &Step,
+    ) -> Result<Option<String>, Error> {
+        match step.operation {
+            Operation::BeginMarkedContentSequence(Name(tag)) => self.begin(resolve, tag, None),
+            Operation::BeginMarkedContentSequenceWithPropertyList {
+                tag: Name(tag),
+                properties,
+            } => {
+                let properties = properties.resolve(resolve, step.resources)?;
+                self.begin(resolve, tag, Some(&properties))
+            }
+            Operation::EndMarkedContentSequence => {
+                if self.sequences.len() > self.bases.last().copied().unwrap_or(0) {
+                    self.sequences.pop();
+                }
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub(crate) fn begin_form(&mut self) {
+        self.bases.push(self.sequences.len());
+    }
+
+    pub(crate) fn end_form(&mut self) {
+        if let Some(base) = self.bases.pop() {
+            self.sequences.truncate(base);
+        }
+    }
+
+    /// Whether the text shown by the current operation is left out, being an artifact or
+    /// replaced.
+    pub(crate) fn is_suppressed(&self) -> bool {
+        matches!(self.sequences.last(), Some(sequence) if *sequence != Sequence::Shown)
+    }
+
+    /// Whether the text shown by the current operation is stood in for by a replacement.
+    pub(crate) fn is_replaced(&self) -> bool {
+        self.sequences.last() == Some(&Sequence::Replaced)
+    }
+
+    fn begin<R: Resolve>(
+        &mut self,
+        resolve: &R,
+        tag: &str,
+        properties: Option<&Dictionary>,
+    ) -> Result<Option<String>, Error> {
+        if self.is_suppressed() {
+            self.sequences
+                .push(self.sequences[self.sequences.len() - 1]);
+            return Ok(None);
+        }
+        if tag == "Artifact" && !self.options.artifacts {
+            self.sequences.push(Sequence::Artifact);
+            return Ok(None);
+        }
+
+        let replacement = match properties {
+            Some(properties) if self.options.replacements => replacement(resolve, properties)?,
+            _ => None,
+        };
+        self.sequences.push(if replacement.is_some() {
+            Sequence::Replaced
+        } else {
+            Sequence::Shown
+        });
+        Ok(replacement)
+    }
+}
+
+struct Extractor<'a, R> {
+    resolve: &'a R,
+    marked: MarkedText,
+    text: String,
+    /// Whether the next text starts on a new line.
+    line_break: bool,
+}
+
+impl<'a, R: Resolve> ContentVisitor for Extractor<'a, R> {
+    fn operation(&mut self, step: &Step) -> Result<(), Error> {
+        if let Some(replacement) = self.marked.update(self.resolve, step)? {
+            self.append(&replacement);
+        }
+        if breaks_line(step.operation) {
+            self.line_break = true;
+        }
+
+        match step.operation {
+            Operation::ShowText(text)
+            | Operation::MoveToNextLineAndShowText(text)
+            | Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText { text, .. } => {
+                self.push(text)
+            }
+            Operation::ShowTextAllowingIndividualGlyphPositioning(array) => {
+                for item in array {
+                    match item {
+                        TextOrGlyphPositioning::Text(text) => self.push(text),
+                        TextOrGlyphPositioning::GlyphPositioning(offset) if -offset >= WORD_GAP => {
+                            self.push(" ")
+                        }
+                        TextOrGlyphPositioning::GlyphPositioning(_) => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn begin_form(&mut self, _step: &Step) -> Result<(), Error> {
+        self.marked.begin_form();
+        Ok(())
+    }
+
+    fn end_form(&mut self, _step: &Step) -> Result<(), Error> {
+        self.marked.end_form();
+        Ok(())
+    }
+}
+
+impl<'a, R: Resolve> Extractor<'a, R> {
+    fn push(&mut self, text: &str) {
+        if !self.marked.is_suppressed() {
+            self.append(text);
+        }
+    }
+
+    fn append(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        if self.line_break {
+            if !self.text.is_empty() && !self.text.ends_with('\n') {
+                self.text.push('\n');
+            }
+            self.line_break = false;
+        }
+        if text == " " && (self.text.is_empty() || self.text.ends_with(char::is_whitespace)) {
+            return;
+        }
+
+        self.text.push_str(text);
+    }
+}
+
+/// The text that replaces the content of a marked content sequence.
+fn replacement<R: Resolve>(resolve: &R, properties: &Dictionary) -> Result<Option<String>, Error> {
+    for key in &REPLACEMENT_KEYS {
+        if let Some(text) = get_text(resolve, properties, key)? {
+            return Ok(Some(text));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::{
+        object::NoResolve,
+        primitive::{PdfStream, PdfString, Primitive},
+    };
+
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn skips_artifacts_and_substitutes_actual_text() {
+        let mut actual_text = Dictionary::new();
+        actual_text.insert(
+            "ActualText".to_string(),
+            Primitive::String(PdfString::new(b"fi".to_vec())),
+        );
+        let mut properties = Dictionary::new();
+        properties.insert("Ligature".to_string(), Primitive::Dictionary(actual_text));
+        let mut resources = Dictionary::new();
+        resources.insert("Properties".to_string(), Primitive::Dictionary(properties));
+
+        let page = fixtures::page(
+            resources,
+            b"/Artifact BMC BT 0 800 Td (Running header) Tj ET EMC \
+              BT 0 700 Td [(The) -300 (de)] TJ /Span /Ligature BDC (x) Tj EMC (ne) Tj \
+              0 -12 Td (Next line) Tj ET",
+        );
+
+        assert_eq!(
+            extract_text(&NoResolve, &page, TextOptions::default()).unwrap(),
+            "The define\nNext line"
+        );
+        assert_eq!(
+            extract_text(
+                &NoResolve,
+                &page,
+                TextOptions {
+                    artifacts: true,
+                    replacements: false,
+                }
+            )
+            .unwrap(),
+            "Running header\nThe dexne\nNext line"
+        );
+    }
+
+    #[test]
+    fn ends_sequences_left_open_by_forms() {
+        let mut form = Dictionary::new();
+        form.insert("Subtype".to_string(), Primitive::Name("Form".to_string()));
+        let mut xobjects = Dictionary::new();
+        xobjects.insert(
+            "Fm0".to_string(),
+            Primitive::Stream(PdfStream {
+                info: form,
+                data: b"BT (In form) Tj ET /Artifact BMC BT (Footer) Tj ET".to_vec(),
+            }),
+        );
+        let mut resources = Dictionary::new();
+        resources.insert("XObject".to_string(), Primitive::Dictionary(xobjects));
+        let page = fixtures::page(resources, b"/Fm0 Do /Fm0 Do BT (After) Tj ET");
+
+        assert_eq!(
+            extract_text(&NoResolve, &page, TextOptions::default()).unwrap(),
+            "In form\nIn form\nAfter"
+        );
+    }
+
+    #[test]
+    fn skips_malformed_operations() {
+        let page = fixtures::page(
+            fixtures::courier_resources(),
+            b"BT /F1 12 Tf (Before) Tj ) 0 0 Td (After) Tj ET",
+        );
+
+        assert_eq!(
+            extract_text(&NoResolve, &page, TextOptions::default()).unwrap(),
+            "BeforeAfter"
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use std::{collections::HashMap, ops::Range};
+
+use pdf::{object::Resolve, primitive::Dictionary};
+
+use crate::{
+    error::Error,
+    font::Font,
+    geometry::{Matrix, Rect},
+    Operation, TextRenderingMode,
+};
+
+/// The text state parameters and the text matrices, sections 9.3 and 9.4.2 of ISO 32000-1.
+/// Like the rest of the graphics state, the parameters are saved and restored by `q` and `Q`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextState {
+    /// `Tc`
+    pub character_spacing: f32,
+    /// `Tw`
+    pub word_spacing: f32,
+    /// `Tz`, as a fraction rather than a percentage.
+    pub horizontal_scaling: f32,
+    /// `TL`
+    pub leading: f32,
+    /// The name of the `/Font` resource set by `Tf`.
+    pub font: Option<String>,
+    pub font_size: f32,
+    /// `Tr`
+    pub rendering_mode: TextRenderingMode,
+    /// `Ts`
+    pub rise: f32,
+    /// `Tm`
+    pub matrix: Matrix,
+    /// `Tlm`
+    pub line_matrix: Matrix,
+}
+
+/// A glyph shown by a text showing operation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Glyph {
+    /// The bytes of the glyph's character code in the shown string.
+    pub bytes: Range<usize>,
+    pub code: u32,
+    /// The corners of the glyph's box from its descent to its ascent, in the space the current
+    /// transformation matrix maps to: lower left, lower right, upper right and upper left.
+    pub quad: [(f32, f32); 4],
+    /// How far the glyph moves the text position, including character and word spacing, in
+    /// unscaled text space units. It's to the right, or up for vertical fonts, so their glyphs'
+    /// advances are negative.
+    pub advance: f32,
+}
+
+impl Glyph {
+    pub fn bounds(&self) -> Rect {
+        self.quad[1..].iter().fold(
+            Rect::new(
+                self.quad[0].0,
+                self.quad[0].1,
+                self.quad[0].0,
+                self.quad[0].1,
+            ),
+            |rect, (x, y)| Rect {
+                x_min: rect.x_min.min(*x),
+                y_min: rect.y_min.min(*y),
+                x_max: rect.x_max.max(*x),
+                y_max: rect.y_max.max(*y),
+            },
+        )
+    }
+}
+
+impl Default for TextState {
+    fn default() -> Self {
+        TextState {
+            character_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scaling: 1.0,
+            leading: 0.0,
+            font: None,
+            font_size: 0.0,
+            rendering_mode: TextRenderingMode::FillText,
+            rise: 0.0,
+            matrix: Matrix::IDENTITY,
+            line_matrix: Matrix::IDENTITY,
+        }
+    }
+}
+
+impl TextState {
+    /// Updates the state for a text state, text positioning or `BT` operation. `'` and `"` move
+    /// to the next line and set their spacing here, the text they show is laid out by `show`.
+    pub fn update(&mut self, operation: &Operation) {
+        match operation {
+            Operation::BeginTextObject => {
+                self.matrix = Matrix::IDENTITY;
+                self.line_matrix = Matrix::IDENTITY;
+            }
+            Operation::SetCharacterSpacing(spacing) => self.character_spacing = *spacing,
+            Operation::SetWordSpacing(spacing) => self.word_spacing = *spacing,
+            Operation::SetHorizontalTextScaling(scale) => self.horizontal_scaling = scale / 100.0,
+            Operation::SetTextLeading(leading) => self.leading = *leading,
+            Operation::SetTextFontAndSize { font, size } => {
+                self.font = Some(font.to_string());
+                self.font_size = *size;
+            }
+            Operation::SetTextRenderingMode(mode) => self.rendering_mode = *mode,
+            Operation::SetTextRise(rise) => self.rise = *rise,
+            Operation::MoveTextPosition { x, y } => self.move_text_position(*x, *y),
+            Operation::MoveTextPositionAndSetLeading { x, y } => {
+                self.leading = -y;
+                self.move_text_position(*x, *y);
+            }
+            Operation::SetTextMatrixAndTextLineMatrix(a, b, c, d, e, f) => {
+                self.matrix = Matrix::new(*a, *b, *c, *d, *e, *f);
+                self.line_matrix = self.matrix;
+            }
+            Operation::MoveToStartOfNextTextLine | Operation::MoveToNextLineAndShowText(_) => {
+                self.move_text_position(0.0, -self.leading)
+            }
+            Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText {
+                word_spacing,
+                character_spacing,
+                ..
+            } => {
+                self.word_spacing = *word_spacing;
+                self.character_spacing = *character_spacing;
+                self.move_text_position(0.0, -self.leading);
+            }
+            _ => {}
+        }
+    }
+
+    /// The font set by the last `Tf`, loaded once for a `cache`.
+    pub(crate) fn load_font<'c, R: Resolve>(
+        &self,
+        resolve: &R,
+        resources: &Dictionary,
+        cache: &'c mut HashMap<String, Font>,
+    ) -> Result<&'c Font, Error> {
+        let name = self
+            .font
+            .as_deref()
+            .ok_or_else(|| Error::MissingEntry("Tf".into()))?;
+
+        Font::cached(resolve, resources, cache, name)
+    }
+
+    fn move_text_position(&mut self, x: f32, y: f32) {
+        self.line_matrix = Matrix::translate(x, y) * self.line_matrix;
+        self.matrix = self.line_matrix;
+    }
+
+    /// Lays out the glyphs of a shown string, calling `f` with each and moving the text position
+    /// past it.
+    pub fn show<F: FnMut(Glyph)>(&mut self, font: &Font, bytes: &[u8], ctm: &Matrix, mut f: F) {
+        for (range, code) in font.codes(bytes) {
+            let width = font.width(code);
+            let rendering = Matrix::new(
+                self.font_size * self.horizontal_scaling,
+                0.0,
+                0.0,
+                self.font_size,
+                0.0,
+                self.rise,
+            ) * self.matrix
+                * *ctm;
+
+            let (mut advance, quad) = if font.vertical {
+                let (height, half) = (font.vertical_advance(), width / 2.0);
+                (
+                    -height * self.font_size,
+                    [
+                        rendering.transform(-half, -height),
+                        rendering.transform(half, -height),
+                        rendering.transform(half, 0.0),
+                        rendering.transform(-half, 0.0),
+                    ],
+                )
+            } else {
+                (
+                    width * self.font_size,
+                    [
+                        rendering.transform(0.0, font.descent),
+                        rendering.transform(width, font.descent),
+                        rendering.transform(width, font.ascent),
+                        rendering.transform(0.0, font.ascent),
+                    ],
+                )
+            };
+            advance += self.character_spacing;
+            if font.is_space(code) {
+                advance += self.word_spacing;
+            }
+
+            f(Glyph {
+                bytes: range,
+                code,
+                quad,
+                advance,
+            });
+
+            self.advance(font, advance);
+        }
+    }
+
+    /// Moves the text position for a number in a `TJ` array, in thousandths of a unit of text
+    /// space.
+    pub fn adjust(&mut self, font: &Font, offset: f32) {
+        self.advance(font, -offset / 1000.0 * self.font_size);
+    }
+
+    /// Moves the text position along the baseline by an unscaled distance, or up for vertical
+    /// fonts, which horizontal scaling doesn't apply to.
+    fn advance(&mut self, font: &Font, distance: f32) {
+        let (x, y) = if font.vertical {
+            (0.0, distance)
+        } else {
+            (distance * self.horizontal_scaling, 0.0)
+        };
+        self.matrix = Matrix::translate(x, y) * self.matrix;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::{
+        object::NoResolve,
+        primitive::{Dictionary, Primitive},
+    };
+
+    use super::*;
+
+    #[test]
+    fn lays_out_glyphs() {
+        let mut font = Dictionary::new();
+        font.insert(
+            "BaseFont".to_string(),
+            Primitive::Name("Courier".to_string()),
+        );
+        let font = Font::load(&NoResolve, &font).unwrap();
+
+        let mut state = TextState::default();
+        for operation in &[
+            Operation::BeginTextObject,
+            Operation::SetTextFontAndSize {
+                font: "F1",
+                size: 10.0,
+            },
+            Operation::SetTextLeading(12.0),
+            Operation::SetWordSpacing(4.0),
+            Operation::SetTextMatrixAndTextLineMatrix(1.0, 0.0, 0.0, 1.0, 100.0, 700.0),
+            Operation::MoveToStartOfNextTextLine,
+        ] {
+            state.update(operation);
+        }
+
+        let mut glyphs = Vec::new();
+        state.show(&font, b"a b", &Matrix::translate(0.0, 10.0), |glyph| {
+            glyphs.push(glyph)
+        });
+        state.adjust(&font, -1000.0);
+
+        let starts: Vec<_> = glyphs.iter().map(|glyph| glyph.quad[0]).collect();
+        assert_eq!(starts, vec![(100.0, 696.0), (106.0, 696.0), (116.0, 696.0)]);
+        assert_eq!(glyphs[2].bounds(), Rect::new(116.0, 696.0, 122.0, 706.0));
+        assert_eq!(state.matrix.transform(0.0, 0.0), (132.0, 688.0));
+    }
+}
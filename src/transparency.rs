@@ -0,0 +1,181 @@
+use pdf::{object::Resolve, primitive::Primitive};
+
+use crate::{
+    document::{get, get_integer, Location, RawPage},
+    error::Error,
+    ext_g_state::{BlendMode, SoftMask},
+    painting::{for_each_painting, is_transparency_group, PaintKind, Painting},
+};
+
+/// A way an object is made transparent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transparency {
+    /// `CA` below 1, for strokes.
+    StrokeAlpha(f32),
+    /// `ca` below 1, for everything but strokes.
+    FillAlpha(f32),
+    /// A `BM` blend mode other than Normal.
+    BlendMode(BlendMode),
+    /// An `SMask` soft mask set in the graphics state.
+    SoftMask,
+    /// An image with an `/SMask` or `/SMaskInData`.
+    ImageSoftMask,
+    /// A Form XObject that is a transparency group.
+    TransparencyGroup,
+}
+
+/// An object painted with transparency.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransparentObject {
+    /// The content stream the object is painted by, such as `page 3 > XObject /Fm1`.
+    pub path: Vec<Location>,
+    /// The index of the painting operation in its content stream.
+    pub index: usize,
+    pub kind: PaintKind,
+    pub transparency: Vec<Transparency>,
+}
+
+/// The objects on a page that are painted with transparency, which PDF/X-1a and PDF/X-3 don't
+/// allow.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransparencyReport {
+    pub objects: Vec<TransparentObject>,
+}
+
+impl TransparencyReport {
+    /// Walks the page's content and the Form XObjects it paints. Transparency set in the graphics
+    /// state is reported on the objects painted with it, not on the `gs` operations that set it.
+    pub fn analyze<R: Resolve>(resolve: &R, page: &RawPage) -> Result<Self, Error> {
+        let mut report = TransparencyReport::default();
+        for_each_painting(resolve, page, |painting| {
+            let transparency = transparency(resolve, &painting)?;
+            if !transparency.is_empty() {
+                report.objects.push(TransparentObject {
+                    path: painting.path.to_vec(),
+                    index: painting.index,
+                    kind: painting.kind,
+                    transparency,
+                });
+            }
+            Ok(())
+        })?;
+
+        Ok(report)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+fn transparency<R: Resolve>(resolve: &R, painting: &Painting) -> Result<Vec<Transparency>, Error> {
+    let state = painting.state;
+    let mut transparency = Vec::new();
+
+    if painting.kind.is_stroke() {
+        if state.stroke_alpha < 1.0 {
+            transparency.push(Transparency::StrokeAlpha(state.stroke_alpha));
+        }
+    } else if state.fill_alpha < 1.0 {
+        transparency.push(Transparency::FillAlpha(state.fill_alpha));
+    }
+    if state.blend_mode != BlendMode::Normal {
+        transparency.push(Transparency::BlendMode(state.blend_mode));
+    }
+    if state.soft_mask != SoftMask::None {
+        transparency.push(Transparency::SoftMask);
+    }
+
+    if let Some(xobject) = painting.xobject {
+        match painting.kind {
+            PaintKind::Image => {
+                let soft_mask =
+                    matches!(get(resolve, xobject, "SMask")?, Some(Primitive::Stream(_)));
+                let in_data = get_integer(resolve, xobject, "SMaskInData")?.unwrap_or(0) != 0;
+                if soft_mask || in_data {
+                    transparency.push(Transparency::ImageSoftMask);
+                }
+            }
+            PaintKind::Form if is_transparency_group(resolve, xobject)? => {
+                transparency.push(Transparency::TransparencyGroup);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(transparency)
+}
+
+#[cfg(test)]
+mod tests {
+    use pdf::{
+        object::NoResolve,
+        primitive::{Dictionary, PdfStream},
+    };
+
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn finds_transparent_objects() {
+        let mut ext_g_state = Dictionary::new();
+        ext_g_state.insert("ca".to_string(), Primitive::Number(0.5));
+        ext_g_state.insert("BM".to_string(), Primitive::Name("Multiply".to_string()));
+        let mut ext_g_states = Dictionary::new();
+        ext_g_states.insert("GS0".to_string(), Primitive::Dictionary(ext_g_state));
+
+        let mut group = Dictionary::new();
+        group.insert("S".to_string(), Primitive::Name("Transparency".to_string()));
+        let mut form = Dictionary::new();
+        form.insert("Subtype".to_string(), Primitive::Name("Form".to_string()));
+        form.insert("Group".to_string(), Primitive::Dictionary(group));
+        let mut xobjects = Dictionary::new();
+        xobjects.insert(
+            "Fm0".to_string(),
+            Primitive::Stream(PdfStream {
+                info: form,
+                data: b"0 0 10 10 re f".to_vec(),
+            }),
+        );
+
+        let mut resources = Dictionary::new();
+        resources.insert("ExtGState".to_string(), Primitive::Dictionary(ext_g_states));
+        resources.insert("XObject".to_string(), Primitive::Dictionary(xobjects));
+        let page = fixtures::page(
+            resources,
+            b"/Fm0 Do q /GS0 gs 0 0 10 10 re f 0 0 10 10 re S \
+              BI /W 1 /H 1 /CS /G /BPC 8 ID \x80 EI Q",
+        );
+
+        let report = TransparencyReport::analyze(&NoResolve, &page).unwrap();
+        let objects: Vec<_> = report
+            .objects
+            .iter()
+            .map(|object| (object.path.len(), object.index, object.transparency.clone()))
+            .collect();
+
+        assert_eq!(
+            objects,
+            vec![
+                (1, 0, vec![Transparency::TransparencyGroup]),
+                (
+                    1,
+                    4,
+                    vec![
+                        Transparency::FillAlpha(0.5),
+                        Transparency::BlendMode(BlendMode::Multiply)
+                    ]
+                ),
+                (1, 6, vec![Transparency::BlendMode(BlendMode::Multiply)]),
+                (
+                    1,
+                    9,
+                    vec![
+                        Transparency::FillAlpha(0.5),
+                        Transparency::BlendMode(BlendMode::Multiply)
+                    ]
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1804 @@
+use std::{borrow::Cow, ops::Range};
+
+use pdf::primitive::Primitive;
+
+use crate::{
+    ColorRenderingIntent, LineCapStyle, LineJoinStyle, Name, Operation, PropertyList,
+    TextOrGlyphPositioning, TextRenderingMode, UntypedColor,
+};
+
+/// Reads a sequence of operations, one method per operator. Each method does nothing by default,
+/// so an implementation only overrides the operators it cares about and is driven by `walk`.
+pub trait OperationVisitor<'src> {
+    /// Visits every operation in order.
+    fn walk(&mut self, operations: &[Operation<'src>]) {
+        for operation in operations {
+            self.visit_operation(operation);
+        }
+    }
+
+    /// Visits a single operation. Overriding this sees every operation before it is dispatched,
+    /// call [`visit_variant`] to keep the dispatch.
+    fn visit_operation(&mut self, operation: &Operation<'src>) {
+        visit_variant(self, operation);
+    }
+
+    /// `b`
+    fn visit_close_fill_and_stroke_path_using_non_zero_winding_number(&mut self) {}
+
+    /// `B`
+    fn visit_fill_and_stroke_path_using_non_zero_winding_number(&mut self) {}
+
+    /// `b*`
+    fn visit_close_fill_and_stroke_path_using_even_odd_rule(&mut self) {}
+
+    /// `B*`
+    fn visit_fill_and_stroke_path_using_even_odd_rule(&mut self) {}
+
+    /// `BDC`
+    fn visit_begin_marked_content_sequence_with_property_list(
+        &mut self,
+        _tag: Name<'src>,
+        _properties: PropertyList<'src>,
+    ) {
+    }
+
+    /// `BI`
+    fn visit_begin_inline_image_object(&mut self) {}
+
+    /// `BMC`
+    fn visit_begin_marked_content_sequence(&mut self, _tag: Name<'src>) {}
+
+    /// `BT`
+    fn visit_begin_text_object(&mut self) {}
+
+    /// `BX`
+    fn visit_begin_compatibility_section(&mut self) {}
+
+    /// `c`
+    fn visit_append_curved_segment_to_path(
+        &mut self,
+        _x1: f32,
+        _y1: f32,
+        _x2: f32,
+        _y2: f32,
+        _x3: f32,
+        _y3: f32,
+    ) {
+    }
+
+    /// `cm`
+    fn visit_concatenate_matrix_to_current_transformation_matrix(
+        &mut self,
+        _a: f32,
+        _b: f32,
+        _c: f32,
+        _d: f32,
+        _e: f32,
+        _f: f32,
+    ) {
+    }
+
+    /// `CS`
+    fn visit_set_color_space_for_stroking_operations(&mut self, _name: Name<'src>) {}
+
+    /// `cs`
+    fn visit_set_color_space_for_non_stroking_operations(&mut self, _name: Name<'src>) {}
+
+    /// `d`
+    fn visit_set_line_dash_pattern(&mut self, _array: &[f32], _phase: f32) {}
+
+    /// `d0`
+    fn visit_set_glyph_width_in_type3_font(&mut self, _wx: f32, _wy: f32) {}
+
+    /// `d1`
+    fn visit_set_glyph_width_and_bounding_box_in_type3_font(
+        &mut self,
+        _wx: f32,
+        _wy: f32,
+        _llx: f32,
+        _lly: f32,
+        _urx: f32,
+        _ury: f32,
+    ) {
+    }
+
+    /// `Do`
+    fn visit_invoke_named_x_object(&mut self, _name: Name<'src>) {}
+
+    /// `DP`
+    fn visit_define_marked_content_point_with_property_list(
+        &mut self,
+        _tag: Name<'src>,
+        _properties: PropertyList<'src>,
+    ) {
+    }
+
+    /// `EI`
+    fn visit_end_inline_image_object(&mut self, _data: &'src [u8]) {}
+
+    /// `EMC`
+    fn visit_end_marked_content_sequence(&mut self) {}
+
+    /// `ET`
+    fn visit_end_text_object(&mut self) {}
+
+    /// `EX`
+    fn visit_end_compatibility_section(&mut self) {}
+
+    /// `f`
+    fn visit_fill_path_using_non_zero_winding_number_rule(&mut self) {}
+
+    /// `F`
+    fn visit_obsolete_fill_path_using_non_zero_winding_mumber_rule(&mut self) {}
+
+    /// `f*`
+    fn visit_fill_path_using_even_odd_rule(&mut self) {}
+
+    /// `G`
+    fn visit_set_gray_level_for_stroking_operations(&mut self, _gray: f32) {}
+
+    /// `g`
+    fn visit_set_gray_level_for_non_stroking_operations(&mut self, _gray: f32) {}
+
+    /// `gs`
+    fn visit_set_parameters_from_graphics_state_parameter_dictionary(&mut self, _name: Name<'src>) {
+    }
+
+    /// `h`
+    fn visit_close_subpath(&mut self) {}
+
+    /// `i`
+    fn visit_set_flatness_tolerance(&mut self, _flatness: i32) {}
+
+    /// `ID`
+    fn visit_begin_inline_image_data(&mut self, _entries: &'src [Primitive]) {}
+
+    /// `j`
+    fn visit_set_line_join_style(&mut self, _style: LineJoinStyle) {}
+
+    /// `J`
+    fn visit_set_line_cap_style(&mut self, _style: LineCapStyle) {}
+
+    /// `K`
+    fn visit_set_cmyk_color_for_stroking_operations(&mut self, _c: f32, _m: f32, _y: f32, _k: f32) {
+    }
+
+    /// `k`
+    fn visit_set_cmyk_color_for_non_stroking_operations(
+        &mut self,
+        _c: f32,
+        _m: f32,
+        _y: f32,
+        _k: f32,
+    ) {
+    }
+
+    /// `l`
+    fn visit_append_straight_line_segment_to_path(&mut self, _x: f32, _y: f32) {}
+
+    /// `m`
+    fn visit_begin_new_subpath(&mut self, _x: f32, _y: f32) {}
+
+    /// `M`
+    fn visit_set_miter_limit(&mut self, _limit: f32) {}
+
+    /// `MP`
+    fn visit_define_marked_content_point(&mut self, _tag: Name<'src>) {}
+
+    /// `n`
+    fn visit_end_path_without_filling_or_stroking(&mut self) {}
+
+    /// `q`
+    fn visit_save_graphics_state(&mut self) {}
+
+    /// `Q`
+    fn visit_restore_graphics_state(&mut self) {}
+
+    /// `re`
+    fn visit_append_rectangle_to_path(&mut self, _x: f32, _y: f32, _width: f32, _height: f32) {}
+
+    /// `RG`
+    fn visit_set_rgb_color_for_stroking_operations(&mut self, _r: f32, _g: f32, _b: f32) {}
+
+    /// `rg`
+    fn visit_set_rgb_color_for_non_stroking_operations(&mut self, _r: f32, _g: f32, _b: f32) {}
+
+    /// `ri`
+    fn visit_set_color_rendering_intent(&mut self, _intent: ColorRenderingIntent) {}
+
+    /// `s`
+    fn visit_close_and_stroke_path(&mut self) {}
+
+    /// `S`
+    fn visit_stroke_path(&mut self) {}
+
+    /// `SC`
+    fn visit_set_color_for_stroking_operations(&mut self, _color: &UntypedColor) {}
+
+    /// `sc`
+    fn visit_set_color_for_non_stroking_operations(&mut self, _color: &UntypedColor) {}
+
+    /// `SCN`
+    fn visit_set_color_for_stroking_operations_icc_based_and_special_color_spaces(
+        &mut self,
+        _cs: &[f32],
+        _name: Option<Name<'src>>,
+    ) {
+    }
+
+    /// `scn`
+    fn visit_set_color_for_non_stroking_operations_icc_based_and_special_color_spaces(
+        &mut self,
+        _cs: &[f32],
+        _name: Option<Name<'src>>,
+    ) {
+    }
+
+    /// `sh`
+    fn visit_paint_area_defined_by_shading_pattern(&mut self, _name: Name<'src>) {}
+
+    /// `T*`
+    fn visit_move_to_start_of_next_text_line(&mut self) {}
+
+    /// `Tc`
+    fn visit_set_character_spacing(&mut self, _spacing: f32) {}
+
+    /// `Td`
+    fn visit_move_text_position(&mut self, _x: f32, _y: f32) {}
+
+    /// `TD`
+    fn visit_move_text_position_and_set_leading(&mut self, _x: f32, _y: f32) {}
+
+    /// `Tf`
+    fn visit_set_text_font_and_size(&mut self, _font: &'src str, _size: f32) {}
+
+    /// `Tj`
+    fn visit_show_text(&mut self, _text: &str) {}
+
+    /// `TJ`
+    fn visit_show_text_allowing_individual_glyph_positioning(
+        &mut self,
+        _array: &[TextOrGlyphPositioning<'src>],
+    ) {
+    }
+
+    /// `TL`
+    fn visit_set_text_leading(&mut self, _leading: f32) {}
+
+    /// `Tm`
+    fn visit_set_text_matrix_and_text_line_matrix(
+        &mut self,
+        _a: f32,
+        _b: f32,
+        _c: f32,
+        _d: f32,
+        _e: f32,
+        _f: f32,
+    ) {
+    }
+
+    /// `Tr`
+    fn visit_set_text_rendering_mode(&mut self, _mode: TextRenderingMode) {}
+
+    /// `Ts`
+    fn visit_set_text_rise(&mut self, _rise: f32) {}
+
+    /// `Tw`
+    fn visit_set_word_spacing(&mut self, _spacing: f32) {}
+
+    /// `Tz`
+    fn visit_set_horizontal_text_scaling(&mut self, _scale: f32) {}
+
+    /// `v`
+    fn visit_append_curved_segment_to_path_initial_point_replicated(
+        &mut self,
+        _x2: f32,
+        _y2: f32,
+        _x3: f32,
+        _y3: f32,
+    ) {
+    }
+
+    /// `w`
+    fn visit_set_line_width(&mut self, _width: f32) {}
+
+    /// `W`
+    fn visit_set_clipping_path_using_non_zero_winding_number_rule(&mut self) {}
+
+    /// `W*`
+    fn visit_set_clipping_path_using_even_odd_rule(&mut self) {}
+
+    /// `y`
+    fn visit_append_curved_segment_to_path_final_point_replicated(
+        &mut self,
+        _x1: f32,
+        _y1: f32,
+        _x3: f32,
+        _y3: f32,
+    ) {
+    }
+
+    /// `'`
+    fn visit_move_to_next_line_and_show_text(&mut self, _text: &str) {}
+
+    /// `"`
+    fn visit_set_word_and_character_spacing_move_to_next_line_and_show_text(
+        &mut self,
+        _text: &str,
+        _word_spacing: f32,
+        _character_spacing: f32,
+    ) {
+    }
+
+    /// An operator this crate does not recognize.
+    fn visit_unknown(&mut self, _operator: &'src str, _operands: &'src [Primitive]) {}
+
+    /// An operation that failed to parse.
+    fn visit_malformed(&mut self, _byte_range: &Range<usize>, _reason: &'src str) {}
+}
+
+/// Calls the [`OperationVisitor`] method matching the variant of `operation`.
+pub fn visit_variant<'src, V: OperationVisitor<'src> + ?Sized>(
+    visitor: &mut V,
+    operation: &Operation<'src>,
+) {
+    match *operation {
+        Operation::CloseFillAndStrokePathUsingNonZeroWindingNumber => {
+            visitor.visit_close_fill_and_stroke_path_using_non_zero_winding_number()
+        }
+        Operation::FillAndStrokePathUsingNonZeroWindingNumber => {
+            visitor.visit_fill_and_stroke_path_using_non_zero_winding_number()
+        }
+        Operation::CloseFillAndStrokePathUsingEvenOddRule => {
+            visitor.visit_close_fill_and_stroke_path_using_even_odd_rule()
+        }
+        Operation::FillAndStrokePathUsingEvenOddRule => {
+            visitor.visit_fill_and_stroke_path_using_even_odd_rule()
+        }
+        Operation::BeginMarkedContentSequenceWithPropertyList { tag, properties } => {
+            visitor.visit_begin_marked_content_sequence_with_property_list(tag, properties)
+        }
+        Operation::BeginInlineImageObject => visitor.visit_begin_inline_image_object(),
+        Operation::BeginMarkedContentSequence(tag) => {
+            visitor.visit_begin_marked_content_sequence(tag)
+        }
+        Operation::BeginTextObject => visitor.visit_begin_text_object(),
+        Operation::BeginCompatibilitySection => visitor.visit_begin_compatibility_section(),
+        Operation::AppendCurvedSegmentToPath {
+            x1,
+            y1,
+            x2,
+            y2,
+            x3,
+            y3,
+        } => visitor.visit_append_curved_segment_to_path(x1, y1, x2, y2, x3, y3),
+        Operation::ConcatenateMatrixToCurrentTransformationMatrix(a, b, c, d, e, f) => {
+            visitor.visit_concatenate_matrix_to_current_transformation_matrix(a, b, c, d, e, f)
+        }
+        Operation::SetColorSpaceForStrokingOperations(name) => {
+            visitor.visit_set_color_space_for_stroking_operations(name)
+        }
+        Operation::SetColorSpaceForNonStrokingOperations(name) => {
+            visitor.visit_set_color_space_for_non_stroking_operations(name)
+        }
+        Operation::SetLineDashPattern { ref array, phase } => {
+            visitor.visit_set_line_dash_pattern(array, phase)
+        }
+        Operation::SetGlyphWidthInType3Font { wx, wy } => {
+            visitor.visit_set_glyph_width_in_type3_font(wx, wy)
+        }
+        Operation::SetGlyphWidthAndBoundingBoxInType3Font {
+            wx,
+            wy,
+            llx,
+            lly,
+            urx,
+            ury,
+        } => {
+            visitor.visit_set_glyph_width_and_bounding_box_in_type3_font(wx, wy, llx, lly, urx, ury)
+        }
+        Operation::InvokeNamedXObject(name) => visitor.visit_invoke_named_x_object(name),
+        Operation::DefineMarkedContentPointWithPropertyList { tag, properties } => {
+            visitor.visit_define_marked_content_point_with_property_list(tag, properties)
+        }
+        Operation::EndInlineImageObject(data) => visitor.visit_end_inline_image_object(data),
+        Operation::EndMarkedContentSequence => visitor.visit_end_marked_content_sequence(),
+        Operation::EndTextObject => visitor.visit_end_text_object(),
+        Operation::EndCompatibilitySection => visitor.visit_end_compatibility_section(),
+        Operation::FillPathUsingNonZeroWindingNumberRule => {
+            visitor.visit_fill_path_using_non_zero_winding_number_rule()
+        }
+        Operation::ObsoleteFillPathUsingNonZeroWindingMumberRule => {
+            visitor.visit_obsolete_fill_path_using_non_zero_winding_mumber_rule()
+        }
+        Operation::FillPathUsingEvenOddRule => visitor.visit_fill_path_using_even_odd_rule(),
+        Operation::SetGrayLevelForStrokingOperations(gray) => {
+            visitor.visit_set_gray_level_for_stroking_operations(gray)
+        }
+        Operation::SetGrayLevelForNonStrokingOperations(gray) => {
+            visitor.visit_set_gray_level_for_non_stroking_operations(gray)
+        }
+        Operation::SetParametersFromGraphicsStateParameterDictionary(name) => {
+            visitor.visit_set_parameters_from_graphics_state_parameter_dictionary(name)
+        }
+        Operation::CloseSubpath => visitor.visit_close_subpath(),
+        Operation::SetFlatnessTolerance(flatness) => visitor.visit_set_flatness_tolerance(flatness),
+        Operation::BeginInlineImageData(entries) => visitor.visit_begin_inline_image_data(entries),
+        Operation::SetLineJoinStyle(style) => visitor.visit_set_line_join_style(style),
+        Operation::SetLineCapStyle(style) => visitor.visit_set_line_cap_style(style),
+        Operation::SetCMYKColorForStrokingOperations(c, m, y, k) => {
+            visitor.visit_set_cmyk_color_for_stroking_operations(c, m, y, k)
+        }
+        Operation::SetCMYKColorForNonStrokingOperations(c, m, y, k) => {
+            visitor.visit_set_cmyk_color_for_non_stroking_operations(c, m, y, k)
+        }
+        Operation::AppendStraightLineSegmentToPath { x, y } => {
+            visitor.visit_append_straight_line_segment_to_path(x, y)
+        }
+        Operation::BeginNewSubpath { x, y } => visitor.visit_begin_new_subpath(x, y),
+        Operation::SetMiterLimit(limit) => visitor.visit_set_miter_limit(limit),
+        Operation::DefineMarkedContentPoint(tag) => visitor.visit_define_marked_content_point(tag),
+        Operation::EndPathWithoutFillingOrStroking => {
+            visitor.visit_end_path_without_filling_or_stroking()
+        }
+        Operation::SaveGraphicsState => visitor.visit_save_graphics_state(),
+        Operation::RestoreGraphicsState => visitor.visit_restore_graphics_state(),
+        Operation::AppendRectangleToPath {
+            x,
+            y,
+            width,
+            height,
+        } => visitor.visit_append_rectangle_to_path(x, y, width, height),
+        Operation::SetRGBColorForStrokingOperations(r, g, b) => {
+            visitor.visit_set_rgb_color_for_stroking_operations(r, g, b)
+        }
+        Operation::SetRGBColorForNonStrokingOperations(r, g, b) => {
+            visitor.visit_set_rgb_color_for_non_stroking_operations(r, g, b)
+        }
+        Operation::SetColorRenderingIntent(intent) => {
+            visitor.visit_set_color_rendering_intent(intent)
+        }
+        Operation::CloseAndStrokePath => visitor.visit_close_and_stroke_path(),
+        Operation::StrokePath => visitor.visit_stroke_path(),
+        Operation::SetColorForStrokingOperations(ref color) => {
+            visitor.visit_set_color_for_stroking_operations(color)
+        }
+        Operation::SetColorForNonStrokingOperations(ref color) => {
+            visitor.visit_set_color_for_non_stroking_operations(color)
+        }
+        Operation::SetColorForStrokingOperationsICCBasedAndSpecialColorSpaces { ref cs, name } => {
+            visitor.visit_set_color_for_stroking_operations_icc_based_and_special_color_spaces(
+                cs, name,
+            )
+        }
+        Operation::SetColorForNonStrokingOperationsICCBasedAndSpecialColorSpaces {
+            ref cs,
+            name,
+        } => visitor
+            .visit_set_color_for_non_stroking_operations_icc_based_and_special_color_spaces(
+                cs, name,
+            ),
+        Operation::PaintAreaDefinedByShadingPattern(name) => {
+            visitor.visit_paint_area_defined_by_shading_pattern(name)
+        }
+        Operation::MoveToStartOfNextTextLine => visitor.visit_move_to_start_of_next_text_line(),
+        Operation::SetCharacterSpacing(spacing) => visitor.visit_set_character_spacing(spacing),
+        Operation::MoveTextPosition { x, y } => visitor.visit_move_text_position(x, y),
+        Operation::MoveTextPositionAndSetLeading { x, y } => {
+            visitor.visit_move_text_position_and_set_leading(x, y)
+        }
+        Operation::SetTextFontAndSize { font, size } => {
+            visitor.visit_set_text_font_and_size(font, size)
+        }
+        Operation::ShowText(ref text) => visitor.visit_show_text(text),
+        Operation::ShowTextAllowingIndividualGlyphPositioning(ref array) => {
+            visitor.visit_show_text_allowing_individual_glyph_positioning(array)
+        }
+        Operation::SetTextLeading(leading) => visitor.visit_set_text_leading(leading),
+        Operation::SetTextMatrixAndTextLineMatrix(a, b, c, d, e, f) => {
+            visitor.visit_set_text_matrix_and_text_line_matrix(a, b, c, d, e, f)
+        }
+        Operation::SetTextRenderingMode(mode) => visitor.visit_set_text_rendering_mode(mode),
+        Operation::SetTextRise(rise) => visitor.visit_set_text_rise(rise),
+        Operation::SetWordSpacing(spacing) => visitor.visit_set_word_spacing(spacing),
+        Operation::SetHorizontalTextScaling(scale) => {
+            visitor.visit_set_horizontal_text_scaling(scale)
+        }
+        Operation::AppendCurvedSegmentToPathInitialPointReplicated { x2, y2, x3, y3 } => {
+            visitor.visit_append_curved_segment_to_path_initial_point_replicated(x2, y2, x3, y3)
+        }
+        Operation::SetLineWidth(width) => visitor.visit_set_line_width(width),
+        Operation::SetClippingPathUsingNonZeroWindingNumberRule => {
+            visitor.visit_set_clipping_path_using_non_zero_winding_number_rule()
+        }
+        Operation::SetClippingPathUsingEvenOddRule => {
+            visitor.visit_set_clipping_path_using_even_odd_rule()
+        }
+        Operation::AppendCurvedSegmentToPathFinalPointReplicated { x1, y1, x3, y3 } => {
+            visitor.visit_append_curved_segment_to_path_final_point_replicated(x1, y1, x3, y3)
+        }
+        Operation::MoveToNextLineAndShowText(ref text) => {
+            visitor.visit_move_to_next_line_and_show_text(text)
+        }
+        Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText {
+            ref text,
+            word_spacing,
+            character_spacing,
+        } => visitor.visit_set_word_and_character_spacing_move_to_next_line_and_show_text(
+            text,
+            word_spacing,
+            character_spacing,
+        ),
+        Operation::Unknown { operator, operands } => visitor.visit_unknown(operator, operands),
+        Operation::Malformed {
+            ref byte_range,
+            reason,
+        } => visitor.visit_malformed(byte_range, reason),
+    }
+}
+
+/// Rewrites a sequence of operations, one method per operator. Each method takes the operands of
+/// an operation and returns the operation to put in its place, rebuilding it unchanged by default.
+pub trait OperationFolder<'src> {
+    /// Folds every operation in order.
+    fn fold(&mut self, operations: Vec<Operation<'src>>) -> Vec<Operation<'src>> {
+        let mut out = Vec::with_capacity(operations.len());
+        for operation in operations {
+            self.fold_operation(operation, &mut out);
+        }
+        out
+    }
+
+    /// Folds a single operation into `out`. Overriding this can drop an operation or emit several
+    /// in its place, call [`fold_variant`] to keep the dispatch.
+    fn fold_operation(&mut self, operation: Operation<'src>, out: &mut Vec<Operation<'src>>) {
+        out.push(fold_variant(self, operation));
+    }
+
+    /// `b`
+    fn fold_close_fill_and_stroke_path_using_non_zero_winding_number(&mut self) -> Operation<'src> {
+        Operation::CloseFillAndStrokePathUsingNonZeroWindingNumber
+    }
+
+    /// `B`
+    fn fold_fill_and_stroke_path_using_non_zero_winding_number(&mut self) -> Operation<'src> {
+        Operation::FillAndStrokePathUsingNonZeroWindingNumber
+    }
+
+    /// `b*`
+    fn fold_close_fill_and_stroke_path_using_even_odd_rule(&mut self) -> Operation<'src> {
+        Operation::CloseFillAndStrokePathUsingEvenOddRule
+    }
+
+    /// `B*`
+    fn fold_fill_and_stroke_path_using_even_odd_rule(&mut self) -> Operation<'src> {
+        Operation::FillAndStrokePathUsingEvenOddRule
+    }
+
+    /// `BDC`
+    fn fold_begin_marked_content_sequence_with_property_list(
+        &mut self,
+        tag: Name<'src>,
+        properties: PropertyList<'src>,
+    ) -> Operation<'src> {
+        Operation::BeginMarkedContentSequenceWithPropertyList { tag, properties }
+    }
+
+    /// `BI`
+    fn fold_begin_inline_image_object(&mut self) -> Operation<'src> {
+        Operation::BeginInlineImageObject
+    }
+
+    /// `BMC`
+    fn fold_begin_marked_content_sequence(&mut self, tag: Name<'src>) -> Operation<'src> {
+        Operation::BeginMarkedContentSequence(tag)
+    }
+
+    /// `BT`
+    fn fold_begin_text_object(&mut self) -> Operation<'src> {
+        Operation::BeginTextObject
+    }
+
+    /// `BX`
+    fn fold_begin_compatibility_section(&mut self) -> Operation<'src> {
+        Operation::BeginCompatibilitySection
+    }
+
+    /// `c`
+    fn fold_append_curved_segment_to_path(
+        &mut self,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        x3: f32,
+        y3: f32,
+    ) -> Operation<'src> {
+        Operation::AppendCurvedSegmentToPath {
+            x1,
+            y1,
+            x2,
+            y2,
+            x3,
+            y3,
+        }
+    }
+
+    /// `cm`
+    fn fold_concatenate_matrix_to_current_transformation_matrix(
+        &mut self,
+        a: f32,
+        b: f32,
+        c: f32,
+        d: f32,
+        e: f32,
+        f: f32,
+    ) -> Operation<'src> {
+        Operation::ConcatenateMatrixToCurrentTransformationMatrix(a, b, c, d, e, f)
+    }
+
+    /// `CS`
+    fn fold_set_color_space_for_stroking_operations(
+        &mut self,
+        name: Name<'src>,
+    ) -> Operation<'src> {
+        Operation::SetColorSpaceForStrokingOperations(name)
+    }
+
+    /// `cs`
+    fn fold_set_color_space_for_non_stroking_operations(
+        &mut self,
+        name: Name<'src>,
+    ) -> Operation<'src> {
+        Operation::SetColorSpaceForNonStrokingOperations(name)
+    }
+
+    /// `d`
+    fn fold_set_line_dash_pattern(&mut self, array: Vec<f32>, phase: f32) -> Operation<'src> {
+        Operation::SetLineDashPattern { array, phase }
+    }
+
+    /// `d0`
+    fn fold_set_glyph_width_in_type3_font(&mut self, wx: f32, wy: f32) -> Operation<'src> {
+        Operation::SetGlyphWidthInType3Font { wx, wy }
+    }
+
+    /// `d1`
+    fn fold_set_glyph_width_and_bounding_box_in_type3_font(
+        &mut self,
+        wx: f32,
+        wy: f32,
+        llx: f32,
+        lly: f32,
+        urx: f32,
+        ury: f32,
+    ) -> Operation<'src> {
+        Operation::SetGlyphWidthAndBoundingBoxInType3Font {
+            wx,
+            wy,
+            llx,
+            lly,
+            urx,
+            ury,
+        }
+    }
+
+    /// `Do`
+    fn fold_invoke_named_x_object(&mut self, name: Name<'src>) -> Operation<'src> {
+        Operation::InvokeNamedXObject(name)
+    }
+
+    /// `DP`
+    fn fold_define_marked_content_point_with_property_list(
+        &mut self,
+        tag: Name<'src>,
+        properties: PropertyList<'src>,
+    ) -> Operation<'src> {
+        Operation::DefineMarkedContentPointWithPropertyList { tag, properties }
+    }
+
+    /// `EI`
+    fn fold_end_inline_image_object(&mut self, data: &'src [u8]) -> Operation<'src> {
+        Operation::EndInlineImageObject(data)
+    }
+
+    /// `EMC`
+    fn fold_end_marked_content_sequence(&mut self) -> Operation<'src> {
+        Operation::EndMarkedContentSequence
+    }
+
+    /// `ET`
+    fn fold_end_text_object(&mut self) -> Operation<'src> {
+        Operation::EndTextObject
+    }
+
+    /// `EX`
+    fn fold_end_compatibility_section(&mut self) -> Operation<'src> {
+        Operation::EndCompatibilitySection
+    }
+
+    /// `f`
+    fn fold_fill_path_using_non_zero_winding_number_rule(&mut self) -> Operation<'src> {
+        Operation::FillPathUsingNonZeroWindingNumberRule
+    }
+
+    /// `F`
+    fn fold_obsolete_fill_path_using_non_zero_winding_mumber_rule(&mut self) -> Operation<'src> {
+        Operation::ObsoleteFillPathUsingNonZeroWindingMumberRule
+    }
+
+    /// `f*`
+    fn fold_fill_path_using_even_odd_rule(&mut self) -> Operation<'src> {
+        Operation::FillPathUsingEvenOddRule
+    }
+
+    /// `G`
+    fn fold_set_gray_level_for_stroking_operations(&mut self, gray: f32) -> Operation<'src> {
+        Operation::SetGrayLevelForStrokingOperations(gray)
+    }
+
+    /// `g`
+    fn fold_set_gray_level_for_non_stroking_operations(&mut self, gray: f32) -> Operation<'src> {
+        Operation::SetGrayLevelForNonStrokingOperations(gray)
+    }
+
+    /// `gs`
+    fn fold_set_parameters_from_graphics_state_parameter_dictionary(
+        &mut self,
+        name: Name<'src>,
+    ) -> Operation<'src> {
+        Operation::SetParametersFromGraphicsStateParameterDictionary(name)
+    }
+
+    /// `h`
+    fn fold_close_subpath(&mut self) -> Operation<'src> {
+        Operation::CloseSubpath
+    }
+
+    /// `i`
+    fn fold_set_flatness_tolerance(&mut self, flatness: i32) -> Operation<'src> {
+        Operation::SetFlatnessTolerance(flatness)
+    }
+
+    /// `ID`
+    fn fold_begin_inline_image_data(&mut self, entries: &'src [Primitive]) -> Operation<'src> {
+        Operation::BeginInlineImageData(entries)
+    }
+
+    /// `j`
+    fn fold_set_line_join_style(&mut self, style: LineJoinStyle) -> Operation<'src> {
+        Operation::SetLineJoinStyle(style)
+    }
+
+    /// `J`
+    fn fold_set_line_cap_style(&mut self, style: LineCapStyle) -> Operation<'src> {
+        Operation::SetLineCapStyle(style)
+    }
+
+    /// `K`
+    fn fold_set_cmyk_color_for_stroking_operations(
+        &mut self,
+        c: f32,
+        m: f32,
+        y: f32,
+        k: f32,
+    ) -> Operation<'src> {
+        Operation::SetCMYKColorForStrokingOperations(c, m, y, k)
+    }
+
+    /// `k`
+    fn fold_set_cmyk_color_for_non_stroking_operations(
+        &mut self,
+        c: f32,
+        m: f32,
+        y: f32,
+        k: f32,
+    ) -> Operation<'src> {
+        Operation::SetCMYKColorForNonStrokingOperations(c, m, y, k)
+    }
+
+    /// `l`
+    fn fold_append_straight_line_segment_to_path(&mut self, x: f32, y: f32) -> Operation<'src> {
+        Operation::AppendStraightLineSegmentToPath { x, y }
+    }
+
+    /// `m`
+    fn fold_begin_new_subpath(&mut self, x: f32, y: f32) -> Operation<'src> {
+        Operation::BeginNewSubpath { x, y }
+    }
+
+    /// `M`
+    fn fold_set_miter_limit(&mut self, limit: f32) -> Operation<'src> {
+        Operation::SetMiterLimit(limit)
+    }
+
+    /// `MP`
+    fn fold_define_marked_content_point(&mut self, tag: Name<'src>) -> Operation<'src> {
+        Operation::DefineMarkedContentPoint(tag)
+    }
+
+    /// `n`
+    fn fold_end_path_without_filling_or_stroking(&mut self) -> Operation<'src> {
+        Operation::EndPathWithoutFillingOrStroking
+    }
+
+    /// `q`
+    fn fold_save_graphics_state(&mut self) -> Operation<'src> {
+        Operation::SaveGraphicsState
+    }
+
+    /// `Q`
+    fn fold_restore_graphics_state(&mut self) -> Operation<'src> {
+        Operation::RestoreGraphicsState
+    }
+
+    /// `re`
+    fn fold_append_rectangle_to_path(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    ) -> Operation<'src> {
+        Operation::AppendRectangleToPath {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// `RG`
+    fn fold_set_rgb_color_for_stroking_operations(
+        &mut self,
+        r: f32,
+        g: f32,
+        b: f32,
+    ) -> Operation<'src> {
+        Operation::SetRGBColorForStrokingOperations(r, g, b)
+    }
+
+    /// `rg`
+    fn fold_set_rgb_color_for_non_stroking_operations(
+        &mut self,
+        r: f32,
+        g: f32,
+        b: f32,
+    ) -> Operation<'src> {
+        Operation::SetRGBColorForNonStrokingOperations(r, g, b)
+    }
+
+    /// `ri`
+    fn fold_set_color_rendering_intent(&mut self, intent: ColorRenderingIntent) -> Operation<'src> {
+        Operation::SetColorRenderingIntent(intent)
+    }
+
+    /// `s`
+    fn fold_close_and_stroke_path(&mut self) -> Operation<'src> {
+        Operation::CloseAndStrokePath
+    }
+
+    /// `S`
+    fn fold_stroke_path(&mut self) -> Operation<'src> {
+        Operation::StrokePath
+    }
+
+    /// `SC`
+    fn fold_set_color_for_stroking_operations(&mut self, color: UntypedColor) -> Operation<'src> {
+        Operation::SetColorForStrokingOperations(color)
+    }
+
+    /// `sc`
+    fn fold_set_color_for_non_stroking_operations(
+        &mut self,
+        color: UntypedColor,
+    ) -> Operation<'src> {
+        Operation::SetColorForNonStrokingOperations(color)
+    }
+
+    /// `SCN`
+    fn fold_set_color_for_stroking_operations_icc_based_and_special_color_spaces(
+        &mut self,
+        cs: Vec<f32>,
+        name: Option<Name<'src>>,
+    ) -> Operation<'src> {
+        Operation::SetColorForStrokingOperationsICCBasedAndSpecialColorSpaces { cs, name }
+    }
+
+    /// `scn`
+    fn fold_set_color_for_non_stroking_operations_icc_based_and_special_color_spaces(
+        &mut self,
+        cs: Vec<f32>,
+        name: Option<Name<'src>>,
+    ) -> Operation<'src> {
+        Operation::SetColorForNonStrokingOperationsICCBasedAndSpecialColorSpaces { cs, name }
+    }
+
+    /// `sh`
+    fn fold_paint_area_defined_by_shading_pattern(&mut self, name: Name<'src>) -> Operation<'src> {
+        Operation::PaintAreaDefinedByShadingPattern(name)
+    }
+
+    /// `T*`
+    fn fold_move_to_start_of_next_text_line(&mut self) -> Operation<'src> {
+        Operation::MoveToStartOfNextTextLine
+    }
+
+    /// `Tc`
+    fn fold_set_character_spacing(&mut self, spacing: f32) -> Operation<'src> {
+        Operation::SetCharacterSpacing(spacing)
+    }
+
+    /// `Td`
+    fn fold_move_text_position(&mut self, x: f32, y: f32) -> Operation<'src> {
+        Operation::MoveTextPosition { x, y }
+    }
+
+    /// `TD`
+    fn fold_move_text_position_and_set_leading(&mut self, x: f32, y: f32) -> Operation<'src> {
+        Operation::MoveTextPositionAndSetLeading { x, y }
+    }
+
+    /// `Tf`
+    fn fold_set_text_font_and_size(&mut self, font: &'src str, size: f32) -> Operation<'src> {
+        Operation::SetTextFontAndSize { font, size }
+    }
+
+    /// `Tj`
+    fn fold_show_text(&mut self, text: Cow<'src, str>) -> Operation<'src> {
+        Operation::ShowText(text)
+    }
+
+    /// `TJ`
+    fn fold_show_text_allowing_individual_glyph_positioning(
+        &mut self,
+        array: Vec<TextOrGlyphPositioning<'src>>,
+    ) -> Operation<'src> {
+        Operation::ShowTextAllowingIndividualGlyphPositioning(array)
+    }
+
+    /// `TL`
+    fn fold_set_text_leading(&mut self, leading: f32) -> Operation<'src> {
+        Operation::SetTextLeading(leading)
+    }
+
+    /// `Tm`
+    fn fold_set_text_matrix_and_text_line_matrix(
+        &mut self,
+        a: f32,
+        b: f32,
+        c: f32,
+        d: f32,
+        e: f32,
+        f: f32,
+    ) -> Operation<'src> {
+        Operation::SetTextMatrixAndTextLineMatrix(a, b, c, d, e, f)
+    }
+
+    /// `Tr`
+    fn fold_set_text_rendering_mode(&mut self, mode: TextRenderingMode) -> Operation<'src> {
+        Operation::SetTextRenderingMode(mode)
+    }
+
+    /// `Ts`
+    fn fold_set_text_rise(&mut self, rise: f32) -> Operation<'src> {
+        Operation::SetTextRise(rise)
+    }
+
+    /// `Tw`
+    fn fold_set_word_spacing(&mut self, spacing: f32) -> Operation<'src> {
+        Operation::SetWordSpacing(spacing)
+    }
+
+    /// `Tz`
+    fn fold_set_horizontal_text_scaling(&mut self, scale: f32) -> Operation<'src> {
+        Operation::SetHorizontalTextScaling(scale)
+    }
+
+    /// `v`
+    fn fold_append_curved_segment_to_path_initial_point_replicated(
+        &mut self,
+        x2: f32,
+        y2: f32,
+        x3: f32,
+        y3: f32,
+    ) -> Operation<'src> {
+        Operation::AppendCurvedSegmentToPathInitialPointReplicated { x2, y2, x3, y3 }
+    }
+
+    /// `w`
+    fn fold_set_line_width(&mut self, width: f32) -> Operation<'src> {
+        Operation::SetLineWidth(width)
+    }
+
+    /// `W`
+    fn fold_set_clipping_path_using_non_zero_winding_number_rule(&mut self) -> Operation<'src> {
+        Operation::SetClippingPathUsingNonZeroWindingNumberRule
+    }
+
+    /// `W*`
+    fn fold_set_clipping_path_using_even_odd_rule(&mut self) -> Operation<'src> {
+        Operation::SetClippingPathUsingEvenOddRule
+    }
+
+    /// `y`
+    fn fold_append_curved_segment_to_path_final_point_replicated(
+        &mut self,
+        x1: f32,
+        y1: f32,
+        x3: f32,
+        y3: f32,
+    ) -> Operation<'src> {
+        Operation::AppendCurvedSegmentToPathFinalPointReplicated { x1, y1, x3, y3 }
+    }
+
+    /// `'`
+    fn fold_move_to_next_line_and_show_text(&mut self, text: Cow<'src, str>) -> Operation<'src> {
+        Operation::MoveToNextLineAndShowText(text)
+    }
+
+    /// `"`
+    fn fold_set_word_and_character_spacing_move_to_next_line_and_show_text(
+        &mut self,
+        text: Cow<'src, str>,
+        word_spacing: f32,
+        character_spacing: f32,
+    ) -> Operation<'src> {
+        Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText {
+            text,
+            word_spacing,
+            character_spacing,
+        }
+    }
+
+    /// An operator this crate does not recognize.
+    fn fold_unknown(
+        &mut self,
+        operator: &'src str,
+        operands: &'src [Primitive],
+    ) -> Operation<'src> {
+        Operation::Unknown { operator, operands }
+    }
+
+    /// An operation that failed to parse.
+    fn fold_malformed(&mut self, byte_range: Range<usize>, reason: &'src str) -> Operation<'src> {
+        Operation::Malformed { byte_range, reason }
+    }
+}
+
+/// Calls the [`OperationFolder`] method matching the variant of `operation`.
+pub fn fold_variant<'src, F: OperationFolder<'src> + ?Sized>(
+    folder: &mut F,
+    operation: Operation<'src>,
+) -> Operation<'src> {
+    match operation {
+        Operation::CloseFillAndStrokePathUsingNonZeroWindingNumber => {
+            folder.fold_close_fill_and_stroke_path_using_non_zero_winding_number()
+        }
+        Operation::FillAndStrokePathUsingNonZeroWindingNumber => {
+            folder.fold_fill_and_stroke_path_using_non_zero_winding_number()
+        }
+        Operation::CloseFillAndStrokePathUsingEvenOddRule => {
+            folder.fold_close_fill_and_stroke_path_using_even_odd_rule()
+        }
+        Operation::FillAndStrokePathUsingEvenOddRule => {
+            folder.fold_fill_and_stroke_path_using_even_odd_rule()
+        }
+        Operation::BeginMarkedContentSequenceWithPropertyList { tag, properties } => {
+            folder.fold_begin_marked_content_sequence_with_property_list(tag, properties)
+        }
+        Operation::BeginInlineImageObject => folder.fold_begin_inline_image_object(),
+        Operation::BeginMarkedContentSequence(tag) => {
+            folder.fold_begin_marked_content_sequence(tag)
+        }
+        Operation::BeginTextObject => folder.fold_begin_text_object(),
+        Operation::BeginCompatibilitySection => folder.fold_begin_compatibility_section(),
+        Operation::AppendCurvedSegmentToPath {
+            x1,
+            y1,
+            x2,
+            y2,
+            x3,
+            y3,
+        } => folder.fold_append_curved_segment_to_path(x1, y1, x2, y2, x3, y3),
+        Operation::ConcatenateMatrixToCurrentTransformationMatrix(a, b, c, d, e, f) => {
+            folder.fold_concatenate_matrix_to_current_transformation_matrix(a, b, c, d, e, f)
+        }
+        Operation::SetColorSpaceForStrokingOperations(name) => {
+            folder.fold_set_color_space_for_stroking_operations(name)
+        }
+        Operation::SetColorSpaceForNonStrokingOperations(name) => {
+            folder.fold_set_color_space_for_non_stroking_operations(name)
+        }
+        Operation::SetLineDashPattern { array, phase } => {
+            folder.fold_set_line_dash_pattern(array, phase)
+        }
+        Operation::SetGlyphWidthInType3Font { wx, wy } => {
+            folder.fold_set_glyph_width_in_type3_font(wx, wy)
+        }
+        Operation::SetGlyphWidthAndBoundingBoxInType3Font {
+            wx,
+            wy,
+            llx,
+            lly,
+            urx,
+            ury,
+        } => folder.fold_set_glyph_width_and_bounding_box_in_type3_font(wx, wy, llx, lly, urx, ury),
+        Operation::InvokeNamedXObject(name) => folder.fold_invoke_named_x_object(name),
+        Operation::DefineMarkedContentPointWithPropertyList { tag, properties } => {
+            folder.fold_define_marked_content_point_with_property_list(tag, properties)
+        }
+        Operation::EndInlineImageObject(data) => folder.fold_end_inline_image_object(data),
+        Operation::EndMarkedContentSequence => folder.fold_end_marked_content_sequence(),
+        Operation::EndTextObject => folder.fold_end_text_object(),
+        Operation::EndCompatibilitySection => folder.fold_end_compatibility_section(),
+        Operation::FillPathUsingNonZeroWindingNumberRule => {
+            folder.fold_fill_path_using_non_zero_winding_number_rule()
+        }
+        Operation::ObsoleteFillPathUsingNonZeroWindingMumberRule => {
+            folder.fold_obsolete_fill_path_using_non_zero_winding_mumber_rule()
+        }
+        Operation::FillPathUsingEvenOddRule => folder.fold_fill_path_using_even_odd_rule(),
+        Operation::SetGrayLevelForStrokingOperations(gray) => {
+            folder.fold_set_gray_level_for_stroking_operations(gray)
+        }
+        Operation::SetGrayLevelForNonStrokingOperations(gray) => {
+            folder.fold_set_gray_level_for_non_stroking_operations(gray)
+        }
+        Operation::SetParametersFromGraphicsStateParameterDictionary(name) => {
+            folder.fold_set_parameters_from_graphics_state_parameter_dictionary(name)
+        }
+        Operation::CloseSubpath => folder.fold_close_subpath(),
+        Operation::SetFlatnessTolerance(flatness) => folder.fold_set_flatness_tolerance(flatness),
+        Operation::BeginInlineImageData(entries) => folder.fold_begin_inline_image_data(entries),
+        Operation::SetLineJoinStyle(style) => folder.fold_set_line_join_style(style),
+        Operation::SetLineCapStyle(style) => folder.fold_set_line_cap_style(style),
+        Operation::SetCMYKColorForStrokingOperations(c, m, y, k) => {
+            folder.fold_set_cmyk_color_for_stroking_operations(c, m, y, k)
+        }
+        Operation::SetCMYKColorForNonStrokingOperations(c, m, y, k) => {
+            folder.fold_set_cmyk_color_for_non_stroking_operations(c, m, y, k)
+        }
+        Operation::AppendStraightLineSegmentToPath { x, y } => {
+            folder.fold_append_straight_line_segment_to_path(x, y)
+        }
+        Operation::BeginNewSubpath { x, y } => folder.fold_begin_new_subpath(x, y),
+        Operation::SetMiterLimit(limit) => folder.fold_set_miter_limit(limit),
+        Operation::DefineMarkedContentPoint(tag) => folder.fold_define_marked_content_point(tag),
+        Operation::EndPathWithoutFillingOrStroking => {
+            folder.fold_end_path_without_filling_or_stroking()
+        }
+        Operation::SaveGraphicsState => folder.fold_save_graphics_state(),
+        Operation::RestoreGraphicsState => folder.fold_restore_graphics_state(),
+        Operation::AppendRectangleToPath {
+            x,
+            y,
+            width,
+            height,
+        } => folder.fold_append_rectangle_to_path(x, y, width, height),
+        Operation::SetRGBColorForStrokingOperations(r, g, b) => {
+            folder.fold_set_rgb_color_for_stroking_operations(r, g, b)
+        }
+        Operation::SetRGBColorForNonStrokingOperations(r, g, b) => {
+            folder.fold_set_rgb_color_for_non_stroking_operations(r, g, b)
+        }
+        Operation::SetColorRenderingIntent(intent) => {
+            folder.fold_set_color_rendering_intent(intent)
+        }
+        Operation::CloseAndStrokePath => folder.fold_close_and_stroke_path(),
+        Operation::StrokePath => folder.fold_stroke_path(),
+        Operation::SetColorForStrokingOperations(color) => {
+            folder.fold_set_color_for_stroking_operations(color)
+        }
+        Operation::SetColorForNonStrokingOperations(color) => {
+            folder.fold_set_color_for_non_stroking_operations(color)
+        }
+        Operation::SetColorForStrokingOperationsICCBasedAndSpecialColorSpaces { cs, name } => {
+            folder
+                .fold_set_color_for_stroking_operations_icc_based_and_special_color_spaces(cs, name)
+        }
+        Operation::SetColorForNonStrokingOperationsICCBasedAndSpecialColorSpaces { cs, name } => {
+            folder.fold_set_color_for_non_stroking_operations_icc_based_and_special_color_spaces(
+                cs, name,
+            )
+        }
+        Operation::PaintAreaDefinedByShadingPattern(name) => {
+            folder.fold_paint_area_defined_by_shading_pattern(name)
+        }
+        Operation::MoveToStartOfNextTextLine => folder.fold_move_to_start_of_next_text_line(),
+        Operation::SetCharacterSpacing(spacing) => folder.fold_set_character_spacing(spacing),
+        Operation::MoveTextPosition { x, y } => folder.fold_move_text_position(x, y),
+        Operation::MoveTextPositionAndSetLeading { x, y } => {
+            folder.fold_move_text_position_and_set_leading(x, y)
+        }
+        Operation::SetTextFontAndSize { font, size } => {
+            folder.fold_set_text_font_and_size(font, size)
+        }
+        Operation::ShowText(text) => folder.fold_show_text(text),
+        Operation::ShowTextAllowingIndividualGlyphPositioning(array) => {
+            folder.fold_show_text_allowing_individual_glyph_positioning(array)
+        }
+        Operation::SetTextLeading(leading) => folder.fold_set_text_leading(leading),
+        Operation::SetTextMatrixAndTextLineMatrix(a, b, c, d, e, f) => {
+            folder.fold_set_text_matrix_and_text_line_matrix(a, b, c, d, e, f)
+        }
+        Operation::SetTextRenderingMode(mode) => folder.fold_set_text_rendering_mode(mode),
+        Operation::SetTextRise(rise) => folder.fold_set_text_rise(rise),
+        Operation::SetWordSpacing(spacing) => folder.fold_set_word_spacing(spacing),
+        Operation::SetHorizontalTextScaling(scale) => {
+            folder.fold_set_horizontal_text_scaling(scale)
+        }
+        Operation::AppendCurvedSegmentToPathInitialPointReplicated { x2, y2, x3, y3 } => {
+            folder.fold_append_curved_segment_to_path_initial_point_replicated(x2, y2, x3, y3)
+        }
+        Operation::SetLineWidth(width) => folder.fold_set_line_width(width),
+        Operation::SetClippingPathUsingNonZeroWindingNumberRule => {
+            folder.fold_set_clipping_path_using_non_zero_winding_number_rule()
+        }
+        Operation::SetClippingPathUsingEvenOddRule => {
+            folder.fold_set_clipping_path_using_even_odd_rule()
+        }
+        Operation::AppendCurvedSegmentToPathFinalPointReplicated { x1, y1, x3, y3 } => {
+            folder.fold_append_curved_segment_to_path_final_point_replicated(x1, y1, x3, y3)
+        }
+        Operation::MoveToNextLineAndShowText(text) => {
+            folder.fold_move_to_next_line_and_show_text(text)
+        }
+        Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText {
+            text,
+            word_spacing,
+            character_spacing,
+        } => folder.fold_set_word_and_character_spacing_move_to_next_line_and_show_text(
+            text,
+            word_spacing,
+            character_spacing,
+        ),
+        Operation::Unknown { operator, operands } => folder.fold_unknown(operator, operands),
+        Operation::Malformed { byte_range, reason } => folder.fold_malformed(byte_range, reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, io::Cursor, mem, slice};
+
+    use super::*;
+    use crate::{normalize_operation, parser::ContentStreamParser};
+
+    #[derive(Default)]
+    struct Collect {
+        text: Vec<String>,
+        widths: Vec<f32>,
+        count: usize,
+    }
+
+    impl<'src> OperationVisitor<'src> for Collect {
+        fn visit_operation(&mut self, operation: &Operation<'src>) {
+            self.count += 1;
+            visit_variant(self, operation);
+        }
+
+        fn visit_show_text(&mut self, text: &str) {
+            self.text.push(text.to_owned());
+        }
+
+        fn visit_set_line_width(&mut self, width: f32) {
+            self.widths.push(width);
+        }
+    }
+
+    struct Thicken;
+
+    impl<'src> OperationFolder<'src> for Thicken {
+        fn fold_operation(&mut self, operation: Operation<'src>, out: &mut Vec<Operation<'src>>) {
+            if operation != Operation::SetLineJoinStyle(LineJoinStyle::MiterJoin) {
+                out.push(fold_variant(self, operation));
+            }
+        }
+
+        fn fold_set_line_width(&mut self, width: f32) -> Operation<'src> {
+            Operation::SetLineWidth(width * 2.0)
+        }
+    }
+
+    #[test]
+    fn visits_and_folds_operations() {
+        let operations = vec![
+            Operation::SetLineWidth(1.5),
+            Operation::SetLineJoinStyle(LineJoinStyle::MiterJoin),
+            Operation::BeginTextObject,
+            Operation::ShowText(Cow::Borrowed("hello")),
+            Operation::EndTextObject,
+            Operation::SetLineWidth(3.0),
+        ];
+
+        let mut collect = Collect::default();
+        collect.walk(&operations);
+        assert_eq!(collect.count, 6);
+        assert_eq!(collect.text, ["hello"]);
+        assert_eq!(collect.widths, [1.5, 3.0]);
+
+        assert_eq!(
+            Thicken.fold(operations),
+            [
+                Operation::SetLineWidth(3.0),
+                Operation::BeginTextObject,
+                Operation::ShowText(Cow::Borrowed("hello")),
+                Operation::EndTextObject,
+                Operation::SetLineWidth(6.0),
+            ]
+        );
+    }
+
+    /// Counts the operations it's given, and rebuilds them unchanged.
+    #[derive(Default)]
+    struct Count(usize);
+
+    impl<'src> OperationVisitor<'src> for Count {
+        fn visit_operation(&mut self, operation: &Operation<'src>) {
+            self.0 += 1;
+            visit_variant(self, operation);
+        }
+    }
+
+    impl<'src> OperationFolder<'src> for Count {
+        fn fold_operation(&mut self, operation: Operation<'src>, out: &mut Vec<Operation<'src>>) {
+            self.0 += 1;
+            out.push(fold_variant(self, operation));
+        }
+    }
+
+    /// Records the name of each method it's called through.
+    #[derive(Default)]
+    struct Record(Vec<&'static str>);
+
+    impl<'src> OperationVisitor<'src> for Record {
+        fn visit_close_fill_and_stroke_path_using_non_zero_winding_number(&mut self) {
+            self.0
+                .push("visit_close_fill_and_stroke_path_using_non_zero_winding_number");
+        }
+
+        fn visit_fill_and_stroke_path_using_non_zero_winding_number(&mut self) {
+            self.0
+                .push("visit_fill_and_stroke_path_using_non_zero_winding_number");
+        }
+
+        fn visit_close_fill_and_stroke_path_using_even_odd_rule(&mut self) {
+            self.0
+                .push("visit_close_fill_and_stroke_path_using_even_odd_rule");
+        }
+
+        fn visit_fill_and_stroke_path_using_even_odd_rule(&mut self) {
+            self.0
+                .push("visit_fill_and_stroke_path_using_even_odd_rule");
+        }
+
+        fn visit_begin_marked_content_sequence_with_property_list(
+            &mut self,
+            _tag: Name<'src>,
+            _properties: PropertyList<'src>,
+        ) {
+            self.0
+                .push("visit_begin_marked_content_sequence_with_property_list");
+        }
+
+        fn visit_begin_inline_image_object(&mut self) {
+            self.0.push("visit_begin_inline_image_object");
+        }
+
+        fn visit_begin_marked_content_sequence(&mut self, _tag: Name<'src>) {
+            self.0.push("visit_begin_marked_content_sequence");
+        }
+
+        fn visit_begin_text_object(&mut self) {
+            self.0.push("visit_begin_text_object");
+        }
+
+        fn visit_begin_compatibility_section(&mut self) {
+            self.0.push("visit_begin_compatibility_section");
+        }
+
+        fn visit_append_curved_segment_to_path(
+            &mut self,
+            _x1: f32,
+            _y1: f32,
+            _x2: f32,
+            _y2: f32,
+            _x3: f32,
+            _y3: f32,
+        ) {
+            self.0.push("visit_append_curved_segment_to_path");
+        }
+
+        fn visit_concatenate_matrix_to_current_transformation_matrix(
+            &mut self,
+            _a: f32,
+            _b: f32,
+            _c: f32,
+            _d: f32,
+            _e: f32,
+            _f: f32,
+        ) {
+            self.0
+                .push("visit_concatenate_matrix_to_current_transformation_matrix");
+        }
+
+        fn visit_set_color_space_for_stroking_operations(&mut self, _name: Name<'src>) {
+            self.0.push("visit_set_color_space_for_stroking_operations");
+        }
+
+        fn visit_set_color_space_for_non_stroking_operations(&mut self, _name: Name<'src>) {
+            self.0
+                .push("visit_set_color_space_for_non_stroking_operations");
+        }
+
+        fn visit_set_line_dash_pattern(&mut self, _array: &[f32], _phase: f32) {
+            self.0.push("visit_set_line_dash_pattern");
+        }
+
+        fn visit_set_glyph_width_in_type3_font(&mut self, _wx: f32, _wy: f32) {
+            self.0.push("visit_set_glyph_width_in_type3_font");
+        }
+
+        fn visit_set_glyph_width_and_bounding_box_in_type3_font(
+            &mut self,
+            _wx: f32,
+            _wy: f32,
+            _llx: f32,
+            _lly: f32,
+            _urx: f32,
+            _ury: f32,
+        ) {
+            self.0
+                .push("visit_set_glyph_width_and_bounding_box_in_type3_font");
+        }
+
+        fn visit_invoke_named_x_object(&mut self, _name: Name<'src>) {
+            self.0.push("visit_invoke_named_x_object");
+        }
+
+        fn visit_define_marked_content_point_with_property_list(
+            &mut self,
+            _tag: Name<'src>,
+            _properties: PropertyList<'src>,
+        ) {
+            self.0
+                .push("visit_define_marked_content_point_with_property_list");
+        }
+
+        fn visit_end_inline_image_object(&mut self, _data: &'src [u8]) {
+            self.0.push("visit_end_inline_image_object");
+        }
+
+        fn visit_end_marked_content_sequence(&mut self) {
+            self.0.push("visit_end_marked_content_sequence");
+        }
+
+        fn visit_end_text_object(&mut self) {
+            self.0.push("visit_end_text_object");
+        }
+
+        fn visit_end_compatibility_section(&mut self) {
+            self.0.push("visit_end_compatibility_section");
+        }
+
+        fn visit_fill_path_using_non_zero_winding_number_rule(&mut self) {
+            self.0
+                .push("visit_fill_path_using_non_zero_winding_number_rule");
+        }
+
+        fn visit_obsolete_fill_path_using_non_zero_winding_mumber_rule(&mut self) {
+            self.0
+                .push("visit_obsolete_fill_path_using_non_zero_winding_mumber_rule");
+        }
+
+        fn visit_fill_path_using_even_odd_rule(&mut self) {
+            self.0.push("visit_fill_path_using_even_odd_rule");
+        }
+
+        fn visit_set_gray_level_for_stroking_operations(&mut self, _gray: f32) {
+            self.0.push("visit_set_gray_level_for_stroking_operations");
+        }
+
+        fn visit_set_gray_level_for_non_stroking_operations(&mut self, _gray: f32) {
+            self.0
+                .push("visit_set_gray_level_for_non_stroking_operations");
+        }
+
+        fn visit_set_parameters_from_graphics_state_parameter_dictionary(
+            &mut self,
+            _name: Name<'src>,
+        ) {
+            self.0
+                .push("visit_set_parameters_from_graphics_state_parameter_dictionary");
+        }
+
+        fn visit_close_subpath(&mut self) {
+            self.0.push("visit_close_subpath");
+        }
+
+        fn visit_set_flatness_tolerance(&mut self, _flatness: i32) {
+            self.0.push("visit_set_flatness_tolerance");
+        }
+
+        fn visit_begin_inline_image_data(&mut self, _entries: &'src [Primitive]) {
+            self.0.push("visit_begin_inline_image_data");
+        }
+
+        fn visit_set_line_join_style(&mut self, _style: LineJoinStyle) {
+            self.0.push("visit_set_line_join_style");
+        }
+
+        fn visit_set_line_cap_style(&mut self, _style: LineCapStyle) {
+            self.0.push("visit_set_line_cap_style");
+        }
+
+        fn visit_set_cmyk_color_for_stroking_operations(
+            &mut self,
+            _c: f32,
+            _m: f32,
+            _y: f32,
+            _k: f32,
+        ) {
+            self.0.push("visit_set_cmyk_color_for_stroking_operations");
+        }
+
+        fn visit_set_cmyk_color_for_non_stroking_operations(
+            &mut self,
+            _c: f32,
+            _m: f32,
+            _y: f32,
+            _k: f32,
+        ) {
+            self.0
+                .push("visit_set_cmyk_color_for_non_stroking_operations");
+        }
+
+        fn visit_append_straight_line_segment_to_path(&mut self, _x: f32, _y: f32) {
+            self.0.push("visit_append_straight_line_segment_to_path");
+        }
+
+        fn visit_begin_new_subpath(&mut self, _x: f32, _y: f32) {
+            self.0.push("visit_begin_new_subpath");
+        }
+
+        fn visit_set_miter_limit(&mut self, _limit: f32) {
+            self.0.push("visit_set_miter_limit");
+        }
+
+        fn visit_define_marked_content_point(&mut self, _tag: Name<'src>) {
+            self.0.push("visit_define_marked_content_point");
+        }
+
+        fn visit_end_path_without_filling_or_stroking(&mut self) {
+            self.0.push("visit_end_path_without_filling_or_stroking");
+        }
+
+        fn visit_save_graphics_state(&mut self) {
+            self.0.push("visit_save_graphics_state");
+        }
+
+        fn visit_restore_graphics_state(&mut self) {
+            self.0.push("visit_restore_graphics_state");
+        }
+
+        fn visit_append_rectangle_to_path(&mut self, _x: f32, _y: f32, _width: f32, _height: f32) {
+            self.0.push("visit_append_rectangle_to_path");
+        }
+
+        fn visit_set_rgb_color_for_stroking_operations(&mut self, _r: f32, _g: f32, _b: f32) {
+            self.0.push("visit_set_rgb_color_for_stroking_operations");
+        }
+
+        fn visit_set_rgb_color_for_non_stroking_operations(&mut self, _r: f32, _g: f32, _b: f32) {
+            self.0
+                .push("visit_set_rgb_color_for_non_stroking_operations");
+        }
+
+        fn visit_set_color_rendering_intent(&mut self, _intent: ColorRenderingIntent) {
+            self.0.push("visit_set_color_rendering_intent");
+        }
+
+        fn visit_close_and_stroke_path(&mut self) {
+            self.0.push("visit_close_and_stroke_path");
+        }
+
+        fn visit_stroke_path(&mut self) {
+            self.0.push("visit_stroke_path");
+        }
+
+        fn visit_set_color_for_stroking_operations(&mut self, _color: &UntypedColor) {
+            self.0.push("visit_set_color_for_stroking_operations");
+        }
+
+        fn visit_set_color_for_non_stroking_operations(&mut self, _color: &UntypedColor) {
+            self.0.push("visit_set_color_for_non_stroking_operations");
+        }
+
+        fn visit_set_color_for_stroking_operations_icc_based_and_special_color_spaces(
+            &mut self,
+            _cs: &[f32],
+            _name: Option<Name<'src>>,
+        ) {
+            self.0
+                .push("visit_set_color_for_stroking_operations_icc_based_and_special_color_spaces");
+        }
+
+        fn visit_set_color_for_non_stroking_operations_icc_based_and_special_color_spaces(
+            &mut self,
+            _cs: &[f32],
+            _name: Option<Name<'src>>,
+        ) {
+            self.0.push(
+                "visit_set_color_for_non_stroking_operations_icc_based_and_special_color_spaces",
+            );
+        }
+
+        fn visit_paint_area_defined_by_shading_pattern(&mut self, _name: Name<'src>) {
+            self.0.push("visit_paint_area_defined_by_shading_pattern");
+        }
+
+        fn visit_move_to_start_of_next_text_line(&mut self) {
+            self.0.push("visit_move_to_start_of_next_text_line");
+        }
+
+        fn visit_set_character_spacing(&mut self, _spacing: f32) {
+            self.0.push("visit_set_character_spacing");
+        }
+
+        fn visit_move_text_position(&mut self, _x: f32, _y: f32) {
+            self.0.push("visit_move_text_position");
+        }
+
+        fn visit_move_text_position_and_set_leading(&mut self, _x: f32, _y: f32) {
+            self.0.push("visit_move_text_position_and_set_leading");
+        }
+
+        fn visit_set_text_font_and_size(&mut self, _font: &'src str, _size: f32) {
+            self.0.push("visit_set_text_font_and_size");
+        }
+
+        fn visit_show_text(&mut self, _text: &str) {
+            self.0.push("visit_show_text");
+        }
+
+        fn visit_show_text_allowing_individual_glyph_positioning(
+            &mut self,
+            _array: &[TextOrGlyphPositioning<'src>],
+        ) {
+            self.0
+                .push("visit_show_text_allowing_individual_glyph_positioning");
+        }
+
+        fn visit_set_text_leading(&mut self, _leading: f32) {
+            self.0.push("visit_set_text_leading");
+        }
+
+        fn visit_set_text_matrix_and_text_line_matrix(
+            &mut self,
+            _a: f32,
+            _b: f32,
+            _c: f32,
+            _d: f32,
+            _e: f32,
+            _f: f32,
+        ) {
+            self.0.push("visit_set_text_matrix_and_text_line_matrix");
+        }
+
+        fn visit_set_text_rendering_mode(&mut self, _mode: TextRenderingMode) {
+            self.0.push("visit_set_text_rendering_mode");
+        }
+
+        fn visit_set_text_rise(&mut self, _rise: f32) {
+            self.0.push("visit_set_text_rise");
+        }
+
+        fn visit_set_word_spacing(&mut self, _spacing: f32) {
+            self.0.push("visit_set_word_spacing");
+        }
+
+        fn visit_set_horizontal_text_scaling(&mut self, _scale: f32) {
+            self.0.push("visit_set_horizontal_text_scaling");
+        }
+
+        fn visit_append_curved_segment_to_path_initial_point_replicated(
+            &mut self,
+            _x2: f32,
+            _y2: f32,
+            _x3: f32,
+            _y3: f32,
+        ) {
+            self.0
+                .push("visit_append_curved_segment_to_path_initial_point_replicated");
+        }
+
+        fn visit_set_line_width(&mut self, _width: f32) {
+            self.0.push("visit_set_line_width");
+        }
+
+        fn visit_set_clipping_path_using_non_zero_winding_number_rule(&mut self) {
+            self.0
+                .push("visit_set_clipping_path_using_non_zero_winding_number_rule");
+        }
+
+        fn visit_set_clipping_path_using_even_odd_rule(&mut self) {
+            self.0.push("visit_set_clipping_path_using_even_odd_rule");
+        }
+
+        fn visit_append_curved_segment_to_path_final_point_replicated(
+            &mut self,
+            _x1: f32,
+            _y1: f32,
+            _x3: f32,
+            _y3: f32,
+        ) {
+            self.0
+                .push("visit_append_curved_segment_to_path_final_point_replicated");
+        }
+
+        fn visit_move_to_next_line_and_show_text(&mut self, _text: &str) {
+            self.0.push("visit_move_to_next_line_and_show_text");
+        }
+
+        fn visit_set_word_and_character_spacing_move_to_next_line_and_show_text(
+            &mut self,
+            _text: &str,
+            _word_spacing: f32,
+            _character_spacing: f32,
+        ) {
+            self.0
+                .push("visit_set_word_and_character_spacing_move_to_next_line_and_show_text");
+        }
+
+        fn visit_unknown(&mut self, _operator: &'src str, _operands: &'src [Primitive]) {
+            self.0.push("visit_unknown");
+        }
+
+        fn visit_malformed(&mut self, _byte_range: &Range<usize>, _reason: &'src str) {
+            self.0.push("visit_malformed");
+        }
+    }
+
+    #[test]
+    fn dispatches_every_operation() {
+        let content = b"/Tag /P0 BDC /Tag BMC /Tag MP /Tag /P0 DP EMC EMC BX EX \
+            q 1 0 0 1 0 0 cm 2 w 0 J 0 j 4 M [3] 0 d /Perceptual ri 1 i /GS0 gs Q \
+            0 0 m 1 1 l 1 2 3 4 5 6 c 1 2 3 4 v 1 2 3 4 y h 0 0 10 10 re W W* n \
+            S s f F f* B B* b b* /DeviceRGB CS /DeviceRGB cs 0.5 SC 0.5 sc 0.5 /P0 SCN \
+            0.5 /P0 scn 0.5 G 0.5 g 1 0 0 RG 1 0 0 rg 0 0 0 1 K 0 0 0 1 k /Sh0 sh \
+            BT 1 Tc 2 Tw 100 Tz 12.5 TL /F1 10 Tf 0 Tr 1 Ts 1 0 0 1 0 0 Tm 1 1 Td 1 1 TD T* \
+            (a) Tj [(b) -10 (c)] TJ (d) ' 1 2 (e) \" ET /Im0 Do 10 0 d0 10 0 0 0 10 10 d1 \
+            BI /W 1 ID x EI zz";
+        let parsed = ContentStreamParser::new(Cursor::new(&content[..]))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let mut operations: Vec<_> = parsed.iter().map(normalize_operation).collect();
+        operations.push(Operation::Malformed {
+            byte_range: 0..2,
+            reason: "unterminated string",
+        });
+
+        let variants: HashSet<_> = operations.iter().map(mem::discriminant).collect();
+        assert_eq!(variants.len(), 75);
+
+        // Each operation is dispatched to one method, and each variant to a method of its own.
+        let mut methods = HashSet::new();
+        for operation in &operations {
+            let mut record = Record::default();
+            record.walk(slice::from_ref(operation));
+            assert_eq!(record.0.len(), 1, "{:?}", operation);
+            methods.insert(record.0[0]);
+        }
+        assert_eq!(methods.len(), variants.len());
+
+        let mut count = Count::default();
+        count.walk(&operations);
+        assert_eq!(count.0, operations.len());
+
+        let mut count = Count::default();
+        assert_eq!(count.fold(operations.clone()), operations);
+        assert_eq!(count.0, operations.len());
+    }
+}
@@ -0,0 +1,541 @@
+use std::io::{self, Write};
+
+use pdf::primitive::Primitive;
+
+use crate::{
+    ColorRenderingIntent, LineCapStyle, LineJoinStyle, Name, Operation, PropertyList,
+    TextOrGlyphPositioning, TextRenderingMode, UntypedColor,
+};
+
+/// Serializes operations into a content stream, one operation per line. `Malformed` operations
+/// are left out, since they don't carry the bytes they were parsed from.
+pub fn write_operations<W: Write>(out: &mut W, operations: &[Operation]) -> io::Result<()> {
+    for operation in operations {
+        if write_operation(out, operation)? {
+            out.write_all(b"\n")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Serializes operations into a content stream in memory.
+pub fn to_content(operations: &[Operation]) -> Vec<u8> {
+    let mut content = Vec::new();
+    // Writing to a `Vec` can't fail.
+    write_operations(&mut content, operations).unwrap();
+    content
+}
+
+/// Serializes one operation without a trailing newline, returning whether anything was written.
+pub fn write_operation<W: Write>(out: &mut W, operation: &Operation) -> io::Result<bool> {
+    match write_operands(out, operation)? {
+        Some(operator) => {
+            out.write_all(operator.as_bytes())?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// The operator an operation is written with, `None` for `Malformed` operations.
+pub fn operator<'o>(operation: &'o Operation) -> Option<&'o str> {
+    // Writing to a sink can't fail.
+    write_operands(&mut io::sink(), operation).unwrap()
+}
+
+/// Writes the operands of an operation, each followed by a space, and returns its operator.
+fn write_operands<'o, W: Write>(
+    out: &mut W,
+    operation: &'o Operation,
+) -> io::Result<Option<&'o str>> {
+    let operator = match operation {
+        Operation::CloseFillAndStrokePathUsingNonZeroWindingNumber => "b",
+        Operation::FillAndStrokePathUsingNonZeroWindingNumber => "B",
+        Operation::CloseFillAndStrokePathUsingEvenOddRule => "b*",
+        Operation::FillAndStrokePathUsingEvenOddRule => "B*",
+        Operation::BeginMarkedContentSequenceWithPropertyList { tag, properties } => {
+            write_name(out, tag.0)?;
+            write_property_list(out, properties)?;
+            "BDC"
+        }
+        Operation::BeginInlineImageObject => "BI",
+        Operation::BeginMarkedContentSequence(Name(tag)) => {
+            write_name(out, tag)?;
+            "BMC"
+        }
+        Operation::BeginTextObject => "BT",
+        Operation::BeginCompatibilitySection => "BX",
+        Operation::AppendCurvedSegmentToPath {
+            x1,
+            y1,
+            x2,
+            y2,
+            x3,
+            y3,
+        } => {
+            write_numbers(out, &[*x1, *y1, *x2, *y2, *x3, *y3])?;
+            "c"
+        }
+        Operation::ConcatenateMatrixToCurrentTransformationMatrix(a, b, c, d, e, f) => {
+            write_numbers(out, &[*a, *b, *c, *d, *e, *f])?;
+            "cm"
+        }
+        Operation::SetColorSpaceForStrokingOperations(Name(name)) => {
+            write_name(out, name)?;
+            "CS"
+        }
+        Operation::SetColorSpaceForNonStrokingOperations(Name(name)) => {
+            write_name(out, name)?;
+            "cs"
+        }
+        Operation::SetLineDashPattern { array, phase } => {
+            out.write_all(b"[")?;
+            for (i, dash) in array.iter().enumerate() {
+                if i > 0 {
+                    out.write_all(b" ")?;
+                }
+                write_number(out, *dash)?;
+            }
+            out.write_all(b"] ")?;
+            write_numbers(out, &[*phase])?;
+            "d"
+        }
+        Operation::SetGlyphWidthInType3Font { wx, wy } => {
+            write_numbers(out, &[*wx, *wy])?;
+            "d0"
+        }
+        Operation::SetGlyphWidthAndBoundingBoxInType3Font {
+            wx,
+            wy,
+            llx,
+            lly,
+            urx,
+            ury,
+        } => {
+            write_numbers(out, &[*wx, *wy, *llx, *lly, *urx, *ury])?;
+            "d1"
+        }
+        Operation::InvokeNamedXObject(Name(name)) => {
+            write_name(out, name)?;
+            "Do"
+        }
+        Operation::DefineMarkedContentPointWithPropertyList { tag, properties } => {
+            write_name(out, tag.0)?;
+            write_property_list(out, properties)?;
+            "DP"
+        }
+        Operation::EndInlineImageObject(data) => {
+            out.write_all(data)?;
+            out.write_all(b"\n")?;
+            "EI"
+        }
+        Operation::EndMarkedContentSequence => "EMC",
+        Operation::EndTextObject => "ET",
+        Operation::EndCompatibilitySection => "EX",
+        Operation::FillPathUsingNonZeroWindingNumberRule => "f",
+        Operation::ObsoleteFillPathUsingNonZeroWindingMumberRule => "F",
+        Operation::FillPathUsingEvenOddRule => "f*",
+        Operation::SetGrayLevelForStrokingOperations(gray) => {
+            write_numbers(out, &[*gray])?;
+            "G"
+        }
+        Operation::SetGrayLevelForNonStrokingOperations(gray) => {
+            write_numbers(out, &[*gray])?;
+            "g"
+        }
+        Operation::SetParametersFromGraphicsStateParameterDictionary(Name(name)) => {
+            write_name(out, name)?;
+            "gs"
+        }
+        Operation::CloseSubpath => "h",
+        Operation::SetFlatnessTolerance(flatness) => {
+            write!(out, "{} ", flatness)?;
+            "i"
+        }
+        Operation::BeginInlineImageData(entries) => {
+            for entry in entries.iter() {
+                write_primitive(out, entry)?;
+                out.write_all(b" ")?;
+            }
+            // The newline after `ID` is the whitespace that separates it from the data.
+            "ID"
+        }
+        Operation::SetLineJoinStyle(style) => {
+            let style = match style {
+                LineJoinStyle::MiterJoin => 0,
+                LineJoinStyle::RoundJoin => 1,
+                LineJoinStyle::BevelJoin => 2,
+            };
+            write!(out, "{} ", style)?;
+            "j"
+        }
+        Operation::SetLineCapStyle(style) => {
+            let style = match style {
+                LineCapStyle::ButtCap => 0,
+                LineCapStyle::RoundCap => 1,
+                LineCapStyle::ProjectingSquareCap => 2,
+            };
+            write!(out, "{} ", style)?;
+            "J"
+        }
+        Operation::SetCMYKColorForStrokingOperations(c, m, y, k) => {
+            write_numbers(out, &[*c, *m, *y, *k])?;
+            "K"
+        }
+        Operation::SetCMYKColorForNonStrokingOperations(c, m, y, k) => {
+            write_numbers(out, &[*c, *m, *y, *k])?;
+            "k"
+        }
+        Operation::AppendStraightLineSegmentToPath { x, y } => {
+            write_numbers(out, &[*x, *y])?;
+            "l"
+        }
+        Operation::BeginNewSubpath { x, y } => {
+            write_numbers(out, &[*x, *y])?;
+            "m"
+        }
+        Operation::SetMiterLimit(limit) => {
+            write_numbers(out, &[*limit])?;
+            "M"
+        }
+        Operation::DefineMarkedContentPoint(Name(tag)) => {
+            write_name(out, tag)?;
+            "MP"
+        }
+        Operation::EndPathWithoutFillingOrStroking => "n",
+        Operation::SaveGraphicsState => "q",
+        Operation::RestoreGraphicsState => "Q",
+        Operation::AppendRectangleToPath {
+            x,
+            y,
+            width,
+            height,
+        } => {
+            write_numbers(out, &[*x, *y, *width, *height])?;
+            "re"
+        }
+        Operation::SetRGBColorForStrokingOperations(r, g, b) => {
+            write_numbers(out, &[*r, *g, *b])?;
+            "RG"
+        }
+        Operation::SetRGBColorForNonStrokingOperations(r, g, b) => {
+            write_numbers(out, &[*r, *g, *b])?;
+            "rg"
+        }
+        Operation::SetColorRenderingIntent(intent) => {
+            write_name(
+                out,
+                match intent {
+                    ColorRenderingIntent::AbsoluteColorimetric => "AbsoluteColorimetric",
+                    ColorRenderingIntent::RelativeColorimetric => "RelativeColorimetric",
+                    ColorRenderingIntent::Saturation => "Saturation",
+                    ColorRenderingIntent::Perceptual => "Perceptual",
+                },
+            )?;
+            "ri"
+        }
+        Operation::CloseAndStrokePath => "s",
+        Operation::StrokePath => "S",
+        Operation::SetColorForStrokingOperations(color) => {
+            write_untyped_color(out, color)?;
+            "SC"
+        }
+        Operation::SetColorForNonStrokingOperations(color) => {
+            write_untyped_color(out, color)?;
+            "sc"
+        }
+        Operation::SetColorForStrokingOperationsICCBasedAndSpecialColorSpaces { cs, name } => {
+            write_numbers(out, cs)?;
+            if let Some(Name(name)) = name {
+                write_name(out, name)?;
+            }
+            "SCN"
+        }
+        Operation::SetColorForNonStrokingOperationsICCBasedAndSpecialColorSpaces { cs, name } => {
+            write_numbers(out, cs)?;
+            if let Some(Name(name)) = name {
+                write_name(out, name)?;
+            }
+            "scn"
+        }
+        Operation::PaintAreaDefinedByShadingPattern(Name(name)) => {
+            write_name(out, name)?;
+            "sh"
+        }
+        Operation::MoveToStartOfNextTextLine => "T*",
+        Operation::SetCharacterSpacing(spacing) => {
+            write_numbers(out, &[*spacing])?;
+            "Tc"
+        }
+        Operation::MoveTextPosition { x, y } => {
+            write_numbers(out, &[*x, *y])?;
+            "Td"
+        }
+        Operation::MoveTextPositionAndSetLeading { x, y } => {
+            write_numbers(out, &[*x, *y])?;
+            "TD"
+        }
+        Operation::SetTextFontAndSize { font, size } => {
+            write_name(out, font)?;
+            write_numbers(out, &[*size])?;
+            "Tf"
+        }
+        Operation::ShowText(text) => {
+            write_string(out, text.as_bytes())?;
+            out.write_all(b" ")?;
+            "Tj"
+        }
+        Operation::ShowTextAllowingIndividualGlyphPositioning(array) => {
+            out.write_all(b"[")?;
+            for item in array {
+                match item {
+                    TextOrGlyphPositioning::Text(text) => write_string(out, text.as_bytes())?,
+                    TextOrGlyphPositioning::GlyphPositioning(offset) => {
+                        out.write_all(b" ")?;
+                        write_number(out, *offset)?;
+                        out.write_all(b" ")?;
+                    }
+                }
+            }
+            out.write_all(b"] ")?;
+            "TJ"
+        }
+        Operation::SetTextLeading(leading) => {
+            write_numbers(out, &[*leading])?;
+            "TL"
+        }
+        Operation::SetTextMatrixAndTextLineMatrix(a, b, c, d, e, f) => {
+            write_numbers(out, &[*a, *b, *c, *d, *e, *f])?;
+            "Tm"
+        }
+        Operation::SetTextRenderingMode(mode) => {
+            let mode = match mode {
+                TextRenderingMode::FillText => 0,
+                TextRenderingMode::StrokeText => 1,
+                TextRenderingMode::FillThenStrokeText => 2,
+                TextRenderingMode::Invisible => 3,
+                TextRenderingMode::FillTextAndAddToPathForClipping => 4,
+                TextRenderingMode::StrokeTextAndAddToPathForClipping => 5,
+                TextRenderingMode::FillThenStrokeTextAndAddToPathForClipping => 6,
+                TextRenderingMode::AddTextToPathForClipping => 7,
+            };
+            write!(out, "{} ", mode)?;
+            "Tr"
+        }
+        Operation::SetTextRise(rise) => {
+            write_numbers(out, &[*rise])?;
+            "Ts"
+        }
+        Operation::SetWordSpacing(spacing) => {
+            write_numbers(out, &[*spacing])?;
+            "Tw"
+        }
+        Operation::SetHorizontalTextScaling(scale) => {
+            write_numbers(out, &[*scale])?;
+            "Tz"
+        }
+        Operation::AppendCurvedSegmentToPathInitialPointReplicated { x2, y2, x3, y3 } => {
+            write_numbers(out, &[*x2, *y2, *x3, *y3])?;
+            "v"
+        }
+        Operation::SetLineWidth(width) => {
+            write_numbers(out, &[*width])?;
+            "w"
+        }
+        Operation::SetClippingPathUsingNonZeroWindingNumberRule => "W",
+        Operation::SetClippingPathUsingEvenOddRule => "W*",
+        Operation::AppendCurvedSegmentToPathFinalPointReplicated { x1, y1, x3, y3 } => {
+            write_numbers(out, &[*x1, *y1, *x3, *y3])?;
+            "y"
+        }
+        Operation::MoveToNextLineAndShowText(text) => {
+            write_string(out, text.as_bytes())?;
+            out.write_all(b" ")?;
+            "'"
+        }
+        Operation::SetWordAndCharacterSpacingMoveToNextLineAndShowText {
+            text,
+            word_spacing,
+            character_spacing,
+        } => {
+            write_numbers(out, &[*word_spacing, *character_spacing])?;
+            write_string(out, text.as_bytes())?;
+            out.write_all(b" ")?;
+            "\""
+        }
+        Operation::Unknown { operator, operands } => {
+            for operand in operands.iter() {
+                write_primitive(out, operand)?;
+                out.write_all(b" ")?;
+            }
+            operator
+        }
+        Operation::Malformed { .. } => return Ok(None),
+    };
+
+    Ok(Some(operator))
+}
+
+/// Writes numbers, each followed by a space.
+fn write_numbers<W: Write>(out: &mut W, numbers: &[f32]) -> io::Result<()> {
+    for number in numbers {
+        write_number(out, *number)?;
+        out.write_all(b" ")?;
+    }
+
+    Ok(())
+}
+
+/// Writes a number as briefly as it can be read back exactly, without an exponent, which PDF
+/// doesn't allow, and without a leading zero before the decimal point.
+pub fn write_number<W: Write>(out: &mut W, number: f32) -> io::Result<()> {
+    if !number.is_finite() || number == 0.0 {
+        return out.write_all(b"0");
+    }
+
+    let written = number.to_string();
+    let compact = match written.strip_prefix("0.") {
+        Some(fraction) => format!(".{}", fraction),
+        None => match written.strip_prefix("-0.") {
+            Some(fraction) => format!("-.{}", fraction),
+            None => written,
+        },
+    };
+    out.write_all(compact.as_bytes())
+}
+
+fn write_untyped_color<W: Write>(out: &mut W, color: &UntypedColor) -> io::Result<()> {
+    match color {
+        UntypedColor::DeviceGrayCalGrayOrIndexed(gray) => write_numbers(out, &[*gray]),
+        UntypedColor::DeviceRGBCalRGBOrLab(a, b, c) => write_numbers(out, &[*a, *b, *c]),
+        UntypedColor::DeviceCMYK(c, m, y, k) => write_numbers(out, &[*c, *m, *y, *k]),
+    }
+}
+
+fn write_property_list<W: Write>(out: &mut W, properties: &PropertyList) -> io::Result<()> {
+    match properties {
+        PropertyList::Inline(dictionary) => {
+            write_primitive(out, &Primitive::Dictionary((*dictionary).clone()))?;
+            out.write_all(b" ")
+        }
+        PropertyList::Named(Name(name)) => write_name(out, name),
+    }
+}
+
+/// Writes a name followed by a space, escaping the characters that aren't allowed in names.
+fn write_name<W: Write>(out: &mut W, name: &str) -> io::Result<()> {
+    out.write_all(b"/")?;
+    for byte in name.bytes() {
+        match byte {
+            b'!'..=b'~' if !b"()<>[]{}/%#".contains(&byte) => out.write_all(&[byte])?,
+            byte => write!(out, "#{:02X}", byte)?,
+        }
+    }
+    out.write_all(b" ")
+}
+
+/// Writes a literal string. Carriage returns are escaped, since readers turn them into line
+/// feeds.
+fn write_string<W: Write>(out: &mut W, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(b"(")?;
+    for byte in bytes {
+        match byte {
+            b'(' | b')' | b'\\' => out.write_all(&[b'\\', *byte])?,
+            b'\r' => out.write_all(b"\\r")?,
+            byte => out.write_all(&[*byte])?,
+        }
+    }
+    out.write_all(b")")
+}
+
+/// Writes an operand, without a trailing space.
+pub fn write_primitive<W: Write>(out: &mut W, primitive: &Primitive) -> io::Result<()> {
+    match primitive {
+        Primitive::Null => out.write_all(b"null"),
+        Primitive::Integer(integer) => write!(out, "{}", integer),
+        Primitive::Number(number) => write_number(out, *number),
+        Primitive::Boolean(boolean) => write!(out, "{}", boolean),
+        Primitive::String(string) => write_string(out, string.as_bytes()),
+        Primitive::Name(name) => {
+            write_name(out, name)?;
+            Ok(())
+        }
+        Primitive::Array(array) => {
+            out.write_all(b"[")?;
+            for (i, item) in array.iter().enumerate() {
+                if i > 0 {
+                    out.write_all(b" ")?;
+                }
+                write_primitive(out, item)?;
+            }
+            out.write_all(b"]")
+        }
+        Primitive::Dictionary(dictionary) => {
+            out.write_all(b"<<")?;
+            for (key, value) in dictionary.iter() {
+                write_name(out, key)?;
+                write_primitive(out, value)?;
+                out.write_all(b" ")?;
+            }
+            out.write_all(b">>")
+        }
+        Primitive::Reference(reference) => write!(out, "{} {} R", reference.id, reference.gen),
+        // Streams can't appear in content streams.
+        Primitive::Stream(_) => out.write_all(b"null"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{normalize_operation, parser::ContentStreamParser};
+
+    #[test]
+    fn round_trips_operations() {
+        let content: &[u8] = b"q 1 0 0 1 72.5 -10 cm /F1 12 Tf BT [(a\\(b) -250 (c)] TJ ET \
+                               /Span <</MCID 3>> BDC /P0 BDC EMC EMC BI /W 1 /H 1 ID \x00\xff EI \
+                               /Odd#20Name Do 0.25 0 1 RG 1 2 foo Q";
+        let parse = |content: &[u8]| {
+            ContentStreamParser::new(Cursor::new(content.to_vec()))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+
+        let operations = parse(content);
+        let written = to_content(
+            &operations
+                .iter()
+                .map(normalize_operation)
+                .collect::<Vec<_>>(),
+        );
+        let reparsed = parse(&written);
+
+        assert_eq!(operations.len(), reparsed.len());
+        for (operation, reparsed) in operations.iter().zip(&reparsed) {
+            assert_eq!(operation.operator, reparsed.operator);
+            assert_eq!(
+                format!("{:?}", operation.operands),
+                format!("{:?}", reparsed.operands)
+            );
+        }
+    }
+
+    #[test]
+    fn writes_numbers_without_leading_zeros() {
+        let write = |number| {
+            let mut out = Vec::new();
+            write_number(&mut out, number).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(write(0.5), ".5");
+        assert_eq!(write(-0.5), "-.5");
+        assert_eq!(write(1.25), "1.25");
+        assert_eq!(write(-10.0), "-10");
+        assert_eq!(write(-0.0), "0");
+        assert_eq!(write(f32::NAN), "0");
+    }
+}